@@ -0,0 +1,307 @@
+#![allow(dead_code, unused_variables)]
+
+use super::ej03::{DiaSemana, Fecha, FechaError};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+
+pub const RUTA_FERIADOS: &str = "src/tp03/registros/feriados.json";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Calendario {
+    #[serde(default)]
+    inamovibles: Vec<FeriadoAnual>,
+    #[serde(default)]
+    trasladables: Vec<FeriadoAnual>,
+    #[serde(default)]
+    relativos_pascua: Vec<FeriadoPascua>,
+    #[serde(default)]
+    puntuales: Vec<FeriadoPuntual>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeriadoAnual {
+    dia: u32,
+    mes: u32,
+    nombre: String,
+}
+
+// Feriados moviles que dependen del domingo de Pascua (Carnaval, Viernes Santo).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeriadoPascua {
+    desplazamiento: i64,
+    nombre: String,
+}
+
+// Feriados o dias no laborables decretados para una fecha puntual.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeriadoPuntual {
+    fecha: Fecha,
+    nombre: String,
+}
+
+#[derive(Debug)]
+pub enum CalendarioError {
+    Io(std::io::Error),
+    Formato(serde_json::Error),
+}
+
+impl fmt::Display for CalendarioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CalendarioError::Io(e) => write!(f, "No se pudo leer el archivo de feriados: {}", e),
+            CalendarioError::Formato(e) => write!(f, "El archivo de feriados es invalido: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for CalendarioError {}
+
+impl Calendario {
+    // Calendario sin feriados: solo sabados y domingos son no habiles.
+    pub fn new() -> Self {
+        Calendario::default()
+    }
+
+    pub fn cargar(ruta: &str) -> Result<Self, CalendarioError> {
+        let texto = fs::read_to_string(ruta).map_err(CalendarioError::Io)?;
+        Calendario::desde_json(&texto)
+    }
+
+    pub fn desde_json(texto: &str) -> Result<Self, CalendarioError> {
+        serde_json::from_str(texto).map_err(CalendarioError::Formato)
+    }
+
+    pub fn feriados_del_año(&self, año: i32) -> Vec<(Fecha, String)> {
+        let mut feriados: Vec<(Fecha, String)> = vec![];
+
+        for f in &self.inamovibles {
+            feriados.push((Fecha::new(f.dia, f.mes, año), f.nombre.clone()));
+        }
+
+        for f in &self.trasladables {
+            if let Some(fecha) = trasladar(&Fecha::new(f.dia, f.mes, año)) {
+                feriados.push((fecha, f.nombre.clone()));
+            }
+        }
+
+        if let Some(pascua) = domingo_de_pascua(año) {
+            for f in &self.relativos_pascua {
+                let mut fecha = pascua.clone();
+                if fecha.try_sumar_dias(f.desplazamiento).is_ok() {
+                    feriados.push((fecha, f.nombre.clone()));
+                }
+            }
+        }
+
+        for f in &self.puntuales {
+            if f.fecha.año == año {
+                feriados.push((f.fecha.clone(), f.nombre.clone()));
+            }
+        }
+
+        feriados.sort_by(|a, b| a.0.cmp(&b.0));
+        feriados
+    }
+
+    pub fn es_feriado(&self, fecha: &Fecha) -> bool {
+        self.feriados_del_año(fecha.año)
+            .iter()
+            .any(|(f, _)| f == fecha)
+    }
+
+    pub fn es_fin_de_semana(&self, fecha: &Fecha) -> bool {
+        fecha.dia_semana().is_ok_and(|d| d.es_fin_de_semana())
+    }
+
+    pub fn es_dia_habil(&self, fecha: &Fecha) -> bool {
+        fecha.es_fecha_valida() && !self.es_fin_de_semana(fecha) && !self.es_feriado(fecha)
+    }
+
+    // Devuelve la misma fecha si es habil, si no el proximo dia habil.
+    pub fn siguiente_dia_habil(&self, fecha: &Fecha) -> Result<Fecha, FechaError> {
+        let mut f = Fecha::try_new(fecha.dia, fecha.mes, fecha.año)?;
+        while !self.es_dia_habil(&f) {
+            f.try_sumar_dias(1)?;
+        }
+        Ok(f)
+    }
+
+    pub fn sumar_dias_habiles(&self, fecha: &Fecha, dias: u32) -> Result<Fecha, FechaError> {
+        let mut f = Fecha::try_new(fecha.dia, fecha.mes, fecha.año)?;
+        let mut restantes = dias;
+        while restantes > 0 {
+            f.try_sumar_dias(1)?;
+            if self.es_dia_habil(&f) {
+                restantes -= 1;
+            }
+        }
+        Ok(f)
+    }
+
+    // Cuenta los dias habiles en [desde, hasta). Si hasta <= desde devuelve 0.
+    pub fn dias_habiles_entre(&self, desde: &Fecha, hasta: &Fecha) -> Result<u32, FechaError> {
+        let mut f = Fecha::try_new(desde.dia, desde.mes, desde.año)?;
+        Fecha::try_new(hasta.dia, hasta.mes, hasta.año)?;
+
+        let mut cant = 0;
+        while f < *hasta {
+            if self.es_dia_habil(&f) {
+                cant += 1;
+            }
+            f.try_sumar_dias(1)?;
+        }
+        Ok(cant)
+    }
+}
+
+// Ley 27.399: si cae martes o miercoles se mueve al lunes anterior,
+// si cae jueves o viernes al lunes siguiente.
+fn trasladar(fecha: &Fecha) -> Option<Fecha> {
+    let mut f = fecha.clone();
+    let desplazamiento = match fecha.dia_semana().ok()? {
+        DiaSemana::Martes => -1,
+        DiaSemana::Miercoles => -2,
+        DiaSemana::Jueves => 4,
+        DiaSemana::Viernes => 3,
+        _ => 0,
+    };
+    f.try_sumar_dias(desplazamiento).ok()?;
+    Some(f)
+}
+
+// Algoritmo de Meeus/Jones/Butcher para el calendario gregoriano.
+fn domingo_de_pascua(año: i32) -> Option<Fecha> {
+    let a = año.rem_euclid(19);
+    let b = año.div_euclid(100);
+    let c = año.rem_euclid(100);
+    let d = b.div_euclid(4);
+    let e = b.rem_euclid(4);
+    let f = (b + 8).div_euclid(25);
+    let g = (b - f + 1).div_euclid(3);
+    let h = (19 * a + b - d - g + 15).rem_euclid(30);
+    let i = c.div_euclid(4);
+    let k = c.rem_euclid(4);
+    let l = (32 + 2 * e + 2 * i - h - k).rem_euclid(7);
+    let m = (a + 11 * h + 22 * l).div_euclid(451);
+    let mes = (h + l - 7 * m + 114).div_euclid(31);
+    let dia = (h + l - 7 * m + 114).rem_euclid(31) + 1;
+    Fecha::try_new(dia as u32, mes as u32, año).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn calendario() -> Calendario {
+        Calendario::cargar(RUTA_FERIADOS).unwrap()
+    }
+
+    #[test]
+    fn test_domingo_de_pascua() {
+        assert_eq!(domingo_de_pascua(2024), Some(Fecha::new(31, 3, 2024)));
+        assert_eq!(domingo_de_pascua(2025), Some(Fecha::new(20, 4, 2025)));
+        assert_eq!(domingo_de_pascua(2026), Some(Fecha::new(5, 4, 2026)));
+    }
+
+    #[test]
+    fn test_feriados_2025() {
+        let c = calendario();
+        // inamovibles
+        assert!(c.es_feriado(&Fecha::new(9, 7, 2025)));
+        assert!(c.es_feriado(&Fecha::new(25, 12, 2025)));
+        // carnaval y viernes santo
+        assert!(c.es_feriado(&Fecha::new(3, 3, 2025)));
+        assert!(c.es_feriado(&Fecha::new(4, 3, 2025)));
+        assert!(c.es_feriado(&Fecha::new(18, 4, 2025)));
+        // Guemes cae martes 17/6 y se traslada al lunes 16/6
+        assert!(c.es_feriado(&Fecha::new(16, 6, 2025)));
+        assert!(!c.es_feriado(&Fecha::new(17, 6, 2025)));
+        // Soberania cae jueves 20/11 y se traslada al lunes 24/11
+        assert!(c.es_feriado(&Fecha::new(24, 11, 2025)));
+        assert!(!c.es_feriado(&Fecha::new(20, 11, 2025)));
+        // dia no laborable puntual
+        assert!(c.es_feriado(&Fecha::new(2, 5, 2025)));
+        assert!(!c.es_feriado(&Fecha::new(2, 5, 2026)));
+    }
+
+    #[test]
+    fn test_es_dia_habil() {
+        let c = calendario();
+        assert!(c.es_dia_habil(&Fecha::new(1, 7, 2025))); // martes
+        assert!(!c.es_dia_habil(&Fecha::new(5, 7, 2025))); // sabado
+        assert!(!c.es_dia_habil(&Fecha::new(6, 7, 2025))); // domingo
+        assert!(!c.es_dia_habil(&Fecha::new(9, 7, 2025))); // feriado
+        assert!(!c.es_dia_habil(&Fecha::new(32, 7, 2025))); // invalida
+    }
+
+    #[test]
+    fn test_sumar_dias_habiles() {
+        let c = calendario();
+        // lunes 7/7 + 3 habiles salta el feriado del miercoles 9/7
+        assert_eq!(
+            c.sumar_dias_habiles(&Fecha::new(7, 7, 2025), 3),
+            Ok(Fecha::new(11, 7, 2025))
+        );
+        // viernes + 1 habil es el lunes
+        assert_eq!(
+            c.sumar_dias_habiles(&Fecha::new(11, 7, 2025), 1),
+            Ok(Fecha::new(14, 7, 2025))
+        );
+        assert_eq!(
+            c.sumar_dias_habiles(&Fecha::new(11, 7, 2025), 0),
+            Ok(Fecha::new(11, 7, 2025))
+        );
+        assert!(c.sumar_dias_habiles(&Fecha::new(30, 2, 2025), 1).is_err());
+    }
+
+    #[test]
+    fn test_siguiente_dia_habil() {
+        let c = calendario();
+        assert_eq!(
+            c.siguiente_dia_habil(&Fecha::new(21, 11, 2025)),
+            Ok(Fecha::new(25, 11, 2025))
+        );
+        assert_eq!(
+            c.siguiente_dia_habil(&Fecha::new(1, 7, 2025)),
+            Ok(Fecha::new(1, 7, 2025))
+        );
+    }
+
+    #[test]
+    fn test_dias_habiles_entre() {
+        let c = calendario();
+        // julio 2025: 23 dias de semana menos el 9/7
+        assert_eq!(
+            c.dias_habiles_entre(&Fecha::new(1, 7, 2025), &Fecha::new(1, 8, 2025)),
+            Ok(22)
+        );
+        assert_eq!(
+            c.dias_habiles_entre(&Fecha::new(1, 8, 2025), &Fecha::new(1, 7, 2025)),
+            Ok(0)
+        );
+        assert!(
+            c.dias_habiles_entre(&Fecha::new(1, 7, 2025), &Fecha::new(0, 8, 2025))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_calendario_sin_feriados() {
+        let c = Calendario::new();
+        assert!(c.es_dia_habil(&Fecha::new(9, 7, 2025)));
+        assert!(c.feriados_del_año(2025).is_empty());
+    }
+
+    #[test]
+    fn test_cargar_errores() {
+        assert!(matches!(
+            Calendario::cargar("no/existe.json"),
+            Err(CalendarioError::Io(_))
+        ));
+        assert!(matches!(
+            Calendario::desde_json("{ \"inamovibles\": 3 }"),
+            Err(CalendarioError::Formato(_))
+        ));
+    }
+}
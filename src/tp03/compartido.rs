@@ -0,0 +1,41 @@
+#![allow(dead_code, unused_variables)]
+
+use std::fmt;
+use std::ops::Deref;
+use std::rc::Rc;
+
+// Referencia compartida a un reloj, repositorio o diario para guardar dentro de los sistemas.
+// No cuenta al comparar ni al mostrar el sistema que la guarda, y no se serializa.
+pub struct Compartido<S: ?Sized>(Rc<S>);
+
+impl<S: ?Sized> Compartido<S> {
+    pub fn de_rc(rc: Rc<S>) -> Self {
+        Compartido(rc)
+    }
+}
+
+impl<S: ?Sized> Deref for Compartido<S> {
+    type Target = S;
+
+    fn deref(&self) -> &S {
+        &self.0
+    }
+}
+
+impl<S: ?Sized> Clone for Compartido<S> {
+    fn clone(&self) -> Self {
+        Compartido(Rc::clone(&self.0))
+    }
+}
+
+impl<S: ?Sized> PartialEq for Compartido<S> {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl<S: ?Sized> fmt::Debug for Compartido<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Compartido")
+    }
+}
@@ -0,0 +1,325 @@
+#![allow(dead_code, unused_variables)]
+
+use std::cmp::Ordering;
+use std::ops::{Bound, RangeBounds};
+
+// Lo que una consulta necesita de un auto. Lo implementan los autos del concesionario de
+// tp03 y los de tp05, que tienen cada uno su propio Color.
+pub trait DatosAuto {
+    type Color: PartialEq;
+
+    fn marca(&self) -> &str;
+    fn modelo(&self) -> &str;
+    fn año(&self) -> u32;
+    fn color(&self) -> &Self::Color;
+    fn precio_bruto(&self) -> f64;
+    // El precio de venta, con recargos y descuentos.
+    fn precio_final(&self) -> f64;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CampoAuto {
+    Marca,
+    Modelo,
+    Año,
+    Precio,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Sentido {
+    Ascendente,
+    Descendente,
+}
+
+// El precio final de cada auto segun quien consulta.
+type Tarifa<'a, A> = Box<dyn Fn(&A) -> f64 + 'a>;
+
+// Filtros, orden y paginacion sobre los autos de un concesionario. Los filtros se combinan
+// con "y"; con_color es la excepcion, llamarlo varias veces acepta cualquiera de esos colores.
+pub struct ConsultaAutos<'a, A: DatosAuto> {
+    autos: &'a [A],
+    marca: Option<String>,
+    modelo: Option<String>,
+    años: (Bound<u32>, Bound<u32>),
+    colores: Vec<A::Color>,
+    precio: (Bound<f64>, Bound<f64>),
+    orden: Vec<(CampoAuto, Sentido)>,
+    // (numero de pagina desde 1, autos por pagina)
+    pagina: Option<(usize, usize)>,
+    // Si no se indica se usa DatosAuto::precio_final.
+    tarifa: Option<Tarifa<'a, A>>,
+}
+
+impl<'a, A: DatosAuto> ConsultaAutos<'a, A> {
+    pub fn new(autos: &'a [A]) -> Self {
+        ConsultaAutos {
+            autos,
+            marca: None,
+            modelo: None,
+            años: (Bound::Unbounded, Bound::Unbounded),
+            colores: vec![],
+            precio: (Bound::Unbounded, Bound::Unbounded),
+            orden: vec![],
+            pagina: None,
+            tarifa: None,
+        }
+    }
+
+    // Con que precio final filtrar y ordenar, cuando el concesionario tiene sus propias reglas.
+    pub fn con_tarifa(mut self, tarifa: impl Fn(&A) -> f64 + 'a) -> Self {
+        self.tarifa = Some(Box::new(tarifa));
+        self
+    }
+
+    fn precio(&self, auto: &A) -> f64 {
+        match &self.tarifa {
+            Some(tarifa) => tarifa(auto),
+            None => auto.precio_final(),
+        }
+    }
+
+    // Marca y modelo se comparan sin importar mayusculas.
+    pub fn con_marca(mut self, marca: &str) -> Self {
+        self.marca = Some(marca.to_lowercase());
+        self
+    }
+
+    pub fn con_modelo(mut self, modelo: &str) -> Self {
+        self.modelo = Some(modelo.to_lowercase());
+        self
+    }
+
+    // con_años(2010..=2015), con_años(2020..)
+    pub fn con_años(mut self, años: impl RangeBounds<u32>) -> Self {
+        self.años = (años.start_bound().cloned(), años.end_bound().cloned());
+        self
+    }
+
+    pub fn con_color(mut self, color: A::Color) -> Self {
+        self.colores.push(color);
+        self
+    }
+
+    // Sobre el precio final, no el bruto.
+    pub fn con_precio(mut self, precio: impl RangeBounds<f64>) -> Self {
+        self.precio = (precio.start_bound().cloned(), precio.end_bound().cloned());
+        self
+    }
+
+    // Cada llamada agrega un criterio que desempata los anteriores. Los autos que quedan
+    // iguales en todos mantienen el orden del concesionario.
+    pub fn ordenada_por(mut self, campo: CampoAuto, sentido: Sentido) -> Self {
+        self.orden.push((campo, sentido));
+        self
+    }
+
+    // La primera pagina es la 1.
+    pub fn pagina(mut self, numero: usize, por_pagina: usize) -> Self {
+        self.pagina = Some((numero.max(1), por_pagina));
+        self
+    }
+
+    fn cumple(&self, auto: &A, precio: f64) -> bool {
+        self.marca
+            .as_ref()
+            .is_none_or(|m| auto.marca().to_lowercase() == *m)
+            && self
+                .modelo
+                .as_ref()
+                .is_none_or(|m| auto.modelo().to_lowercase() == *m)
+            && self.años.contains(&auto.año())
+            && (self.colores.is_empty() || self.colores.contains(auto.color()))
+            && self.precio.contains(&precio)
+    }
+
+    // Los que cumplen los filtros, ya ordenados, con su precio final para no recalcularlo.
+    fn filtrados(&self) -> Vec<(&'a A, f64)> {
+        let mut autos: Vec<(&'a A, f64)> = self
+            .autos
+            .iter()
+            .map(|a| (a, self.precio(a)))
+            .filter(|(a, precio)| self.cumple(a, *precio))
+            .collect();
+        if !self.orden.is_empty() {
+            autos.sort_by(|a, b| self.comparar(a, b));
+        }
+        autos
+    }
+
+    fn comparar(&self, (a, precio_a): &(&A, f64), (b, precio_b): &(&A, f64)) -> Ordering {
+        for (campo, sentido) in &self.orden {
+            let orden = match campo {
+                CampoAuto::Marca => a.marca().to_lowercase().cmp(&b.marca().to_lowercase()),
+                CampoAuto::Modelo => a.modelo().to_lowercase().cmp(&b.modelo().to_lowercase()),
+                CampoAuto::Año => a.año().cmp(&b.año()),
+                CampoAuto::Precio => precio_a.total_cmp(precio_b),
+            };
+            let orden = match sentido {
+                Sentido::Ascendente => orden,
+                Sentido::Descendente => orden.reverse(),
+            };
+            if orden != Ordering::Equal {
+                return orden;
+            }
+        }
+        Ordering::Equal
+    }
+
+    // Cuantos cumplen los filtros, sin contar la paginacion.
+    pub fn total(&self) -> usize {
+        self.autos
+            .iter()
+            .filter(|a| self.cumple(a, self.precio(a)))
+            .count()
+    }
+
+    pub fn cantidad_de_paginas(&self) -> usize {
+        match self.pagina {
+            Some((_, 0)) => 0,
+            Some((_, por_pagina)) => self.total().div_ceil(por_pagina),
+            None => 1,
+        }
+    }
+
+    pub fn resultados(&self) -> Vec<&'a A> {
+        let autos = self.filtrados().into_iter().map(|(a, _)| a);
+        match self.pagina {
+            Some((numero, por_pagina)) => autos
+                .skip((numero - 1).saturating_mul(por_pagina))
+                .take(por_pagina)
+                .collect(),
+            None => autos.collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tp03::ej07::{Auto, Color};
+
+    fn autos() -> Vec<Auto> {
+        vec![
+            Auto::new(
+                "Ford".to_string(),
+                "Ka".to_string(),
+                2015,
+                1000.0,
+                Color::ROJO,
+            ),
+            Auto::new(
+                "BMW".to_string(),
+                "M3".to_string(),
+                2020,
+                1000.0,
+                Color::NEGRO,
+            ),
+            Auto::new(
+                "Ford".to_string(),
+                "Focus".to_string(),
+                1998,
+                2000.0,
+                Color::AZUL,
+            ),
+            Auto::new(
+                "Fiat".to_string(),
+                "Uno".to_string(),
+                2015,
+                900.0,
+                Color::BLANCO,
+            ),
+            Auto::new(
+                "ford".to_string(),
+                "Fiesta".to_string(),
+                2010,
+                1100.0,
+                Color::NEGRO,
+            ),
+        ]
+    }
+
+    fn modelos(autos: Vec<&Auto>) -> Vec<&str> {
+        autos.into_iter().map(|a| a.modelo()).collect()
+    }
+
+    #[test]
+    fn test_sin_filtros_devuelve_todo_en_orden() {
+        let autos = autos();
+        let consulta = ConsultaAutos::new(&autos);
+        assert_eq!(consulta.total(), 5);
+        assert_eq!(
+            modelos(consulta.resultados()),
+            vec!["Ka", "M3", "Focus", "Uno", "Fiesta"]
+        );
+    }
+
+    #[test]
+    fn test_filtros_combinados() {
+        let autos = autos();
+        let ford = ConsultaAutos::new(&autos).con_marca("FORD");
+        assert_eq!(modelos(ford.resultados()), vec!["Ka", "Focus", "Fiesta"]);
+
+        let consulta = ConsultaAutos::new(&autos)
+            .con_marca("ford")
+            .con_años(2000..);
+        assert_eq!(modelos(consulta.resultados()), vec!["Ka", "Fiesta"]);
+
+        let consulta = ConsultaAutos::new(&autos)
+            .con_color(Color::NEGRO)
+            .con_color(Color::ROJO);
+        assert_eq!(modelos(consulta.resultados()), vec!["Ka", "M3", "Fiesta"]);
+
+        let consulta = ConsultaAutos::new(&autos)
+            .con_modelo("uno")
+            .con_años(..2015);
+        assert_eq!(consulta.total(), 0);
+        assert!(consulta.resultados().is_empty());
+    }
+
+    #[test]
+    fn test_filtro_por_precio_final() {
+        let autos = autos();
+        // Ka 1250, M3 1050, Focus 2400, Uno 810, Fiesta 990
+        let consulta = ConsultaAutos::new(&autos).con_precio(1000.0..=1250.0);
+        assert_eq!(modelos(consulta.resultados()), vec!["Ka", "M3"]);
+        let consulta = ConsultaAutos::new(&autos).con_precio(..1000.0);
+        assert_eq!(modelos(consulta.resultados()), vec!["Uno", "Fiesta"]);
+    }
+
+    #[test]
+    fn test_orden_con_desempate() {
+        let autos = autos();
+        let consulta = ConsultaAutos::new(&autos)
+            .ordenada_por(CampoAuto::Año, Sentido::Descendente)
+            .ordenada_por(CampoAuto::Precio, Sentido::Ascendente);
+        assert_eq!(
+            modelos(consulta.resultados()),
+            vec!["M3", "Uno", "Ka", "Fiesta", "Focus"]
+        );
+
+        let consulta =
+            ConsultaAutos::new(&autos).ordenada_por(CampoAuto::Marca, Sentido::Ascendente);
+        // las marcas iguales sin importar mayusculas quedan en el orden original
+        assert_eq!(
+            modelos(consulta.resultados()),
+            vec!["M3", "Uno", "Ka", "Focus", "Fiesta"]
+        );
+    }
+
+    #[test]
+    fn test_paginacion() {
+        let autos = autos();
+        let consulta = |numero| {
+            ConsultaAutos::new(&autos)
+                .ordenada_por(CampoAuto::Precio, Sentido::Ascendente)
+                .pagina(numero, 2)
+        };
+        assert_eq!(consulta(1).cantidad_de_paginas(), 3);
+        assert_eq!(consulta(1).total(), 5);
+        assert_eq!(modelos(consulta(1).resultados()), vec!["Uno", "Fiesta"]);
+        assert_eq!(modelos(consulta(3).resultados()), vec!["Focus"]);
+        assert!(consulta(4).resultados().is_empty());
+        // la pagina 0 es la primera
+        assert_eq!(modelos(consulta(0).resultados()), vec!["Uno", "Fiesta"]);
+    }
+}
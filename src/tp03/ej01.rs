@@ -1,8 +1,13 @@
+#![allow(dead_code, unused_variables)]
+
+use super::ej03::Fecha;
+
 #[derive(Debug)]
 pub struct Persona {
     nombre: String,
     direccion: Option<String>,
     edad: u8,
+    fecha_nacimiento: Option<Fecha>,
 }
 
 impl Persona {
@@ -11,9 +16,23 @@ impl Persona {
             nombre,
             direccion,
             edad,
+            fecha_nacimiento: None,
         }
     }
 
+    pub fn con_fecha_nacimiento(mut self, fecha_nacimiento: Fecha) -> Self {
+        self.fecha_nacimiento = Some(fecha_nacimiento);
+        self
+    }
+
+    pub fn edad_al(&self, fecha: &Fecha) -> u8 {
+        Fecha::edad_desde(self.fecha_nacimiento.as_ref(), self.edad, fecha)
+    }
+
+    pub fn edad_actual(&self) -> u8 {
+        self.edad_al(&Fecha::fecha_actual())
+    }
+
     pub fn to_string(&self) -> String {
         let dir_str = match &self.direccion {
             Some(dir) => dir.clone(),
@@ -21,15 +40,43 @@ impl Persona {
         };
         format!(
             "Nombre:{0}, Direccion: {dir_str}, Edad:{1}",
-            self.nombre, self.edad
+            self.nombre,
+            self.edad_actual()
         )
     }
 
     pub fn obtener_edad(&self) -> u8 {
-        self.edad
+        self.edad_actual()
     }
 
     pub fn actualizar_direccion(&mut self, nueva_dir: String) {
         self.direccion = Some(nueva_dir);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edad_sin_fecha_nacimiento() {
+        let p = Persona::new("Tao".to_string(), None, 33);
+        assert_eq!(p.edad_al(&Fecha::new(1, 1, 2100)), 33);
+        assert_eq!(p.obtener_edad(), 33);
+    }
+
+    #[test]
+    fn test_edad_con_fecha_nacimiento() {
+        let p =
+            Persona::new("Tao".to_string(), None, 0).con_fecha_nacimiento(Fecha::new(15, 10, 2001));
+        assert_eq!(p.edad_al(&Fecha::new(14, 10, 2025)), 23);
+        assert_eq!(p.edad_al(&Fecha::new(15, 10, 2025)), 24);
+        assert_eq!(p.edad_al(&Fecha::new(1, 1, 1990)), 0);
+
+        let mut hoy = Fecha::fecha_actual();
+        hoy.sumar_años(-30);
+        let p = Persona::new("Ana".to_string(), None, 0).con_fecha_nacimiento(hoy);
+        assert_eq!(p.obtener_edad(), 30);
+        assert!(p.to_string().ends_with("Edad:30"));
+    }
+}
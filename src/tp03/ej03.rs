@@ -1,20 +1,145 @@
 #![allow(dead_code, unused_variables)]
 
-use chrono::{Datelike, Duration, Local, NaiveDate};
+use chrono::{Datelike, Duration, Local, NaiveDate, Weekday};
 use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::Sub;
+use std::str::FromStr;
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Fecha {
     pub año: i32,
     pub dia: u32,
     pub mes: u32,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum DiaSemana {
+    Lunes,
+    Martes,
+    Miercoles,
+    Jueves,
+    Viernes,
+    Sabado,
+    Domingo,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FechaError {
+    Invalida {
+        dia: u32,
+        mes: u32,
+        año: i32,
+    },
+    FormatoInvalido(String),
+    FueraDeRango,
+    HoraInvalida {
+        hora: u32,
+        minuto: u32,
+        segundo: u32,
+    },
+    DesplazamientoInvalido(i32),
+}
+
+impl fmt::Display for FechaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FechaError::Invalida { dia, mes, año } => {
+                write!(f, "La fecha {}/{}/{} no es valida", dia, mes, año)
+            }
+            FechaError::FormatoInvalido(texto) => write!(
+                f,
+                "No se pudo interpretar '{}' como fecha (use aaaa-mm-dd o dd/mm/aaaa)",
+                texto
+            ),
+            FechaError::FueraDeRango => write!(f, "El resultado queda fuera del rango de fechas"),
+            FechaError::HoraInvalida {
+                hora,
+                minuto,
+                segundo,
+            } => write!(
+                f,
+                "La hora {}:{:02}:{:02} no es valida",
+                hora, minuto, segundo
+            ),
+            FechaError::DesplazamientoInvalido(segundos) => write!(
+                f,
+                "El desplazamiento de {} segundos respecto de UTC no es valido",
+                segundos
+            ),
+        }
+    }
+}
+
+impl std::error::Error for FechaError {}
+
+impl DiaSemana {
+    pub fn nombre(&self) -> &'static str {
+        match self {
+            DiaSemana::Lunes => "lunes",
+            DiaSemana::Martes => "martes",
+            DiaSemana::Miercoles => "miércoles",
+            DiaSemana::Jueves => "jueves",
+            DiaSemana::Viernes => "viernes",
+            DiaSemana::Sabado => "sábado",
+            DiaSemana::Domingo => "domingo",
+        }
+    }
+
+    pub fn es_fin_de_semana(&self) -> bool {
+        matches!(self, DiaSemana::Sabado | DiaSemana::Domingo)
+    }
+
+    fn desde_weekday(dia: Weekday) -> Self {
+        match dia {
+            Weekday::Mon => DiaSemana::Lunes,
+            Weekday::Tue => DiaSemana::Martes,
+            Weekday::Wed => DiaSemana::Miercoles,
+            Weekday::Thu => DiaSemana::Jueves,
+            Weekday::Fri => DiaSemana::Viernes,
+            Weekday::Sat => DiaSemana::Sabado,
+            Weekday::Sun => DiaSemana::Domingo,
+        }
+    }
+}
+
+impl fmt::Display for DiaSemana {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.nombre())
+    }
+}
+
 impl Fecha {
+    pub const FORMATO_ISO: &'static str = "aaaa-mm-dd";
+    pub const FORMATO_ARGENTINO: &'static str = "dd/mm/aaaa";
+
     pub fn new(dia: u32, mes: u32, año: i32) -> Self {
         Fecha { dia, mes, año }
     }
 
+    pub fn try_new(dia: u32, mes: u32, año: i32) -> Result<Self, FechaError> {
+        let fecha = Fecha { dia, mes, año };
+        fecha.a_naive()?;
+        Ok(fecha)
+    }
+
+    pub(crate) fn a_naive(&self) -> Result<NaiveDate, FechaError> {
+        NaiveDate::from_ymd_opt(self.año, self.mes, self.dia).ok_or(FechaError::Invalida {
+            dia: self.dia,
+            mes: self.mes,
+            año: self.año,
+        })
+    }
+
+    pub(crate) fn desde_naive(date: NaiveDate) -> Self {
+        Fecha {
+            dia: date.day(),
+            mes: date.month(),
+            año: date.year(),
+        }
+    }
+
     pub fn es_fecha_valida(&self) -> bool {
         NaiveDate::from_ymd_opt(self.año, self.mes, self.dia).is_some()
     }
@@ -23,50 +148,202 @@ impl Fecha {
         (self.año % 4 == 0 && self.año % 100 != 0) || (self.año % 400 == 0)
     }
 
+    // Si la fecha es invalida o el resultado se sale de rango la fecha queda sin cambios,
+    // para enterarse del error usar try_sumar_dias.
     pub fn sumar_dias(&mut self, dias: i64) {
-        let date = NaiveDate::from_ymd_opt(self.año, self.mes, self.dia).unwrap();
-        let date = date + Duration::days(dias);
-
-        self.dia = date.day();
-        self.mes = date.month();
-        self.año = date.year();
+        let _ = self.try_sumar_dias(dias);
     }
 
     pub fn restar_dias(&mut self, dias: i64) {
-        let date = NaiveDate::from_ymd_opt(self.año, self.mes, self.dia).unwrap();
-        let date = date - Duration::days(dias);
+        let _ = self.try_restar_dias(dias);
+    }
 
-        self.dia = date.day();
-        self.mes = date.month();
-        self.año = date.year();
+    pub fn try_sumar_dias(&mut self, dias: i64) -> Result<(), FechaError> {
+        let date = self.a_naive()?;
+        let date = Duration::try_days(dias)
+            .and_then(|d| date.checked_add_signed(d))
+            .ok_or(FechaError::FueraDeRango)?;
+
+        *self = Fecha::desde_naive(date);
+        Ok(())
     }
 
-    pub fn es_mayor(&self, fecha: &Fecha) -> bool {
-        let date1 = match NaiveDate::from_ymd_opt(self.año, self.mes, self.dia) {
-            Some(date) => date,
-            None => return false,
-        };
+    pub fn try_restar_dias(&mut self, dias: i64) -> Result<(), FechaError> {
+        let dias = dias.checked_neg().ok_or(FechaError::FueraDeRango)?;
+        self.try_sumar_dias(dias)
+    }
 
-        let date2 = match NaiveDate::from_ymd_opt(fecha.año, fecha.mes, fecha.dia) {
-            Some(date) => date,
-            None => return true,
-        };
+    // Mismo comportamiento que sumar_dias: ante un error la fecha queda sin cambios.
+    pub fn sumar_meses(&mut self, meses: i32) {
+        let _ = self.try_sumar_meses(meses);
+    }
 
-        date1 > date2
+    pub fn sumar_años(&mut self, años: i32) {
+        let _ = self.try_sumar_años(años);
     }
 
-    pub fn es_menor(&self, fecha: Fecha) -> bool {
-        let date1 = match NaiveDate::from_ymd_opt(self.año, self.mes, self.dia) {
-            Some(date) => date,
-            None => return false,
-        };
+    // Si el dia no existe en el mes destino se ajusta al ultimo dia (31/01 + 1 mes = 28/02).
+    pub fn try_sumar_meses(&mut self, meses: i32) -> Result<(), FechaError> {
+        self.a_naive()?;
+        let total = (self.año as i64 * 12 + (self.mes as i64 - 1))
+            .checked_add(meses as i64)
+            .ok_or(FechaError::FueraDeRango)?;
+        let año = i32::try_from(total.div_euclid(12)).map_err(|_| FechaError::FueraDeRango)?;
+        let mes = total.rem_euclid(12) as u32 + 1;
 
-        let date2 = match NaiveDate::from_ymd_opt(fecha.año, fecha.mes, fecha.dia) {
-            Some(date) => date,
-            None => return true,
-        };
+        let mut fecha = Fecha::new(1, mes, año);
+        fecha.a_naive().map_err(|_| FechaError::FueraDeRango)?;
+        fecha.dia = self.dia.min(fecha.dias_del_mes());
+
+        *self = fecha;
+        Ok(())
+    }
+
+    pub fn try_sumar_años(&mut self, años: i32) -> Result<(), FechaError> {
+        let meses = años.checked_mul(12).ok_or(FechaError::FueraDeRango)?;
+        self.try_sumar_meses(meses)
+    }
+
+    // Años cumplidos entre self y fecha (por ejemplo una fecha de nacimiento y hoy). Quien
+    // nacio un 29/02 cumple el 01/03 en los años no bisiestos. Negativo si fecha < self.
+    pub fn años_hasta(&self, fecha: &Fecha) -> i32 {
+        let mut años = fecha.año - self.año;
+        if (fecha.mes, fecha.dia) < (self.mes, self.dia) {
+            años -= 1;
+        }
+        if años < 0 && (fecha.mes, fecha.dia) != (self.mes, self.dia) {
+            años += 1;
+        }
+        años
+    }
+
+    // La edad al `fecha` para quien puede tener fecha de nacimiento: si la tiene se calcula
+    // (0 antes de nacer) y `edad_declarada` deja de usarse; si no, es esa.
+    pub fn edad_desde(nacimiento: Option<&Fecha>, edad_declarada: u8, fecha: &Fecha) -> u8 {
+        match nacimiento {
+            Some(nacimiento) => {
+                u8::try_from(nacimiento.años_hasta(fecha).max(0)).unwrap_or(u8::MAX)
+            }
+            None => edad_declarada,
+        }
+    }
+
+    // Meses entre self y fecha con la fraccion del mes en curso
+    // (01/01 -> 16/02 = 1.5 meses). Negativo si fecha < self.
+    pub fn meses_hasta(&self, fecha: &Fecha) -> f64 {
+        if fecha < self {
+            return -fecha.meses_hasta(self);
+        }
+
+        let mut completos = (fecha.año - self.año) * 12 + fecha.mes as i32 - self.mes as i32;
+        if fecha.dia < self.dia {
+            completos -= 1;
+        }
+
+        let mut desde = self.clone();
+        let mut hasta = self.clone();
+        if desde.try_sumar_meses(completos).is_err()
+            || hasta.try_sumar_meses(completos + 1).is_err()
+        {
+            return completos as f64;
+        }
+        completos as f64 + (fecha - &desde) as f64 / (&hasta - &desde) as f64
+    }
+
+    pub fn dias_del_mes(&self) -> u32 {
+        match self.mes {
+            1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+            4 | 6 | 9 | 11 => 30,
+            2 if self.es_bisiesto() => 29,
+            2 => 28,
+            _ => 0,
+        }
+    }
+
+    pub fn dia_semana(&self) -> Result<DiaSemana, FechaError> {
+        Ok(DiaSemana::desde_weekday(self.a_naive()?.weekday()))
+    }
+
+    // (año ISO, numero de semana ISO). Los primeros dias de enero pueden pertenecer a la
+    // ultima semana del año anterior, y los ultimos de diciembre a la semana 1 del siguiente.
+    pub fn semana_iso(&self) -> Result<(i32, u32), FechaError> {
+        let semana = self.a_naive()?.iso_week();
+        Ok((semana.year(), semana.week()))
+    }
+
+    // 1 a 366.
+    pub fn dia_del_año(&self) -> Result<u32, FechaError> {
+        Ok(self.a_naive()?.ordinal())
+    }
+
+    // 1 a 4, o 0 si el mes es invalido.
+    pub fn trimestre(&self) -> u32 {
+        match self.mes {
+            1..=12 => (self.mes - 1) / 3 + 1,
+            _ => 0,
+        }
+    }
+
+    // Los limites de mes, trimestre y año solo dependen del mes y el año de la fecha.
+    pub fn primer_dia_del_mes(&self) -> Fecha {
+        Fecha::new(1, self.mes, self.año)
+    }
+
+    pub fn ultimo_dia_del_mes(&self) -> Fecha {
+        Fecha::new(self.dias_del_mes(), self.mes, self.año)
+    }
+
+    pub fn primer_dia_del_trimestre(&self) -> Fecha {
+        Fecha::new(1, (self.trimestre() * 3).saturating_sub(2), self.año)
+    }
+
+    pub fn ultimo_dia_del_trimestre(&self) -> Fecha {
+        Fecha::new(1, self.trimestre() * 3, self.año).ultimo_dia_del_mes()
+    }
+
+    pub fn primer_dia_del_año(&self) -> Fecha {
+        Fecha::new(1, 1, self.año)
+    }
+
+    pub fn ultimo_dia_del_año(&self) -> Fecha {
+        Fecha::new(31, 12, self.año)
+    }
+
+    // "martes 1 de julio de 2025"
+    pub fn formato_largo(&self) -> Result<String, FechaError> {
+        Ok(format!(
+            "{} {} de {} de {}",
+            self.dia_semana()?,
+            self.dia,
+            self.nombre_mes(),
+            self.año
+        ))
+    }
+
+    pub fn nombre_mes(&self) -> &'static str {
+        match self.mes {
+            1 => "enero",
+            2 => "febrero",
+            3 => "marzo",
+            4 => "abril",
+            5 => "mayo",
+            6 => "junio",
+            7 => "julio",
+            8 => "agosto",
+            9 => "septiembre",
+            10 => "octubre",
+            11 => "noviembre",
+            12 => "diciembre",
+            _ => "",
+        }
+    }
+
+    pub fn es_mayor(&self, fecha: &Fecha) -> bool {
+        self > fecha
+    }
 
-        date1 < date2
+    pub fn es_menor(&self, fecha: &Fecha) -> bool {
+        self < fecha
     }
 
     pub fn comparar(&self, f: &Fecha) -> bool {
@@ -74,11 +351,416 @@ impl Fecha {
     }
 
     pub fn fecha_actual() -> Self {
-        let hoy = Local::now().date_naive();
-        Fecha {
-            dia: hoy.day(),
-            mes: hoy.month(),
-            año: hoy.year(),
+        Fecha::desde_naive(Local::now().date_naive())
+    }
+
+    // Patrones: "dd"/"d" dia, "mm"/"m" mes, "aaaa"/"aa" año. El resto se copia tal cual.
+    pub fn formatear(&self, patron: &str) -> String {
+        let mut resul = String::new();
+        let mut resto = patron;
+
+        while let Some(c) = resto.chars().next() {
+            if let Some(r) = resto.strip_prefix("aaaa") {
+                resul.push_str(&format!("{:04}", self.año));
+                resto = r;
+            } else if let Some(r) = resto.strip_prefix("aa") {
+                resul.push_str(&format!("{:02}", self.año.rem_euclid(100)));
+                resto = r;
+            } else if let Some(r) = resto.strip_prefix("dd") {
+                resul.push_str(&format!("{:02}", self.dia));
+                resto = r;
+            } else if let Some(r) = resto.strip_prefix("mm") {
+                resul.push_str(&format!("{:02}", self.mes));
+                resto = r;
+            } else if c == 'd' {
+                resul.push_str(&self.dia.to_string());
+                resto = &resto[1..];
+            } else if c == 'm' {
+                resul.push_str(&self.mes.to_string());
+                resto = &resto[1..];
+            } else {
+                resul.push(c);
+                resto = &resto[c.len_utf8()..];
+            }
         }
+        resul
+    }
+}
+
+impl Fecha {
+    // Dias desde 1970-01-01 (algoritmo days_from_civil). Al no pasar por chrono
+    // tambien funciona con fechas invalidas, sin paniquear.
+    fn dias_desde_epoca(&self) -> i64 {
+        let mes = self.mes as i64;
+        let año = self.año as i64 - if mes <= 2 { 1 } else { 0 };
+        let era = año.div_euclid(400);
+        let año_de_era = año - era * 400;
+        let mes_desde_marzo = if mes > 2 { mes - 3 } else { mes + 9 };
+        let dia_del_año = (153 * mes_desde_marzo + 2) / 5 + self.dia as i64 - 1;
+        let dia_de_era = año_de_era * 365 + año_de_era / 4 - año_de_era / 100 + dia_del_año;
+        era * 146097 + dia_de_era - 719468
+    }
+}
+
+impl Ord for Fecha {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.año, self.mes, self.dia).cmp(&(other.año, other.mes, other.dia))
+    }
+}
+
+impl PartialOrd for Fecha {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// a - b devuelve la cantidad de dias entre ambas fechas (negativa si a < b).
+impl Sub for &Fecha {
+    type Output = i64;
+
+    fn sub(self, other: &Fecha) -> i64 {
+        self.dias_desde_epoca() - other.dias_desde_epoca()
+    }
+}
+
+impl Sub for Fecha {
+    type Output = i64;
+
+    fn sub(self, other: Fecha) -> i64 {
+        &self - &other
+    }
+}
+
+impl fmt::Display for Fecha {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.formatear(Fecha::FORMATO_ARGENTINO))
+    }
+}
+
+impl FromStr for Fecha {
+    type Err = FechaError;
+
+    // Acepta ISO (2025-07-01) y argentino (01/07/2025 o 1/7/2025).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let texto = s.trim();
+        let error = || FechaError::FormatoInvalido(s.to_string());
+
+        let partes: Vec<&str> = if texto.contains('-') {
+            texto.split('-').collect()
+        } else {
+            texto.split('/').rev().collect()
+        };
+        if partes.len() != 3
+            || partes
+                .iter()
+                .any(|p| p.is_empty() || !p.chars().all(|c| c.is_ascii_digit()))
+        {
+            return Err(error());
+        }
+
+        let año: i32 = partes[0].parse().map_err(|_| error())?;
+        let mes: u32 = partes[1].parse().map_err(|_| error())?;
+        let dia: u32 = partes[2].parse().map_err(|_| error())?;
+        Fecha::try_new(dia, mes, año)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_try_new_valida() {
+        let f = Fecha::try_new(29, 2, 2024).unwrap();
+        assert_eq!(f, Fecha::new(29, 2, 2024));
+    }
+
+    #[test]
+    fn test_try_new_invalida() {
+        assert_eq!(
+            Fecha::try_new(29, 2, 2023).unwrap_err(),
+            FechaError::Invalida {
+                dia: 29,
+                mes: 2,
+                año: 2023
+            }
+        );
+        assert!(Fecha::try_new(0, 0, 0).is_err());
+        assert_eq!(
+            Fecha::try_new(32, 7, 2023).unwrap_err().to_string(),
+            "La fecha 32/7/2023 no es valida"
+        );
+    }
+
+    #[test]
+    fn test_sumar_dias_fecha_invalida_no_paniquea() {
+        let mut f = Fecha::new(31, 2, 2025);
+        f.sumar_dias(3);
+        f.restar_dias(3);
+        assert_eq!(f, Fecha::new(31, 2, 2025));
+        assert!(f.try_sumar_dias(3).is_err());
+    }
+
+    #[test]
+    fn test_try_sumar_dias_fuera_de_rango() {
+        let mut f = Fecha::new(1, 1, 2025);
+        assert_eq!(f.try_sumar_dias(i64::MAX), Err(FechaError::FueraDeRango));
+        assert_eq!(f.try_restar_dias(i64::MIN), Err(FechaError::FueraDeRango));
+        assert_eq!(f, Fecha::new(1, 1, 2025));
+        f.try_sumar_dias(31).unwrap();
+        assert_eq!(f, Fecha::new(1, 2, 2025));
+        f.try_restar_dias(32).unwrap();
+        assert_eq!(f, Fecha::new(31, 12, 2024));
+    }
+
+    #[test]
+    fn test_orden_total() {
+        let mut fechas = vec![
+            Fecha::new(1, 2, 2025),
+            Fecha::new(31, 1, 2025),
+            Fecha::new(15, 12, 2024),
+        ];
+        fechas.sort();
+        assert_eq!(
+            fechas,
+            vec![
+                Fecha::new(15, 12, 2024),
+                Fecha::new(31, 1, 2025),
+                Fecha::new(1, 2, 2025),
+            ]
+        );
+        assert!(Fecha::new(1, 2, 2025).es_mayor(&Fecha::new(31, 1, 2025)));
+        assert!(Fecha::new(31, 1, 2025).es_menor(&Fecha::new(1, 2, 2025)));
+    }
+
+    #[test]
+    fn test_orden_con_fechas_invalidas_es_simetrico() {
+        let valida = Fecha::new(1, 1, 2025);
+        let invalida = Fecha::new(32, 1, 2025);
+        assert!(invalida.es_mayor(&valida));
+        assert!(valida.es_menor(&invalida));
+        assert!(!valida.es_mayor(&invalida));
+        assert!(!invalida.es_menor(&valida));
+    }
+
+    #[test]
+    fn test_hash_y_eq() {
+        use std::collections::HashSet;
+        let mut set = HashSet::new();
+        set.insert(Fecha::new(1, 7, 2025));
+        set.insert("2025-07-01".parse::<Fecha>().unwrap());
+        assert_eq!(set.len(), 1);
+    }
+
+    #[test]
+    fn test_resta_de_fechas() {
+        assert_eq!(Fecha::new(1, 3, 2024) - Fecha::new(1, 2, 2024), 29);
+        assert_eq!(&Fecha::new(1, 1, 2025) - &Fecha::new(1, 1, 2024), 366);
+        assert_eq!(&Fecha::new(1, 1, 2024) - &Fecha::new(1, 1, 2025), -366);
+        assert_eq!(&Fecha::new(1, 1, 1970) - &Fecha::new(31, 12, 1969), 1);
+        // no paniquea con fechas invalidas
+        let _ = &Fecha::new(40, 13, 2025) - &Fecha::new(0, 0, 0);
+    }
+
+    #[test]
+    fn test_sumar_meses_ajusta_fin_de_mes() {
+        let mut f = Fecha::new(31, 1, 2025);
+        f.sumar_meses(1);
+        assert_eq!(f, Fecha::new(28, 2, 2025));
+
+        let mut f = Fecha::new(31, 1, 2024);
+        f.sumar_meses(1);
+        assert_eq!(f, Fecha::new(29, 2, 2024));
+
+        let mut f = Fecha::new(31, 3, 2025);
+        f.sumar_meses(-1);
+        assert_eq!(f, Fecha::new(28, 2, 2025));
+
+        let mut f = Fecha::new(15, 11, 2025);
+        f.sumar_meses(14);
+        assert_eq!(f, Fecha::new(15, 1, 2027));
+
+        let mut f = Fecha::new(15, 1, 2025);
+        f.sumar_meses(-13);
+        assert_eq!(f, Fecha::new(15, 12, 2023));
+    }
+
+    #[test]
+    fn test_sumar_años_bisiesto() {
+        let mut f = Fecha::new(29, 2, 2024);
+        f.sumar_años(1);
+        assert_eq!(f, Fecha::new(28, 2, 2025));
+
+        let mut f = Fecha::new(29, 2, 2024);
+        f.sumar_años(4);
+        assert_eq!(f, Fecha::new(29, 2, 2028));
+    }
+
+    #[test]
+    fn test_sumar_meses_errores() {
+        let mut f = Fecha::new(31, 2, 2025);
+        assert!(f.try_sumar_meses(1).is_err());
+        f.sumar_meses(1);
+        assert_eq!(f, Fecha::new(31, 2, 2025));
+
+        let mut f = Fecha::new(1, 1, 2025);
+        assert_eq!(f.try_sumar_años(i32::MAX), Err(FechaError::FueraDeRango));
+        assert_eq!(f.try_sumar_meses(i32::MAX), Err(FechaError::FueraDeRango));
+        assert_eq!(f, Fecha::new(1, 1, 2025));
+    }
+
+    #[test]
+    fn test_parse_iso_y_argentino() {
+        assert_eq!("2025-07-01".parse::<Fecha>(), Ok(Fecha::new(1, 7, 2025)));
+        assert_eq!("01/07/2025".parse::<Fecha>(), Ok(Fecha::new(1, 7, 2025)));
+        assert_eq!(" 1/7/2025 ".parse::<Fecha>(), Ok(Fecha::new(1, 7, 2025)));
+    }
+
+    #[test]
+    fn test_parse_errores() {
+        assert_eq!(
+            "2025/07".parse::<Fecha>(),
+            Err(FechaError::FormatoInvalido("2025/07".to_string()))
+        );
+        assert!("hoy".parse::<Fecha>().is_err());
+        assert!("2025-+7-01".parse::<Fecha>().is_err());
+        assert!("99999999999-01-01".parse::<Fecha>().is_err());
+        assert_eq!(
+            "31/04/2025".parse::<Fecha>(),
+            Err(FechaError::Invalida {
+                dia: 31,
+                mes: 4,
+                año: 2025
+            })
+        );
+    }
+
+    #[test]
+    fn test_display_y_formatear() {
+        let f = Fecha::new(1, 7, 2025);
+        assert_eq!(f.to_string(), "01/07/2025");
+        assert_eq!(f.formatear(Fecha::FORMATO_ISO), "2025-07-01");
+        assert_eq!(f.formatear("d/m/aa"), "1/7/25");
+        assert_eq!(f.formatear("aaaa.mm.dd"), "2025.07.01");
+        assert_eq!(Fecha::new(0, 0, 0).to_string(), "00/00/0000");
+    }
+
+    #[test]
+    fn test_años_hasta() {
+        let nacimiento = Fecha::new(15, 10, 2001);
+        assert_eq!(nacimiento.años_hasta(&Fecha::new(14, 10, 2025)), 23);
+        assert_eq!(nacimiento.años_hasta(&Fecha::new(15, 10, 2025)), 24);
+        assert_eq!(nacimiento.años_hasta(&Fecha::new(1, 1, 2001)), 0);
+        assert_eq!(nacimiento.años_hasta(&Fecha::new(15, 10, 2000)), -1);
+
+        let bisiesto = Fecha::new(29, 2, 2024);
+        assert_eq!(bisiesto.años_hasta(&Fecha::new(28, 2, 2025)), 0);
+        assert_eq!(bisiesto.años_hasta(&Fecha::new(1, 3, 2025)), 1);
+    }
+
+    #[test]
+    fn test_edad_desde() {
+        let nacimiento = Fecha::new(15, 10, 2001);
+        let hoy = Fecha::new(15, 10, 2025);
+        assert_eq!(Fecha::edad_desde(Some(&nacimiento), 40, &hoy), 24);
+        assert_eq!(Fecha::edad_desde(None, 40, &hoy), 40);
+        assert_eq!(Fecha::edad_desde(Some(&hoy), 40, &nacimiento), 0);
+        assert_eq!(
+            Fecha::edad_desde(Some(&Fecha::new(1, 1, 1)), 0, &hoy),
+            u8::MAX
+        );
+    }
+
+    #[test]
+    fn test_meses_hasta() {
+        let nacimiento = Fecha::new(1, 1, 2025);
+        assert_eq!(nacimiento.meses_hasta(&Fecha::new(1, 1, 2025)), 0.0);
+        assert_eq!(nacimiento.meses_hasta(&Fecha::new(1, 3, 2025)), 2.0);
+        // febrero tiene 28 dias
+        assert_eq!(nacimiento.meses_hasta(&Fecha::new(15, 2, 2025)), 1.5);
+        assert_eq!(Fecha::new(15, 2, 2025).meses_hasta(&nacimiento), -1.5);
+        assert_eq!(
+            Fecha::new(31, 1, 2025).meses_hasta(&Fecha::new(28, 2, 2025)),
+            1.0
+        );
+    }
+
+    #[test]
+    fn test_dia_semana() {
+        assert_eq!(Fecha::new(1, 7, 2025).dia_semana(), Ok(DiaSemana::Martes));
+        assert_eq!(Fecha::new(29, 2, 2024).dia_semana(), Ok(DiaSemana::Jueves));
+        assert!(
+            Fecha::new(5, 7, 2025)
+                .dia_semana()
+                .unwrap()
+                .es_fin_de_semana()
+        );
+        assert_eq!(DiaSemana::Miercoles.to_string(), "miércoles");
+        assert!(Fecha::new(29, 2, 2025).dia_semana().is_err());
+    }
+
+    #[test]
+    fn test_semana_iso() {
+        assert_eq!(Fecha::new(1, 7, 2025).semana_iso(), Ok((2025, 27)));
+        // el 29/12/2025 es lunes de la semana 1 de 2026
+        assert_eq!(Fecha::new(29, 12, 2025).semana_iso(), Ok((2026, 1)));
+        // el 1/1/2021 es viernes de la semana 53 de 2020
+        assert_eq!(Fecha::new(1, 1, 2021).semana_iso(), Ok((2020, 53)));
+        assert!(Fecha::new(0, 1, 2021).semana_iso().is_err());
+    }
+
+    #[test]
+    fn test_dia_del_año_y_trimestre() {
+        assert_eq!(Fecha::new(1, 1, 2025).dia_del_año(), Ok(1));
+        assert_eq!(Fecha::new(31, 12, 2024).dia_del_año(), Ok(366));
+        assert_eq!(Fecha::new(31, 3, 2025).trimestre(), 1);
+        assert_eq!(Fecha::new(1, 4, 2025).trimestre(), 2);
+        assert_eq!(Fecha::new(25, 12, 2025).trimestre(), 4);
+        assert_eq!(Fecha::new(1, 13, 2025).trimestre(), 0);
+    }
+
+    #[test]
+    fn test_limites_de_periodo() {
+        let f = Fecha::new(14, 8, 2024);
+        assert_eq!(f.primer_dia_del_mes(), Fecha::new(1, 8, 2024));
+        assert_eq!(f.ultimo_dia_del_mes(), Fecha::new(31, 8, 2024));
+        assert_eq!(f.primer_dia_del_trimestre(), Fecha::new(1, 7, 2024));
+        assert_eq!(f.ultimo_dia_del_trimestre(), Fecha::new(30, 9, 2024));
+        assert_eq!(f.primer_dia_del_año(), Fecha::new(1, 1, 2024));
+        assert_eq!(f.ultimo_dia_del_año(), Fecha::new(31, 12, 2024));
+        assert_eq!(
+            Fecha::new(10, 2, 2024).ultimo_dia_del_trimestre(),
+            Fecha::new(31, 3, 2024)
+        );
+        assert_eq!(
+            Fecha::new(10, 2, 2024).ultimo_dia_del_mes(),
+            Fecha::new(29, 2, 2024)
+        );
+        // con mes invalido no paniquea, devuelve una fecha invalida
+        assert!(
+            !Fecha::new(1, 13, 2024)
+                .primer_dia_del_trimestre()
+                .es_fecha_valida()
+        );
+    }
+
+    #[test]
+    fn test_formato_largo() {
+        assert_eq!(
+            Fecha::new(1, 7, 2025).formato_largo(),
+            Ok("martes 1 de julio de 2025".to_string())
+        );
+        assert_eq!(
+            Fecha::new(20, 9, 2025).formato_largo(),
+            Ok("sábado 20 de septiembre de 2025".to_string())
+        );
+        assert!(Fecha::new(31, 9, 2025).formato_largo().is_err());
+    }
+
+    #[test]
+    fn test_display_parse_ida_y_vuelta() {
+        let f = Fecha::new(15, 10, 2001);
+        assert_eq!(f.to_string().parse::<Fecha>(), Ok(f.clone()));
+        assert_eq!(f.formatear(Fecha::FORMATO_ISO).parse::<Fecha>(), Ok(f));
     }
 }
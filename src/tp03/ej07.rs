@@ -1,5 +1,7 @@
 #![allow(dead_code, unused_variables)]
 
+use super::consulta_autos::{ConsultaAutos, DatosAuto};
+
 pub struct ConcesionarioAuto {
     nombre: String,
     direccion: String,
@@ -16,7 +18,7 @@ pub struct Auto {
     color: Color,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Color {
     ROJO,
     VERDE,
@@ -65,6 +67,10 @@ impl ConcesionarioAuto {
         }
         None
     }
+
+    pub fn consultar(&self) -> ConsultaAutos<'_, Auto> {
+        ConsultaAutos::new(&self.autos)
+    }
 }
 
 impl Auto {
@@ -103,3 +109,31 @@ impl Auto {
         self.precio_bruto * (1.0 + tasa)
     }
 }
+
+impl DatosAuto for Auto {
+    type Color = Color;
+
+    fn marca(&self) -> &str {
+        &self.marca
+    }
+
+    fn modelo(&self) -> &str {
+        &self.modelo
+    }
+
+    fn año(&self) -> u32 {
+        self.año
+    }
+
+    fn color(&self) -> &Color {
+        &self.color
+    }
+
+    fn precio_bruto(&self) -> f64 {
+        self.precio_bruto
+    }
+
+    fn precio_final(&self) -> f64 {
+        self.calcular_precio()
+    }
+}
@@ -15,6 +15,7 @@ pub struct Veterinaria {
 pub struct Mascota {
     nombre: String,
     edad: u8,
+    fecha_nacimiento: Option<Fecha>,
     tipo: TipoAnimal,
     dueño: Cliente,
 }
@@ -138,11 +139,37 @@ impl Mascota {
         Mascota {
             nombre,
             edad,
+            fecha_nacimiento: None,
             tipo,
             dueño: cliente,
         }
     }
 
+    pub fn con_fecha_nacimiento(mut self, fecha_nacimiento: Fecha) -> Self {
+        self.fecha_nacimiento = Some(fecha_nacimiento);
+        self
+    }
+
+    pub fn edad_al(&self, fecha: &Fecha) -> u8 {
+        Fecha::edad_desde(self.fecha_nacimiento.as_ref(), self.edad, fecha)
+    }
+
+    pub fn edad_actual(&self) -> u8 {
+        self.edad_al(&Fecha::fecha_actual())
+    }
+
+    // Para cachorros: edad en meses con fraccion. Sin fecha de nacimiento se estima desde `edad`.
+    pub fn edad_en_meses_al(&self, fecha: &Fecha) -> f64 {
+        match &self.fecha_nacimiento {
+            Some(nacimiento) => nacimiento.meses_hasta(fecha).max(0.0),
+            None => self.edad as f64 * 12.0,
+        }
+    }
+
+    pub fn edad_en_meses(&self) -> f64 {
+        self.edad_en_meses_al(&Fecha::fecha_actual())
+    }
+
     pub fn comparar(&self, m: &Mascota) -> bool {
         m.nombre == self.nombre && m.dueño.comparar(&self.dueño)
     }
@@ -200,3 +227,32 @@ impl RegistroAtencion {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cliente() -> Cliente {
+        Cliente::new("calle 1".to_string(), "Tao".to_string(), "221".to_string())
+    }
+
+    #[test]
+    fn test_edad_mascota() {
+        let m = Mascota::new("bobi".to_string(), 3, TipoAnimal::Perro, cliente());
+        assert_eq!(m.edad_al(&Fecha::new(1, 1, 2100)), 3);
+        assert_eq!(m.edad_en_meses_al(&Fecha::new(1, 1, 2100)), 36.0);
+
+        let m = m.con_fecha_nacimiento(Fecha::new(10, 5, 2020));
+        assert_eq!(m.edad_al(&Fecha::new(9, 5, 2025)), 4);
+        assert_eq!(m.edad_al(&Fecha::new(10, 5, 2025)), 5);
+    }
+
+    #[test]
+    fn test_edad_en_meses_cachorro() {
+        let m = Mascota::new("michi".to_string(), 0, TipoAnimal::Gato, cliente())
+            .con_fecha_nacimiento(Fecha::new(1, 1, 2025));
+        assert_eq!(m.edad_al(&Fecha::new(15, 2, 2025)), 0);
+        assert_eq!(m.edad_en_meses_al(&Fecha::new(15, 2, 2025)), 1.5);
+        assert_eq!(m.edad_en_meses_al(&Fecha::new(1, 12, 2024)), 0.0);
+    }
+}
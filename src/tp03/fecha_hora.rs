@@ -0,0 +1,384 @@
+#![allow(dead_code, unused_variables)]
+
+use super::ej03::{Fecha, FechaError};
+use chrono::{
+    DateTime, Duration, FixedOffset, Local, NaiveTime, SecondsFormat, TimeZone, Timelike, Utc,
+};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+
+// Desplazamiento respecto de UTC en segundos (UTC-03:00).
+pub const DESPLAZAMIENTO_ARGENTINA: i32 = -3 * 3600;
+
+// Un instante en UTC mas el desplazamiento con el que se registro. Dos FechaHora son
+// iguales si representan el mismo instante, aunque se hayan registrado en zonas distintas.
+#[derive(Debug, Clone, Copy)]
+pub struct FechaHora {
+    instante: DateTime<Utc>,
+    desplazamiento: FixedOffset,
+}
+
+impl FechaHora {
+    // Hora local en la zona `desplazamiento` (en segundos respecto de UTC).
+    pub fn new(
+        fecha: &Fecha,
+        hora: u32,
+        minuto: u32,
+        segundo: u32,
+        desplazamiento: i32,
+    ) -> Result<Self, FechaError> {
+        let zona = zona(desplazamiento)?;
+        let tiempo =
+            NaiveTime::from_hms_opt(hora, minuto, segundo).ok_or(FechaError::HoraInvalida {
+                hora,
+                minuto,
+                segundo,
+            })?;
+        let local = fecha.a_naive()?.and_time(tiempo);
+        let instante = zona
+            .from_local_datetime(&local)
+            .single()
+            .ok_or(FechaError::FueraDeRango)?
+            .with_timezone(&Utc);
+
+        Ok(FechaHora {
+            instante,
+            desplazamiento: zona,
+        })
+    }
+
+    pub fn inicio_del_dia(fecha: &Fecha, desplazamiento: i32) -> Result<Self, FechaError> {
+        FechaHora::new(fecha, 0, 0, 0, desplazamiento)
+    }
+
+    // Segundos desde 1970-01-01T00:00:00Z.
+    pub fn desde_timestamp(segundos: i64, desplazamiento: i32) -> Result<Self, FechaError> {
+        Ok(FechaHora {
+            instante: DateTime::from_timestamp(segundos, 0).ok_or(FechaError::FueraDeRango)?,
+            desplazamiento: zona(desplazamiento)?,
+        })
+    }
+
+    // Instante actual con el desplazamiento de la zona local del sistema.
+    pub fn ahora() -> Self {
+        let local = Local::now();
+        FechaHora {
+            instante: local.with_timezone(&Utc),
+            desplazamiento: *local.offset(),
+        }
+    }
+
+    fn local(&self) -> DateTime<FixedOffset> {
+        self.instante.with_timezone(&self.desplazamiento)
+    }
+
+    pub fn timestamp(&self) -> i64 {
+        self.instante.timestamp()
+    }
+
+    pub fn desplazamiento(&self) -> i32 {
+        self.desplazamiento.local_minus_utc()
+    }
+
+    // Fecha del calendario en la zona en la que se registro.
+    pub fn fecha(&self) -> Fecha {
+        Fecha::desde_naive(self.local().date_naive())
+    }
+
+    pub fn fecha_utc(&self) -> Fecha {
+        Fecha::desde_naive(self.instante.date_naive())
+    }
+
+    pub fn hora(&self) -> u32 {
+        self.local().hour()
+    }
+
+    pub fn minuto(&self) -> u32 {
+        self.local().minute()
+    }
+
+    pub fn segundo(&self) -> u32 {
+        self.local().second()
+    }
+
+    // El mismo instante visto desde otra zona.
+    pub fn en_desplazamiento(&self, desplazamiento: i32) -> Result<Self, FechaError> {
+        Ok(FechaHora {
+            instante: self.instante,
+            desplazamiento: zona(desplazamiento)?,
+        })
+    }
+
+    // Ante un error queda sin cambios, igual que Fecha::sumar_dias.
+    pub fn sumar_segundos(&mut self, segundos: i64) {
+        let _ = self.try_sumar_segundos(segundos);
+    }
+
+    pub fn sumar_dias(&mut self, dias: i64) {
+        let _ = dias
+            .checked_mul(86400)
+            .map(|segundos| self.try_sumar_segundos(segundos));
+    }
+
+    pub fn sumar_meses(&mut self, meses: i32) {
+        let _ = self.try_sumar_meses(meses);
+    }
+
+    pub fn try_sumar_segundos(&mut self, segundos: i64) -> Result<(), FechaError> {
+        self.instante = Duration::try_seconds(segundos)
+            .and_then(|d| self.instante.checked_add_signed(d))
+            .ok_or(FechaError::FueraDeRango)?;
+        Ok(())
+    }
+
+    // Suma meses a la fecha local conservando la hora (mismo ajuste de fin de mes que Fecha).
+    pub fn try_sumar_meses(&mut self, meses: i32) -> Result<(), FechaError> {
+        let mut fecha = self.fecha();
+        fecha.try_sumar_meses(meses)?;
+        *self = FechaHora::new(
+            &fecha,
+            self.hora(),
+            self.minuto(),
+            self.segundo(),
+            self.desplazamiento(),
+        )?;
+        Ok(())
+    }
+
+    // "1 de julio de 2025, 10:30 (UTC-03:00)"
+    pub fn formato_largo(&self) -> String {
+        let fecha = self.fecha();
+        format!(
+            "{} de {} de {}, {:02}:{:02} (UTC{})",
+            fecha.dia,
+            fecha.nombre_mes(),
+            fecha.año,
+            self.hora(),
+            self.minuto(),
+            self.desplazamiento
+        )
+    }
+
+    pub fn a_rfc3339(&self) -> String {
+        self.local().to_rfc3339_opts(SecondsFormat::AutoSi, false)
+    }
+}
+
+fn zona(desplazamiento: i32) -> Result<FixedOffset, FechaError> {
+    FixedOffset::east_opt(desplazamiento).ok_or(FechaError::DesplazamientoInvalido(desplazamiento))
+}
+
+impl TryFrom<&Fecha> for FechaHora {
+    type Error = FechaError;
+
+    // Medianoche en Argentina.
+    fn try_from(fecha: &Fecha) -> Result<Self, FechaError> {
+        FechaHora::inicio_del_dia(fecha, DESPLAZAMIENTO_ARGENTINA)
+    }
+}
+
+impl From<&FechaHora> for Fecha {
+    fn from(fecha_hora: &FechaHora) -> Self {
+        fecha_hora.fecha()
+    }
+}
+
+impl PartialEq for FechaHora {
+    fn eq(&self, other: &Self) -> bool {
+        self.instante == other.instante
+    }
+}
+
+impl Eq for FechaHora {}
+
+impl Hash for FechaHora {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.instante.hash(state);
+    }
+}
+
+impl Ord for FechaHora {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.instante.cmp(&other.instante)
+    }
+}
+
+impl PartialOrd for FechaHora {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// "01/07/2025 10:30:00 -03:00"
+impl fmt::Display for FechaHora {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {:02}:{:02}:{:02} {}",
+            self.fecha(),
+            self.hora(),
+            self.minuto(),
+            self.segundo(),
+            self.desplazamiento
+        )
+    }
+}
+
+// Acepta RFC 3339 (2025-07-01T10:30:00-03:00), que es como se serializa.
+impl FromStr for FechaHora {
+    type Err = FechaError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let dt = DateTime::parse_from_rfc3339(s.trim())
+            .map_err(|_| FechaError::FormatoInvalido(s.to_string()))?;
+        Ok(FechaHora {
+            instante: dt.with_timezone(&Utc),
+            desplazamiento: *dt.offset(),
+        })
+    }
+}
+
+impl Serialize for FechaHora {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.a_rfc3339())
+    }
+}
+
+impl<'de> Deserialize<'de> for FechaHora {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let texto = String::deserialize(deserializer)?;
+        texto.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tp03::reloj::{Reloj, RelojFijo};
+
+    fn fh(dia: u32, mes: u32, año: i32, hora: u32, minuto: u32) -> FechaHora {
+        FechaHora::new(
+            &Fecha::new(dia, mes, año),
+            hora,
+            minuto,
+            0,
+            DESPLAZAMIENTO_ARGENTINA,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_new_y_errores() {
+        let f = fh(1, 7, 2025, 10, 30);
+        assert_eq!(f.fecha(), Fecha::new(1, 7, 2025));
+        assert_eq!((f.hora(), f.minuto(), f.segundo()), (10, 30, 0));
+        assert_eq!(f.desplazamiento(), -10800);
+
+        assert_eq!(
+            FechaHora::new(&Fecha::new(1, 7, 2025), 24, 0, 0, 0),
+            Err(FechaError::HoraInvalida {
+                hora: 24,
+                minuto: 0,
+                segundo: 0
+            })
+        );
+        assert!(FechaHora::new(&Fecha::new(31, 6, 2025), 0, 0, 0, 0).is_err());
+        assert_eq!(
+            FechaHora::new(&Fecha::new(1, 7, 2025), 0, 0, 0, 90000),
+            Err(FechaError::DesplazamientoInvalido(90000))
+        );
+    }
+
+    #[test]
+    fn test_fecha_depende_de_la_zona() {
+        // 22:00 en Argentina ya es el dia siguiente en UTC
+        let f = fh(31, 12, 2024, 22, 0);
+        assert_eq!(f.fecha(), Fecha::new(31, 12, 2024));
+        assert_eq!(f.fecha_utc(), Fecha::new(1, 1, 2025));
+
+        let utc = f.en_desplazamiento(0).unwrap();
+        assert_eq!(utc, f);
+        assert_eq!(utc.fecha(), Fecha::new(1, 1, 2025));
+        assert_eq!(utc.hora(), 1);
+    }
+
+    #[test]
+    fn test_orden_por_instante() {
+        let a = fh(1, 7, 2025, 9, 0);
+        let b = fh(1, 7, 2025, 9, 1);
+        let c = b.en_desplazamiento(2 * 3600).unwrap();
+        assert!(a < b);
+        assert_eq!(b.cmp(&c), Ordering::Equal);
+        // las 11:01 de UTC+2 son las 6:01 de Argentina
+        assert!(a.en_desplazamiento(2 * 3600).unwrap() < b);
+    }
+
+    #[test]
+    fn test_sumar() {
+        let mut f = fh(31, 1, 2025, 23, 30);
+        f.sumar_segundos(3600);
+        assert_eq!(f, fh(1, 2, 2025, 0, 30));
+        f.sumar_dias(-1);
+        assert_eq!(f, fh(31, 1, 2025, 0, 30));
+        f.sumar_meses(1);
+        assert_eq!(f, fh(28, 2, 2025, 0, 30));
+        assert_eq!(
+            f.try_sumar_segundos(i64::MAX),
+            Err(FechaError::FueraDeRango)
+        );
+        assert_eq!(f, fh(28, 2, 2025, 0, 30));
+    }
+
+    #[test]
+    fn test_formatos() {
+        let f = fh(1, 7, 2025, 9, 5);
+        assert_eq!(f.to_string(), "01/07/2025 09:05:00 -03:00");
+        assert_eq!(f.formato_largo(), "1 de julio de 2025, 09:05 (UTC-03:00)");
+        assert_eq!(f.a_rfc3339(), "2025-07-01T09:05:00-03:00");
+    }
+
+    #[test]
+    fn test_parse_y_serde() {
+        let f = fh(1, 7, 2025, 9, 5);
+        assert_eq!("2025-07-01T09:05:00-03:00".parse::<FechaHora>(), Ok(f));
+        assert_eq!("2025-07-01T12:05:00Z".parse::<FechaHora>(), Ok(f));
+        assert!(matches!(
+            "01/07/2025".parse::<FechaHora>(),
+            Err(FechaError::FormatoInvalido(_))
+        ));
+
+        let json = serde_json::to_string(&f).unwrap();
+        assert_eq!(json, "\"2025-07-01T09:05:00-03:00\"");
+        let leida: FechaHora = serde_json::from_str(&json).unwrap();
+        assert_eq!(leida, f);
+        assert_eq!(leida.desplazamiento(), f.desplazamiento());
+        assert!(serde_json::from_str::<FechaHora>("\"ayer\"").is_err());
+    }
+
+    #[test]
+    fn test_conversion_con_fecha() {
+        let f = FechaHora::try_from(&Fecha::new(9, 7, 2025)).unwrap();
+        assert_eq!((f.hora(), f.minuto()), (0, 0));
+        assert_eq!(Fecha::from(&f), Fecha::new(9, 7, 2025));
+        assert!(FechaHora::try_from(&Fecha::new(30, 2, 2025)).is_err());
+
+        let g = FechaHora::desde_timestamp(0, DESPLAZAMIENTO_ARGENTINA).unwrap();
+        assert_eq!(g.fecha(), Fecha::new(31, 12, 1969));
+        assert_eq!(g.timestamp(), 0);
+    }
+
+    #[test]
+    fn test_ahora_coincide_con_fecha_actual() {
+        // una sola lectura del reloj del sistema: fecha_actual es la fecha local de ese instante
+        let reloj = RelojFijo::en(FechaHora::ahora());
+        let local = Local.timestamp_opt(reloj.ahora().timestamp(), 0).unwrap();
+        assert_eq!(
+            reloj.ahora().desplazamiento(),
+            local.offset().local_minus_utc()
+        );
+        assert_eq!(reloj.hoy(), Fecha::desde_naive(local.date_naive()));
+    }
+}
@@ -1,3 +1,6 @@
+pub mod calendario;
+pub mod compartido;
+pub mod consulta_autos;
 pub mod ej01;
 pub mod ej02;
 pub mod ej03;
@@ -8,7 +11,11 @@ pub mod ej07;
 pub mod ej08;
 pub mod ej09;
 pub mod ej10;
+pub mod fecha_hora;
 pub mod gustavo_lopez_v1;
+pub mod rango_fechas;
+pub mod recurrencia;
+pub mod reloj;
 
 // #[cfg(test)]
 // mod tests;
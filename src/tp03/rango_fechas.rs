@@ -0,0 +1,252 @@
+#![allow(dead_code, unused_variables)]
+
+use super::ej03::{Fecha, FechaError};
+use super::recurrencia::{FinRecurrencia, Frecuencia, IntoIterRecurrencia, Recurrencia};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+// Rango de dias. Los limites exclusivos se normalizan al construirlo, asi que internamente
+// siempre es [primero, ultimo]; si primero > ultimo el rango es vacio.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RangoFechas {
+    primero: Fecha,
+    ultimo: Fecha,
+}
+
+impl RangoFechas {
+    pub fn new(
+        desde: &Fecha,
+        incluye_desde: bool,
+        hasta: &Fecha,
+        incluye_hasta: bool,
+    ) -> Result<Self, FechaError> {
+        let mut primero = Fecha::try_new(desde.dia, desde.mes, desde.año)?;
+        let mut ultimo = Fecha::try_new(hasta.dia, hasta.mes, hasta.año)?;
+        if !incluye_desde {
+            primero.try_sumar_dias(1)?;
+        }
+        if !incluye_hasta {
+            ultimo.try_restar_dias(1)?;
+        }
+        Ok(RangoFechas { primero, ultimo })
+    }
+
+    // [desde, hasta]
+    pub fn cerrado(desde: &Fecha, hasta: &Fecha) -> Result<Self, FechaError> {
+        RangoFechas::new(desde, true, hasta, true)
+    }
+
+    // [desde, hasta)
+    pub fn semiabierto(desde: &Fecha, hasta: &Fecha) -> Result<Self, FechaError> {
+        RangoFechas::new(desde, true, hasta, false)
+    }
+
+    pub fn mes(mes: u32, año: i32) -> Result<Self, FechaError> {
+        let primero = Fecha::try_new(1, mes, año)?;
+        let ultimo = Fecha::new(primero.dias_del_mes(), mes, año);
+        Ok(RangoFechas { primero, ultimo })
+    }
+
+    pub fn es_vacio(&self) -> bool {
+        self.primero > self.ultimo
+    }
+
+    pub fn primero(&self) -> Option<&Fecha> {
+        (!self.es_vacio()).then_some(&self.primero)
+    }
+
+    pub fn ultimo(&self) -> Option<&Fecha> {
+        (!self.es_vacio()).then_some(&self.ultimo)
+    }
+
+    pub fn cantidad_dias(&self) -> i64 {
+        (&self.ultimo - &self.primero + 1).max(0)
+    }
+
+    pub fn contiene(&self, fecha: &Fecha) -> bool {
+        self.primero <= *fecha && *fecha <= self.ultimo
+    }
+
+    pub fn se_superpone(&self, otro: &RangoFechas) -> bool {
+        self.interseccion(otro).is_some()
+    }
+
+    pub fn interseccion(&self, otro: &RangoFechas) -> Option<RangoFechas> {
+        let rango = RangoFechas {
+            primero: self.primero.clone().max(otro.primero.clone()),
+            ultimo: self.ultimo.clone().min(otro.ultimo.clone()),
+        };
+        (!rango.es_vacio()).then_some(rango)
+    }
+
+    pub fn dias(&self) -> IntoIterRecurrencia {
+        self.iterar(Frecuencia::Diaria)
+    }
+
+    // Una fecha cada 7 dias empezando por el primer dia del rango.
+    pub fn semanas(&self) -> IntoIterRecurrencia {
+        self.iterar(Frecuencia::Semanal)
+    }
+
+    // El mismo dia de cada mes empezando por el primer dia del rango.
+    pub fn meses(&self) -> IntoIterRecurrencia {
+        self.iterar(Frecuencia::Mensual)
+    }
+
+    fn iterar(&self, frecuencia: Frecuencia) -> IntoIterRecurrencia {
+        let fin = if self.es_vacio() {
+            FinRecurrencia::Cantidad(0)
+        } else {
+            FinRecurrencia::Hasta(self.ultimo.clone())
+        };
+        Recurrencia::new(self.primero.clone(), frecuencia, 1, fin).into_iter()
+    }
+
+    // Parte el rango en los tramos de cada mes calendario que toca.
+    pub fn dividir_por_mes(&self) -> Vec<RangoFechas> {
+        let mut tramos = vec![];
+        if self.es_vacio() {
+            return tramos;
+        }
+
+        let mut inicio_mes = Fecha::new(1, self.primero.mes, self.primero.año);
+        while inicio_mes <= self.ultimo {
+            if let Ok(mes) = RangoFechas::mes(inicio_mes.mes, inicio_mes.año)
+                && let Some(tramo) = self.interseccion(&mes)
+            {
+                tramos.push(tramo);
+            }
+            if inicio_mes.try_sumar_meses(1).is_err() {
+                break;
+            }
+        }
+        tramos
+    }
+}
+
+impl fmt::Display for RangoFechas {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.es_vacio() {
+            write!(f, "[]")
+        } else {
+            write!(f, "[{} - {}]", self.primero, self.ultimo)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rango(desde: (u32, u32, i32), hasta: (u32, u32, i32)) -> RangoFechas {
+        RangoFechas::cerrado(
+            &Fecha::new(desde.0, desde.1, desde.2),
+            &Fecha::new(hasta.0, hasta.1, hasta.2),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_limites_inclusivos_y_exclusivos() {
+        let desde = Fecha::new(1, 7, 2025);
+        let hasta = Fecha::new(10, 7, 2025);
+
+        let cerrado = RangoFechas::cerrado(&desde, &hasta).unwrap();
+        assert!(cerrado.contiene(&desde));
+        assert!(cerrado.contiene(&hasta));
+        assert_eq!(cerrado.cantidad_dias(), 10);
+
+        let semiabierto = RangoFechas::semiabierto(&desde, &hasta).unwrap();
+        assert!(semiabierto.contiene(&desde));
+        assert!(!semiabierto.contiene(&hasta));
+        assert_eq!(semiabierto.cantidad_dias(), 9);
+
+        let abierto = RangoFechas::new(&desde, false, &hasta, false).unwrap();
+        assert!(!abierto.contiene(&desde));
+        assert_eq!(abierto.primero(), Some(&Fecha::new(2, 7, 2025)));
+        assert_eq!(abierto.ultimo(), Some(&Fecha::new(9, 7, 2025)));
+    }
+
+    #[test]
+    fn test_rango_vacio_e_invalido() {
+        let dia = Fecha::new(1, 7, 2025);
+        let vacio = RangoFechas::semiabierto(&dia, &dia).unwrap();
+        assert!(vacio.es_vacio());
+        assert_eq!(vacio.cantidad_dias(), 0);
+        assert_eq!(vacio.primero(), None);
+        assert!(!vacio.contiene(&dia));
+        assert_eq!(vacio.dias().count(), 0);
+        assert!(vacio.dividir_por_mes().is_empty());
+        assert_eq!(vacio.to_string(), "[]");
+
+        assert!(RangoFechas::cerrado(&Fecha::new(31, 6, 2025), &dia).is_err());
+        assert!(RangoFechas::mes(13, 2025).is_err());
+    }
+
+    #[test]
+    fn test_superposicion_e_interseccion() {
+        let julio = RangoFechas::mes(7, 2025).unwrap();
+        let vacaciones = rango((20, 7, 2025), (3, 8, 2025));
+        let agosto = RangoFechas::mes(8, 2025).unwrap();
+
+        assert!(julio.se_superpone(&vacaciones));
+        assert!(!julio.se_superpone(&agosto));
+        assert_eq!(
+            julio.interseccion(&vacaciones),
+            Some(rango((20, 7, 2025), (31, 7, 2025)))
+        );
+        assert_eq!(julio.interseccion(&agosto), None);
+
+        // rangos que solo se tocan en un dia
+        let a = rango((1, 7, 2025), (10, 7, 2025));
+        let b = rango((10, 7, 2025), (15, 7, 2025));
+        assert_eq!(a.interseccion(&b).unwrap().cantidad_dias(), 1);
+    }
+
+    #[test]
+    fn test_iterar_por_dia_semana_y_mes() {
+        let r = rango((30, 12, 2024), (2, 1, 2025));
+        assert_eq!(
+            r.dias().collect::<Vec<Fecha>>(),
+            vec![
+                Fecha::new(30, 12, 2024),
+                Fecha::new(31, 12, 2024),
+                Fecha::new(1, 1, 2025),
+                Fecha::new(2, 1, 2025),
+            ]
+        );
+
+        let r = rango((1, 7, 2025), (31, 7, 2025));
+        assert_eq!(r.semanas().count(), 5);
+        assert_eq!(r.semanas().last(), Some(Fecha::new(29, 7, 2025)));
+
+        let r = rango((31, 1, 2025), (30, 4, 2025));
+        assert_eq!(
+            r.meses().collect::<Vec<Fecha>>(),
+            vec![
+                Fecha::new(31, 1, 2025),
+                Fecha::new(28, 2, 2025),
+                Fecha::new(31, 3, 2025),
+                Fecha::new(30, 4, 2025),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_dividir_por_mes() {
+        let r = rango((20, 11, 2024), (10, 2, 2025));
+        let tramos = r.dividir_por_mes();
+        assert_eq!(
+            tramos,
+            vec![
+                rango((20, 11, 2024), (30, 11, 2024)),
+                rango((1, 12, 2024), (31, 12, 2024)),
+                rango((1, 1, 2025), (31, 1, 2025)),
+                rango((1, 2, 2025), (10, 2, 2025)),
+            ]
+        );
+        let total: i64 = tramos.iter().map(|t| t.cantidad_dias()).sum();
+        assert_eq!(total, r.cantidad_dias());
+        assert_eq!(tramos[0].to_string(), "[20/11/2024 - 30/11/2024]");
+    }
+}
@@ -0,0 +1,301 @@
+#![allow(dead_code, unused_variables)]
+
+use super::ej03::Fecha;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Frecuencia {
+    Diaria,
+    Semanal,
+    Mensual,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum FinRecurrencia {
+    Cantidad(u32),
+    Hasta(Fecha),
+}
+
+// Regla al estilo RRULE: desde `inicio`, cada `intervalo` dias/semanas/meses hasta `fin`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "RecurrenciaGuardada")]
+pub struct Recurrencia {
+    inicio: Fecha,
+    frecuencia: Frecuencia,
+    intervalo: u32,
+    fin: FinRecurrencia,
+}
+
+// Los mismos campos, para revisar el intervalo al leer: con 0 todas las ocurrencias caen en
+// el inicio y buscar una posterior no termina nunca.
+#[derive(Deserialize)]
+struct RecurrenciaGuardada {
+    inicio: Fecha,
+    frecuencia: Frecuencia,
+    intervalo: u32,
+    fin: FinRecurrencia,
+}
+
+impl TryFrom<RecurrenciaGuardada> for Recurrencia {
+    type Error = String;
+
+    fn try_from(guardada: RecurrenciaGuardada) -> Result<Self, Self::Error> {
+        if guardada.intervalo == 0 {
+            return Err("el intervalo de una recurrencia no puede ser 0".to_string());
+        }
+        Ok(Recurrencia {
+            inicio: guardada.inicio,
+            frecuencia: guardada.frecuencia,
+            intervalo: guardada.intervalo,
+            fin: guardada.fin,
+        })
+    }
+}
+
+pub struct IterRecurrencia<'a> {
+    recurrencia: &'a Recurrencia,
+    indice: u32,
+}
+
+pub struct IntoIterRecurrencia {
+    recurrencia: Recurrencia,
+    indice: u32,
+}
+
+impl Recurrencia {
+    // Un intervalo de 0 se toma como 1.
+    pub fn new(inicio: Fecha, frecuencia: Frecuencia, intervalo: u32, fin: FinRecurrencia) -> Self {
+        Recurrencia {
+            inicio,
+            frecuencia,
+            intervalo: intervalo.max(1),
+            fin,
+        }
+    }
+
+    pub fn cada_n_meses(inicio: Fecha, meses: u32, fin: FinRecurrencia) -> Self {
+        Recurrencia::new(inicio, Frecuencia::Mensual, meses, fin)
+    }
+
+    pub fn inicio(&self) -> &Fecha {
+        &self.inicio
+    }
+
+    pub fn frecuencia(&self) -> &Frecuencia {
+        &self.frecuencia
+    }
+
+    pub fn intervalo(&self) -> u32 {
+        self.intervalo
+    }
+
+    pub fn fin(&self) -> &FinRecurrencia {
+        &self.fin
+    }
+
+    pub fn iter(&self) -> IterRecurrencia<'_> {
+        IterRecurrencia {
+            recurrencia: self,
+            indice: 0,
+        }
+    }
+
+    // Primera ocurrencia estrictamente posterior a `fecha`.
+    pub fn siguiente_a(&self, fecha: &Fecha) -> Option<Fecha> {
+        self.iter().find(|f| f > fecha)
+    }
+
+    // Ocurrencia numero `indice` (desde 0) o None si la regla ya termino.
+    fn enesima(&self, indice: u32) -> Option<Fecha> {
+        if let FinRecurrencia::Cantidad(cant) = self.fin
+            && indice >= cant
+        {
+            return None;
+        }
+
+        let fecha = self.ocurrencia(indice)?;
+        if let FinRecurrencia::Hasta(hasta) = &self.fin
+            && fecha > *hasta
+        {
+            return None;
+        }
+        Some(fecha)
+    }
+
+    // Cada ocurrencia se calcula desde el inicio, asi el ajuste de fin de mes no se
+    // arrastra (31/01, 28/02, 31/03 y no 31/01, 28/02, 28/03).
+    fn ocurrencia(&self, indice: u32) -> Option<Fecha> {
+        let pasos = indice as i64 * self.intervalo as i64;
+        let mut f = self.inicio.clone();
+        match self.frecuencia {
+            Frecuencia::Diaria => f.try_sumar_dias(pasos).ok()?,
+            Frecuencia::Semanal => f.try_sumar_dias(pasos.checked_mul(7)?).ok()?,
+            Frecuencia::Mensual => f.try_sumar_meses(i32::try_from(pasos).ok()?).ok()?,
+        }
+        Some(f)
+    }
+}
+
+impl Iterator for IterRecurrencia<'_> {
+    type Item = Fecha;
+
+    fn next(&mut self) -> Option<Fecha> {
+        let fecha = self.recurrencia.enesima(self.indice)?;
+        self.indice += 1;
+        Some(fecha)
+    }
+}
+
+impl<'a> IntoIterator for &'a Recurrencia {
+    type Item = Fecha;
+    type IntoIter = IterRecurrencia<'a>;
+
+    fn into_iter(self) -> IterRecurrencia<'a> {
+        self.iter()
+    }
+}
+
+impl Iterator for IntoIterRecurrencia {
+    type Item = Fecha;
+
+    fn next(&mut self) -> Option<Fecha> {
+        let fecha = self.recurrencia.enesima(self.indice)?;
+        self.indice += 1;
+        Some(fecha)
+    }
+}
+
+impl IntoIterator for Recurrencia {
+    type Item = Fecha;
+    type IntoIter = IntoIterRecurrencia;
+
+    fn into_iter(self) -> IntoIterRecurrencia {
+        IntoIterRecurrencia {
+            recurrencia: self,
+            indice: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diaria_con_cantidad() {
+        let r = Recurrencia::new(
+            Fecha::new(30, 12, 2024),
+            Frecuencia::Diaria,
+            2,
+            FinRecurrencia::Cantidad(3),
+        );
+        let fechas: Vec<Fecha> = r.iter().collect();
+        assert_eq!(
+            fechas,
+            vec![
+                Fecha::new(30, 12, 2024),
+                Fecha::new(1, 1, 2025),
+                Fecha::new(3, 1, 2025),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_semanal_hasta() {
+        let r = Recurrencia::new(
+            Fecha::new(1, 7, 2025),
+            Frecuencia::Semanal,
+            1,
+            FinRecurrencia::Hasta(Fecha::new(22, 7, 2025)),
+        );
+        let fechas: Vec<Fecha> = r.iter().collect();
+        assert_eq!(fechas.len(), 4);
+        assert_eq!(fechas[3], Fecha::new(22, 7, 2025));
+    }
+
+    #[test]
+    fn test_mensual_no_arrastra_ajuste_fin_de_mes() {
+        let r = Recurrencia::cada_n_meses(Fecha::new(31, 1, 2025), 1, FinRecurrencia::Cantidad(4));
+        let fechas: Vec<Fecha> = (&r).into_iter().collect();
+        assert_eq!(r.into_iter().collect::<Vec<Fecha>>(), fechas);
+        assert_eq!(
+            fechas,
+            vec![
+                Fecha::new(31, 1, 2025),
+                Fecha::new(28, 2, 2025),
+                Fecha::new(31, 3, 2025),
+                Fecha::new(30, 4, 2025),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cada_n_meses_hasta() {
+        let r = Recurrencia::cada_n_meses(
+            Fecha::new(15, 3, 2025),
+            6,
+            FinRecurrencia::Hasta(Fecha::new(1, 1, 2027)),
+        );
+        let fechas: Vec<Fecha> = r.iter().collect();
+        assert_eq!(
+            fechas,
+            vec![
+                Fecha::new(15, 3, 2025),
+                Fecha::new(15, 9, 2025),
+                Fecha::new(15, 3, 2026),
+                Fecha::new(15, 9, 2026),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_siguiente_a() {
+        let r = Recurrencia::cada_n_meses(Fecha::new(10, 1, 2025), 3, FinRecurrencia::Cantidad(3));
+        assert_eq!(
+            r.siguiente_a(&Fecha::new(10, 1, 2025)),
+            Some(Fecha::new(10, 4, 2025))
+        );
+        assert_eq!(
+            r.siguiente_a(&Fecha::new(1, 1, 2000)),
+            Some(Fecha::new(10, 1, 2025))
+        );
+        assert_eq!(r.siguiente_a(&Fecha::new(10, 7, 2025)), None);
+    }
+
+    #[test]
+    fn test_intervalo_cero_y_fecha_invalida() {
+        let r = Recurrencia::new(
+            Fecha::new(1, 1, 2025),
+            Frecuencia::Diaria,
+            0,
+            FinRecurrencia::Cantidad(2),
+        );
+        assert_eq!(r.iter().last(), Some(Fecha::new(2, 1, 2025)));
+
+        let r = Recurrencia::new(
+            Fecha::new(31, 2, 2025),
+            Frecuencia::Mensual,
+            1,
+            FinRecurrencia::Cantidad(5),
+        );
+        assert_eq!(r.iter().count(), 0);
+    }
+
+    #[test]
+    fn test_intervalo_cero_guardado() {
+        let r = Recurrencia::cada_n_meses(
+            Fecha::new(1, 1, 2025),
+            6,
+            FinRecurrencia::Hasta(Fecha::new(1, 1, 2030)),
+        );
+        let mut json = serde_json::to_value(&r).unwrap();
+        assert_eq!(
+            serde_json::from_value::<Recurrencia>(json.clone()).unwrap(),
+            r
+        );
+
+        json["intervalo"] = serde_json::json!(0);
+        let error = serde_json::from_value::<Recurrencia>(json).unwrap_err();
+        assert!(error.to_string().contains("intervalo"));
+    }
+}
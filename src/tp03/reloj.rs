@@ -0,0 +1,189 @@
+#![allow(dead_code, unused_variables)]
+
+use super::compartido::Compartido;
+use super::ej03::{Fecha, FechaError};
+use super::fecha_hora::{DESPLAZAMIENTO_ARGENTINA, FechaHora};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+pub trait Reloj {
+    fn ahora(&self) -> FechaHora;
+
+    // Fecha en la zona del reloj.
+    fn hoy(&self) -> Fecha {
+        self.ahora().fecha()
+    }
+}
+
+pub struct RelojSistema;
+
+pub struct RelojFijo {
+    momento: FechaHora,
+}
+
+// Reloj que solo avanza cuando se le pide, para simular el paso del tiempo en tests.
+pub struct RelojManual {
+    momento: RefCell<FechaHora>,
+}
+
+// Por defecto es el reloj del sistema.
+pub type RelojCompartido = Compartido<dyn Reloj>;
+
+impl Reloj for RelojSistema {
+    fn ahora(&self) -> FechaHora {
+        FechaHora::ahora()
+    }
+}
+
+// Los relojes construidos a partir de una Fecha arrancan a la medianoche de Argentina.
+// Fallan si la fecha es invalida.
+fn medianoche(fecha: &Fecha) -> Result<FechaHora, FechaError> {
+    FechaHora::inicio_del_dia(fecha, DESPLAZAMIENTO_ARGENTINA)
+}
+
+impl RelojFijo {
+    pub fn new(fecha: Fecha) -> Result<Self, FechaError> {
+        Ok(RelojFijo::en(medianoche(&fecha)?))
+    }
+
+    pub fn en(momento: FechaHora) -> Self {
+        RelojFijo { momento }
+    }
+}
+
+impl Reloj for RelojFijo {
+    fn ahora(&self) -> FechaHora {
+        self.momento
+    }
+}
+
+impl RelojManual {
+    pub fn new(fecha: Fecha) -> Result<Self, FechaError> {
+        Ok(RelojManual::en(medianoche(&fecha)?))
+    }
+
+    pub fn en(momento: FechaHora) -> Self {
+        RelojManual {
+            momento: RefCell::new(momento),
+        }
+    }
+
+    pub fn fijar(&self, fecha: Fecha) -> Result<(), FechaError> {
+        *self.momento.borrow_mut() = medianoche(&fecha)?;
+        Ok(())
+    }
+
+    pub fn avanzar_segundos(&self, segundos: i64) {
+        self.momento.borrow_mut().sumar_segundos(segundos);
+    }
+
+    pub fn avanzar_dias(&self, dias: i64) {
+        self.momento.borrow_mut().sumar_dias(dias);
+    }
+
+    pub fn avanzar_meses(&self, meses: i32) {
+        self.momento.borrow_mut().sumar_meses(meses);
+    }
+}
+
+impl Reloj for RelojManual {
+    fn ahora(&self) -> FechaHora {
+        *self.momento.borrow()
+    }
+}
+
+impl RelojCompartido {
+    pub fn new<R: Reloj + 'static>(reloj: R) -> Self {
+        Compartido::de_rc(Rc::new(reloj))
+    }
+}
+
+impl<R: Reloj + 'static> From<Rc<R>> for RelojCompartido {
+    fn from(reloj: Rc<R>) -> Self {
+        Compartido::de_rc(reloj)
+    }
+}
+
+impl Reloj for RelojCompartido {
+    fn ahora(&self) -> FechaHora {
+        (**self).ahora()
+    }
+
+    fn hoy(&self) -> Fecha {
+        (**self).hoy()
+    }
+}
+
+impl Default for RelojCompartido {
+    fn default() -> Self {
+        RelojCompartido::new(RelojSistema)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reloj_fijo() {
+        let reloj = RelojFijo::new(Fecha::new(1, 7, 2025)).unwrap();
+        assert_eq!(reloj.hoy(), Fecha::new(1, 7, 2025));
+        assert_eq!(reloj.hoy(), Fecha::new(1, 7, 2025));
+    }
+
+    #[test]
+    fn test_reloj_manual_avanza() {
+        let reloj = RelojManual::new(Fecha::new(31, 1, 2025)).unwrap();
+        reloj.avanzar_meses(1);
+        assert_eq!(reloj.hoy(), Fecha::new(28, 2, 2025));
+        reloj.avanzar_dias(1);
+        assert_eq!(reloj.hoy(), Fecha::new(1, 3, 2025));
+        reloj.fijar(Fecha::new(25, 12, 2030)).unwrap();
+        assert_eq!(reloj.hoy(), Fecha::new(25, 12, 2030));
+    }
+
+    #[test]
+    fn test_reloj_con_fecha_invalida() {
+        assert!(matches!(
+            RelojFijo::new(Fecha::new(30, 2, 2025)),
+            Err(FechaError::Invalida { .. })
+        ));
+        assert!(RelojManual::new(Fecha::new(0, 1, 2025)).is_err());
+        let reloj = RelojManual::new(Fecha::new(1, 1, 2025)).unwrap();
+        assert!(reloj.fijar(Fecha::new(32, 1, 2025)).is_err());
+        assert_eq!(reloj.hoy(), Fecha::new(1, 1, 2025));
+    }
+
+    #[test]
+    fn test_reloj_manual_avanza_segundos() {
+        let reloj = RelojManual::new(Fecha::new(31, 12, 2024)).unwrap();
+        let antes = reloj.ahora();
+        reloj.avanzar_segundos(23 * 3600 + 59 * 60);
+        assert!(reloj.ahora() > antes);
+        assert_eq!(reloj.hoy(), Fecha::new(31, 12, 2024));
+        assert_eq!((reloj.ahora().hora(), reloj.ahora().minuto()), (23, 59));
+        reloj.avanzar_segundos(60);
+        assert_eq!(reloj.hoy(), Fecha::new(1, 1, 2025));
+    }
+
+    #[test]
+    fn test_reloj_compartido_ve_los_avances() {
+        let manual = Rc::new(RelojManual::new(Fecha::new(1, 1, 2025)).unwrap());
+        let compartido = RelojCompartido::from(manual.clone());
+        manual.avanzar_dias(10);
+        assert_eq!(compartido.hoy(), Fecha::new(11, 1, 2025));
+        assert_eq!(compartido.clone().hoy(), Fecha::new(11, 1, 2025));
+    }
+
+    #[test]
+    fn test_reloj_sistema_por_defecto() {
+        // se compara contra instantes y no contra fechas, por si pasa la medianoche
+        let antes = RelojSistema.ahora();
+        let ahora = RelojCompartido::default().ahora();
+        let despues = RelojSistema.ahora();
+        assert!(antes <= ahora && ahora <= despues);
+
+        let fijo = RelojCompartido::new(RelojFijo::en(ahora));
+        assert_eq!(fijo.hoy(), ahora.fecha());
+    }
+}
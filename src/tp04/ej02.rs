@@ -1,4 +1,5 @@
 #![allow(dead_code, unused_variables)]
+use crate::tp03::ej03::Fecha;
 use std::cmp::Ordering;
 
 #[derive(Clone, PartialEq)]
@@ -9,6 +10,7 @@ struct Persona<'a> {
     ciudad: &'a str,
     salario: f64,
     edad: u8,
+    fecha_nacimiento: Option<Fecha>,
 }
 
 impl<'a> Persona<'a> {
@@ -27,8 +29,22 @@ impl<'a> Persona<'a> {
             ciudad,
             salario,
             edad,
+            fecha_nacimiento: None,
         }
     }
+
+    pub fn con_fecha_nacimiento(mut self, fecha_nacimiento: Fecha) -> Self {
+        self.fecha_nacimiento = Some(fecha_nacimiento);
+        self
+    }
+
+    pub fn edad_al(&self, fecha: &Fecha) -> u8 {
+        Fecha::edad_desde(self.fecha_nacimiento.as_ref(), self.edad, fecha)
+    }
+
+    pub fn edad_actual(&self) -> u8 {
+        self.edad_al(&Fecha::fecha_actual())
+    }
 }
 
 fn salario_mayor_a<'a>(personas: &Vec<Persona<'a>>, salario: f64) -> Vec<Persona<'a>> {
@@ -42,7 +58,7 @@ fn salario_mayor_a<'a>(personas: &Vec<Persona<'a>>, salario: f64) -> Vec<Persona
 fn edad_vive_en<'a>(personas: &Vec<Persona<'a>>, edad: u8, ciudad: &'a str) -> Vec<Persona<'a>> {
     personas
         .iter()
-        .filter(|&p| p.edad > edad && p.ciudad == ciudad)
+        .filter(|&p| p.edad_actual() > edad && p.ciudad == ciudad)
         .cloned()
         .collect()
 }
@@ -60,7 +76,7 @@ fn tiene_persona<'a>(personas: &Vec<Persona<'a>>, persona: &Persona<'a>) -> bool
 }
 
 fn devolver_edades(personas: &Vec<Persona>) -> Vec<u8> {
-    personas.iter().map(|p| p.edad).collect()
+    personas.iter().map(|p| p.edad_actual()).collect()
 }
 
 // g - Escriba una función que reciba un arreglo de personas y retorna la persona con el menor
@@ -77,14 +93,14 @@ fn devolver_salarios_limite<'a>(personas: &Vec<Persona<'a>>) -> Resultado<'a> {
         p1.salario
             .partial_cmp(&p2.salario)
             .unwrap_or(Ordering::Equal)
-            .then_with(|| p1.edad.cmp(&p2.edad))
+            .then_with(|| p1.edad_actual().cmp(&p2.edad_actual()))
     });
 
     let p_min = personas.iter().min_by(|p1, p2| {
         p1.salario
             .partial_cmp(&p2.salario)
             .unwrap_or(Ordering::Equal)
-            .then_with(|| p2.edad.cmp(&p1.edad))
+            .then_with(|| p2.edad_actual().cmp(&p1.edad_actual()))
     });
 
     Resultado {
@@ -118,6 +134,26 @@ mod test {
         assert!(edad_vive_en(&personas, 35, "capital").len() == 1);
     }
 
+    #[test]
+    fn test_ej02_b_edad_calculada() {
+        // cumple 20 mañana, hoy tiene 19 aunque `edad` diga otra cosa
+        let hoy = Fecha::new(15, 6, 2025);
+        let p = Persona::new("nue", "va", "calle 9", "capital", 1.0, 99)
+            .con_fecha_nacimiento(Fecha::new(16, 6, 2005));
+        assert_eq!(p.edad_al(&hoy), 19);
+        assert_eq!(p.edad_al(&Fecha::new(16, 6, 2025)), 20);
+
+        // los filtros usan la fecha de hoy: a medio año del cumpleaños la edad no cambia
+        // aunque pase la medianoche durante el test
+        let mut nacimiento = Fecha::fecha_actual();
+        nacimiento.sumar_años(-20);
+        nacimiento.sumar_dias(-180);
+        let personas = vec![p.con_fecha_nacimiento(nacimiento)];
+        assert_eq!(edad_vive_en(&personas, 19, "capital").len(), 1);
+        assert!(edad_vive_en(&personas, 20, "capital").is_empty());
+        assert_eq!(devolver_edades(&personas), vec![20]);
+    }
+
     #[test]
     fn test_ej02_c_ciudad() {
         let mut personas = data();
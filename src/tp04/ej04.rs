@@ -1,7 +1,9 @@
 #![allow(dead_code, unused_variables)]
 
 use crate::tp03::ej03::Fecha;
-use std::collections::HashMap;
+use crate::tp03::fecha_hora::FechaHora;
+use crate::tp03::rango_fechas::RangoFechas;
+use std::collections::{BTreeMap, HashMap};
 const DESCUENTO_GRAL_NEWSLETTER: u32 = 10;
 
 struct Sistema {
@@ -22,7 +24,7 @@ struct Venta {
     vendedor: Persona,
     cliente: Persona,
     medio_pago: MedioPago,
-    fecha: Fecha,
+    fecha_hora: FechaHora,
     registros: Vec<Registro>,
 }
 
@@ -80,7 +82,7 @@ impl Sistema {
     // productos con sus cantidades.
     fn crear_venta(
         &mut self,
-        fecha: Fecha,
+        fecha_hora: FechaHora,
         cliente: Persona,
         vendedor: Persona,
         medio_pago: MedioPago,
@@ -90,7 +92,7 @@ impl Sistema {
             vendedor,
             cliente,
             medio_pago,
-            fecha,
+            fecha_hora,
             registros,
         };
         self.ventas.push(venta.clone());
@@ -136,8 +138,65 @@ impl Sistema {
     // ➢ Para llevar un control de las ventas realizadas, se debe implementar un reporte que
     // permita visualizar las ventas totales por categoría de producto y otro por vendedor.
     fn reporte_ventas_categoria(&self, categoria: &str) -> Reporte {
-        let mut cantidad_ventas: u32 = 0;
+        self.reporte_categoria(categoria, None)
+    }
+
+    fn reporte_ventas_vendedor(&self, nombre_vendedor: &str) -> Reporte {
+        self.reporte_vendedor(nombre_vendedor, None)
+    }
+
+    // Mismos reportes pero contando solo las ventas hechas dentro del periodo.
+    fn reporte_ventas_categoria_en(&self, categoria: &str, periodo: &RangoFechas) -> Reporte {
+        self.reporte_categoria(categoria, Some(periodo))
+    }
+
+    fn reporte_ventas_vendedor_en(&self, nombre_vendedor: &str, periodo: &RangoFechas) -> Reporte {
+        self.reporte_vendedor(nombre_vendedor, Some(periodo))
+    }
+
+    // Cantidad de ventas por semana ISO ("2025-S18"), en orden cronologico.
+    fn reporte_ventas_por_semana(&self) -> Vec<Reporte> {
+        self.agrupar_ventas(|f| {
+            f.semana_iso()
+                .ok()
+                .map(|(año, semana)| (año, semana, format!("{}-S{:02}", año, semana)))
+        })
+    }
+
+    // Cantidad de ventas por trimestre ("2025-T2"), en orden cronologico.
+    fn reporte_ventas_por_trimestre(&self) -> Vec<Reporte> {
+        self.agrupar_ventas(|f| {
+            let trimestre = f.trimestre();
+            Some((f.año, trimestre, format!("{}-T{}", f.año, trimestre)))
+        })
+    }
+
+    // `clave` devuelve (año, periodo, nombre); se ordena por (año, periodo).
+    fn agrupar_ventas(&self, clave: impl Fn(&Fecha) -> Option<(i32, u32, String)>) -> Vec<Reporte> {
+        let mut grupos: BTreeMap<(i32, u32), Reporte> = BTreeMap::new();
         for v in &self.ventas {
+            if let Some((año, periodo, nombre)) = clave(&v.fecha_hora.fecha()) {
+                grupos
+                    .entry((año, periodo))
+                    .or_insert_with(|| Reporte::new(nombre, 0))
+                    .cantidad_ventas += 1;
+            }
+        }
+        grupos.into_values().collect()
+    }
+
+    fn ventas_en<'a>(
+        &'a self,
+        periodo: Option<&'a RangoFechas>,
+    ) -> impl Iterator<Item = &'a Venta> + 'a {
+        self.ventas
+            .iter()
+            .filter(move |v| periodo.is_none_or(|p| p.contiene(&v.fecha_hora.fecha())))
+    }
+
+    fn reporte_categoria(&self, categoria: &str, periodo: Option<&RangoFechas>) -> Reporte {
+        let mut cantidad_ventas: u32 = 0;
+        for v in self.ventas_en(periodo) {
             if v.registros
                 .iter()
                 .any(|r| r.producto.nombre_categoria == categoria)
@@ -149,10 +208,9 @@ impl Sistema {
         Reporte::new(categoria.to_string(), cantidad_ventas)
     }
 
-    fn reporte_ventas_vendedor(&self, nombre_vendedor: &str) -> Reporte {
+    fn reporte_vendedor(&self, nombre_vendedor: &str, periodo: Option<&RangoFechas>) -> Reporte {
         let cantidad_ventas = self
-            .ventas
-            .iter()
+            .ventas_en(periodo)
             .filter(|v| v.vendedor.nombre == nombre_vendedor)
             .count() as u32;
 
@@ -163,13 +221,25 @@ impl Sistema {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::tp03::fecha_hora::DESPLAZAMIENTO_ARGENTINA;
+
+    fn fecha_hora(dia: u32, mes: u32, año: i32, hora: u32) -> FechaHora {
+        FechaHora::new(
+            &Fecha::new(dia, mes, año),
+            hora,
+            0,
+            0,
+            DESPLAZAMIENTO_ARGENTINA,
+        )
+        .unwrap()
+    }
 
     struct TestData {
         sistema: Sistema,
         productos: Vec<Producto>,
         vendedor: Persona,
         cliente: Persona,
-        fecha: Fecha,
+        fecha: FechaHora,
     }
 
     fn setup() -> TestData {
@@ -211,11 +281,7 @@ mod test {
         };
 
         sistema.crear_venta(
-            Fecha {
-                dia: 1,
-                mes: 5,
-                año: 2025,
-            },
+            fecha_hora(1, 5, 2025, 10),
             cliente.clone(),
             vendedor.clone(),
             MedioPago::Efectivo,
@@ -225,11 +291,7 @@ mod test {
             }],
         );
         sistema.crear_venta(
-            Fecha {
-                dia: 2,
-                mes: 5,
-                año: 2025,
-            },
+            fecha_hora(2, 5, 2025, 10),
             cliente.clone(),
             vendedor.clone(),
             MedioPago::TarjetaCredito,
@@ -250,11 +312,7 @@ mod test {
             productos: vec![prod1, prod2],
             vendedor,
             cliente,
-            fecha: Fecha {
-                dia: 1,
-                mes: 5,
-                año: 2025,
-            },
+            fecha: fecha_hora(1, 5, 2025, 10),
         }
     }
 
@@ -266,7 +324,7 @@ mod test {
             cantidad: 1,
         }];
         let venta = data.sistema.crear_venta(
-            data.fecha.clone(),
+            data.fecha,
             data.cliente.clone(),
             data.vendedor.clone(),
             MedioPago::Transferencia,
@@ -278,6 +336,8 @@ mod test {
         assert_eq!(venta.vendedor, data.vendedor);
         assert_eq!(venta.medio_pago, MedioPago::Transferencia);
         assert_eq!(venta.registros, registros);
+        assert_eq!(venta.fecha_hora, data.fecha);
+        assert_eq!(venta.fecha_hora.fecha(), Fecha::new(1, 5, 2025));
     }
 
     #[test]
@@ -301,11 +361,7 @@ mod test {
             vendedor: data.vendedor.clone(),
             cliente: data.cliente.clone(),
             medio_pago: MedioPago::Efectivo,
-            fecha: Fecha {
-                dia: 3,
-                mes: 5,
-                año: 2025,
-            },
+            fecha_hora: fecha_hora(3, 5, 2025, 10),
             registros: vec![],
         };
         let precio = data.sistema.calcular_precio_final(&venta);
@@ -327,6 +383,93 @@ mod test {
         assert_eq!(reporte_inexistente.nombre, "Hogar");
     }
 
+    #[test]
+    fn test_reportes_por_periodo() {
+        let data = setup();
+        let primero_de_mayo =
+            RangoFechas::cerrado(&Fecha::new(1, 5, 2025), &Fecha::new(1, 5, 2025)).unwrap();
+        let junio = RangoFechas::mes(6, 2025).unwrap();
+
+        assert_eq!(
+            data.sistema
+                .reporte_ventas_categoria_en("Electrónica", &primero_de_mayo)
+                .cantidad_ventas,
+            1
+        );
+        assert_eq!(
+            data.sistema
+                .reporte_ventas_categoria_en("Ropa", &primero_de_mayo)
+                .cantidad_ventas,
+            0
+        );
+        assert_eq!(
+            data.sistema
+                .reporte_ventas_categoria_en("Electrónica", &junio)
+                .cantidad_ventas,
+            0
+        );
+        assert_eq!(
+            data.sistema
+                .reporte_ventas_vendedor_en("Juan", &RangoFechas::mes(5, 2025).unwrap())
+                .cantidad_ventas,
+            2
+        );
+        assert_eq!(
+            data.sistema
+                .reporte_ventas_vendedor_en("Juan", &junio)
+                .cantidad_ventas,
+            0
+        );
+    }
+
+    #[test]
+    fn test_reportes_por_semana_y_trimestre() {
+        let mut data = setup();
+        // domingo 4/5 cierra la semana 18, el lunes 5/5 ya es la 19
+        for fecha in [fecha_hora(4, 5, 2025, 10), fecha_hora(5, 5, 2025, 10)] {
+            data.sistema.crear_venta(
+                fecha,
+                data.cliente.clone(),
+                data.vendedor.clone(),
+                MedioPago::Efectivo,
+                vec![],
+            );
+        }
+        data.sistema.crear_venta(
+            fecha_hora(20, 12, 2024, 10),
+            data.cliente.clone(),
+            data.vendedor.clone(),
+            MedioPago::Efectivo,
+            vec![],
+        );
+
+        let semanas: Vec<(String, u32)> = data
+            .sistema
+            .reporte_ventas_por_semana()
+            .into_iter()
+            .map(|r| (r.nombre, r.cantidad_ventas))
+            .collect();
+        assert_eq!(
+            semanas,
+            vec![
+                ("2024-S51".to_string(), 1),
+                ("2025-S18".to_string(), 3),
+                ("2025-S19".to_string(), 1),
+            ]
+        );
+
+        let trimestres: Vec<(String, u32)> = data
+            .sistema
+            .reporte_ventas_por_trimestre()
+            .into_iter()
+            .map(|r| (r.nombre, r.cantidad_ventas))
+            .collect();
+        assert_eq!(
+            trimestres,
+            vec![("2024-T4".to_string(), 1), ("2025-T2".to_string(), 4)]
+        );
+    }
+
     #[test]
     fn test_reporte_ventas_vendedor() {
         let data = setup();
@@ -0,0 +1,422 @@
+#![allow(dead_code, unused_variables)]
+
+use crate::tp05::repositorio::PersistenciaError;
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use argon2::{Config, Variant, Version};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::cell::RefCell;
+use std::fmt;
+
+// Version del formato cifrado; cambia si cambia el algoritmo o los campos de la cabecera.
+pub const FORMATO_CIFRADO: u32 = 1;
+
+// Costo de argon2id que recomienda OWASP: 19 MiB de memoria y 2 pasadas.
+pub const MEMORIA_KIB_POR_DEFECTO: u32 = 19 * 1024;
+pub const ITERACIONES_POR_DEFECTO: u32 = 2;
+
+// La cabecera se autentica recien con la clave, asi que un costo fuera de estos limites se
+// rechaza antes de derivarla: un archivo modificado podria colgar el programa o pedir toda
+// la memoria. El minimo de memoria es el de argon2 para un carril.
+const MEMORIA_KIB_MINIMA: u32 = 8;
+const MEMORIA_KIB_MAXIMA: u32 = 1024 * 1024;
+const ITERACIONES_MAXIMAS: u32 = 16;
+
+const ALGORITMO: &str = "aes-256-gcm";
+const KDF: &str = "argon2id";
+const LARGO_SAL: usize = 16;
+const LARGO_NONCE: usize = 12;
+const LARGO_CLAVE: u32 = 32;
+
+// Cifra los datos con una clave derivada de una contraseña. La sal y el costo de la
+// derivacion quedan en la cabecera de cada texto cifrado, asi se puede leer aunque despues
+// cambie el costo por defecto.
+#[derive(Clone)]
+pub struct Cifrado {
+    contraseña: String,
+    memoria_kib: u32,
+    iteraciones: u32,
+    // Derivar la clave es lento a proposito; se guarda la ultima para no repetirlo en cada
+    // guardado.
+    clave: RefCell<Option<ClaveDerivada>>,
+}
+
+#[derive(Clone, PartialEq)]
+struct Parametros {
+    sal: Vec<u8>,
+    memoria_kib: u32,
+    iteraciones: u32,
+}
+
+#[derive(Clone)]
+struct ClaveDerivada {
+    parametros: Parametros,
+    clave: Vec<u8>,
+}
+
+// Lo que se escribe en lugar de los datos. Todos los campos menos `datos` se autentican
+// junto con el texto cifrado, asi no se puede tocar la cabecera sin que falle el descifrado.
+#[derive(Serialize, Deserialize)]
+struct Sellado {
+    cifrado: u32,
+    algoritmo: String,
+    kdf: String,
+    memoria_kib: u32,
+    iteraciones: u32,
+    sal: String,
+    nonce: String,
+    datos: String,
+}
+
+impl Sellado {
+    fn datos_asociados(&self) -> Vec<u8> {
+        format!(
+            "{}:{}:{}:{}:{}:{}:{}",
+            self.cifrado,
+            self.algoritmo,
+            self.kdf,
+            self.memoria_kib,
+            self.iteraciones,
+            self.sal,
+            self.nonce
+        )
+        .into_bytes()
+    }
+
+    fn es_sellado(valor: &Value) -> bool {
+        valor.as_object().is_some_and(|campos| {
+            ["cifrado", "nonce", "datos"]
+                .iter()
+                .all(|campo| campos.contains_key(*campo))
+        })
+    }
+}
+
+impl Cifrado {
+    pub fn new(contraseña: &str) -> Self {
+        Cifrado {
+            contraseña: contraseña.to_string(),
+            memoria_kib: MEMORIA_KIB_POR_DEFECTO,
+            iteraciones: ITERACIONES_POR_DEFECTO,
+            clave: RefCell::new(None),
+        }
+    }
+
+    // Solo afecta lo que se cifra de aca en adelante.
+    pub fn con_costo(mut self, memoria_kib: u32, iteraciones: u32) -> Self {
+        self.memoria_kib = memoria_kib;
+        self.iteraciones = iteraciones;
+        self
+    }
+
+    // Un JSON de una sola linea con la cabecera y los datos cifrados. Cada llamada usa un
+    // nonce nuevo.
+    pub fn cifrar(&self, texto: &str) -> Result<String, PersistenciaError> {
+        let (parametros, clave) = self.clave_para_cifrar()?;
+        let mut nonce = [0u8; LARGO_NONCE];
+        rand::thread_rng().fill_bytes(&mut nonce);
+        let mut sellado = Sellado {
+            cifrado: FORMATO_CIFRADO,
+            algoritmo: ALGORITMO.to_string(),
+            kdf: KDF.to_string(),
+            memoria_kib: parametros.memoria_kib,
+            iteraciones: parametros.iteraciones,
+            sal: a_hex(&parametros.sal),
+            nonce: a_hex(&nonce),
+            datos: String::new(),
+        };
+        let datos_asociados = sellado.datos_asociados();
+        let cifrado = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&clave))
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: texto.as_bytes(),
+                    aad: &datos_asociados,
+                },
+            )
+            .map_err(|_| PersistenciaError::Cifrado("no se pudieron cifrar los datos".into()))?;
+        sellado.datos = a_hex(&cifrado);
+        Ok(serde_json::to_string(&sellado)?)
+    }
+
+    fn descifrar(&self, sellado: Sellado, origen: &str) -> Result<String, PersistenciaError> {
+        if sellado.cifrado != FORMATO_CIFRADO
+            || sellado.algoritmo != ALGORITMO
+            || sellado.kdf != KDF
+        {
+            return Err(PersistenciaError::Cifrado(format!(
+                "{} usa el formato de cifrado {} ({}, {}) y solo se soporta el {} ({}, {})",
+                origen,
+                sellado.cifrado,
+                sellado.algoritmo,
+                sellado.kdf,
+                FORMATO_CIFRADO,
+                ALGORITMO,
+                KDF
+            )));
+        }
+        let nonce = de_hex(&sellado.nonce, origen)?;
+        if nonce.len() != LARGO_NONCE {
+            return Err(PersistenciaError::Corrupto(format!(
+                "{}: el nonce no tiene {} bytes",
+                origen, LARGO_NONCE
+            )));
+        }
+        let clave = self.clave_para(&Parametros {
+            sal: de_hex(&sellado.sal, origen)?,
+            memoria_kib: sellado.memoria_kib,
+            iteraciones: sellado.iteraciones,
+        })?;
+        let plano = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&clave))
+            .decrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: &de_hex(&sellado.datos, origen)?,
+                    aad: &sellado.datos_asociados(),
+                },
+            )
+            .map_err(|_| {
+                PersistenciaError::Cifrado(format!(
+                    "la contraseña de {} es incorrecta o el archivo fue modificado",
+                    origen
+                ))
+            })?;
+        String::from_utf8(plano).map_err(|_| {
+            PersistenciaError::Corrupto(format!("{}: los datos descifrados no son texto", origen))
+        })
+    }
+
+    // Reusa la sal de la ultima clave si el costo no cambio, y si no arranca con una sal nueva.
+    fn clave_para_cifrar(&self) -> Result<(Parametros, Vec<u8>), PersistenciaError> {
+        if let Some(derivada) = &*self.clave.borrow()
+            && derivada.parametros.memoria_kib == self.memoria_kib
+            && derivada.parametros.iteraciones == self.iteraciones
+        {
+            return Ok((derivada.parametros.clone(), derivada.clave.clone()));
+        }
+        let mut sal = vec![0u8; LARGO_SAL];
+        rand::thread_rng().fill_bytes(&mut sal);
+        let parametros = Parametros {
+            sal,
+            memoria_kib: self.memoria_kib,
+            iteraciones: self.iteraciones,
+        };
+        let clave = self.clave_para(&parametros)?;
+        Ok((parametros, clave))
+    }
+
+    fn clave_para(&self, parametros: &Parametros) -> Result<Vec<u8>, PersistenciaError> {
+        if let Some(derivada) = &*self.clave.borrow()
+            && derivada.parametros == *parametros
+        {
+            return Ok(derivada.clave.clone());
+        }
+        if !(MEMORIA_KIB_MINIMA..=MEMORIA_KIB_MAXIMA).contains(&parametros.memoria_kib) {
+            return Err(PersistenciaError::Cifrado(format!(
+                "derivar la clave pide {} KiB de memoria y se aceptan de {} a {}",
+                parametros.memoria_kib, MEMORIA_KIB_MINIMA, MEMORIA_KIB_MAXIMA
+            )));
+        }
+        if !(1..=ITERACIONES_MAXIMAS).contains(&parametros.iteraciones) {
+            return Err(PersistenciaError::Cifrado(format!(
+                "derivar la clave pide {} iteraciones y se aceptan de 1 a {}",
+                parametros.iteraciones, ITERACIONES_MAXIMAS
+            )));
+        }
+        let config = Config {
+            variant: Variant::Argon2id,
+            version: Version::Version13,
+            mem_cost: parametros.memoria_kib,
+            time_cost: parametros.iteraciones,
+            lanes: 1,
+            hash_length: LARGO_CLAVE,
+            secret: &[],
+            ad: &[],
+        };
+        let clave = argon2::hash_raw(self.contraseña.as_bytes(), &parametros.sal, &config)
+            .map_err(|e| {
+                PersistenciaError::Cifrado(format!("no se pudo derivar la clave: {}", e))
+            })?;
+        *self.clave.borrow_mut() = Some(ClaveDerivada {
+            parametros: parametros.clone(),
+            clave: clave.clone(),
+        });
+        Ok(clave)
+    }
+}
+
+// Sin la contraseña.
+impl fmt::Debug for Cifrado {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Cifrado")
+            .field("memoria_kib", &self.memoria_kib)
+            .field("iteraciones", &self.iteraciones)
+            .finish_non_exhaustive()
+    }
+}
+
+// Si `texto` esta cifrado devuelve el texto de adentro; sin contraseña se devuelve tal cual.
+// Con contraseña un texto sin cifrar es un error, para que nadie pueda reemplazar los datos
+// cifrados por otros sin conocerla. Lo que estaba guardado sin cifrar se cifra con re_cifrar
+// desde None, que lo lee sin contraseña.
+pub fn abrir(
+    texto: &str,
+    cifrado: Option<&Cifrado>,
+    origen: &str,
+) -> Result<String, PersistenciaError> {
+    let valor = match serde_json::from_str::<Value>(texto) {
+        Ok(valor) if Sellado::es_sellado(&valor) => valor,
+        _ if cifrado.is_some() => {
+            return Err(PersistenciaError::Cifrado(format!(
+                "{} no esta cifrado y se esperaba con contraseña",
+                origen
+            )));
+        }
+        _ => return Ok(texto.to_string()),
+    };
+    let Some(cifrado) = cifrado else {
+        return Err(PersistenciaError::Cifrado(format!(
+            "{} esta cifrado y no se indico la contraseña",
+            origen
+        )));
+    };
+    let sellado: Sellado = serde_json::from_value(valor).map_err(|e| {
+        PersistenciaError::Corrupto(format!("{}: cabecera de cifrado invalida: {}", origen, e))
+    })?;
+    cifrado.descifrar(sellado, origen)
+}
+
+// Cifra `texto` si hay contraseña.
+pub fn sellar(texto: String, cifrado: Option<&Cifrado>) -> Result<String, PersistenciaError> {
+    match cifrado {
+        Some(cifrado) => cifrado.cifrar(&texto),
+        None => Ok(texto),
+    }
+}
+
+fn a_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn de_hex(texto: &str, origen: &str) -> Result<Vec<u8>, PersistenciaError> {
+    let invalido = || PersistenciaError::Corrupto(format!("{}: hexadecimal invalido", origen));
+    if !texto.len().is_multiple_of(2) {
+        return Err(invalido());
+    }
+    (0..texto.len())
+        .step_by(2)
+        .map(|i| {
+            texto
+                .get(i..i + 2)
+                .and_then(|par| u8::from_str_radix(par, 16).ok())
+                .ok_or_else(invalido)
+        })
+        .collect()
+}
+
+// Con el costo por defecto cada test tardaria segundos en modo debug.
+#[cfg(test)]
+pub fn de_prueba(contraseña: &str) -> Cifrado {
+    Cifrado::new(contraseña).con_costo(64, 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_cifrar_y_abrir() {
+        let cifrado = de_prueba("secreto");
+        let datos = r#"{"dni": 12345678, "email": "tao@example.com"}"#;
+        let texto = cifrado.cifrar(datos).unwrap();
+        assert!(!texto.contains("tao@example.com"));
+        assert!(!texto.contains('\n'));
+        assert!(texto.starts_with(r#"{"cifrado":1,"algoritmo":"aes-256-gcm","kdf":"argon2id""#));
+        assert_eq!(abrir(&texto, Some(&cifrado), "test").unwrap(), datos);
+
+        // otra instancia con la misma contraseña tiene que derivar la misma clave
+        assert_eq!(
+            abrir(&texto, Some(&de_prueba("secreto")), "test").unwrap(),
+            datos
+        );
+        // el nonce cambia en cada cifrado
+        assert_ne!(cifrado.cifrar("1").unwrap(), cifrado.cifrar("1").unwrap());
+    }
+
+    #[test]
+    fn test_contraseña_incorrecta_o_archivo_modificado() {
+        let texto = de_prueba("secreto").cifrar("[1, 2, 3]").unwrap();
+        assert!(matches!(
+            abrir(&texto, Some(&de_prueba("otra")), "test"),
+            Err(PersistenciaError::Cifrado(_))
+        ));
+
+        let mut sellado: Value = serde_json::from_str(&texto).unwrap();
+        sellado["iteraciones"] = json!(2);
+        assert!(matches!(
+            abrir(&sellado.to_string(), Some(&de_prueba("secreto")), "test"),
+            Err(PersistenciaError::Cifrado(_))
+        ));
+    }
+
+    #[test]
+    fn test_costo_fuera_de_limites() {
+        let texto = de_prueba("secreto").cifrar("1").unwrap();
+        for (campo, valor) in [
+            ("iteraciones", u32::MAX),
+            ("iteraciones", 0),
+            ("memoria_kib", u32::MAX),
+            ("memoria_kib", 0),
+        ] {
+            let mut sellado: Value = serde_json::from_str(&texto).unwrap();
+            sellado[campo] = json!(valor);
+            match abrir(&sellado.to_string(), Some(&de_prueba("secreto")), "test") {
+                Err(PersistenciaError::Cifrado(detalle)) => assert!(detalle.contains("aceptan")),
+                _ => panic!("se esperaba un error de cifrado para {} = {}", campo, valor),
+            }
+        }
+        assert!(matches!(
+            de_prueba("secreto").con_costo(64, 100).cifrar("1"),
+            Err(PersistenciaError::Cifrado(_))
+        ));
+    }
+
+    #[test]
+    fn test_formato_desconocido_y_sin_contraseña() {
+        let texto = de_prueba("secreto").cifrar("1").unwrap();
+        let mut sellado: Value = serde_json::from_str(&texto).unwrap();
+        sellado["cifrado"] = json!(2);
+        match abrir(&sellado.to_string(), Some(&de_prueba("secreto")), "test") {
+            Err(PersistenciaError::Cifrado(detalle)) => assert!(detalle.contains("formato")),
+            _ => panic!("se esperaba un error de cifrado"),
+        }
+        assert!(matches!(
+            abrir(&texto, None, "test"),
+            Err(PersistenciaError::Cifrado(_))
+        ));
+    }
+
+    #[test]
+    fn test_sin_cifrar_solo_sin_contraseña() {
+        let datos = r#"{"version": 1, "datos": [1, 2]}"#;
+        assert_eq!(abrir(datos, None, "test").unwrap(), datos);
+        let error = abrir(datos, Some(&de_prueba("secreto")), "test").unwrap_err();
+        assert!(matches!(error, PersistenciaError::Cifrado(_)));
+        assert!(error.to_string().contains("test no esta cifrado"));
+        // sin contraseña tampoco se toca lo que no es JSON, eso lo reporta quien lo deserializa
+        assert_eq!(abrir("[1, 2,", None, "test").unwrap(), "[1, 2,");
+        assert_eq!(sellar("[1]".to_string(), None).unwrap(), "[1]");
+    }
+
+    #[test]
+    fn test_hex() {
+        assert_eq!(a_hex(&[0, 15, 255]), "000fff");
+        assert_eq!(de_hex("000fff", "test").unwrap(), vec![0, 15, 255]);
+        assert!(de_hex("0f0", "test").is_err());
+        assert!(de_hex("zz", "test").is_err());
+        assert!(de_hex("ñ", "test").is_err());
+    }
+}
@@ -0,0 +1,606 @@
+#![allow(dead_code, unused_variables)]
+
+use crate::tp03::compartido::Compartido;
+use crate::tp05::cifrado::{self, Cifrado};
+use crate::tp05::esquema::Esquema;
+use crate::tp05::repositorio::{
+    Bloqueo, ESPERA_BLOQUEO_POR_DEFECTO, PersistenciaError, con_sufijo, deserializar,
+};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::cell::RefCell;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::Duration;
+
+// Registro de eventos que solo crece: cada evento es una linea JSON agregada al final, asi
+// guardar una operacion no obliga a reescribir todo el estado.
+pub trait Diario<E> {
+    fn agregar(&self, evento: &E) -> Result<(), PersistenciaError>;
+
+    // Agrega el evento solo si `sigue_a` acepta el ultimo que hay en el diario (None si esta
+    // vacio), mirandolo con el mismo bloqueo con el que se escribe. Si otro proceso agrego algo
+    // que quien llama no vio, devuelve Conflicto en lugar de escribir a continuacion.
+    fn agregar_despues_de(
+        &self,
+        evento: &E,
+        sigue_a: &dyn Fn(Option<&E>) -> bool,
+    ) -> Result<(), PersistenciaError> {
+        if !sigue_a(self.leer()?.last()) {
+            return Err(conflicto(None));
+        }
+        self.agregar(evento)
+    }
+
+    // Los eventos en el orden en que se agregaron.
+    fn leer(&self) -> Result<Vec<E>, PersistenciaError>;
+
+    // Descarta todos los eventos, una vez que quedaron incluidos en una instantanea.
+    fn vaciar(&self) -> Result<(), PersistenciaError>;
+
+    // Guarda la instantanea y vacia el diario sin que nadie agregue un evento en el medio,
+    // que se perderia al vaciar sin haber quedado en la instantanea. Como en
+    // agregar_despues_de, `incluye` tiene que aceptar el ultimo evento: si no, la instantanea
+    // no lo tiene y es Conflicto. Ese ultimo evento se conserva, para que el que agregue
+    // despues pueda ver por donde iba el diario.
+    fn vaciar_con_instantanea(
+        &self,
+        incluye: &dyn Fn(Option<&E>) -> bool,
+        guardar: &mut dyn FnMut() -> Result<(), PersistenciaError>,
+    ) -> Result<(), PersistenciaError> {
+        let ultimo = self.leer()?.pop();
+        if !incluye(ultimo.as_ref()) {
+            return Err(conflicto(None));
+        }
+        guardar()?;
+        self.vaciar()?;
+        match ultimo {
+            Some(ultimo) => self.agregar(&ultimo),
+            None => Ok(()),
+        }
+    }
+}
+
+// Los diarios que no son archivos se identifican como "memoria", igual que al parsearlos.
+fn conflicto(ruta: Option<&Path>) -> PersistenciaError {
+    PersistenciaError::Conflicto(ruta.unwrap_or(Path::new("memoria")).to_path_buf())
+}
+
+// Diario en un archivo JSON lines. Cada linea lleva la version del esquema, igual que los
+// archivos de RepositorioJson. Varios procesos pueden agregar al mismo diario: cada
+// escritura toma el bloqueo del archivo, asi las lineas no se mezclan. Con una contraseña
+// cada linea se cifra por separado.
+pub struct DiarioJsonl {
+    ruta: PathBuf,
+    esquema: Esquema,
+    espera_bloqueo: Duration,
+    cifrado: Option<Cifrado>,
+}
+
+// Diario en memoria, para tests. Guarda el mismo texto que escribiria el archivo.
+#[derive(Default)]
+pub struct DiarioMemoria {
+    contenido: RefCell<String>,
+    esquema: Esquema,
+}
+
+pub type DiarioCompartido<E> = Compartido<dyn Diario<E>>;
+
+fn linea<E: Serialize>(
+    evento: &E,
+    esquema: &Esquema,
+    cifrado: Option<&Cifrado>,
+) -> Result<String, PersistenciaError> {
+    let mut linea = cifrado::sellar(esquema.a_linea(evento)?, cifrado)?;
+    linea.push('\n');
+    Ok(linea)
+}
+
+// Si se corto la luz mientras se agregaba un evento la ultima linea queda sin terminar; ese
+// evento nunca se confirmo, asi que se ignora. Una linea invalida en cualquier otro lugar es
+// corrupcion.
+fn lineas_completas(texto: &str) -> impl Iterator<Item = (usize, &str)> {
+    let completas = match texto.rfind('\n') {
+        Some(fin) => &texto[..=fin],
+        None => "",
+    };
+    completas
+        .lines()
+        .enumerate()
+        .filter(|(_, l)| !l.trim().is_empty())
+}
+
+fn parsear_linea<E: DeserializeOwned>(
+    (i, linea): (usize, &str),
+    origen: &str,
+    esquema: &Esquema,
+    cifrado: Option<&Cifrado>,
+) -> Result<E, PersistenciaError> {
+    let origen = format!("{} linea {}", origen, i + 1);
+    let valor = deserializar(&cifrado::abrir(linea, cifrado, &origen)?, &origen)?;
+    esquema.desenvolver(valor)
+}
+
+fn parsear<E: DeserializeOwned>(
+    texto: &str,
+    origen: &str,
+    esquema: &Esquema,
+    cifrado: Option<&Cifrado>,
+) -> Result<Vec<E>, PersistenciaError> {
+    lineas_completas(texto)
+        .map(|linea| parsear_linea(linea, origen, esquema, cifrado))
+        .collect()
+}
+
+impl DiarioJsonl {
+    pub fn new(directorio: impl AsRef<Path>, archivo: &str) -> Self {
+        DiarioJsonl {
+            ruta: directorio.as_ref().join(archivo),
+            esquema: Esquema::new(),
+            espera_bloqueo: ESPERA_BLOQUEO_POR_DEFECTO,
+            cifrado: None,
+        }
+    }
+
+    pub fn con_esquema(mut self, esquema: Esquema) -> Self {
+        self.esquema = esquema;
+        self
+    }
+
+    pub fn con_espera_bloqueo(mut self, espera: Duration) -> Self {
+        self.espera_bloqueo = espera;
+        self
+    }
+
+    pub fn con_cifrado(mut self, cifrado: Cifrado) -> Self {
+        self.cifrado = Some(cifrado);
+        self
+    }
+
+    pub fn ruta(&self) -> &Path {
+        &self.ruta
+    }
+
+    // Reescribe todas las lineas con otra contraseña, o sin cifrar si es None. Si alguna no se
+    // puede descifrar con la contraseña actual el archivo queda como estaba.
+    pub fn re_cifrar(&mut self, nuevo: Option<Cifrado>) -> Result<(), PersistenciaError> {
+        if !self.ruta.exists() {
+            self.cifrado = nuevo;
+            return Ok(());
+        }
+        let _bloqueo = Bloqueo::exclusivo(&self.ruta, self.espera_bloqueo)?;
+        let texto = fs::read_to_string(&self.ruta)?;
+        let mut nuevo_texto = String::new();
+        for (i, l) in lineas_completas(&texto) {
+            let origen = format!("{} linea {}", self.ruta.display(), i + 1);
+            let plano = cifrado::abrir(l, self.cifrado.as_ref(), &origen)?;
+            deserializar::<serde_json::Value>(&plano, &origen)?;
+            nuevo_texto.push_str(&cifrado::sellar(plano, nuevo.as_ref())?);
+            nuevo_texto.push('\n');
+        }
+
+        let temporal = con_sufijo(&self.ruta, "tmp");
+        let mut archivo = File::create(&temporal)?;
+        archivo.write_all(nuevo_texto.as_bytes())?;
+        archivo.sync_all()?;
+        fs::rename(&temporal, &self.ruta)?;
+        self.cifrado = nuevo;
+        Ok(())
+    }
+
+    // Sin tomar el bloqueo: quien llama ya lo tiene.
+    fn truncar(&self) -> Result<(), PersistenciaError> {
+        match OpenOptions::new().write(true).open(&self.ruta) {
+            Ok(archivo) => {
+                archivo.set_len(0)?;
+                archivo.sync_all()?;
+                Ok(())
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    // El ultimo evento completo del texto del archivo.
+    fn ultimo<E: DeserializeOwned>(&self, texto: &str) -> Result<Option<E>, PersistenciaError> {
+        lineas_completas(texto)
+            .last()
+            .map(|linea| {
+                parsear_linea(
+                    linea,
+                    &self.ruta.display().to_string(),
+                    &self.esquema,
+                    self.cifrado.as_ref(),
+                )
+            })
+            .transpose()
+    }
+
+    // Deja solo la ultima linea completa, de una vez: si se corta en el medio el archivo
+    // queda como estaba.
+    fn dejar_ultima_linea(&self, texto: &str) -> Result<(), PersistenciaError> {
+        let mut resto = String::new();
+        if let Some((_, linea)) = lineas_completas(texto).last() {
+            resto.push_str(linea);
+            resto.push('\n');
+        }
+        let temporal = con_sufijo(&self.ruta, "tmp");
+        let mut archivo = File::create(&temporal)?;
+        archivo.write_all(resto.as_bytes())?;
+        archivo.sync_all()?;
+        fs::rename(&temporal, &self.ruta)?;
+        Ok(())
+    }
+
+    // Recorta una ultima linea sin terminar, para no pegarle el evento siguiente.
+    fn descartar_linea_cortada(archivo: &mut File) -> io::Result<()> {
+        let largo = archivo.metadata()?.len();
+        if largo == 0 {
+            return Ok(());
+        }
+        let mut ultimo = [0u8; 1];
+        archivo.seek(SeekFrom::Start(largo - 1))?;
+        archivo.read_exact(&mut ultimo)?;
+        if ultimo[0] == b'\n' {
+            return Ok(());
+        }
+
+        let mut contenido = vec![];
+        archivo.seek(SeekFrom::Start(0))?;
+        archivo.read_to_end(&mut contenido)?;
+        let fin = contenido
+            .iter()
+            .rposition(|&b| b == b'\n')
+            .map_or(0, |i| i + 1);
+        archivo.set_len(fin as u64)
+    }
+}
+
+impl<E: Serialize + DeserializeOwned> Diario<E> for DiarioJsonl {
+    fn agregar(&self, evento: &E) -> Result<(), PersistenciaError> {
+        self.agregar_despues_de(evento, &|_| true)
+    }
+
+    fn agregar_despues_de(
+        &self,
+        evento: &E,
+        sigue_a: &dyn Fn(Option<&E>) -> bool,
+    ) -> Result<(), PersistenciaError> {
+        let linea = linea(evento, &self.esquema, self.cifrado.as_ref())?;
+        if let Some(directorio) = self.ruta.parent() {
+            fs::create_dir_all(directorio)?;
+        }
+        let _bloqueo = Bloqueo::exclusivo(&self.ruta, self.espera_bloqueo)?;
+        let mut archivo = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(&self.ruta)?;
+        DiarioJsonl::descartar_linea_cortada(&mut archivo)?;
+        let mut texto = String::new();
+        archivo.seek(SeekFrom::Start(0))?;
+        archivo.read_to_string(&mut texto)?;
+        if !sigue_a(self.ultimo(&texto)?.as_ref()) {
+            return Err(conflicto(Some(&self.ruta)));
+        }
+        archivo.write_all(linea.as_bytes())?;
+        archivo.sync_data()?;
+        Ok(())
+    }
+
+    fn leer(&self) -> Result<Vec<E>, PersistenciaError> {
+        if !self.ruta.exists() {
+            return Ok(vec![]);
+        }
+        let _bloqueo = Bloqueo::compartido(&self.ruta, self.espera_bloqueo)?;
+        match fs::read_to_string(&self.ruta) {
+            Ok(texto) => parsear(
+                &texto,
+                &self.ruta.display().to_string(),
+                &self.esquema,
+                self.cifrado.as_ref(),
+            ),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(vec![]),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn vaciar(&self) -> Result<(), PersistenciaError> {
+        if !self.ruta.exists() {
+            return Ok(());
+        }
+        let _bloqueo = Bloqueo::exclusivo(&self.ruta, self.espera_bloqueo)?;
+        self.truncar()
+    }
+
+    // Un solo bloqueo para todo. Si el diario no existe no hay nada que vaciar.
+    fn vaciar_con_instantanea(
+        &self,
+        incluye: &dyn Fn(Option<&E>) -> bool,
+        guardar: &mut dyn FnMut() -> Result<(), PersistenciaError>,
+    ) -> Result<(), PersistenciaError> {
+        if !self.ruta.exists() {
+            return guardar();
+        }
+        let _bloqueo = Bloqueo::exclusivo(&self.ruta, self.espera_bloqueo)?;
+        let texto = match fs::read_to_string(&self.ruta) {
+            Ok(texto) => texto,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e.into()),
+        };
+        if !incluye(self.ultimo(&texto)?.as_ref()) {
+            return Err(conflicto(Some(&self.ruta)));
+        }
+        guardar()?;
+        self.dejar_ultima_linea(&texto)
+    }
+}
+
+impl DiarioMemoria {
+    pub fn new() -> Self {
+        DiarioMemoria::default()
+    }
+
+    pub fn con_esquema(mut self, esquema: Esquema) -> Self {
+        self.esquema = esquema;
+        self
+    }
+
+    pub fn contenido(&self) -> String {
+        self.contenido.borrow().clone()
+    }
+}
+
+impl<E: Serialize + DeserializeOwned> Diario<E> for DiarioMemoria {
+    fn agregar(&self, evento: &E) -> Result<(), PersistenciaError> {
+        self.contenido
+            .borrow_mut()
+            .push_str(&linea(evento, &self.esquema, None)?);
+        Ok(())
+    }
+
+    fn leer(&self) -> Result<Vec<E>, PersistenciaError> {
+        parsear(&self.contenido.borrow(), "memoria", &self.esquema, None)
+    }
+
+    fn vaciar(&self) -> Result<(), PersistenciaError> {
+        self.contenido.borrow_mut().clear();
+        Ok(())
+    }
+}
+
+impl<E> DiarioCompartido<E> {
+    pub fn new<D: Diario<E> + 'static>(diario: D) -> Self {
+        Compartido::de_rc(Rc::new(diario))
+    }
+}
+
+impl<E: Serialize + DeserializeOwned> DiarioCompartido<E> {
+    pub fn jsonl(directorio: impl AsRef<Path>, archivo: &str, esquema: Esquema) -> Self {
+        DiarioCompartido::new(DiarioJsonl::new(directorio, archivo).con_esquema(esquema))
+    }
+
+    pub fn memoria() -> Self {
+        DiarioCompartido::new(DiarioMemoria::new())
+    }
+}
+
+impl<E, D: Diario<E> + 'static> From<Rc<D>> for DiarioCompartido<E> {
+    fn from(diario: Rc<D>) -> Self {
+        Compartido::de_rc(diario)
+    }
+}
+
+impl<E> Diario<E> for DiarioCompartido<E> {
+    fn agregar(&self, evento: &E) -> Result<(), PersistenciaError> {
+        (**self).agregar(evento)
+    }
+
+    fn agregar_despues_de(
+        &self,
+        evento: &E,
+        sigue_a: &dyn Fn(Option<&E>) -> bool,
+    ) -> Result<(), PersistenciaError> {
+        (**self).agregar_despues_de(evento, sigue_a)
+    }
+
+    fn leer(&self) -> Result<Vec<E>, PersistenciaError> {
+        (**self).leer()
+    }
+
+    fn vaciar(&self) -> Result<(), PersistenciaError> {
+        (**self).vaciar()
+    }
+
+    fn vaciar_con_instantanea(
+        &self,
+        incluye: &dyn Fn(Option<&E>) -> bool,
+        guardar: &mut dyn FnMut() -> Result<(), PersistenciaError>,
+    ) -> Result<(), PersistenciaError> {
+        (**self).vaciar_con_instantanea(incluye, guardar)
+    }
+}
+
+// Diario donde no se puede escribir nada, para probar que los sistemas no pierden ni
+// aplican una operacion que no se llego a registrar.
+#[cfg(test)]
+pub struct DiarioLleno;
+
+#[cfg(test)]
+impl DiarioLleno {
+    pub fn compartido<E>() -> DiarioCompartido<E> {
+        DiarioCompartido::new(DiarioLleno)
+    }
+}
+
+#[cfg(test)]
+impl<E> Diario<E> for DiarioLleno {
+    fn agregar(&self, evento: &E) -> Result<(), PersistenciaError> {
+        Err(io::Error::new(io::ErrorKind::StorageFull, "no queda espacio en el disco").into())
+    }
+
+    fn leer(&self) -> Result<Vec<E>, PersistenciaError> {
+        Ok(vec![])
+    }
+
+    fn vaciar(&self) -> Result<(), PersistenciaError> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tp05::pruebas::directorio_temporal;
+
+    #[test]
+    fn test_memoria_agregar_leer_vaciar() {
+        let diario = DiarioMemoria::new();
+        diario.agregar(&(1u32, "uno".to_string())).unwrap();
+        diario.agregar(&(2u32, "dos".to_string())).unwrap();
+        assert_eq!(
+            diario.contenido(),
+            "{\"version\":1,\"datos\":[1,\"uno\"]}\n{\"version\":1,\"datos\":[2,\"dos\"]}\n"
+        );
+        let eventos: Vec<(u32, String)> = diario.leer().unwrap();
+        assert_eq!(
+            eventos,
+            vec![(1u32, "uno".to_string()), (2u32, "dos".to_string())]
+        );
+
+        Diario::<u32>::vaciar(&diario).unwrap();
+        assert!(Diario::<u32>::leer(&diario).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_jsonl_agrega_al_final() {
+        let dir = directorio_temporal("diario");
+        let diario = DiarioJsonl::new(&dir, "eventos.jsonl");
+        assert!(Diario::<u32>::leer(&diario).unwrap().is_empty());
+
+        for i in 1..=3u32 {
+            diario.agregar(&i).unwrap();
+        }
+        assert_eq!(
+            fs::read_to_string(diario.ruta()).unwrap().lines().last(),
+            Some("{\"version\":1,\"datos\":3}")
+        );
+        let eventos: Vec<u32> = diario.leer().unwrap();
+        assert_eq!(eventos, vec![1, 2, 3]);
+
+        Diario::<u32>::vaciar(&diario).unwrap();
+        assert_eq!(fs::read_to_string(diario.ruta()).unwrap(), "");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_jsonl_linea_cortada() {
+        let dir = directorio_temporal("diario-cortado");
+        let diario = DiarioJsonl::new(&dir, "eventos.jsonl");
+        diario.agregar(&[1u32, 2]).unwrap();
+        // se corto a mitad del segundo evento
+        let mut archivo = OpenOptions::new().append(true).open(diario.ruta()).unwrap();
+        archivo.write_all(b"[3,").unwrap();
+
+        let eventos: Vec<[u32; 2]> = diario.leer().unwrap();
+        assert_eq!(eventos, vec![[1, 2]]);
+        diario.agregar(&[5u32, 6]).unwrap();
+        let eventos: Vec<[u32; 2]> = diario.leer().unwrap();
+        assert_eq!(eventos, vec![[1, 2], [5, 6]]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_jsonl_bloqueado_por_otro_proceso() {
+        let dir = directorio_temporal("diario-bloqueado");
+        let diario = DiarioJsonl::new(&dir, "eventos.jsonl").con_espera_bloqueo(Duration::ZERO);
+        diario.agregar(&1u32).unwrap();
+        let otro = Bloqueo::exclusivo(diario.ruta(), Duration::ZERO).unwrap();
+
+        assert!(matches!(
+            diario.agregar(&2u32),
+            Err(PersistenciaError::Bloqueado(_))
+        ));
+        assert!(matches!(
+            Diario::<u32>::leer(&diario),
+            Err(PersistenciaError::Bloqueado(_))
+        ));
+        drop(otro);
+        diario.agregar(&2u32).unwrap();
+        let eventos: Vec<u32> = diario.leer().unwrap();
+        assert_eq!(eventos, vec![1, 2]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_jsonl_nadie_agrega_mientras_se_guarda_la_instantanea() {
+        let dir = directorio_temporal("diario-instantanea");
+        let diario = DiarioJsonl::new(&dir, "eventos.jsonl").con_espera_bloqueo(Duration::ZERO);
+        diario.agregar(&1u32).unwrap();
+
+        let otro = DiarioJsonl::new(&dir, "eventos.jsonl").con_espera_bloqueo(Duration::ZERO);
+        Diario::<u32>::vaciar_con_instantanea(&diario, &|_| true, &mut || {
+            assert!(matches!(
+                otro.agregar(&2u32),
+                Err(PersistenciaError::Bloqueado(_))
+            ));
+            Ok(())
+        })
+        .unwrap();
+        // queda el ultimo, que ya esta en la instantanea
+        let eventos: Vec<u32> = diario.leer().unwrap();
+        assert_eq!(eventos, vec![1]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_jsonl_otro_proceso_agrego_antes() {
+        let dir = directorio_temporal("diario-secuencia");
+        let diario = DiarioJsonl::new(&dir, "eventos.jsonl");
+        let sigue_a = |anterior: u32| move |ultimo: Option<&u32>| ultimo.copied() == Some(anterior);
+        diario.agregar(&1u32).unwrap();
+        diario.agregar_despues_de(&2u32, &sigue_a(1)).unwrap();
+
+        // otro proceso que solo vio el 1 no puede agregar ni vaciar
+        assert!(matches!(
+            diario.agregar_despues_de(&2u32, &sigue_a(1)),
+            Err(PersistenciaError::Conflicto(_))
+        ));
+        let mut guardo = false;
+        assert!(matches!(
+            diario.vaciar_con_instantanea(&sigue_a(1), &mut || {
+                guardo = true;
+                Ok(())
+            }),
+            Err(PersistenciaError::Conflicto(_))
+        ));
+        assert!(!guardo);
+        let eventos: Vec<u32> = diario.leer().unwrap();
+        assert_eq!(eventos, vec![1, 2]);
+
+        // el ultimo evento sobrevive a la compactacion y sigue marcando por donde va
+        diario
+            .vaciar_con_instantanea(&sigue_a(2), &mut || Ok(()))
+            .unwrap();
+        assert!(matches!(
+            diario.agregar_despues_de(&2u32, &sigue_a(1)),
+            Err(PersistenciaError::Conflicto(_))
+        ));
+        diario.agregar_despues_de(&3u32, &sigue_a(2)).unwrap();
+        let eventos: Vec<u32> = diario.leer().unwrap();
+        assert_eq!(eventos, vec![2, 3]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_linea_invalida_en_el_medio() {
+        let diario = DiarioMemoria::new();
+        diario.contenido.borrow_mut().push_str("1\n2x\n3\n");
+        match Diario::<u32>::leer(&diario) {
+            Err(PersistenciaError::Corrupto(detalle)) => assert!(detalle.contains("linea 2")),
+            _ => panic!("se esperaba Corrupto"),
+        }
+    }
+}
@@ -1,24 +1,49 @@
 #![allow(dead_code, unused_variables)]
 
+use crate::tp03::consulta_autos::{ConsultaAutos, DatosAuto};
+use crate::tp03::fecha_hora::FechaHora;
+use crate::tp03::reloj::{Reloj, RelojCompartido};
+use crate::tp05::esquema::{Esquema, normalizar_variante, por_cada_elemento};
+use crate::tp05::financiacion::Financiacion;
+use crate::tp05::precios::{Cotizacion, ReglasError, ReglasPrecio};
+use crate::tp05::repositorio::{
+    ConUnidadDeTrabajo, DIRECTORIO_REGISTROS, PersistenciaError, Repositorio,
+    RepositorioCompartido, UnidadDeTrabajo,
+};
+use crate::tp05::tabla::{self, ErrorCsv, ErrorFila, Fila, MapeoColumnas, RegistroCsv};
+use crate::tp05::vin::Vin;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::fmt;
-use std::fs::File;
-use std::io::Write;
+use std::io;
 
 pub struct ConcesionarioAuto {
     nombre: String,
     direccion: String,
     capacidad: u16,
     autos: Vec<Auto>,
+    // Los autos vendidos ya no estan en stock ni ocupan lugar.
+    ventas: Vec<Venta>,
+    repositorio: RepositorioCompartido<Vec<Auto>>,
+    repo_ventas: RepositorioCompartido<Vec<Venta>>,
+    unidad: UnidadDeTrabajo,
+    // Si entre los cambios pendientes hay ventas, para no reescribir su archivo por cada auto.
+    ventas_pendientes: bool,
+    reglas_precio: ReglasPrecio,
+    reloj: RelojCompartido,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Auto {
+    vin: Vin,
     marca: String,
     modelo: String,
     año: u32,
     precio_bruto: f64,
     color: Color,
+    // Los archivos de antes de las reservas no lo tienen: esos autos estan en stock.
+    #[serde(default)]
+    estado: EstadoAuto,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
@@ -31,6 +56,77 @@ pub enum Color {
     Negro,
 }
 
+// Un auto del concesionario esta en stock o reservado; al venderlo pasa a las ventas.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub enum EstadoAuto {
+    #[default]
+    EnStock,
+    Reservado(Reserva),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Reserva {
+    pub cliente: String,
+    pub seña: f64,
+    pub vence: FechaHora,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Venta {
+    pub auto: Auto,
+    pub cliente: String,
+    pub fecha: FechaHora,
+    // Con las reglas de precio del concesionario al momento de la venta.
+    pub precio_final: f64,
+    // Lo que el cliente ya habia pagado al reservar; 0 si no reservo.
+    pub seña: f64,
+}
+
+impl Reserva {
+    pub fn vencida(&self, ahora: &FechaHora) -> bool {
+        self.vence <= *ahora
+    }
+}
+
+impl Venta {
+    // Lo que falta pagar descontando la seña.
+    pub fn saldo(&self) -> f64 {
+        self.precio_final - self.seña
+    }
+}
+
+// Version 2: los colores en mayusculas de tp03 (ROJO) pasan a como se escriben aca (Rojo).
+// Version 3: cada auto tiene su VIN.
+pub fn esquema() -> Esquema {
+    Esquema::new()
+        .migracion(normalizar_colores)
+        .migracion(exigir_vin)
+}
+
+fn normalizar_colores(datos: Value) -> Result<Value, String> {
+    por_cada_elemento(datos, |auto| {
+        if let Some(color) = auto.get_mut("color") {
+            normalizar_variante(color);
+        }
+        Ok(())
+    })
+}
+
+// El VIN no se puede inventar: un archivo anterior hay que completarlo a mano.
+fn exigir_vin(datos: Value) -> Result<Value, String> {
+    por_cada_elemento(datos, |auto| {
+        if auto.contains_key("vin") {
+            return Ok(());
+        }
+        let campo = |nombre: &str| auto.get(nombre).and_then(Value::as_str).unwrap_or("?");
+        Err(format!(
+            "el auto {} {} no tiene VIN",
+            campo("marca"),
+            campo("modelo")
+        ))
+    })
+}
+
 #[derive(Debug, PartialEq)]
 pub struct CapacidadError(u16);
 
@@ -44,6 +140,81 @@ impl fmt::Display for CapacidadError {
     }
 }
 
+#[derive(Debug)]
+pub enum ConcesionarioError {
+    Capacidad(CapacidadError),
+    // Ya hay un auto con ese VIN en el concesionario.
+    VinRepetido(Vin),
+    // No hay en stock un auto con ese VIN.
+    AutoInexistente(Vin),
+    // El auto tiene una reserva vigente de otro cliente.
+    AutoReservado { vin: Vin, cliente: String },
+    SinReserva(Vin),
+    SeñaInvalida(f64),
+    // Cero o menos, o tantos que el vencimiento queda fuera del calendario.
+    DiasInvalidos(i64),
+    Persistencia(PersistenciaError),
+    Csv(ErrorCsv),
+    Reglas(ReglasError),
+}
+
+impl fmt::Display for ConcesionarioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConcesionarioError::Capacidad(e) => write!(f, "{}", e),
+            ConcesionarioError::VinRepetido(vin) => {
+                write!(f, "Ya hay un auto con el VIN {} en la concesionaria", vin)
+            }
+            ConcesionarioError::AutoInexistente(vin) => {
+                write!(f, "No hay ningun auto con el VIN {} en stock", vin)
+            }
+            ConcesionarioError::AutoReservado { vin, cliente } => {
+                write!(f, "El auto con VIN {} esta reservado por {}", vin, cliente)
+            }
+            ConcesionarioError::SinReserva(vin) => {
+                write!(f, "El auto con VIN {} no tiene una reserva vigente", vin)
+            }
+            ConcesionarioError::SeñaInvalida(seña) => write!(
+                f,
+                "La seña debe ser mayor a 0 y no superar el precio del auto: {}",
+                seña
+            ),
+            ConcesionarioError::DiasInvalidos(dias) => {
+                write!(f, "La reserva debe durar al menos un dia: {}", dias)
+            }
+            ConcesionarioError::Persistencia(e) => write!(f, "{}", e),
+            ConcesionarioError::Csv(e) => write!(f, "{}", e),
+            ConcesionarioError::Reglas(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ConcesionarioError {}
+
+impl From<CapacidadError> for ConcesionarioError {
+    fn from(e: CapacidadError) -> Self {
+        ConcesionarioError::Capacidad(e)
+    }
+}
+
+impl From<PersistenciaError> for ConcesionarioError {
+    fn from(e: PersistenciaError) -> Self {
+        ConcesionarioError::Persistencia(e)
+    }
+}
+
+impl From<ReglasError> for ConcesionarioError {
+    fn from(e: ReglasError) -> Self {
+        ConcesionarioError::Reglas(e)
+    }
+}
+
+impl From<ErrorCsv> for ConcesionarioError {
+    fn from(e: ErrorCsv) -> Self {
+        ConcesionarioError::Csv(e)
+    }
+}
+
 impl ConcesionarioAuto {
     pub fn new(nombre: String, direccion: String, capacidad: u16) -> Self {
         let autos: Vec<Auto> = vec![];
@@ -52,30 +223,271 @@ impl ConcesionarioAuto {
             direccion,
             capacidad,
             autos,
+            ventas: vec![],
+            repositorio: RepositorioCompartido::json(
+                DIRECTORIO_REGISTROS,
+                "ej01/autos.json",
+                esquema(),
+            ),
+            repo_ventas: RepositorioCompartido::json(
+                DIRECTORIO_REGISTROS,
+                "ej01/ventas.json",
+                Esquema::new(),
+            ),
+            unidad: UnidadDeTrabajo::default(),
+            ventas_pendientes: false,
+            reglas_precio: ReglasPrecio::default(),
+            reloj: RelojCompartido::default(),
         }
     }
 
+    // Restaura el stock y las ventas de los repositorios y cotiza con `reglas`. Los
+    // repositorios que todavia no tienen nada guardado arrancan vacios.
+    pub fn abrir(
+        nombre: String,
+        direccion: String,
+        capacidad: u16,
+        autos: RepositorioCompartido<Vec<Auto>>,
+        ventas: RepositorioCompartido<Vec<Venta>>,
+        reglas: ReglasPrecio,
+    ) -> Result<Self, PersistenciaError> {
+        ConcesionarioAuto::new(nombre, direccion, capacidad)
+            .con_repositorio(autos)
+            .con_repositorio_ventas(ventas)
+            .con_reglas_precio(reglas)
+            .leer_guardado()
+    }
+
+    // Igual que abrir pero con los archivos por defecto en DIRECTORIO_REGISTROS, incluidas
+    // las reglas de precio que edita marketing.
+    pub fn cargar(
+        nombre: String,
+        direccion: String,
+        capacidad: u16,
+    ) -> Result<Self, ConcesionarioError> {
+        let reglas = ReglasPrecio::cargar()?;
+        Ok(ConcesionarioAuto::new(nombre, direccion, capacidad)
+            .con_reglas_precio(reglas)
+            .leer_guardado()?)
+    }
+
+    fn leer_guardado(mut self) -> Result<Self, PersistenciaError> {
+        self.autos = self.repositorio.cargar()?.unwrap_or_default();
+        self.ventas = self.repo_ventas.cargar()?.unwrap_or_default();
+        Ok(self)
+    }
+
+    pub fn con_reloj(mut self, reloj: RelojCompartido) -> Self {
+        self.reloj = reloj;
+        self
+    }
+
+    pub fn con_repositorio_ventas(
+        mut self,
+        repositorio: RepositorioCompartido<Vec<Venta>>,
+    ) -> Self {
+        self.repo_ventas = repositorio;
+        self
+    }
+
+    // Las reglas con las que este concesionario calcula precios, en lugar de las por defecto.
+    pub fn con_reglas_precio(mut self, reglas: ReglasPrecio) -> Self {
+        self.reglas_precio = reglas;
+        self
+    }
+
+    pub fn cotizar(&self, auto: &Auto) -> Cotizacion {
+        self.reglas_precio.cotizar(auto)
+    }
+
+    // Sobre el precio de este concesionario; Financiacion::para_auto usa las reglas por defecto.
+    pub fn financiar(&self, auto: &Auto, meses: u32) -> Financiacion {
+        Financiacion::new(self.reglas_precio.precio(auto), meses)
+    }
+
+    pub fn con_repositorio(mut self, repositorio: RepositorioCompartido<Vec<Auto>>) -> Self {
+        self.repositorio = repositorio;
+        self
+    }
+
     // 1a- Al agregar un auto si supera el límite de la concesionaria debe arrojar un error propio con un mensaje de contexto.
-    pub fn agregar_auto(&mut self, auto: &Auto) -> Result<(), CapacidadError> {
+    pub fn agregar_auto(&mut self, auto: &Auto) -> Result<(), ConcesionarioError> {
         if self.capacidad == self.autos.len() as u16 {
-            return Err(CapacidadError(self.capacidad));
+            return Err(CapacidadError(self.capacidad).into());
+        }
+        if self.buscar_por_vin(&auto.vin).is_some() {
+            return Err(ConcesionarioError::VinRepetido(auto.vin.clone()));
         }
 
-        self.autos.push(auto.clone());
-        self.persistir_autos();
+        self.cambiar(|c| c.autos.push(auto.clone()))?;
 
         Ok(())
     }
 
-    pub fn eliminar_auto(&mut self, auto: &Auto) {
-        self.autos.retain(|a| !a.comparar(auto));
-        self.persistir_autos();
+    pub fn eliminar_auto(&mut self, auto: &Auto) -> Result<(), ConcesionarioError> {
+        self.eliminar_por_vin(&auto.vin)?;
+        Ok(())
     }
 
-    fn persistir_autos(&self) {
-        let mut f = File::create("src/tp05/registros/ej01/autos.json").unwrap();
-        let auto_serializado = serde_json::to_string_pretty(&self.autos).unwrap();
-        f.write_all(auto_serializado.as_bytes()).unwrap();
+    // Elimina solo esa unidad, aunque haya otras de la misma marca y modelo. Devuelve si estaba.
+    pub fn eliminar_por_vin(&mut self, vin: &Vin) -> Result<bool, ConcesionarioError> {
+        let Some(posicion) = self.autos.iter().position(|a| a.vin == *vin) else {
+            return Ok(false);
+        };
+        self.cambiar(|c| c.autos.remove(posicion))?;
+        Ok(true)
+    }
+
+    // Primero las ventas: si falla el stock, lo vendido no se pierde.
+    fn persistir_autos(&mut self) -> Result<(), PersistenciaError> {
+        if self.ventas_pendientes {
+            self.repo_ventas.guardar(&self.ventas)?;
+            self.ventas_pendientes = false;
+        }
+        self.repositorio.guardar(&self.autos)
+    }
+
+    // Reserva un auto en stock para un cliente por `dias` dias. Una reserva vencida no
+    // cuenta: el auto se puede volver a reservar o vender a cualquiera.
+    pub fn reservar(
+        &mut self,
+        vin: &Vin,
+        cliente: &str,
+        seña: f64,
+        dias: i64,
+    ) -> Result<Reserva, ConcesionarioError> {
+        let ahora = self.reloj.ahora();
+        let posicion = self.posicion_en_stock(vin)?;
+        if let Some(reserva) = self.reserva_vigente(&self.autos[posicion]) {
+            return Err(ConcesionarioError::AutoReservado {
+                vin: vin.clone(),
+                cliente: reserva.cliente.clone(),
+            });
+        }
+        let precio = self.reglas_precio.precio(&self.autos[posicion]);
+        if !seña.is_finite() || seña <= 0.0 || seña > precio {
+            return Err(ConcesionarioError::SeñaInvalida(seña));
+        }
+        let mut vence = ahora;
+        if dias <= 0
+            || dias
+                .checked_mul(86400)
+                .is_none_or(|segundos| vence.try_sumar_segundos(segundos).is_err())
+        {
+            return Err(ConcesionarioError::DiasInvalidos(dias));
+        }
+        let reserva = Reserva {
+            cliente: cliente.to_string(),
+            seña,
+            vence,
+        };
+        self.cambiar(|c| c.autos[posicion].estado = EstadoAuto::Reservado(reserva.clone()))?;
+        Ok(reserva)
+    }
+
+    pub fn cancelar_reserva(&mut self, vin: &Vin) -> Result<Reserva, ConcesionarioError> {
+        let posicion = self.posicion_en_stock(vin)?;
+        let Some(reserva) = self.reserva_vigente(&self.autos[posicion]).cloned() else {
+            return Err(ConcesionarioError::SinReserva(vin.clone()));
+        };
+        self.cambiar(|c| c.autos[posicion].estado = EstadoAuto::EnStock)?;
+        Ok(reserva)
+    }
+
+    // Saca el auto del stock, liberando su lugar, y registra la venta. Si estaba reservado
+    // solo lo puede comprar quien lo reservo, y la seña se descuenta del saldo.
+    pub fn vender(&mut self, vin: &Vin, cliente: &str) -> Result<Venta, ConcesionarioError> {
+        let posicion = self.posicion_en_stock(vin)?;
+        let seña = match self.reserva_vigente(&self.autos[posicion]) {
+            Some(reserva) if reserva.cliente != cliente => {
+                return Err(ConcesionarioError::AutoReservado {
+                    vin: vin.clone(),
+                    cliente: reserva.cliente.clone(),
+                });
+            }
+            Some(reserva) => reserva.seña,
+            None => 0.0,
+        };
+        let mut auto = self.autos[posicion].clone();
+        auto.estado = EstadoAuto::EnStock;
+        let venta = Venta {
+            precio_final: self.reglas_precio.precio(&auto),
+            auto,
+            cliente: cliente.to_string(),
+            fecha: self.reloj.ahora(),
+            seña,
+        };
+        self.cambiar(|c| {
+            c.autos.remove(posicion);
+            c.ventas.push(venta.clone());
+            c.ventas_pendientes = true;
+        })?;
+        Ok(venta)
+    }
+
+    // Vuelve a stock los autos con reservas vencidas. Devuelve cuantos libero.
+    pub fn liberar_reservas_vencidas(&mut self) -> Result<usize, ConcesionarioError> {
+        let ahora = self.reloj.ahora();
+        let vencida =
+            |auto: &Auto| matches!(&auto.estado, EstadoAuto::Reservado(r) if r.vencida(&ahora));
+        let liberados = self.autos.iter().filter(|a| vencida(a)).count();
+        if liberados > 0 {
+            self.cambiar(|c| {
+                for auto in c.autos.iter_mut().filter(|a| vencida(a)) {
+                    auto.estado = EstadoAuto::EnStock;
+                }
+            })?;
+        }
+        Ok(liberados)
+    }
+
+    // El estado de un auto en stock, tomando las reservas vencidas como stock.
+    pub fn estado(&self, vin: &Vin) -> Option<EstadoAuto> {
+        let auto = self.buscar_por_vin(vin)?;
+        Some(match self.reserva_vigente(auto) {
+            Some(reserva) => EstadoAuto::Reservado(reserva.clone()),
+            None => EstadoAuto::EnStock,
+        })
+    }
+
+    pub fn ventas(&self) -> &[Venta] {
+        &self.ventas
+    }
+
+    fn posicion_en_stock(&self, vin: &Vin) -> Result<usize, ConcesionarioError> {
+        self.autos
+            .iter()
+            .position(|a| a.vin == *vin)
+            .ok_or_else(|| ConcesionarioError::AutoInexistente(vin.clone()))
+    }
+
+    fn reserva_vigente<'a>(&self, auto: &'a Auto) -> Option<&'a Reserva> {
+        match &auto.estado {
+            EstadoAuto::Reservado(reserva) if !reserva.vencida(&self.reloj.ahora()) => {
+                Some(reserva)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn exportar_csv(&self, escritor: impl io::Write) -> Result<(), ErrorCsv> {
+        tabla::exportar(&self.autos, escritor)
+    }
+
+    // Agrega todos los autos del archivo o ninguno, si alguna fila es invalida o no entran.
+    // Devuelve cuantos agrego.
+    pub fn importar_csv(
+        &mut self,
+        lector: impl io::Read,
+        mapeo: &MapeoColumnas,
+    ) -> Result<usize, ConcesionarioError> {
+        let autos: Vec<Auto> = tabla::importar(lector, mapeo)?;
+        self.unidad_de_trabajo(|c| {
+            for auto in &autos {
+                c.agregar_auto(auto)?;
+            }
+            Ok(autos.len())
+        })
     }
 
     pub fn buscar_auto(&self, auto: &Auto) -> Option<Auto> {
@@ -88,42 +500,152 @@ impl ConcesionarioAuto {
         }
         None
     }
+
+    pub fn buscar_por_vin(&self, vin: &Vin) -> Option<&Auto> {
+        self.autos.iter().find(|a| a.vin == *vin)
+    }
+
+    // Consulta sobre el stock, sin copiar los autos.
+    pub fn consultar(&self) -> ConsultaAutos<'_, Auto> {
+        ConsultaAutos::new(&self.autos).con_tarifa(|a| self.reglas_precio.precio(a))
+    }
+}
+
+// Para cargas masivas: todo lo que se haga en una unidad de trabajo escribe los archivos una
+// sola vez, y si algo falla, por ejemplo porque se llena la concesionaria a mitad de la carga,
+// no queda ninguno de los cambios.
+impl ConUnidadDeTrabajo for ConcesionarioAuto {
+    type Copia = (Vec<Auto>, Vec<Venta>, bool);
+
+    fn unidad(&self) -> &UnidadDeTrabajo {
+        &self.unidad
+    }
+
+    fn unidad_mut(&mut self) -> &mut UnidadDeTrabajo {
+        &mut self.unidad
+    }
+
+    fn copia(&self) -> Self::Copia {
+        (
+            self.autos.clone(),
+            self.ventas.clone(),
+            self.ventas_pendientes,
+        )
+    }
+
+    // Si se llegaron a guardar las ventas pero no el stock, el archivo de ventas tiene las
+    // que se deshacen: queda pendiente volver a escribirlo.
+    fn restaurar(&mut self, (autos, ventas, ventas_pendientes): Self::Copia) {
+        self.ventas_pendientes = ventas_pendientes || self.ventas != ventas;
+        self.autos = autos;
+        self.ventas = ventas;
+    }
+
+    fn persistir(&mut self) -> Result<(), PersistenciaError> {
+        self.persistir_autos()
+    }
 }
 
 impl Auto {
-    pub fn new(marca: String, modelo: String, año: u32, precio_bruto: f64, color: Color) -> Self {
+    pub fn new(
+        vin: Vin,
+        marca: String,
+        modelo: String,
+        año: u32,
+        precio_bruto: f64,
+        color: Color,
+    ) -> Self {
         Auto {
+            vin,
             marca,
             modelo,
             año,
             precio_bruto,
             color,
+            estado: EstadoAuto::EnStock,
         }
     }
 
+    // Dos autos son la misma unidad si tienen el mismo VIN.
     fn comparar(&self, a2: &Auto) -> bool {
-        self.marca == a2.marca && self.modelo == a2.modelo
+        self.vin == a2.vin
+    }
+
+    pub fn vin(&self) -> &Vin {
+        &self.vin
     }
 
     pub fn get_info(&self) -> String {
         format!("Marca: {}, Modelo: {}", self.marca, self.modelo)
     }
 
+    // Con las reglas por defecto; las del archivo de marketing se pasan a calcular_precio_con
+    // despues de leerlas con ReglasPrecio::cargar.
     pub fn calcular_precio(&self) -> f64 {
-        let mut tasa = match self.color {
-            Color::Rojo | Color::Amarillo | Color::Azul => 0.25,
-            _ => -0.10,
-        };
+        self.calcular_precio_con(ReglasPrecio::por_defecto())
+    }
 
-        if self.marca == "BMW" {
-            tasa += 0.15;
-        }
+    pub fn calcular_precio_con(&self, reglas: &ReglasPrecio) -> f64 {
+        reglas.precio(self)
+    }
+}
 
-        if self.año < 2000 {
-            tasa -= 0.05;
-        }
+impl DatosAuto for Auto {
+    type Color = Color;
+
+    fn marca(&self) -> &str {
+        &self.marca
+    }
+
+    fn modelo(&self) -> &str {
+        &self.modelo
+    }
+
+    fn año(&self) -> u32 {
+        self.año
+    }
+
+    fn color(&self) -> &Color {
+        &self.color
+    }
+
+    fn precio_bruto(&self) -> f64 {
+        self.precio_bruto
+    }
 
-        self.precio_bruto * (1.0 + tasa)
+    fn precio_final(&self) -> f64 {
+        self.calcular_precio()
+    }
+}
+
+impl RegistroCsv for Auto {
+    const COLUMNAS: &'static [&'static str] =
+        &["vin", "marca", "modelo", "año", "precio_bruto", "color"];
+
+    fn a_fila(&self) -> Vec<String> {
+        vec![
+            self.vin.to_string(),
+            self.marca.clone(),
+            self.modelo.clone(),
+            self.año.to_string(),
+            self.precio_bruto.to_string(),
+            tabla::variante(&self.color),
+        ]
+    }
+
+    fn de_fila(fila: &Fila) -> Result<Self, ErrorFila> {
+        let precio_bruto: f64 = fila.valor("precio_bruto")?;
+        if !precio_bruto.is_finite() || precio_bruto < 0.0 {
+            return Err(fila.error_en("precio_bruto", "no puede ser negativo"));
+        }
+        Ok(Auto::new(
+            fila.valor("vin")?,
+            fila.texto("marca")?,
+            fila.texto("modelo")?,
+            fila.valor("año")?,
+            precio_bruto,
+            fila.variante("color")?,
+        ))
     }
 }
 
@@ -145,16 +667,48 @@ impl Auto {
 
 #[cfg(test)]
 mod tests {
-    use super::{Auto, Color, ConcesionarioAuto};
+    use super::{Auto, Color, ConcesionarioAuto, ConcesionarioError, EstadoAuto, Venta, esquema};
+    use crate::tp03::consulta_autos::{CampoAuto, Sentido};
+    use crate::tp03::ej03::Fecha;
+    use crate::tp03::reloj::{Reloj, RelojCompartido, RelojManual};
+    use crate::tp05::precios::{Efecto, Regla, ReglasPrecio};
+    use crate::tp05::repositorio::{
+        ConUnidadDeTrabajo, PersistenciaError, Repositorio, RepositorioCompartido,
+        RepositorioLleno, RepositorioMemoria,
+    };
+    use crate::tp05::tabla::{ErrorCsv, MapeoColumnas};
+    use crate::tp05::vin::Vin;
+    use std::rc::Rc;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    // Un VIN valido distinto para cada numero de serie.
+    fn vin(serie: u32) -> Vin {
+        Vin::completar(&format!("8AP17817?LP{:06}", serie)).unwrap()
+    }
 
+    fn unidad(serie: u32, marca: &str, modelo: &str, año: u32, precio: f64, color: Color) -> Auto {
+        Auto::new(
+            vin(serie),
+            marca.to_string(),
+            modelo.to_string(),
+            año,
+            precio,
+            color,
+        )
+    }
+
+    // Cada auto creado es una unidad distinta, con su propio VIN.
     fn crear_auto(marca: &str, modelo: &str, año: u32, precio: f64, color: Color) -> Auto {
-        Auto::new(marca.to_string(), modelo.to_string(), año, precio, color)
+        static SERIE: AtomicU32 = AtomicU32::new(100_000);
+        let serie = SERIE.fetch_add(1, Ordering::Relaxed);
+        unidad(serie, marca, modelo, año, precio, color)
     }
 
     #[test]
     fn test_agregar_auto_concesionaria_vacia() {
         let mut concesionario =
-            ConcesionarioAuto::new("Test".to_string(), "Calle 1".to_string(), 2);
+            ConcesionarioAuto::new("Test".to_string(), "Calle 1".to_string(), 2)
+                .con_repositorio(RepositorioCompartido::memoria());
         let auto = crear_auto("Toyota", "Corolla", 2020, 20000.0, Color::Azul);
 
         let result = concesionario.agregar_auto(&auto);
@@ -174,7 +728,8 @@ mod tests {
     #[test]
     fn test_agregar_auto_concesionaria_llena() {
         let mut concesionario =
-            ConcesionarioAuto::new("Test".to_string(), "Calle 1".to_string(), 1);
+            ConcesionarioAuto::new("Test".to_string(), "Calle 1".to_string(), 1)
+                .con_repositorio(RepositorioCompartido::memoria());
         let auto1 = crear_auto("Toyota", "Corolla", 2020, 20000.0, Color::Azul);
         let auto2 = crear_auto("Honda", "Civic", 2021, 22000.0, Color::Rojo);
 
@@ -202,7 +757,8 @@ mod tests {
     #[test]
     fn test_agregar_auto_hasta_capacidad() {
         let mut concesionario =
-            ConcesionarioAuto::new("Test".to_string(), "Calle 1".to_string(), 3);
+            ConcesionarioAuto::new("Test".to_string(), "Calle 1".to_string(), 3)
+                .con_repositorio(RepositorioCompartido::memoria());
         let auto1 = crear_auto("Toyota", "Corolla", 2020, 20000.0, Color::Azul);
         let auto2 = crear_auto("Honda", "Civic", 2021, 22000.0, Color::Rojo);
         let auto3 = crear_auto("Ford", "Focus", 2019, 18000.0, Color::Blanco);
@@ -234,13 +790,14 @@ mod tests {
     #[test]
     fn test_eliminar_auto_existente() {
         let mut concesionario =
-            ConcesionarioAuto::new("Test".to_string(), "Calle 1".to_string(), 2);
+            ConcesionarioAuto::new("Test".to_string(), "Calle 1".to_string(), 2)
+                .con_repositorio(RepositorioCompartido::memoria());
         let auto1 = crear_auto("Toyota", "Corolla", 2020, 20000.0, Color::Azul);
         let auto2 = crear_auto("Honda", "Civic", 2021, 22000.0, Color::Rojo);
 
         concesionario.agregar_auto(&auto1).unwrap();
         concesionario.agregar_auto(&auto2).unwrap();
-        concesionario.eliminar_auto(&auto1);
+        concesionario.eliminar_auto(&auto1).unwrap();
 
         assert_eq!(
             concesionario.autos.len(),
@@ -260,12 +817,13 @@ mod tests {
     #[test]
     fn test_eliminar_auto_no_existente() {
         let mut concesionario =
-            ConcesionarioAuto::new("Test".to_string(), "Calle 1".to_string(), 2);
+            ConcesionarioAuto::new("Test".to_string(), "Calle 1".to_string(), 2)
+                .con_repositorio(RepositorioCompartido::memoria());
         let auto1 = crear_auto("Toyota", "Corolla", 2020, 20000.0, Color::Azul);
         let auto2 = crear_auto("Honda", "Civic", 2021, 22000.0, Color::Rojo);
 
         concesionario.agregar_auto(&auto1).unwrap();
-        concesionario.eliminar_auto(&auto2);
+        concesionario.eliminar_auto(&auto2).unwrap();
 
         assert_eq!(concesionario.autos.len(), 1, "La lista no debería cambiar");
         assert!(
@@ -277,10 +835,11 @@ mod tests {
     #[test]
     fn test_eliminar_auto_concesionaria_vacia() {
         let mut concesionario =
-            ConcesionarioAuto::new("Test".to_string(), "Calle 1".to_string(), 2);
+            ConcesionarioAuto::new("Test".to_string(), "Calle 1".to_string(), 2)
+                .con_repositorio(RepositorioCompartido::memoria());
         let auto = crear_auto("Toyota", "Corolla", 2020, 20000.0, Color::Azul);
 
-        concesionario.eliminar_auto(&auto);
+        concesionario.eliminar_auto(&auto).unwrap();
 
         assert_eq!(
             concesionario.autos.len(),
@@ -292,17 +851,18 @@ mod tests {
     #[test]
     fn test_comparar_auto_ignora_atributos_no_relevantes() {
         let mut concesionario =
-            ConcesionarioAuto::new("Test".to_string(), "Calle 1".to_string(), 2);
-        let auto1 = crear_auto("Toyota", "Corolla", 2020, 20000.0, Color::Azul);
-        let auto2 = crear_auto("Toyota", "Corolla", 2019, 18000.0, Color::Rojo);
+            ConcesionarioAuto::new("Test".to_string(), "Calle 1".to_string(), 2)
+                .con_repositorio(RepositorioCompartido::memoria());
+        let auto1 = unidad(1, "Toyota", "Corolla", 2020, 20000.0, Color::Azul);
+        let auto2 = unidad(1, "Toyota", "Corolla", 2019, 18000.0, Color::Rojo);
 
         concesionario.agregar_auto(&auto1).unwrap();
-        concesionario.eliminar_auto(&auto2);
+        concesionario.eliminar_auto(&auto2).unwrap();
 
         assert_eq!(
             concesionario.autos.len(),
             0,
-            "Debería eliminar el auto por marca y modelo"
+            "Debería eliminar el auto por VIN"
         );
         assert!(
             concesionario.buscar_auto(&auto1).is_none(),
@@ -310,9 +870,473 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_dos_unidades_del_mismo_modelo() {
+        let repo = Rc::new(RepositorioMemoria::new());
+        let mut concesionario =
+            ConcesionarioAuto::new("Test".to_string(), "Calle 1".to_string(), 5)
+                .con_repositorio(RepositorioCompartido::from(repo.clone()));
+        let auto1 = unidad(1, "Toyota", "Corolla", 2020, 20000.0, Color::Azul);
+        let auto2 = unidad(2, "Toyota", "Corolla", 2020, 20000.0, Color::Azul);
+        concesionario.agregar_auto(&auto1).unwrap();
+        concesionario.agregar_auto(&auto2).unwrap();
+        assert_eq!(concesionario.autos.len(), 2);
+
+        let repetido = unidad(2, "Ford", "Ka", 2015, 9000.0, Color::Rojo);
+        let error = concesionario.agregar_auto(&repetido).unwrap_err();
+        assert!(matches!(&error, ConcesionarioError::VinRepetido(v) if *v == vin(2)));
+        assert_eq!(
+            error.to_string(),
+            format!("Ya hay un auto con el VIN {} en la concesionaria", vin(2))
+        );
+
+        assert_eq!(concesionario.buscar_por_vin(&vin(2)), Some(&auto2));
+        assert!(concesionario.eliminar_por_vin(&vin(1)).unwrap());
+        assert_eq!(concesionario.autos, vec![auto2]);
+        assert!(!concesionario.eliminar_por_vin(&vin(1)).unwrap());
+        assert_eq!(repo.guardados(), 3);
+    }
+
+    #[test]
+    fn test_agregar_auto_sin_espacio_en_disco() {
+        let mut concesionario =
+            ConcesionarioAuto::new("Test".to_string(), "Calle 1".to_string(), 2)
+                .con_repositorio(RepositorioLleno::compartido());
+        let auto = crear_auto("Toyota", "Corolla", 2020, 20000.0, Color::Azul);
+
+        let error = concesionario.agregar_auto(&auto).unwrap_err();
+        assert!(matches!(
+            error,
+            ConcesionarioError::Persistencia(PersistenciaError::Io(_))
+        ));
+        assert!(error.to_string().contains("no queda espacio en el disco"));
+    }
+
+    #[test]
+    fn test_carga_masiva_en_una_unidad_de_trabajo() {
+        let repo = Rc::new(RepositorioMemoria::new());
+        let mut concesionario =
+            ConcesionarioAuto::new("Test".to_string(), "Calle 1".to_string(), 5000)
+                .con_repositorio(RepositorioCompartido::from(repo.clone()));
+        let autos: Vec<Auto> = (0..3000)
+            .map(|i| crear_auto("Fiat", &format!("Modelo {}", i), 2000, 1000.0, Color::Negro))
+            .collect();
+
+        concesionario
+            .unidad_de_trabajo(|c| {
+                for auto in &autos {
+                    c.agregar_auto(auto)?;
+                }
+                c.eliminar_auto(&autos[0])
+            })
+            .unwrap();
+        assert_eq!(repo.guardados(), 1);
+        let guardados: Vec<Auto> = repo.cargar().unwrap().unwrap();
+        assert_eq!(guardados.len(), 2999);
+
+        // eliminar uno que no esta no escribe
+        concesionario.eliminar_auto(&autos[0]).unwrap();
+        assert_eq!(repo.guardados(), 1);
+    }
+
+    #[test]
+    fn test_carga_masiva_que_supera_la_capacidad() {
+        let repo = Rc::new(RepositorioMemoria::new());
+        let mut concesionario =
+            ConcesionarioAuto::new("Test".to_string(), "Calle 1".to_string(), 2)
+                .con_repositorio(RepositorioCompartido::from(repo.clone()));
+        let auto1 = crear_auto("Toyota", "Corolla", 2020, 20000.0, Color::Azul);
+        let auto2 = crear_auto("Honda", "Civic", 2021, 22000.0, Color::Rojo);
+        let auto3 = crear_auto("Ford", "Ka", 2015, 9000.0, Color::Blanco);
+        concesionario.agregar_auto(&auto1).unwrap();
+
+        let error = concesionario
+            .unidad_de_trabajo(|c| {
+                c.eliminar_auto(&auto1)?;
+                c.agregar_auto(&auto2)?;
+                c.agregar_auto(&auto3)?;
+                c.agregar_auto(&auto1)
+            })
+            .unwrap_err();
+        assert!(matches!(error, ConcesionarioError::Capacidad(_)));
+        assert_eq!(concesionario.autos, vec![auto1]);
+        assert_eq!(repo.guardados(), 1);
+        assert!(!concesionario.cambios_sin_guardar());
+    }
+
+    #[test]
+    fn test_lo_que_no_se_guarda_no_queda_en_memoria() {
+        let mut concesionario =
+            ConcesionarioAuto::new("Test".to_string(), "Calle 1".to_string(), 2)
+                .con_repositorio(RepositorioLleno::compartido());
+        let auto = crear_auto("Toyota", "Corolla", 2020, 20000.0, Color::Azul);
+        assert!(concesionario.agregar_auto(&auto).is_err());
+        assert!(concesionario.buscar_por_vin(auto.vin()).is_none());
+        assert!(!concesionario.cambios_sin_guardar());
+
+        let repo = Rc::new(RepositorioMemoria::new());
+        let repo_ventas = Rc::new(RepositorioMemoria::new());
+        concesionario.repositorio = RepositorioCompartido::from(repo.clone());
+        concesionario.repo_ventas = RepositorioCompartido::from(repo_ventas.clone());
+        concesionario.agregar_auto(&auto).unwrap();
+
+        // se guardan las ventas pero no el stock: la venta se deshace
+        concesionario.repositorio = RepositorioLleno::compartido();
+        assert!(concesionario.vender(auto.vin(), "Ana").is_err());
+        assert!(concesionario.ventas().is_empty());
+        assert!(concesionario.buscar_por_vin(auto.vin()).is_some());
+
+        concesionario.repositorio = RepositorioCompartido::from(repo.clone());
+        concesionario.vender(auto.vin(), "Beto").unwrap();
+        let autos: Vec<Auto> = repo.cargar().unwrap().unwrap();
+        assert!(autos.is_empty());
+        let ventas: Vec<Venta> = repo_ventas.cargar().unwrap().unwrap();
+        assert_eq!(ventas.len(), 1);
+        assert_eq!(ventas[0].cliente, "Beto");
+    }
+
+    #[test]
+    fn test_exportar_e_importar_csv() {
+        let mut origen = ConcesionarioAuto::new("A".to_string(), "Calle 1".to_string(), 5)
+            .con_repositorio(RepositorioCompartido::memoria());
+        origen
+            .agregar_auto(&unidad(1, "Fiat", "Uno, Fire", 1995, 1000.0, Color::Rojo))
+            .unwrap();
+        origen
+            .agregar_auto(&unidad(2, "BMW", "M3", 2020, 50000.5, Color::Negro))
+            .unwrap();
+        let mut csv = vec![];
+        origen.exportar_csv(&mut csv).unwrap();
+        assert_eq!(
+            String::from_utf8(csv.clone()).unwrap(),
+            "vin,marca,modelo,año,precio_bruto,color\n\
+             8AP178178LP000001,Fiat,\"Uno, Fire\",1995,1000,Rojo\n\
+             8AP17817XLP000002,BMW,M3,2020,50000.5,Negro\n"
+        );
+
+        let repo = Rc::new(RepositorioMemoria::new());
+        let mut destino = ConcesionarioAuto::new("B".to_string(), "Calle 2".to_string(), 5)
+            .con_repositorio(RepositorioCompartido::from(repo.clone()));
+        assert_eq!(
+            destino
+                .importar_csv(csv.as_slice(), &MapeoColumnas::new())
+                .unwrap(),
+            2
+        );
+        assert_eq!(destino.autos, origen.autos);
+        assert_eq!(repo.guardados(), 1);
+    }
+
+    #[test]
+    fn test_importar_csv_de_una_planilla() {
+        let mut concesionario =
+            ConcesionarioAuto::new("Test".to_string(), "Calle 1".to_string(), 1)
+                .con_repositorio(RepositorioCompartido::memoria());
+        let planilla = "VIN,Marca,Modelo,Anio,Precio Bruto,Color\n\
+                        8AP178178LP000001,Ford,Ka,2015,-5,azul\n\
+                        8AP17817XLP000002,Ford,Fiesta,dos mil,9000,Violeta\n\
+                        8AP178174LP000003,Ford,Focus,2018,9000,Rojo\n";
+        let mapeo = MapeoColumnas::new().con_alias("anio", "año");
+        match concesionario.importar_csv(planilla.as_bytes(), &mapeo) {
+            Err(ConcesionarioError::Csv(ErrorCsv::Filas(errores))) => {
+                let lineas: Vec<(u64, Option<String>)> =
+                    errores.into_iter().map(|e| (e.linea, e.columna)).collect();
+                assert_eq!(
+                    lineas,
+                    vec![
+                        (2, Some("precio_bruto".to_string())),
+                        (3, Some("año".to_string())),
+                        (4, Some("vin".to_string()))
+                    ]
+                );
+            }
+            _ => panic!("se esperaban errores por fila"),
+        }
+
+        // filas validas pero que no entran: no se agrega ninguna
+        let planilla = "VIN,Marca,Modelo,Anio,Precio Bruto,Color\n\
+                        8AP178178LP000001,Ford,Ka,2015,5000,azul\n\
+                        8AP17817XLP000002,Ford,Fiesta,2010,9000,ROJO\n";
+        assert!(matches!(
+            concesionario.importar_csv(planilla.as_bytes(), &mapeo),
+            Err(ConcesionarioError::Capacidad(_))
+        ));
+        assert!(concesionario.autos.is_empty());
+    }
+
+    #[test]
+    fn test_consultar_stock() {
+        let mut concesionario =
+            ConcesionarioAuto::new("Test".to_string(), "Calle 1".to_string(), 10)
+                .con_repositorio(RepositorioCompartido::memoria());
+        let autos = vec![
+            crear_auto("BMW", "M3", 2020, 40000.0, Color::Negro),
+            crear_auto("BMW", "X1", 2018, 30000.0, Color::Rojo),
+            crear_auto("Fiat", "Uno", 1999, 5000.0, Color::Rojo),
+            crear_auto("BMW", "M5", 1995, 20000.0, Color::Blanco),
+        ];
+        for auto in &autos {
+            concesionario.agregar_auto(auto).unwrap();
+        }
+
+        let consulta = concesionario
+            .consultar()
+            .con_marca("bmw")
+            .con_precio(..=42000.0)
+            .ordenada_por(CampoAuto::Precio, Sentido::Descendente);
+        // M3 42000, X1 42000, M5 20000
+        let encontrados = consulta.resultados();
+        assert_eq!(encontrados, vec![&autos[0], &autos[1], &autos[3]]);
+        // sin copias: son los mismos autos del concesionario
+        assert!(std::ptr::eq(encontrados[0], &concesionario.autos[0]));
+
+        let rojos = concesionario
+            .consultar()
+            .con_color(Color::Rojo)
+            .con_años(..2000)
+            .resultados();
+        assert_eq!(rojos, vec![&autos[2]]);
+    }
+
+    #[test]
+    fn test_reglas_de_precio_propias() {
+        let reglas = ReglasPrecio::new().con_regla(
+            Regla::new("Promo Fiat", Efecto::Porcentaje(-50.0)).con_marcas(vec!["Fiat"]),
+        );
+        let mut concesionario =
+            ConcesionarioAuto::new("Test".to_string(), "Calle 1".to_string(), 10)
+                .con_repositorio(RepositorioCompartido::memoria())
+                .con_reglas_precio(reglas);
+        let fiat = crear_auto("Fiat", "Uno", 2010, 10000.0, Color::Rojo);
+        let ford = crear_auto("Ford", "Ka", 2010, 6000.0, Color::Rojo);
+        concesionario.agregar_auto(&fiat).unwrap();
+        concesionario.agregar_auto(&ford).unwrap();
+
+        let cotizacion = concesionario.cotizar(&fiat);
+        assert_eq!(cotizacion.precio_final, 5000.0);
+        assert_eq!(cotizacion.aplicadas[0].nombre, "Promo Fiat");
+        // con las reglas por defecto el Fiat rojo sale 12500
+        assert_eq!(fiat.calcular_precio(), 12500.0);
+        assert_eq!(
+            fiat.calcular_precio_con(&concesionario.reglas_precio),
+            5000.0
+        );
+
+        // la consulta filtra y ordena con las reglas del concesionario
+        let baratos = concesionario.consultar().con_precio(..=5000.0).resultados();
+        assert_eq!(baratos, vec![&fiat]);
+        let por_precio = concesionario
+            .consultar()
+            .ordenada_por(CampoAuto::Precio, Sentido::Ascendente)
+            .resultados();
+        assert_eq!(por_precio, vec![&fiat, &ford]);
+    }
+
+    #[test]
+    fn test_financiar_con_las_reglas_propias() {
+        let reglas = ReglasPrecio::new().con_regla(
+            Regla::new("Promo Fiat", Efecto::Porcentaje(-50.0)).con_marcas(vec!["Fiat"]),
+        );
+        let concesionario = ConcesionarioAuto::new("Test".to_string(), "Calle 1".to_string(), 10)
+            .con_repositorio(RepositorioCompartido::memoria())
+            .con_reglas_precio(reglas);
+        let fiat = crear_auto("Fiat", "Uno", 2010, 10000.0, Color::Rojo);
+
+        // se financia el precio con la promo, no el de las reglas por defecto
+        let plan = concesionario.financiar(&fiat, 10).plan().unwrap();
+        assert_eq!(plan.monto_financiado(), 5000.0);
+        assert_eq!(plan.cuotas.len(), 10);
+    }
+
+    #[test]
+    fn test_reservar_y_vender() {
+        let reloj = Rc::new(RelojManual::new(Fecha::new(1, 3, 2025)).unwrap());
+        let ventas = Rc::new(RepositorioMemoria::new());
+        let mut concesionario =
+            ConcesionarioAuto::new("Test".to_string(), "Calle 1".to_string(), 2)
+                .con_repositorio(RepositorioCompartido::memoria())
+                .con_repositorio_ventas(RepositorioCompartido::from(ventas.clone()))
+                .con_reloj(RelojCompartido::from(reloj.clone()));
+        let auto1 = unidad(1, "Toyota", "Corolla", 2020, 20000.0, Color::Azul);
+        let auto2 = unidad(2, "Toyota", "Corolla", 2020, 20000.0, Color::Azul);
+        concesionario.agregar_auto(&auto1).unwrap();
+        concesionario.agregar_auto(&auto2).unwrap();
+
+        let reserva = concesionario.reservar(&vin(1), "Ana", 1000.0, 7).unwrap();
+        assert_eq!(
+            concesionario.estado(&vin(1)),
+            Some(EstadoAuto::Reservado(reserva))
+        );
+        assert_eq!(concesionario.estado(&vin(2)), Some(EstadoAuto::EnStock));
+        let error = concesionario.vender(&vin(1), "Luis").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!("El auto con VIN {} esta reservado por Ana", vin(1))
+        );
+        assert!(matches!(
+            concesionario.reservar(&vin(1), "Luis", 500.0, 7),
+            Err(ConcesionarioError::AutoReservado { .. })
+        ));
+        assert_eq!(ventas.guardados(), 0);
+
+        reloj.avanzar_dias(3);
+        let venta = concesionario.vender(&vin(1), "Ana").unwrap();
+        assert_eq!(venta.auto, auto1);
+        assert_eq!(venta.precio_final, auto1.calcular_precio());
+        assert_eq!(venta.saldo(), auto1.calcular_precio() - 1000.0);
+        assert_eq!(venta.fecha, reloj.ahora());
+        // el vendido sale del stock y deja lugar
+        assert_eq!(concesionario.estado(&vin(1)), None);
+        assert_eq!(concesionario.ventas(), std::slice::from_ref(&venta));
+        assert!(
+            concesionario
+                .agregar_auto(&unidad(3, "Ford", "Ka", 2015, 9000.0, Color::Rojo))
+                .is_ok()
+        );
+        let guardadas: Vec<Venta> = ventas.cargar().unwrap().unwrap();
+        assert_eq!(guardadas, vec![venta]);
+        assert_eq!(ventas.guardados(), 1);
+
+        assert!(matches!(
+            concesionario.vender(&vin(1), "Ana"),
+            Err(ConcesionarioError::AutoInexistente(_))
+        ));
+    }
+
+    #[test]
+    fn test_vender_reabrir_y_vender() {
+        let (autos, ventas) = (
+            Rc::new(RepositorioMemoria::new()),
+            Rc::new(RepositorioMemoria::new()),
+        );
+        let abrir = || {
+            ConcesionarioAuto::abrir(
+                "Test".to_string(),
+                "Calle 1".to_string(),
+                2,
+                RepositorioCompartido::from(autos.clone()),
+                RepositorioCompartido::from(ventas.clone()),
+                ReglasPrecio::default(),
+            )
+            .unwrap()
+        };
+        let auto1 = unidad(1, "Toyota", "Corolla", 2020, 20000.0, Color::Azul);
+        let auto2 = unidad(2, "Ford", "Ka", 2015, 9000.0, Color::Rojo);
+        let mut concesionario = abrir();
+        concesionario.agregar_auto(&auto1).unwrap();
+        concesionario.agregar_auto(&auto2).unwrap();
+        concesionario.vender(auto1.vin(), "Ana").unwrap();
+
+        let mut reabierto = abrir();
+        assert_eq!(reabierto.ventas().len(), 1);
+        assert!(reabierto.buscar_por_vin(auto1.vin()).is_none());
+        reabierto.vender(auto2.vin(), "Beto").unwrap();
+
+        let guardadas: Vec<Venta> = ventas.cargar().unwrap().unwrap();
+        let clientes: Vec<&str> = guardadas.iter().map(|v| v.cliente.as_str()).collect();
+        assert_eq!(clientes, vec!["Ana", "Beto"]);
+        assert_eq!(abrir().ventas(), guardadas.as_slice());
+    }
+
+    #[test]
+    fn test_reservas_vencidas_y_canceladas() {
+        let reloj = Rc::new(RelojManual::new(Fecha::new(1, 3, 2025)).unwrap());
+        let mut concesionario =
+            ConcesionarioAuto::new("Test".to_string(), "Calle 1".to_string(), 2)
+                .con_repositorio(RepositorioCompartido::memoria())
+                .con_repositorio_ventas(RepositorioCompartido::memoria())
+                .con_reloj(RelojCompartido::from(reloj.clone()));
+        concesionario
+            .agregar_auto(&unidad(1, "Fiat", "Uno", 2010, 10000.0, Color::Negro))
+            .unwrap();
+        concesionario
+            .agregar_auto(&unidad(2, "Fiat", "Uno", 2010, 10000.0, Color::Negro))
+            .unwrap();
+
+        // el Uno negro sale 9000
+        for seña in [0.0, -5.0, 9000.5, f64::NAN] {
+            assert!(matches!(
+                concesionario.reservar(&vin(1), "Ana", seña, 7),
+                Err(ConcesionarioError::SeñaInvalida(_))
+            ));
+        }
+        for dias in [0, -1, 1_000_000_000_000, i64::MAX] {
+            assert!(matches!(
+                concesionario.reservar(&vin(1), "Ana", 500.0, dias),
+                Err(ConcesionarioError::DiasInvalidos(_))
+            ));
+        }
+        assert_eq!(concesionario.estado(&vin(1)), Some(EstadoAuto::EnStock));
+        concesionario.reservar(&vin(1), "Ana", 9000.0, 7).unwrap();
+        concesionario.reservar(&vin(2), "Ana", 500.0, 30).unwrap();
+
+        // vencida: la puede comprar otro y no se descuenta la seña
+        reloj.avanzar_dias(7);
+        assert_eq!(concesionario.estado(&vin(1)), Some(EstadoAuto::EnStock));
+        let venta = concesionario.vender(&vin(1), "Luis").unwrap();
+        assert_eq!((venta.seña, venta.saldo()), (0.0, 9000.0));
+
+        assert_eq!(concesionario.cancelar_reserva(&vin(2)).unwrap().seña, 500.0);
+        assert!(matches!(
+            concesionario.cancelar_reserva(&vin(2)),
+            Err(ConcesionarioError::SinReserva(_))
+        ));
+        concesionario.reservar(&vin(2), "Luis", 500.0, 1).unwrap();
+        reloj.avanzar_dias(2);
+        assert_eq!(concesionario.liberar_reservas_vencidas().unwrap(), 1);
+        assert_eq!(concesionario.liberar_reservas_vencidas().unwrap(), 0);
+
+        // una venta dentro de una unidad que falla se deshace
+        let error = concesionario
+            .unidad_de_trabajo(|c| {
+                c.vender(&vin(2), "Ana")?;
+                c.agregar_auto(&unidad(3, "Ford", "Ka", 2015, 9000.0, Color::Rojo))?;
+                c.agregar_auto(&unidad(4, "Ford", "Ka", 2015, 9000.0, Color::Rojo))?;
+                c.agregar_auto(&unidad(5, "Ford", "Ka", 2015, 9000.0, Color::Rojo))
+            })
+            .unwrap_err();
+        assert!(matches!(error, ConcesionarioError::Capacidad(_)));
+        assert_eq!(concesionario.ventas().len(), 1);
+        assert_eq!(concesionario.estado(&vin(2)), Some(EstadoAuto::EnStock));
+    }
+
+    #[test]
+    fn test_cargar_autos_con_colores_de_tp03() {
+        let repo = RepositorioMemoria::new().con_esquema(esquema()).con_contenido(
+            r#"[{"vin":"8AP178178LP000001","marca":"Fiat","modelo":"Uno","año":1995,"precio_bruto":1000.0,"color":"ROJO"}]"#,
+        );
+        let autos: Vec<Auto> = repo.cargar().unwrap().unwrap();
+        assert_eq!(
+            autos,
+            vec![unidad(1, "Fiat", "Uno", 1995, 1000.0, Color::Rojo)]
+        );
+
+        repo.guardar(&autos).unwrap();
+        let guardado = repo.contenido().unwrap();
+        assert!(guardado.contains("\"version\": 3"));
+        assert!(guardado.contains("\"Rojo\""));
+    }
+
+    #[test]
+    fn test_cargar_autos_sin_vin() {
+        let repo = RepositorioMemoria::new().con_esquema(esquema()).con_contenido(
+            r#"{"version":2,"datos":[{"marca":"Fiat","modelo":"Uno","año":1995,"precio_bruto":1000.0,"color":"Rojo"}]}"#,
+        );
+        let cargados: Result<Option<Vec<Auto>>, _> = repo.cargar();
+        match cargados {
+            // falla al pasar de la version 2 a la 3
+            Err(PersistenciaError::Migracion { version, detalle }) => {
+                assert_eq!(version, 2);
+                assert_eq!(detalle, "el auto Fiat Uno no tiene VIN");
+            }
+            _ => panic!("se esperaba un error de migracion"),
+        }
+    }
+
     #[test]
     fn test_ej01_concesionario() {
-        let mut c = ConcesionarioAuto::new("Concesionario".to_string(), "Calle 1".to_string(), 1);
+        let mut c = ConcesionarioAuto::new("Concesionario".to_string(), "Calle 1".to_string(), 1)
+            .con_repositorio(RepositorioCompartido::memoria());
         let a1 = crear_auto("Audi", "A3", 1999, 100.0, Color::Negro);
         let a2 = crear_auto("BMW", "A4", 2015, 100.0, Color::Rojo);
 
@@ -327,7 +1351,7 @@ mod tests {
             "Debería encontrar a1"
         );
         assert!(c.buscar_auto(&a2).is_none(), "No debería encontrar a2");
-        c.eliminar_auto(&a1);
+        c.eliminar_auto(&a1).unwrap();
         assert!(
             c.buscar_auto(&a1).is_none(),
             "a1 debería haber sido eliminado"
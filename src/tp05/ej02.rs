@@ -1,8 +1,15 @@
 #![allow(dead_code, unused_variables)]
 
 use serde::{Deserialize, Serialize};
-use std::fs::File;
-use std::io::Write;
+use serde_json::Value;
+use std::io;
+
+use crate::tp05::esquema::{Esquema, normalizar_variante, por_cada_elemento};
+use crate::tp05::repositorio::{
+    ConUnidadDeTrabajo, DIRECTORIO_REGISTROS, PersistenciaError, Repositorio,
+    RepositorioCompartido, UnidadDeTrabajo,
+};
+use crate::tp05::tabla::{self, ErrorCsv, ErrorFila, Fila, MapeoColumnas, RegistroCsv};
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Cancion {
@@ -20,10 +27,26 @@ pub enum Genero {
     Otros,
 }
 
+// Version 2: los generos en mayusculas de tp03 (ROCK) pasan a como se escriben aca (Rock).
+pub fn esquema() -> Esquema {
+    Esquema::new().migracion(normalizar_generos)
+}
+
+fn normalizar_generos(datos: Value) -> Result<Value, String> {
+    por_cada_elemento(datos, |cancion| {
+        if let Some(genero) = cancion.get_mut("genero") {
+            normalizar_variante(genero);
+        }
+        Ok(())
+    })
+}
+
 #[derive(Debug)]
 pub struct Playlist {
     canciones: Vec<Cancion>,
     nombre: String,
+    repositorio: RepositorioCompartido<Vec<Cancion>>,
+    unidad: UnidadDeTrabajo,
 }
 
 impl Cancion {
@@ -47,47 +70,93 @@ impl Cancion {
     }
 }
 
+impl RegistroCsv for Cancion {
+    const COLUMNAS: &'static [&'static str] = &["titulo", "artista", "genero"];
+
+    fn a_fila(&self) -> Vec<String> {
+        vec![
+            self.titulo.clone(),
+            self.artista.clone(),
+            tabla::variante(&self.genero),
+        ]
+    }
+
+    fn de_fila(fila: &Fila) -> Result<Self, ErrorFila> {
+        Ok(Cancion::new(
+            fila.texto("titulo")?,
+            fila.texto("artista")?,
+            fila.variante("genero")?,
+        ))
+    }
+}
+
 impl Playlist {
     pub fn new(nombre: String) -> Playlist {
         Playlist {
             canciones: vec![],
             nombre,
+            repositorio: RepositorioCompartido::json(
+                DIRECTORIO_REGISTROS,
+                "ej02/canciones.json",
+                esquema(),
+            ),
+            unidad: UnidadDeTrabajo::default(),
         }
     }
 
-    pub fn persistir_canciones(&self) {
-        let mut f = File::create("src/tp05/registros/ej02/canciones.json").unwrap();
-        let canciones_serializado = serde_json::to_string_pretty(&self.canciones).unwrap();
-        f.write_all(canciones_serializado.as_bytes()).unwrap();
+    pub fn con_repositorio(mut self, repositorio: RepositorioCompartido<Vec<Cancion>>) -> Self {
+        self.repositorio = repositorio;
+        self
     }
 
-    pub fn agregar_cancion(&mut self, c: &Cancion) {
-        self.canciones.push(c.clone());
-        self.persistir_canciones();
+    pub fn persistir_canciones(&self) -> Result<(), PersistenciaError> {
+        self.repositorio.guardar(&self.canciones)
     }
 
-    pub fn eliminar_cancion(&mut self, c: &Cancion) {
-        for i in 0..self.canciones.len() {
-            if self.canciones[i].comparar(c) {
-                self.canciones.remove(i);
-                return;
-            }
+    pub fn agregar_cancion(&mut self, c: &Cancion) -> Result<(), PersistenciaError> {
+        self.cambiar(|p| p.canciones.push(c.clone()))
+    }
+
+    pub fn eliminar_cancion(&mut self, c: &Cancion) -> Result<(), PersistenciaError> {
+        if let Some(i) = self.get_posicion_cancion(c) {
+            self.cambiar(|p| {
+                p.canciones.remove(i);
+            })?;
         }
-        self.persistir_canciones();
+        Ok(())
     }
 
-    pub fn mover_cancion(&mut self, c: &Cancion, pos: usize) {
+    pub fn mover_cancion(&mut self, c: &Cancion, pos: usize) -> Result<(), PersistenciaError> {
         if pos >= self.canciones.len() {
-            return;
+            return Ok(());
         }
 
-        for i in 0..self.canciones.len() {
-            if self.canciones[i].comparar(c) {
-                let c = self.canciones.remove(i);
-                self.canciones.insert(pos, c);
-                return;
-            }
+        if let Some(i) = self.get_posicion_cancion(c) {
+            self.cambiar(|p| {
+                let c = p.canciones.remove(i);
+                p.canciones.insert(pos, c);
+            })?;
         }
+        Ok(())
+    }
+
+    pub fn exportar_csv(&self, escritor: impl io::Write) -> Result<(), ErrorCsv> {
+        tabla::exportar(&self.canciones, escritor)
+    }
+
+    // Agrega las canciones al final de la playlist guardando una sola vez.
+    pub fn importar_csv(
+        &mut self,
+        lector: impl io::Read,
+        mapeo: &MapeoColumnas,
+    ) -> Result<usize, ErrorCsv> {
+        let canciones: Vec<Cancion> = tabla::importar(lector, mapeo)?;
+        self.unidad_de_trabajo(|p| {
+            for cancion in &canciones {
+                p.agregar_cancion(cancion)?;
+            }
+            Ok(canciones.len())
+        })
     }
 
     pub fn buscar_cancion_por_nombre(&self, nombre: String) -> Option<Cancion> {
@@ -129,14 +198,12 @@ impl Playlist {
         cs
     }
 
-    pub fn cambiar_titulo(&mut self, titulo: String) {
-        self.nombre = titulo;
-        self.persistir_canciones();
+    pub fn cambiar_titulo(&mut self, titulo: String) -> Result<(), PersistenciaError> {
+        self.cambiar(|p| p.nombre = titulo)
     }
 
-    pub fn del_all_canciones(&mut self) {
-        self.canciones.clear();
-        self.persistir_canciones();
+    pub fn del_all_canciones(&mut self) -> Result<(), PersistenciaError> {
+        self.cambiar(|p| p.canciones.clear())
     }
 
     pub fn get_nombre(&self) -> &String {
@@ -148,6 +215,31 @@ impl Playlist {
     }
 }
 
+impl ConUnidadDeTrabajo for Playlist {
+    type Copia = (Vec<Cancion>, String);
+
+    fn unidad(&self) -> &UnidadDeTrabajo {
+        &self.unidad
+    }
+
+    fn unidad_mut(&mut self) -> &mut UnidadDeTrabajo {
+        &mut self.unidad
+    }
+
+    fn copia(&self) -> Self::Copia {
+        (self.canciones.clone(), self.nombre.clone())
+    }
+
+    fn restaurar(&mut self, (canciones, nombre): Self::Copia) {
+        self.canciones = canciones;
+        self.nombre = nombre;
+    }
+
+    fn persistir(&mut self) -> Result<(), PersistenciaError> {
+        self.persistir_canciones()
+    }
+}
+
 impl Genero {
     pub fn igual(&self, genero: &Genero) -> bool {
         self.a_str() == genero.a_str()
@@ -166,7 +258,13 @@ impl Genero {
 
 #[cfg(test)]
 mod tests {
-    use super::{Cancion, Genero, Playlist};
+    use super::{Cancion, Genero, Playlist, esquema};
+    use crate::tp05::repositorio::{
+        ConUnidadDeTrabajo, PersistenciaError, Repositorio, RepositorioCompartido,
+        RepositorioLleno, RepositorioMemoria,
+    };
+    use crate::tp05::tabla::{ErrorCsv, MapeoColumnas};
+    use std::rc::Rc;
 
     fn crear_cancion(titulo: &str, artista: &str, genero: Genero) -> Cancion {
         Cancion::new(titulo.to_string(), artista.to_string(), genero)
@@ -219,9 +317,28 @@ mod tests {
         assert_eq!(Genero::Otros.a_str(), "OTROS");
     }
 
+    #[test]
+    fn test_cargar_canciones_con_generos_de_tp03() {
+        let repo = RepositorioMemoria::new()
+            .con_esquema(esquema())
+            .con_contenido(
+                r#"[{"titulo":"a","artista":"x","genero":"ROCK"},
+                {"titulo":"b","artista":"y","genero":"Jazz"}]"#,
+            );
+        let canciones: Vec<Cancion> = repo.cargar().unwrap().unwrap();
+        assert_eq!(
+            canciones,
+            vec![
+                crear_cancion("a", "x", Genero::Rock),
+                crear_cancion("b", "y", Genero::Jazz),
+            ]
+        );
+    }
+
     #[test]
     fn test_playlist_new() {
-        let playlist = Playlist::new("My Playlist".to_string());
+        let playlist = Playlist::new("My Playlist".to_string())
+            .con_repositorio(RepositorioCompartido::memoria());
         assert_eq!(playlist.get_nombre(), "My Playlist");
         assert_eq!(playlist.get_len_canciones(), 0);
         assert!(playlist.canciones.is_empty());
@@ -229,62 +346,67 @@ mod tests {
 
     #[test]
     fn test_agregar_cancion() {
-        let mut playlist = Playlist::new("Test Playlist".to_string());
+        let mut playlist = Playlist::new("Test Playlist".to_string())
+            .con_repositorio(RepositorioCompartido::memoria());
         let cancion = crear_cancion("Song1", "Artist1", Genero::Rock);
 
-        playlist.agregar_cancion(&cancion);
+        playlist.agregar_cancion(&cancion).unwrap();
         assert_eq!(playlist.get_len_canciones(), 1);
         assert_eq!(playlist.canciones[0].info(), cancion.info());
     }
 
     #[test]
     fn test_eliminar_cancion_existente() {
-        let mut playlist = Playlist::new("Test Playlist".to_string());
+        let mut playlist = Playlist::new("Test Playlist".to_string())
+            .con_repositorio(RepositorioCompartido::memoria());
         let cancion1 = crear_cancion("Song1", "Artist1", Genero::Rock);
         let cancion2 = crear_cancion("Song2", "Artist2", Genero::Pop);
 
-        playlist.agregar_cancion(&cancion1);
-        playlist.agregar_cancion(&cancion2);
+        playlist.agregar_cancion(&cancion1).unwrap();
+        playlist.agregar_cancion(&cancion2).unwrap();
         assert_eq!(playlist.get_len_canciones(), 2);
 
-        playlist.eliminar_cancion(&cancion1);
+        playlist.eliminar_cancion(&cancion1).unwrap();
         assert_eq!(playlist.get_len_canciones(), 1);
         assert_eq!(playlist.canciones[0].info(), cancion2.info());
     }
 
     #[test]
     fn test_eliminar_cancion_no_existente() {
-        let mut playlist = Playlist::new("Test Playlist".to_string());
+        let mut playlist = Playlist::new("Test Playlist".to_string())
+            .con_repositorio(RepositorioCompartido::memoria());
         let cancion1 = crear_cancion("Song1", "Artist1", Genero::Rock);
         let cancion2 = crear_cancion("Song2", "Artist2", Genero::Pop);
 
-        playlist.agregar_cancion(&cancion1);
-        playlist.eliminar_cancion(&cancion2);
+        playlist.agregar_cancion(&cancion1).unwrap();
+        playlist.eliminar_cancion(&cancion2).unwrap();
         assert_eq!(playlist.get_len_canciones(), 1);
         assert_eq!(playlist.canciones[0].info(), cancion1.info());
     }
 
     #[test]
     fn test_eliminar_cancion_lista_vacia() {
-        let mut playlist = Playlist::new("Test Playlist".to_string());
+        let mut playlist = Playlist::new("Test Playlist".to_string())
+            .con_repositorio(RepositorioCompartido::memoria());
         let cancion = crear_cancion("Song1", "Artist1", Genero::Rock);
 
-        playlist.eliminar_cancion(&cancion);
+        playlist.eliminar_cancion(&cancion).unwrap();
         assert_eq!(playlist.get_len_canciones(), 0);
     }
 
     #[test]
     fn test_mover_cancion_valida() {
-        let mut playlist = Playlist::new("Test Playlist".to_string());
+        let mut playlist = Playlist::new("Test Playlist".to_string())
+            .con_repositorio(RepositorioCompartido::memoria());
         let cancion1 = crear_cancion("Song1", "Artist1", Genero::Rock);
         let cancion2 = crear_cancion("Song2", "Artist2", Genero::Pop);
         let cancion3 = crear_cancion("Song3", "Artist3", Genero::Jazz);
 
-        playlist.agregar_cancion(&cancion1);
-        playlist.agregar_cancion(&cancion2);
-        playlist.agregar_cancion(&cancion3);
+        playlist.agregar_cancion(&cancion1).unwrap();
+        playlist.agregar_cancion(&cancion2).unwrap();
+        playlist.agregar_cancion(&cancion3).unwrap();
 
-        playlist.mover_cancion(&cancion1, 2);
+        playlist.mover_cancion(&cancion1, 2).unwrap();
         assert_eq!(playlist.canciones[2].info(), cancion1.info());
         assert_eq!(playlist.canciones[0].info(), cancion2.info());
         assert_eq!(playlist.canciones[1].info(), cancion3.info());
@@ -292,33 +414,36 @@ mod tests {
 
     #[test]
     fn test_mover_cancion_posicion_invalida() {
-        let mut playlist = Playlist::new("Test Playlist".to_string());
+        let mut playlist = Playlist::new("Test Playlist".to_string())
+            .con_repositorio(RepositorioCompartido::memoria());
         let cancion = crear_cancion("Song1", "Artist1", Genero::Rock);
 
-        playlist.agregar_cancion(&cancion);
+        playlist.agregar_cancion(&cancion).unwrap();
         let original = playlist.canciones.clone();
-        playlist.mover_cancion(&cancion, 5); // Posición inválida
+        playlist.mover_cancion(&cancion, 5).unwrap(); // Posición inválida
         assert_eq!(playlist.canciones, original, "Playlist should not change");
     }
 
     #[test]
     fn test_mover_cancion_no_existente() {
-        let mut playlist = Playlist::new("Test Playlist".to_string());
+        let mut playlist = Playlist::new("Test Playlist".to_string())
+            .con_repositorio(RepositorioCompartido::memoria());
         let cancion1 = crear_cancion("Song1", "Artist1", Genero::Rock);
         let cancion2 = crear_cancion("Song2", "Artist2", Genero::Pop);
 
-        playlist.agregar_cancion(&cancion1);
+        playlist.agregar_cancion(&cancion1).unwrap();
         let original = playlist.canciones.clone();
-        playlist.mover_cancion(&cancion2, 0);
+        playlist.mover_cancion(&cancion2, 0).unwrap();
         assert_eq!(playlist.canciones, original, "Playlist should not change");
     }
 
     #[test]
     fn test_buscar_cancion_por_nombre_existente() {
-        let mut playlist = Playlist::new("Test Playlist".to_string());
+        let mut playlist = Playlist::new("Test Playlist".to_string())
+            .con_repositorio(RepositorioCompartido::memoria());
         let cancion = crear_cancion("Song1", "Artist1", Genero::Rock);
 
-        playlist.agregar_cancion(&cancion);
+        playlist.agregar_cancion(&cancion).unwrap();
         let result = playlist.buscar_cancion_por_nombre("Song1".to_string());
         assert!(result.is_some());
         assert_eq!(result.unwrap().info(), cancion.info());
@@ -326,19 +451,21 @@ mod tests {
 
     #[test]
     fn test_buscar_cancion_por_nombre_no_existente() {
-        let playlist = Playlist::new("Test Playlist".to_string());
+        let playlist = Playlist::new("Test Playlist".to_string())
+            .con_repositorio(RepositorioCompartido::memoria());
         let result = playlist.buscar_cancion_por_nombre("Nonexistent".to_string());
         assert!(result.is_none());
     }
 
     #[test]
     fn test_get_posicion_cancion() {
-        let mut playlist = Playlist::new("Test Playlist".to_string());
+        let mut playlist = Playlist::new("Test Playlist".to_string())
+            .con_repositorio(RepositorioCompartido::memoria());
         let cancion1 = crear_cancion("Song1", "Artist1", Genero::Rock);
         let cancion2 = crear_cancion("Song2", "Artist2", Genero::Pop);
 
-        playlist.agregar_cancion(&cancion1);
-        playlist.agregar_cancion(&cancion2);
+        playlist.agregar_cancion(&cancion1).unwrap();
+        playlist.agregar_cancion(&cancion2).unwrap();
 
         assert_eq!(playlist.get_posicion_cancion(&cancion1), Some(0));
         assert_eq!(playlist.get_posicion_cancion(&cancion2), Some(1));
@@ -348,14 +475,15 @@ mod tests {
 
     #[test]
     fn test_get_canciones_genero() {
-        let mut playlist = Playlist::new("Test Playlist".to_string());
+        let mut playlist = Playlist::new("Test Playlist".to_string())
+            .con_repositorio(RepositorioCompartido::memoria());
         let cancion1 = crear_cancion("Song1", "Artist1", Genero::Rock);
         let cancion2 = crear_cancion("Song2", "Artist2", Genero::Pop);
         let cancion3 = crear_cancion("Song3", "Artist1", Genero::Rock);
 
-        playlist.agregar_cancion(&cancion1);
-        playlist.agregar_cancion(&cancion2);
-        playlist.agregar_cancion(&cancion3);
+        playlist.agregar_cancion(&cancion1).unwrap();
+        playlist.agregar_cancion(&cancion2).unwrap();
+        playlist.agregar_cancion(&cancion3).unwrap();
 
         let rock_songs = playlist.get_canciones_genero(&Genero::Rock);
         assert_eq!(rock_songs.len(), 2);
@@ -372,14 +500,15 @@ mod tests {
 
     #[test]
     fn test_get_canciones_artista() {
-        let mut playlist = Playlist::new("Test Playlist".to_string());
+        let mut playlist = Playlist::new("Test Playlist".to_string())
+            .con_repositorio(RepositorioCompartido::memoria());
         let cancion1 = crear_cancion("Song1", "Artist1", Genero::Rock);
         let cancion2 = crear_cancion("Song2", "Artist2", Genero::Pop);
         let cancion3 = crear_cancion("Song3", "Artist1", Genero::Jazz);
 
-        playlist.agregar_cancion(&cancion1);
-        playlist.agregar_cancion(&cancion2);
-        playlist.agregar_cancion(&cancion3);
+        playlist.agregar_cancion(&cancion1).unwrap();
+        playlist.agregar_cancion(&cancion2).unwrap();
+        playlist.agregar_cancion(&cancion3).unwrap();
 
         let artist1_songs = playlist.get_canciones_artista("Artist1".to_string());
         assert_eq!(artist1_songs.len(), 2);
@@ -396,26 +525,215 @@ mod tests {
 
     #[test]
     fn test_cambiar_titulo() {
-        let mut playlist = Playlist::new("Old Playlist".to_string());
-        playlist.cambiar_titulo("New Playlist".to_string());
+        let mut playlist = Playlist::new("Old Playlist".to_string())
+            .con_repositorio(RepositorioCompartido::memoria());
+        playlist.cambiar_titulo("New Playlist".to_string()).unwrap();
         assert_eq!(playlist.get_nombre(), "New Playlist");
     }
 
     #[test]
     fn test_del_all_canciones() {
-        let mut playlist = Playlist::new("Test Playlist".to_string());
+        let mut playlist = Playlist::new("Test Playlist".to_string())
+            .con_repositorio(RepositorioCompartido::memoria());
         let cancion = crear_cancion("Song1", "Artist1", Genero::Rock);
-        playlist.agregar_cancion(&cancion);
+        playlist.agregar_cancion(&cancion).unwrap();
         assert_eq!(playlist.get_len_canciones(), 1);
 
-        playlist.del_all_canciones();
+        playlist.del_all_canciones().unwrap();
         assert_eq!(playlist.get_len_canciones(), 0);
         assert!(playlist.canciones.is_empty());
     }
 
+    #[test]
+    fn test_eliminar_y_mover_persisten() {
+        let repo = Rc::new(RepositorioMemoria::new());
+        let mut playlist = Playlist::new("Test Playlist".to_string())
+            .con_repositorio(RepositorioCompartido::from(repo.clone()));
+        let cancion1 = crear_cancion("Song1", "Artist1", Genero::Rock);
+        let cancion2 = crear_cancion("Song2", "Artist2", Genero::Pop);
+        let cancion3 = crear_cancion("Song3", "Artist3", Genero::Jazz);
+        playlist.agregar_cancion(&cancion1).unwrap();
+        playlist.agregar_cancion(&cancion2).unwrap();
+        playlist.agregar_cancion(&cancion3).unwrap();
+
+        playlist.mover_cancion(&cancion1, 2).unwrap();
+        let guardadas: Vec<Cancion> = repo.cargar().unwrap().unwrap();
+        assert_eq!(
+            guardadas,
+            vec![cancion2.clone(), cancion3.clone(), cancion1]
+        );
+
+        playlist.eliminar_cancion(&cancion3).unwrap();
+        let guardadas: Vec<Cancion> = repo.cargar().unwrap().unwrap();
+        assert_eq!(guardadas, playlist.canciones);
+        assert_eq!(guardadas.len(), 2);
+    }
+
+    #[test]
+    fn test_agregar_cancion_sin_espacio_en_disco() {
+        let mut playlist = Playlist::new("Test Playlist".to_string())
+            .con_repositorio(RepositorioLleno::compartido());
+        let cancion = crear_cancion("Song1", "Artist1", Genero::Rock);
+        assert!(matches!(
+            playlist.agregar_cancion(&cancion),
+            Err(PersistenciaError::Io(_))
+        ));
+        // lo que no se pudo guardar no queda en memoria
+        assert_eq!(playlist.get_len_canciones(), 0);
+        assert!(!playlist.cambios_sin_guardar());
+    }
+
+    #[test]
+    fn test_unidad_de_trabajo_guarda_una_vez() {
+        let repo = Rc::new(RepositorioMemoria::new());
+        let mut playlist = Playlist::new("Test Playlist".to_string())
+            .con_repositorio(RepositorioCompartido::from(repo.clone()));
+        playlist
+            .unidad_de_trabajo(|p| {
+                for i in 0..1000 {
+                    p.agregar_cancion(&crear_cancion(
+                        &format!("Song{}", i),
+                        "Artist",
+                        Genero::Pop,
+                    ))?;
+                }
+                let primera = crear_cancion("Song0", "Artist", Genero::Pop);
+                p.mover_cancion(&primera, 999)?;
+                // una unidad anidada es parte de esta
+                p.unidad_de_trabajo(|p| p.eliminar_cancion(&primera))
+            })
+            .unwrap();
+        assert_eq!(repo.guardados(), 1);
+        let guardadas: Vec<Cancion> = repo.cargar().unwrap().unwrap();
+        assert_eq!(guardadas, playlist.canciones);
+        assert_eq!(guardadas.len(), 999);
+        assert!(!playlist.cambios_sin_guardar());
+
+        // sin cambios no se escribe nada
+        playlist
+            .unidad_de_trabajo(|p| p.eliminar_cancion(&crear_cancion("x", "y", Genero::Rap)))
+            .unwrap();
+        assert_eq!(repo.guardados(), 1);
+    }
+
+    #[derive(Debug)]
+    enum ErrorImportacion {
+        LineaInvalida(usize),
+        Persistencia(PersistenciaError),
+    }
+
+    impl From<PersistenciaError> for ErrorImportacion {
+        fn from(e: PersistenciaError) -> Self {
+            ErrorImportacion::Persistencia(e)
+        }
+    }
+
+    #[test]
+    fn test_unidad_de_trabajo_revierte_si_falla() {
+        let repo = Rc::new(RepositorioMemoria::new());
+        let mut playlist = Playlist::new("Test Playlist".to_string())
+            .con_repositorio(RepositorioCompartido::from(repo.clone()));
+        let cancion1 = crear_cancion("Song1", "Artist1", Genero::Rock);
+        playlist.agregar_cancion(&cancion1).unwrap();
+
+        let resultado: Result<(), _> = playlist.unidad_de_trabajo(|p| {
+            p.agregar_cancion(&crear_cancion("Song2", "Artist2", Genero::Pop))?;
+            p.cambiar_titulo("Otra".to_string())?;
+            Err(ErrorImportacion::LineaInvalida(3))
+        });
+        assert!(matches!(resultado, Err(ErrorImportacion::LineaInvalida(3))));
+        assert_eq!(playlist.canciones, vec![cancion1.clone()]);
+        assert_eq!(playlist.get_nombre(), "Test Playlist");
+        assert_eq!(repo.guardados(), 1);
+        assert!(!playlist.cambios_sin_guardar());
+    }
+
+    #[test]
+    fn test_unidad_de_trabajo_sin_espacio_en_disco() {
+        let mut playlist = Playlist::new("Test Playlist".to_string())
+            .con_repositorio(RepositorioLleno::compartido());
+        let resultado = playlist.unidad_de_trabajo(|p| {
+            p.agregar_cancion(&crear_cancion("Song1", "Artist1", Genero::Rock))
+        });
+        assert!(matches!(resultado, Err(PersistenciaError::Io(_))));
+        assert_eq!(playlist.get_len_canciones(), 0);
+        assert!(!playlist.cambios_sin_guardar());
+    }
+
+    #[test]
+    fn test_unidad_de_trabajo_con_panico() {
+        let repo = Rc::new(RepositorioMemoria::new());
+        let mut playlist = Playlist::new("Test Playlist".to_string())
+            .con_repositorio(RepositorioCompartido::from(repo.clone()));
+        let panico = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            playlist.unidad_de_trabajo(|p| -> Result<(), PersistenciaError> { panic!("falla") })
+        }));
+        assert!(panico.is_err());
+
+        // la unidad quedo cerrada: los cambios siguientes se guardan enseguida
+        playlist
+            .agregar_cancion(&crear_cancion("Song1", "Artist1", Genero::Rock))
+            .unwrap();
+        assert_eq!(repo.guardados(), 1);
+    }
+
+    #[test]
+    fn test_importar_csv() {
+        let repo = Rc::new(RepositorioMemoria::new());
+        let mut playlist = Playlist::new("Test Playlist".to_string())
+            .con_repositorio(RepositorioCompartido::from(repo.clone()));
+        playlist
+            .agregar_cancion(&crear_cancion("Song1", "Artist1", Genero::Rock))
+            .unwrap();
+        let csv = "Artista;Cancion;Genero\nArtist2;Song2;JAZZ\nArtist3;Song3;pop\n";
+        let mapeo = MapeoColumnas::new()
+            .con_delimitador(b';')
+            .con_alias("cancion", "titulo");
+        assert_eq!(playlist.importar_csv(csv.as_bytes(), &mapeo).unwrap(), 2);
+        assert_eq!(playlist.get_len_canciones(), 3);
+        assert_eq!(
+            playlist.get_posicion_cancion(&crear_cancion("Song3", "Artist3", Genero::Pop)),
+            Some(2)
+        );
+        assert_eq!(repo.guardados(), 2);
+
+        let mut exportado = vec![];
+        playlist.exportar_csv(&mut exportado).unwrap();
+        let mut copia =
+            Playlist::new("Copia".to_string()).con_repositorio(RepositorioCompartido::memoria());
+        copia
+            .importar_csv(exportado.as_slice(), &MapeoColumnas::new())
+            .unwrap();
+        assert_eq!(copia.canciones, playlist.canciones);
+    }
+
+    #[test]
+    fn test_importar_csv_con_errores_no_agrega_nada() {
+        let mut playlist = Playlist::new("Test Playlist".to_string())
+            .con_repositorio(RepositorioCompartido::memoria());
+        let csv = "titulo,artista,genero\nSong1,Artist1,Rock\nSong2,Artist2,Cumbia\n,Artist3,Pop\n";
+        match playlist.importar_csv(csv.as_bytes(), &MapeoColumnas::new()) {
+            Err(ErrorCsv::Filas(errores)) => {
+                assert_eq!(errores.len(), 2);
+                assert_eq!(errores[0].linea, 3);
+                assert_eq!(errores[0].columna.as_deref(), Some("genero"));
+                assert_eq!(errores[1].linea, 4);
+                assert_eq!(errores[1].columna.as_deref(), Some("titulo"));
+            }
+            otro => panic!("se esperaban errores por fila: {:?}", otro),
+        }
+        assert_eq!(playlist.get_len_canciones(), 0);
+
+        assert!(matches!(
+            playlist.importar_csv("titulo,genero\nSong1,Rock\n".as_bytes(), &MapeoColumnas::new()),
+            Err(ErrorCsv::FaltanColumnas(faltan)) if faltan == vec!["artista".to_string()]
+        ));
+    }
+
     #[test]
     fn test_get_len_canciones_empty() {
-        let playlist = Playlist::new("Test Playlist".to_string());
+        let playlist = Playlist::new("Test Playlist".to_string())
+            .con_repositorio(RepositorioCompartido::memoria());
         assert_eq!(playlist.get_len_canciones(), 0);
     }
 }
@@ -1,10 +1,16 @@
 #![allow(unused_variables, dead_code)]
 
+use crate::tp03::calendario::Calendario;
 use crate::tp03::ej03::Fecha;
+use crate::tp03::recurrencia::{FinRecurrencia, Recurrencia};
+use crate::tp05::esquema::Esquema;
+use crate::tp05::repositorio::{
+    DIRECTORIO_REGISTROS, PersistenciaError, Repositorio, RepositorioCompartido, cambiar_y_guardar,
+};
+use crate::tp05::tabla::{self, ErrorCsv, ErrorFila, Fila, MapeoColumnas, RegistroCsv};
 use serde::{Deserialize, Serialize};
 use std::collections::VecDeque;
-use std::fs::File;
-use std::io::Write;
+use std::io;
 
 pub struct Veterinaria {
     nombre: String,
@@ -12,6 +18,8 @@ pub struct Veterinaria {
     id: u64,
     cola_atencion: VecDeque<Mascota>,
     registro_atencion: Vec<RegistroAtencion>,
+    calendario: Option<Calendario>,
+    repositorio: RepositorioCompartido<Vec<RegistroAtencion>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -44,6 +52,8 @@ pub struct RegistroAtencion {
     tratamiento: String,
     fecha: Fecha,
     pub proxima_fecha: Option<Fecha>,
+    #[serde(default)]
+    controles: Option<Recurrencia>,
 }
 
 impl Veterinaria {
@@ -54,9 +64,29 @@ impl Veterinaria {
             id,
             cola_atencion: VecDeque::new(),
             registro_atencion: Vec::new(),
+            calendario: None,
+            repositorio: RepositorioCompartido::json(
+                DIRECTORIO_REGISTROS,
+                "ej03/registros_atencion.json",
+                Esquema::new(),
+            ),
         }
     }
 
+    pub fn con_repositorio(
+        mut self,
+        repositorio: RepositorioCompartido<Vec<RegistroAtencion>>,
+    ) -> Self {
+        self.repositorio = repositorio;
+        self
+    }
+
+    // Con calendario los seguimientos que caen en un dia no habil pasan al siguiente habil.
+    pub fn con_calendario(mut self, calendario: Calendario) -> Self {
+        self.calendario = Some(calendario);
+        self
+    }
+
     pub fn agregar_mascota(&mut self, mascota: &Mascota) {
         self.cola_atencion.push_back(mascota.clone());
     }
@@ -79,9 +109,24 @@ impl Veterinaria {
         false
     }
 
-    pub fn registrar_atencion(&mut self, atencion: &RegistroAtencion) {
-        self.persistir_registros_atencion();
-        self.registro_atencion.push(atencion.clone());
+    pub fn registrar_atencion(
+        &mut self,
+        atencion: &RegistroAtencion,
+    ) -> Result<(), PersistenciaError> {
+        self.cambiar_registros(|registros| registros.push(atencion.clone()))
+    }
+
+    // Agenda el seguimiento de una atencion a `dias_habiles` de la fecha en que se atendio.
+    pub fn programar_seguimiento(
+        &mut self,
+        ra: &RegistroAtencion,
+        dias_habiles: u32,
+    ) -> Result<Option<RegistroAtencion>, PersistenciaError> {
+        let calendario = self.calendario.clone().unwrap_or_default();
+        match calendario.sumar_dias_habiles(&ra.fecha, dias_habiles) {
+            Ok(fecha) => self.modificar_fecha_atencion(ra, fecha),
+            Err(_) => Ok(None),
+        }
     }
 
     pub fn buscar_registro_atencion(
@@ -102,47 +147,70 @@ impl Veterinaria {
         &mut self,
         ra_original: &RegistroAtencion,
         ra_modificado: &RegistroAtencion,
-    ) -> Option<RegistroAtencion> {
+    ) -> Result<Option<RegistroAtencion>, PersistenciaError> {
         for i in 0..self.registro_atencion.len() {
             if self.registro_atencion[i].comparar_completo(ra_original) {
-                self.registro_atencion[i] = ra_modificado.clone();
-                self.persistir_registros_atencion();
-                return Some(self.registro_atencion[i].clone());
+                self.cambiar_registros(|registros| registros[i] = ra_modificado.clone())?;
+                return Ok(Some(self.registro_atencion[i].clone()));
             }
         }
-        None
+        Ok(None)
     }
 
     pub fn modificar_fecha_atencion(
         &mut self,
         ra: &RegistroAtencion,
         f: Fecha,
-    ) -> Option<RegistroAtencion> {
+    ) -> Result<Option<RegistroAtencion>, PersistenciaError> {
+        let f = match &self.calendario {
+            Some(c) => c.siguiente_dia_habil(&f).unwrap_or(f),
+            None => f,
+        };
         for i in 0..self.registro_atencion.len() {
             if self.registro_atencion[i].comparar_completo(ra) {
-                self.registro_atencion[i].proxima_fecha = Some(f);
-                self.persistir_registros_atencion();
-                return Some(self.registro_atencion[i].clone());
+                self.cambiar_registros(|registros| registros[i].proxima_fecha = Some(f))?;
+                return Ok(Some(self.registro_atencion[i].clone()));
             }
         }
-        None
+        Ok(None)
     }
 
-    pub fn eliminar_atencion(&mut self, ra: &RegistroAtencion) -> bool {
+    pub fn eliminar_atencion(&mut self, ra: &RegistroAtencion) -> Result<bool, PersistenciaError> {
         for i in 0..self.registro_atencion.len() {
             if self.registro_atencion[i].comparar_completo(ra) {
-                self.registro_atencion.remove(i);
-                self.persistir_registros_atencion();
-                return true;
+                self.cambiar_registros(|registros| registros.remove(i))?;
+                return Ok(true);
             }
         }
-        false
+        Ok(false)
     }
 
-    pub fn persistir_registros_atencion(&self) {
-        let mut f = File::create("src/tp05/registros/ej03/registros_atencion.json").unwrap();
-        let registros_serializado = serde_json::to_string_pretty(&self.registro_atencion).unwrap();
-        f.write_all(registros_serializado.as_bytes()).unwrap();
+    pub fn persistir_registros_atencion(&self) -> Result<(), PersistenciaError> {
+        self.repositorio.guardar(&self.registro_atencion)
+    }
+
+    fn cambiar_registros<R>(
+        &mut self,
+        f: impl FnOnce(&mut Vec<RegistroAtencion>) -> R,
+    ) -> Result<R, PersistenciaError> {
+        cambiar_y_guardar(&*self.repositorio, &mut self.registro_atencion, f)
+    }
+
+    pub fn exportar_csv(&self, escritor: impl io::Write) -> Result<(), ErrorCsv> {
+        tabla::exportar(&self.registro_atencion, escritor)
+    }
+
+    // Suma las atenciones de otro sistema al historial. Si alguna fila es invalida no
+    // registra ninguna.
+    pub fn importar_csv(
+        &mut self,
+        lector: impl io::Read,
+        mapeo: &MapeoColumnas,
+    ) -> Result<usize, ErrorCsv> {
+        let registros: Vec<RegistroAtencion> = tabla::importar(lector, mapeo)?;
+        let cantidad = registros.len();
+        self.cambiar_registros(|todos| todos.extend(registros))?;
+        Ok(cantidad)
     }
 }
 
@@ -189,9 +257,23 @@ impl RegistroAtencion {
             tratamiento,
             fecha: fecha.clone(),
             proxima_fecha,
+            controles: None,
         }
     }
 
+    // Fija los controles periodicos y deja como proxima fecha el primero posterior a la atencion.
+    pub fn asignar_controles(&mut self, controles: Recurrencia) {
+        self.proxima_fecha = controles.siguiente_a(&self.fecha);
+        self.controles = Some(controles);
+    }
+
+    // Marca el control actual como hecho y pasa al siguiente, si queda alguno.
+    pub fn avanzar_control(&mut self) -> Option<Fecha> {
+        let actual = self.proxima_fecha.clone()?;
+        self.proxima_fecha = self.controles.as_ref()?.siguiente_a(&actual);
+        self.proxima_fecha.clone()
+    }
+
     pub fn comparar(
         &self,
         nombre_mascota: &String,
@@ -220,9 +302,120 @@ impl RegistroAtencion {
     }
 }
 
+// La mascota y su dueño van aplanados en la misma fila, y los controles periodicos en
+// columnas controles_* que quedan vacias si no tiene.
+impl RegistroCsv for RegistroAtencion {
+    const COLUMNAS: &'static [&'static str] = &[
+        "mascota_nombre",
+        "mascota_edad",
+        "mascota_tipo",
+        "dueño_nombre",
+        "dueño_direccion",
+        "dueño_telefono",
+        "diagnostico",
+        "tratamiento",
+        "fecha",
+        "proxima_fecha",
+        "controles_inicio",
+        "controles_frecuencia",
+        "controles_intervalo",
+        "controles_cantidad",
+        "controles_hasta",
+    ];
+    const OPCIONALES: &'static [&'static str] = &[
+        "proxima_fecha",
+        "controles_inicio",
+        "controles_frecuencia",
+        "controles_intervalo",
+        "controles_cantidad",
+        "controles_hasta",
+    ];
+
+    fn a_fila(&self) -> Vec<String> {
+        let mascota = &self.mascota;
+        let controles = self.controles.as_ref();
+        let fin = controles.map(Recurrencia::fin);
+        vec![
+            mascota.nombre.clone(),
+            mascota.edad.to_string(),
+            tabla::variante(&mascota.tipo),
+            mascota.dueño.nombre.clone(),
+            mascota.dueño.direccion.clone(),
+            mascota.dueño.telefono.clone(),
+            self.diagnostico.clone(),
+            self.tratamiento.clone(),
+            tabla::fecha(&self.fecha),
+            tabla::opcional(&self.proxima_fecha, tabla::fecha),
+            controles.map_or(String::new(), |c| tabla::fecha(c.inicio())),
+            controles.map_or(String::new(), |c| tabla::variante(c.frecuencia())),
+            controles.map_or(String::new(), |c| c.intervalo().to_string()),
+            match fin {
+                Some(FinRecurrencia::Cantidad(n)) => n.to_string(),
+                _ => String::new(),
+            },
+            match fin {
+                Some(FinRecurrencia::Hasta(f)) => tabla::fecha(f),
+                _ => String::new(),
+            },
+        ]
+    }
+
+    fn de_fila(fila: &Fila) -> Result<Self, ErrorFila> {
+        let dueño = Cliente::new(
+            fila.texto("dueño_direccion")?,
+            fila.texto("dueño_nombre")?,
+            fila.texto("dueño_telefono")?,
+        );
+        let mascota = Mascota::new(
+            fila.texto("mascota_nombre")?,
+            fila.valor("mascota_edad")?,
+            fila.variante("mascota_tipo")?,
+            dueño,
+        );
+        let mut registro = RegistroAtencion::new(
+            &mascota,
+            fila.texto("diagnostico")?,
+            fila.texto("tratamiento")?,
+            &fila.valor("fecha")?,
+            fila.valor_opcional("proxima_fecha")?,
+        );
+        // se respeta la proxima fecha del archivo en lugar de recalcularla
+        registro.controles = controles_de_fila(fila)?;
+        Ok(registro)
+    }
+}
+
+fn controles_de_fila(fila: &Fila) -> Result<Option<Recurrencia>, ErrorFila> {
+    let columnas = &RegistroAtencion::COLUMNAS[10..];
+    if columnas.iter().all(|c| fila.opcional(c).is_none()) {
+        return Ok(None);
+    }
+    let fin = match (
+        fila.valor_opcional("controles_cantidad")?,
+        fila.valor_opcional("controles_hasta")?,
+    ) {
+        (Some(cantidad), None) => FinRecurrencia::Cantidad(cantidad),
+        (None, Some(hasta)) => FinRecurrencia::Hasta(hasta),
+        _ => {
+            return Err(fila
+                .error("los controles necesitan controles_cantidad o controles_hasta, uno solo"));
+        }
+    };
+    Ok(Some(Recurrencia::new(
+        fila.valor("controles_inicio")?,
+        fila.variante("controles_frecuencia")?,
+        fila.valor_opcional("controles_intervalo")?.unwrap_or(1),
+        fin,
+    )))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::tp05::repositorio::{
+        Repositorio, RepositorioCompartido, RepositorioLleno, RepositorioMemoria,
+    };
+    use std::rc::Rc;
 
     // Estructura para datos de prueba reutilizables
     struct TestData {
@@ -262,7 +455,8 @@ mod tests {
         );
 
         let veterinaria =
-            Veterinaria::new("Vet Copada".to_string(), "Calle Falsa 456".to_string(), 42);
+            Veterinaria::new("Vet Copada".to_string(), "Calle Falsa 456".to_string(), 42)
+                .con_repositorio(RepositorioCompartido::memoria());
 
         TestData {
             veterinaria,
@@ -377,7 +571,9 @@ mod tests {
     #[test]
     fn test_registrar_atencion() {
         let mut data = setup();
-        data.veterinaria.registrar_atencion(&data.registro_atencion);
+        data.veterinaria
+            .registrar_atencion(&data.registro_atencion)
+            .unwrap();
         assert_eq!(data.veterinaria.registro_atencion.len(), 1);
         assert_eq!(
             data.veterinaria.registro_atencion[0].mascota.nombre,
@@ -389,10 +585,56 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_registrar_atencion_persiste_en_repositorio() {
+        let repo = Rc::new(RepositorioMemoria::new());
+        let mut data = setup();
+        data.veterinaria = data
+            .veterinaria
+            .con_repositorio(RepositorioCompartido::from(repo.clone()));
+        data.veterinaria
+            .registrar_atencion(&data.registro_atencion)
+            .unwrap();
+
+        let guardado: Vec<RegistroAtencion> = repo.cargar().unwrap().unwrap();
+        assert_eq!(guardado.len(), 1);
+        assert_eq!(guardado[0].diagnostico, "Fiebre loca");
+    }
+
+    #[test]
+    fn test_registrar_atencion_sin_espacio_en_disco() {
+        let mut data = setup();
+        data.veterinaria
+            .registrar_atencion(&data.registro_atencion)
+            .unwrap();
+        data.veterinaria = data
+            .veterinaria
+            .con_repositorio(RepositorioLleno::compartido());
+
+        let otra = RegistroAtencion::new(
+            &data.mascota_gato,
+            "Tos".to_string(),
+            "Jarabe".to_string(),
+            &data.fecha,
+            None,
+        );
+        assert!(data.veterinaria.registrar_atencion(&otra).is_err());
+        assert!(
+            data.veterinaria
+                .eliminar_atencion(&data.registro_atencion)
+                .is_err()
+        );
+        // el historial sigue como esta en el archivo
+        assert_eq!(data.veterinaria.registro_atencion.len(), 1);
+        assert!(data.veterinaria.registro_atencion[0].comparar_completo(&data.registro_atencion));
+    }
+
     #[test]
     fn test_buscar_registro_atencion_existe() {
         let mut data = setup();
-        data.veterinaria.registrar_atencion(&data.registro_atencion);
+        data.veterinaria
+            .registrar_atencion(&data.registro_atencion)
+            .unwrap();
         let result = data.veterinaria.buscar_registro_atencion(
             "Firulais".to_string(),
             "Lucho".to_string(),
@@ -406,7 +648,9 @@ mod tests {
     #[test]
     fn test_buscar_registro_atencion_no_existe() {
         let mut data = setup();
-        data.veterinaria.registrar_atencion(&data.registro_atencion);
+        data.veterinaria
+            .registrar_atencion(&data.registro_atencion)
+            .unwrap();
         let result = data.veterinaria.buscar_registro_atencion(
             "Michi".to_string(),
             "Lucho".to_string(),
@@ -418,7 +662,9 @@ mod tests {
     #[test]
     fn test_modificar_diagnostico_existe() {
         let mut data = setup();
-        data.veterinaria.registrar_atencion(&data.registro_atencion);
+        data.veterinaria
+            .registrar_atencion(&data.registro_atencion)
+            .unwrap();
         let nuevo_registro = RegistroAtencion::new(
             &data.mascota_perro,
             "Tos perruna".to_string(),
@@ -428,7 +674,8 @@ mod tests {
         );
         let result = data
             .veterinaria
-            .modificar_diagnostico(&data.registro_atencion, &nuevo_registro);
+            .modificar_diagnostico(&data.registro_atencion, &nuevo_registro)
+            .unwrap();
         assert!(result.is_some());
         let registro = result.unwrap();
         assert_eq!(registro.diagnostico, "Tos perruna");
@@ -458,18 +705,22 @@ mod tests {
         );
         let result = data
             .veterinaria
-            .modificar_diagnostico(&registro_falso, &nuevo_registro);
+            .modificar_diagnostico(&registro_falso, &nuevo_registro)
+            .unwrap();
         assert!(result.is_none());
     }
 
     #[test]
     fn test_modificar_fecha_atencion_existe() {
         let mut data = setup();
-        data.veterinaria.registrar_atencion(&data.registro_atencion);
+        data.veterinaria
+            .registrar_atencion(&data.registro_atencion)
+            .unwrap();
         let nueva_fecha = Fecha::new(15, 7, 2025);
         let result = data
             .veterinaria
-            .modificar_fecha_atencion(&data.registro_atencion, nueva_fecha.clone());
+            .modificar_fecha_atencion(&data.registro_atencion, nueva_fecha.clone())
+            .unwrap();
         assert!(result.is_some());
         let registro = result.unwrap();
         assert!(registro.proxima_fecha.is_some());
@@ -489,15 +740,21 @@ mod tests {
         let nueva_fecha = Fecha::new(15, 7, 2025);
         let result = data
             .veterinaria
-            .modificar_fecha_atencion(&registro_falso, nueva_fecha);
+            .modificar_fecha_atencion(&registro_falso, nueva_fecha)
+            .unwrap();
         assert!(result.is_none());
     }
 
     #[test]
     fn test_eliminar_atencion_existe() {
         let mut data = setup();
-        data.veterinaria.registrar_atencion(&data.registro_atencion);
-        let result = data.veterinaria.eliminar_atencion(&data.registro_atencion);
+        data.veterinaria
+            .registrar_atencion(&data.registro_atencion)
+            .unwrap();
+        let result = data
+            .veterinaria
+            .eliminar_atencion(&data.registro_atencion)
+            .unwrap();
         assert!(result);
         assert!(data.veterinaria.registro_atencion.is_empty());
     }
@@ -512,7 +769,7 @@ mod tests {
             &data.fecha,
             None,
         );
-        let result = data.veterinaria.eliminar_atencion(&registro_falso);
+        let result = data.veterinaria.eliminar_atencion(&registro_falso).unwrap();
         assert!(!result);
         assert!(data.veterinaria.registro_atencion.is_empty());
     }
@@ -608,4 +865,155 @@ mod tests {
             "diagnostico:Fiebre loca tratamiento:Paracetamol canino prox_dia:0"
         );
     }
+
+    #[test]
+    fn test_programar_seguimiento_dias_habiles() {
+        let data = setup();
+        let mut vet = data
+            .veterinaria
+            .con_calendario(Calendario::cargar(crate::tp03::calendario::RUTA_FERIADOS).unwrap());
+        let ra = RegistroAtencion::new(
+            &data.mascota_perro,
+            "Control".to_string(),
+            "Reposo".to_string(),
+            &Fecha::new(4, 7, 2025),
+            None,
+        );
+        vet.registrar_atencion(&ra).unwrap();
+        // viernes 4/7 + 3 habiles: lunes 7, martes 8, (miercoles 9 feriado) jueves 10
+        let ra_mod = vet.programar_seguimiento(&ra, 3).unwrap().unwrap();
+        assert_eq!(ra_mod.proxima_fecha, Some(Fecha::new(10, 7, 2025)));
+
+        // una fecha puesta a mano en dia no habil se corre al siguiente habil
+        let ra_mod = vet
+            .modificar_fecha_atencion(&ra, Fecha::new(12, 7, 2025))
+            .unwrap()
+            .unwrap();
+        assert_eq!(ra_mod.proxima_fecha, Some(Fecha::new(14, 7, 2025)));
+    }
+
+    #[test]
+    fn test_programar_seguimiento_sin_calendario() {
+        let mut data = setup();
+        data.veterinaria
+            .registrar_atencion(&data.registro_atencion)
+            .unwrap();
+        // 10/6/2025 es martes, sin calendario solo se saltean fines de semana
+        let ra_mod = data
+            .veterinaria
+            .programar_seguimiento(&data.registro_atencion, 5)
+            .unwrap()
+            .unwrap();
+        assert_eq!(ra_mod.proxima_fecha, Some(Fecha::new(17, 6, 2025)));
+    }
+
+    #[test]
+    fn test_controles_periodicos() {
+        use crate::tp03::recurrencia::FinRecurrencia;
+
+        let mut ra = RegistroAtencion::new(
+            &setup().mascota_perro,
+            "Vacunacion".to_string(),
+            "Refuerzo".to_string(),
+            &Fecha::new(10, 1, 2025),
+            None,
+        );
+        ra.asignar_controles(Recurrencia::cada_n_meses(
+            Fecha::new(10, 1, 2025),
+            6,
+            FinRecurrencia::Cantidad(3),
+        ));
+        assert_eq!(ra.proxima_fecha, Some(Fecha::new(10, 7, 2025)));
+        assert_eq!(ra.avanzar_control(), Some(Fecha::new(10, 1, 2026)));
+        assert_eq!(ra.avanzar_control(), None);
+        assert_eq!(ra.proxima_fecha, None);
+        assert_eq!(ra.avanzar_control(), None);
+    }
+
+    #[test]
+    fn test_exportar_e_importar_csv() {
+        let mut data = setup();
+        let mut con_controles = RegistroAtencion::new(
+            &data.mascota_gato,
+            "Control, anual".to_string(),
+            "Vacuna".to_string(),
+            &Fecha::new(10, 1, 2025),
+            None,
+        );
+        con_controles.asignar_controles(Recurrencia::new(
+            Fecha::new(10, 1, 2025),
+            crate::tp03::recurrencia::Frecuencia::Semanal,
+            2,
+            FinRecurrencia::Hasta(Fecha::new(1, 3, 2025)),
+        ));
+        data.veterinaria
+            .registrar_atencion(&data.registro_atencion)
+            .unwrap();
+        data.veterinaria.registrar_atencion(&con_controles).unwrap();
+
+        let mut csv = vec![];
+        data.veterinaria.exportar_csv(&mut csv).unwrap();
+        let texto = String::from_utf8(csv).unwrap();
+        let lineas: Vec<&str> = texto.lines().collect();
+        assert_eq!(
+            lineas[1],
+            "Firulais,5,Perro,Lucho,Av. Siempre Viva 123,1234-5678,Fiebre loca,\
+             Paracetamol canino,2025-06-10,2025-06-10,,,,,"
+        );
+        assert_eq!(
+            lineas[2],
+            "Michi,3,Gato,Lucho,Av. Siempre Viva 123,1234-5678,\"Control, anual\",Vacuna,\
+             2025-01-10,2025-01-24,2025-01-10,Semanal,2,,2025-03-01"
+        );
+
+        let repo = Rc::new(RepositorioMemoria::new());
+        let mut otra = Veterinaria::new("Otra".to_string(), "Calle 1".to_string(), 1)
+            .con_repositorio(RepositorioCompartido::from(repo.clone()));
+        assert_eq!(
+            otra.importar_csv(texto.as_bytes(), &MapeoColumnas::new())
+                .unwrap(),
+            2
+        );
+        let guardados: Vec<RegistroAtencion> = repo.cargar().unwrap().unwrap();
+        assert_eq!(guardados.len(), 2);
+        assert!(guardados[0].comparar_completo(&data.registro_atencion));
+        assert_eq!(guardados[1].controles, con_controles.controles);
+        assert_eq!(guardados[1].proxima_fecha, Some(Fecha::new(24, 1, 2025)));
+    }
+
+    #[test]
+    fn test_importar_csv_con_controles_incompletos() {
+        let mut data = setup();
+        let texto = "mascota_nombre,mascota_edad,mascota_tipo,dueño_nombre,dueño_direccion,\
+                     dueño_telefono,diagnostico,tratamiento,fecha,controles_inicio,\
+                     controles_frecuencia,controles_cantidad,controles_hasta\n\
+                     Toby,2,Perro,Ana,Calle 2,111,Tos,Jarabe,2025-02-01,,,,\n\
+                     Toby,2,Perro,Ana,Calle 2,111,Tos,Jarabe,2025-02-01,2025-02-01,Mensual,,\n\
+                     Toby,300,Perro,Ana,Calle 2,111,Tos,Jarabe,2025-02-30,,,,\n";
+        match data
+            .veterinaria
+            .importar_csv(texto.as_bytes(), &MapeoColumnas::new())
+        {
+            Err(ErrorCsv::Filas(errores)) => {
+                assert_eq!(errores.len(), 2);
+                assert_eq!((errores[0].linea, errores[0].columna.clone()), (3, None));
+                assert_eq!(
+                    (errores[1].linea, errores[1].columna.as_deref()),
+                    (4, Some("mascota_edad"))
+                );
+            }
+            otro => panic!("se esperaban errores por fila: {:?}", otro.map(|_| ())),
+        }
+        assert!(
+            data.veterinaria
+                .buscar_registro_atencion("Toby".to_string(), "Ana".to_string(), "111".to_string())
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_avanzar_control_sin_controles() {
+        let mut data = setup();
+        assert_eq!(data.registro_atencion.avanzar_control(), None);
+    }
 }
@@ -1,14 +1,27 @@
 #![allow(dead_code, unused_variables)]
+use crate::tp03::calendario::Calendario;
 use crate::tp03::ej03::Fecha;
+use crate::tp03::fecha_hora::FechaHora;
+use crate::tp03::reloj::{Reloj, RelojCompartido};
+use crate::tp05::esquema::{Esquema, fecha_a_fecha_hora, por_cada_elemento};
+use crate::tp05::repositorio::{
+    DIRECTORIO_REGISTROS, PersistenciaError, Repositorio, RepositorioCompartido, cambiar_y_guardar,
+};
+use crate::tp05::tabla::{self, ErrorCsv, ErrorFila, Fila, MapeoColumnas, RegistroCsv};
 use serde::{Deserialize, Serialize};
-use std::fs::File;
-use std::io::Write;
+use serde_json::Value;
+use std::fmt;
+use std::io;
+
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
 
 pub struct Biblioteca {
     nombre: String,
     direccion: String,
-    prestamos: Vec<Prestamo>,
-    disponibles: Vec<RegistroDisponible>,
+    calendario: Option<Calendario>,
+    reloj: RelojCompartido,
+    almacen: Box<dyn AlmacenBiblioteca>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -30,6 +43,7 @@ pub struct Libro {
 pub struct Prestamo {
     libro: Libro,
     cliente: Cliente,
+    fecha_prestamo: FechaHora,
     fecha_vencimiento: Fecha,
     fecha_devolucion: Option<Fecha>,
     estado: Estado,
@@ -56,143 +70,531 @@ pub enum Estado {
     EnPrestamo,
 }
 
-impl Biblioteca {
-    pub fn new(nombre: String, direccion: String) -> Self {
-        Biblioteca {
-            nombre,
-            direccion,
-            prestamos: vec![],
+// Version 2: se agrego fecha_prestamo. De los prestamos viejos no se sabe cuando se hicieron,
+// asi que se toma el inicio del dia de vencimiento.
+const MAX_PRESTAMOS_POR_CLIENTE: u8 = 5;
+
+#[derive(Debug)]
+pub enum BibliotecaError {
+    // El cliente ya tiene el maximo de prestamos en curso.
+    LimitePrestamos(u8),
+    // No quedan ejemplares del libro (titulo).
+    SinEjemplares(String),
+    Persistencia(PersistenciaError),
+}
+
+impl fmt::Display for BibliotecaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BibliotecaError::LimitePrestamos(max) => {
+                write!(f, "El cliente ya tiene {} libros prestados", max)
+            }
+            BibliotecaError::SinEjemplares(titulo) => {
+                write!(f, "No quedan ejemplares disponibles de {}", titulo)
+            }
+            BibliotecaError::Persistencia(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for BibliotecaError {}
+
+impl From<PersistenciaError> for BibliotecaError {
+    fn from(e: PersistenciaError) -> Self {
+        BibliotecaError::Persistencia(e)
+    }
+}
+
+pub fn esquema_prestamos() -> Esquema {
+    Esquema::new().migracion(agregar_fecha_prestamo)
+}
+
+fn agregar_fecha_prestamo(datos: Value) -> Result<Value, String> {
+    por_cada_elemento(datos, |prestamo| {
+        let vencimiento = prestamo
+            .get("fecha_vencimiento")
+            .cloned()
+            .ok_or("falta fecha_vencimiento")?;
+        prestamo.insert(
+            "fecha_prestamo".to_string(),
+            fecha_a_fecha_hora(vencimiento)?,
+        );
+        Ok(())
+    })
+}
+
+// Donde la biblioteca guarda libros y prestamos. Las consultas tambien pasan por aca para que
+// cada almacen las resuelva a su manera: AlmacenJson recorriendo lo que tiene en memoria y
+// AlmacenSqlite (feature "sqlite") con SQL sobre indices.
+pub trait AlmacenBiblioteca {
+    fn libros(&self) -> Result<Vec<RegistroDisponible>, PersistenciaError>;
+
+    // En el orden en que se hicieron.
+    fn prestamos(&self) -> Result<Vec<Prestamo>, PersistenciaError>;
+
+    fn agregar_libro(&mut self, registro: &RegistroDisponible) -> Result<(), PersistenciaError>;
+
+    // Todos de una vez: si no se pueden guardar no queda ninguno.
+    fn agregar_libros(&mut self, registros: &[RegistroDisponible])
+    -> Result<(), PersistenciaError>;
+
+    // 0 si el libro no es de la biblioteca.
+    fn cant_disponibles(&self, lib: &Libro) -> Result<u8, PersistenciaError>;
+
+    // No hace nada si el libro no es de la biblioteca.
+    fn cambiar_disponibles(&mut self, lib: &Libro, cant: u8) -> Result<(), PersistenciaError>;
+
+    fn agregar_prestamo(&mut self, prestamo: &Prestamo) -> Result<(), PersistenciaError>;
+
+    // Todos de una vez: si no se pueden guardar no queda ninguno.
+    fn agregar_prestamos(&mut self, prestamos: &[Prestamo]) -> Result<(), PersistenciaError>;
+
+    // Quita el ultimo prestamo agregado si es `prestamo`, para deshacer un agregar_prestamo.
+    fn deshacer_prestamo(&mut self, prestamo: &Prestamo) -> Result<(), PersistenciaError>;
+
+    // Agrega el prestamo y descuenta un ejemplar del libro como un solo cambio: si no se puede
+    // guardar el descuento, el prestamo se deshace.
+    fn registrar_prestamo(&mut self, prestamo: &Prestamo) -> Result<(), PersistenciaError> {
+        let disponibles = self.cant_disponibles(&prestamo.libro)?;
+        self.agregar_prestamo(prestamo)?;
+        if let Err(e) = self.cambiar_disponibles(&prestamo.libro, disponibles.saturating_sub(1)) {
+            self.deshacer_prestamo(prestamo)?;
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    fn buscar_prestamo(
+        &self,
+        lib: &Libro,
+        cli: &Cliente,
+    ) -> Result<Option<Prestamo>, PersistenciaError>;
+
+    // Solo cuenta los que siguen en prestamo.
+    fn cant_prestamos_cli(&self, cli: &Cliente) -> Result<u8, PersistenciaError>;
+
+    // Los prestamos, devueltos o no, que vencen hasta `fecha` inclusive.
+    fn prestamos_que_vencen_hasta(&self, fecha: &Fecha)
+    -> Result<Vec<Prestamo>, PersistenciaError>;
+
+    // Marca como devueltos los prestamos del libro al cliente; false si no habia ninguno.
+    fn devolver(
+        &mut self,
+        lib: &Libro,
+        cli: &Cliente,
+        fecha: &Fecha,
+    ) -> Result<bool, PersistenciaError>;
+
+    // Vuelve los prestamos del libro al cliente a como estaban en `anteriores`, en el mismo
+    // orden, para deshacer un devolver.
+    fn deshacer_devolucion(
+        &mut self,
+        lib: &Libro,
+        cli: &Cliente,
+        anteriores: &[Prestamo],
+    ) -> Result<(), PersistenciaError>;
+
+    // Como registrar_prestamo, pero al reves: si no se puede guardar el ejemplar que vuelve,
+    // los prestamos quedan como antes de devolverlos.
+    fn registrar_devolucion(
+        &mut self,
+        lib: &Libro,
+        cli: &Cliente,
+        fecha: &Fecha,
+    ) -> Result<bool, PersistenciaError> {
+        let anteriores: Vec<Prestamo> = self
+            .prestamos()?
+            .into_iter()
+            .filter(|p| p.libro.igual(lib) && p.cliente.igual(cli))
+            .collect();
+        let disponibles = self.cant_disponibles(lib)?;
+        if !self.devolver(lib, cli, fecha)? {
+            return Ok(false);
+        }
+        if let Err(e) = self.cambiar_disponibles(lib, disponibles.saturating_add(1)) {
+            self.deshacer_devolucion(lib, cli, &anteriores)?;
+            return Err(e);
+        }
+        Ok(true)
+    }
+
+    fn quitar_prestamos(&mut self, lib: &Libro, cli: &Cliente) -> Result<(), PersistenciaError>;
+}
+
+// Libros y prestamos en memoria. Despues de cada cambio se guarda la lista completa en su
+// repositorio, que por defecto es un archivo JSON.
+pub struct AlmacenJson {
+    disponibles: Vec<RegistroDisponible>,
+    prestamos: Vec<Prestamo>,
+    repo_libros: RepositorioCompartido<Vec<RegistroDisponible>>,
+    repo_prestamos: RepositorioCompartido<Vec<Prestamo>>,
+}
+
+impl AlmacenJson {
+    // Vacio, sin leer lo que haya en los repositorios.
+    pub fn new(
+        libros: RepositorioCompartido<Vec<RegistroDisponible>>,
+        prestamos: RepositorioCompartido<Vec<Prestamo>>,
+    ) -> Self {
+        AlmacenJson {
             disponibles: vec![],
+            prestamos: vec![],
+            repo_libros: libros,
+            repo_prestamos: prestamos,
         }
     }
 
-    pub fn prestar(&mut self, cli: &Cliente, lib: &Libro, fecha_vencimiento: &Fecha) -> bool {
-        if self.cant_prestamos_cli(cli) >= 5 || self.cant_disponibles(lib) == 0 {
-            return false;
+    // Con los archivos de DIRECTORIO_REGISTROS.
+    pub fn por_defecto() -> Self {
+        AlmacenJson::new(
+            RepositorioCompartido::json(
+                DIRECTORIO_REGISTROS,
+                "ej04/libros_disponibles.json",
+                Esquema::new(),
+            ),
+            RepositorioCompartido::json(
+                DIRECTORIO_REGISTROS,
+                "ej04/prestamos.json",
+                esquema_prestamos(),
+            ),
+        )
+    }
+
+    // Restaura libros y prestamos de los repositorios. Los que todavia no tienen nada
+    // guardado se inicializan vacios.
+    pub fn abrir(
+        libros: RepositorioCompartido<Vec<RegistroDisponible>>,
+        prestamos: RepositorioCompartido<Vec<Prestamo>>,
+    ) -> Result<Self, PersistenciaError> {
+        AlmacenJson::new(libros, prestamos).restaurar()
+    }
+
+    fn restaurar(mut self) -> Result<Self, PersistenciaError> {
+        match self.repo_libros.cargar()? {
+            Some(disponibles) => self.disponibles = disponibles,
+            None => self.repo_libros.guardar(&self.disponibles)?,
+        }
+        match self.repo_prestamos.cargar()? {
+            Some(prestamos) => self.prestamos = prestamos,
+            None => self.repo_prestamos.guardar(&self.prestamos)?,
         }
+        Ok(self)
+    }
 
-        let p = Prestamo::new(lib, cli, fecha_vencimiento);
-        self.prestamos.push(p);
-        self.decrementar_disponibilidad(lib);
-        self.persistir_prestamos();
+    fn persistir_libros(&self) -> Result<(), PersistenciaError> {
+        self.repo_libros.guardar(&self.disponibles)
+    }
 
-        true
+    fn persistir_prestamos(&self) -> Result<(), PersistenciaError> {
+        self.repo_prestamos.guardar(&self.prestamos)
     }
+}
 
-    pub fn prestamos_a_vencer(&self, dias: i64) -> Vec<Prestamo> {
-        let mut pres: Vec<Prestamo> = vec![];
-        let mut f = Fecha::fecha_actual();
-        f.sumar_dias(dias);
-        for p in &self.prestamos {
-            if !p.fecha_vencimiento.es_mayor(&f) {
-                pres.push(p.clone());
+impl AlmacenBiblioteca for AlmacenJson {
+    fn libros(&self) -> Result<Vec<RegistroDisponible>, PersistenciaError> {
+        Ok(self.disponibles.clone())
+    }
+
+    fn prestamos(&self) -> Result<Vec<Prestamo>, PersistenciaError> {
+        Ok(self.prestamos.clone())
+    }
+
+    fn agregar_libro(&mut self, registro: &RegistroDisponible) -> Result<(), PersistenciaError> {
+        self.agregar_libros(std::slice::from_ref(registro))
+    }
+
+    fn agregar_libros(
+        &mut self,
+        registros: &[RegistroDisponible],
+    ) -> Result<(), PersistenciaError> {
+        cambiar_y_guardar(&*self.repo_libros, &mut self.disponibles, |disponibles| {
+            disponibles.extend_from_slice(registros)
+        })
+    }
+
+    fn cant_disponibles(&self, lib: &Libro) -> Result<u8, PersistenciaError> {
+        let mut disponibles: u8 = 0;
+        for d in &self.disponibles {
+            if d.libro.igual(lib) {
+                disponibles = d.cant_disponibles;
+                break;
             }
         }
-        pres
+        Ok(disponibles)
     }
 
-    pub fn prestamos_vencidos(&self) -> Vec<Prestamo> {
-        let mut pres: Vec<Prestamo> = vec![];
-        let fecha_actual = Fecha::fecha_actual();
-        for p in &self.prestamos {
-            if !p.fecha_vencimiento.es_mayor(&fecha_actual) {
-                pres.push(p.clone());
+    fn cambiar_disponibles(&mut self, lib: &Libro, cant: u8) -> Result<(), PersistenciaError> {
+        cambiar_y_guardar(&*self.repo_libros, &mut self.disponibles, |disponibles| {
+            if let Some(d) = disponibles.iter_mut().find(|d| d.libro.igual(lib)) {
+                d.cant_disponibles = cant;
             }
+        })
+    }
+
+    fn agregar_prestamo(&mut self, prestamo: &Prestamo) -> Result<(), PersistenciaError> {
+        self.agregar_prestamos(std::slice::from_ref(prestamo))
+    }
+
+    fn agregar_prestamos(&mut self, prestamos: &[Prestamo]) -> Result<(), PersistenciaError> {
+        cambiar_y_guardar(&*self.repo_prestamos, &mut self.prestamos, |todos| {
+            todos.extend_from_slice(prestamos)
+        })
+    }
+
+    fn deshacer_prestamo(&mut self, prestamo: &Prestamo) -> Result<(), PersistenciaError> {
+        match self.prestamos.last() {
+            Some(ultimo)
+                if ultimo.libro.igual(&prestamo.libro)
+                    && ultimo.cliente.igual(&prestamo.cliente) =>
+            {
+                cambiar_y_guardar(&*self.repo_prestamos, &mut self.prestamos, Vec::pop)?;
+                Ok(())
+            }
+            _ => Ok(()),
         }
-        pres
     }
 
-    pub fn buscar_prestamo(&self, lib: &Libro, cli: &Cliente) -> Option<Prestamo> {
+    fn buscar_prestamo(
+        &self,
+        lib: &Libro,
+        cli: &Cliente,
+    ) -> Result<Option<Prestamo>, PersistenciaError> {
         for p in &self.prestamos {
             if p.libro.igual(lib) && p.cliente.igual(cli) {
-                return Some(p.clone());
+                return Ok(Some(p.clone()));
             }
         }
-        None
+        Ok(None)
     }
 
-    pub fn devolver_libro(&mut self, lib: &Libro, cli: &Cliente) -> bool {
-        let mut encontrado = false;
-        for p in &mut self.prestamos {
-            if p.libro.igual(lib) && p.cliente.igual(cli) {
-                p.estado = Estado::Devuelto;
-                p.fecha_devolucion = Some(Fecha::fecha_actual());
-                encontrado = true;
+    fn cant_prestamos_cli(&self, cli: &Cliente) -> Result<u8, PersistenciaError> {
+        let mut cant_prestamos: u8 = 0;
+        for p in &self.prestamos {
+            if p.cliente.igual(cli) && p.estado.igual(&Estado::EnPrestamo) {
+                cant_prestamos = cant_prestamos.saturating_add(1);
             }
         }
-        if encontrado {
-            self.incrementar_disponibilidad(lib);
-            self.persistir_prestamos();
-            return true;
-        }
-        false
+        Ok(cant_prestamos)
     }
 
-    pub fn incrementar_disponibilidad(&mut self, lib: &Libro) {
-        for d in &mut self.disponibles {
-            if d.libro.igual(lib) {
-                d.cant_disponibles += 1;
-                return;
+    fn prestamos_que_vencen_hasta(
+        &self,
+        fecha: &Fecha,
+    ) -> Result<Vec<Prestamo>, PersistenciaError> {
+        let mut pres: Vec<Prestamo> = vec![];
+        for p in &self.prestamos {
+            if !p.fecha_vencimiento.es_mayor(fecha) {
+                pres.push(p.clone());
             }
         }
-        self.persistir_libros();
+        Ok(pres)
     }
 
-    pub fn decrementar_disponibilidad(&mut self, lib: &Libro) {
-        for d in &mut self.disponibles {
-            if d.libro.igual(lib) {
-                if d.cant_disponibles > 0 {
-                    d.cant_disponibles -= 1;
-                }
-                return;
-            }
+    fn devolver(
+        &mut self,
+        lib: &Libro,
+        cli: &Cliente,
+        fecha: &Fecha,
+    ) -> Result<bool, PersistenciaError> {
+        let es_el_prestamo = |p: &Prestamo| p.libro.igual(lib) && p.cliente.igual(cli);
+        if !self.prestamos.iter().any(es_el_prestamo) {
+            return Ok(false);
         }
-        self.persistir_libros();
+        cambiar_y_guardar(&*self.repo_prestamos, &mut self.prestamos, |prestamos| {
+            for p in prestamos.iter_mut().filter(|p| es_el_prestamo(p)) {
+                p.estado = Estado::Devuelto;
+                p.fecha_devolucion = Some(fecha.clone());
+            }
+        })?;
+        Ok(true)
     }
 
-    pub fn cant_prestamos_cli(&self, cli: &Cliente) -> u8 {
-        let mut cant_prestamos = 0;
-        for p in &self.prestamos {
-            if p.cliente.igual(cli) && p.estado.igual(&Estado::EnPrestamo) {
-                cant_prestamos += 1;
+    fn deshacer_devolucion(
+        &mut self,
+        lib: &Libro,
+        cli: &Cliente,
+        anteriores: &[Prestamo],
+    ) -> Result<(), PersistenciaError> {
+        cambiar_y_guardar(&*self.repo_prestamos, &mut self.prestamos, |prestamos| {
+            let del_cliente = prestamos
+                .iter_mut()
+                .filter(|p| p.libro.igual(lib) && p.cliente.igual(cli));
+            for (prestamo, anterior) in del_cliente.zip(anteriores) {
+                *prestamo = anterior.clone();
             }
+        })
+    }
+
+    fn quitar_prestamos(&mut self, lib: &Libro, cli: &Cliente) -> Result<(), PersistenciaError> {
+        cambiar_y_guardar(&*self.repo_prestamos, &mut self.prestamos, |prestamos| {
+            prestamos.retain(|prestamo| !(prestamo.libro.igual(lib) && prestamo.cliente.igual(cli)))
+        })
+    }
+}
+
+impl Biblioteca {
+    pub fn new(nombre: String, direccion: String) -> Self {
+        Biblioteca {
+            nombre,
+            direccion,
+            calendario: None,
+            reloj: RelojCompartido::default(),
+            almacen: Box::new(AlmacenJson::por_defecto()),
         }
-        cant_prestamos
     }
 
-    pub fn cant_disponibles(&self, lib: &Libro) -> u8 {
-        let mut disponibles: u8 = 0;
-        for d in &self.disponibles {
-            if d.libro.igual(lib) {
-                disponibles = d.cant_disponibles;
-                break;
-            }
+    // Restaura libros y prestamos de los repositorios. Los que todavia no tienen nada
+    // guardado se inicializan vacios.
+    pub fn abrir(
+        nombre: String,
+        direccion: String,
+        libros: RepositorioCompartido<Vec<RegistroDisponible>>,
+        prestamos: RepositorioCompartido<Vec<Prestamo>>,
+    ) -> Result<Self, PersistenciaError> {
+        let almacen = AlmacenJson::abrir(libros, prestamos)?;
+        Ok(Biblioteca::new(nombre, direccion).con_almacen(almacen))
+    }
+
+    // Igual que abrir pero con los archivos por defecto en DIRECTORIO_REGISTROS.
+    pub fn cargar(nombre: String, direccion: String) -> Result<Self, PersistenciaError> {
+        let almacen = AlmacenJson::por_defecto().restaurar()?;
+        Ok(Biblioteca::new(nombre, direccion).con_almacen(almacen))
+    }
+
+    // Empieza sin libros ni prestamos, guardandolos en estos repositorios.
+    pub fn con_repositorios(
+        self,
+        libros: RepositorioCompartido<Vec<RegistroDisponible>>,
+        prestamos: RepositorioCompartido<Vec<Prestamo>>,
+    ) -> Self {
+        self.con_almacen(AlmacenJson::new(libros, prestamos))
+    }
+
+    pub fn con_almacen(mut self, almacen: impl AlmacenBiblioteca + 'static) -> Self {
+        self.almacen = Box::new(almacen);
+        self
+    }
+
+    // Con calendario los vencimientos que caen en un dia no habil pasan al siguiente habil.
+    pub fn con_calendario(mut self, calendario: Calendario) -> Self {
+        self.calendario = Some(calendario);
+        self
+    }
+
+    pub fn con_reloj(mut self, reloj: RelojCompartido) -> Self {
+        self.reloj = reloj;
+        self
+    }
+
+    pub fn prestar(
+        &mut self,
+        cli: &Cliente,
+        lib: &Libro,
+        fecha_vencimiento: &Fecha,
+    ) -> Result<(), BibliotecaError> {
+        if self.cant_prestamos_cli(cli)? >= MAX_PRESTAMOS_POR_CLIENTE {
+            return Err(BibliotecaError::LimitePrestamos(MAX_PRESTAMOS_POR_CLIENTE));
         }
-        disponibles
+        if self.cant_disponibles(lib)? == 0 {
+            return Err(BibliotecaError::SinEjemplares(lib.titulo.clone()));
+        }
+
+        let vencimiento = match &self.calendario {
+            Some(c) => c
+                .siguiente_dia_habil(fecha_vencimiento)
+                .unwrap_or(fecha_vencimiento.clone()),
+            None => fecha_vencimiento.clone(),
+        };
+        let p = Prestamo::new(lib, cli, &self.reloj.ahora(), &vencimiento);
+        self.almacen.registrar_prestamo(&p)?;
+
+        Ok(())
+    }
+
+    pub fn prestamos_a_vencer(&self, dias: i64) -> Result<Vec<Prestamo>, PersistenciaError> {
+        let mut f = self.reloj.hoy();
+        f.sumar_dias(dias);
+        self.almacen.prestamos_que_vencen_hasta(&f)
+    }
+
+    pub fn prestamos_vencidos(&self) -> Result<Vec<Prestamo>, PersistenciaError> {
+        self.almacen.prestamos_que_vencen_hasta(&self.reloj.hoy())
+    }
+
+    pub fn buscar_prestamo(
+        &self,
+        lib: &Libro,
+        cli: &Cliente,
+    ) -> Result<Option<Prestamo>, PersistenciaError> {
+        self.almacen.buscar_prestamo(lib, cli)
+    }
+
+    pub fn devolver_libro(&mut self, lib: &Libro, cli: &Cliente) -> Result<bool, BibliotecaError> {
+        let hoy = self.reloj.hoy();
+        Ok(self.almacen.registrar_devolucion(lib, cli, &hoy)?)
+    }
+
+    pub fn incrementar_disponibilidad(&mut self, lib: &Libro) -> Result<(), BibliotecaError> {
+        let cant = self.almacen.cant_disponibles(lib)?;
+        Ok(self
+            .almacen
+            .cambiar_disponibles(lib, cant.saturating_add(1))?)
+    }
+
+    pub fn decrementar_disponibilidad(&mut self, lib: &Libro) -> Result<(), BibliotecaError> {
+        let cant = self.almacen.cant_disponibles(lib)?;
+        Ok(self
+            .almacen
+            .cambiar_disponibles(lib, cant.saturating_sub(1))?)
+    }
+
+    pub fn cant_prestamos_cli(&self, cli: &Cliente) -> Result<u8, PersistenciaError> {
+        self.almacen.cant_prestamos_cli(cli)
     }
 
-    pub fn agregar_libro(&mut self, libro: &Libro, cant: u8) {
-        self.disponibles
-            .push(RegistroDisponible::new(libro.clone(), cant));
-        self.persistir_libros();
+    pub fn cant_disponibles(&self, lib: &Libro) -> Result<u8, PersistenciaError> {
+        self.almacen.cant_disponibles(lib)
     }
 
-    pub fn quitar_prestamo(&mut self, cli: &Cliente, lib: &Libro) {
-        self.prestamos
-            .retain(|prestamo| !(prestamo.libro.igual(lib) && prestamo.cliente.igual(cli)));
-        self.persistir_prestamos();
+    pub fn agregar_libro(&mut self, libro: &Libro, cant: u8) -> Result<(), BibliotecaError> {
+        let registro = RegistroDisponible::new(libro.clone(), cant);
+        Ok(self.almacen.agregar_libro(&registro)?)
     }
 
-    pub fn persistir_libros(&self) {
-        let mut f = File::create("src/tp05/registros/ej04/libros_disponibles.json").unwrap();
-        let registros_serializado = serde_json::to_string_pretty(&self.disponibles).unwrap();
-        f.write_all(registros_serializado.as_bytes()).unwrap();
+    pub fn quitar_prestamo(&mut self, cli: &Cliente, lib: &Libro) -> Result<(), BibliotecaError> {
+        Ok(self.almacen.quitar_prestamos(lib, cli)?)
     }
 
-    pub fn persistir_prestamos(&self) {
-        let mut f = File::create("src/tp05/registros/ej04/prestamos.json").unwrap();
-        let registros_serializado = serde_json::to_string_pretty(&self.prestamos).unwrap();
-        f.write_all(registros_serializado.as_bytes()).unwrap();
+    // Los libros con sus ejemplares disponibles.
+    pub fn exportar_libros_csv(&self, escritor: impl io::Write) -> Result<(), ErrorCsv> {
+        tabla::exportar(&self.almacen.libros()?, escritor)
+    }
+
+    // Sin la columna cant_disponibles se toma un ejemplar de cada libro.
+    pub fn importar_libros_csv(
+        &mut self,
+        lector: impl io::Read,
+        mapeo: &MapeoColumnas,
+    ) -> Result<usize, ErrorCsv> {
+        let registros: Vec<RegistroDisponible> = tabla::importar(lector, mapeo)?;
+        self.almacen.agregar_libros(&registros)?;
+        Ok(registros.len())
+    }
+
+    pub fn exportar_prestamos_csv(&self, escritor: impl io::Write) -> Result<(), ErrorCsv> {
+        tabla::exportar(&self.almacen.prestamos()?, escritor)
+    }
+
+    // Agrega los prestamos tal como estan en el archivo, sin tocar los ejemplares
+    // disponibles: se supone que los libros importados ya los descuentan.
+    pub fn importar_prestamos_csv(
+        &mut self,
+        lector: impl io::Read,
+        mapeo: &MapeoColumnas,
+    ) -> Result<usize, ErrorCsv> {
+        let prestamos: Vec<Prestamo> = tabla::importar(lector, mapeo)?;
+        self.almacen.agregar_prestamos(&prestamos)?;
+        Ok(prestamos.len())
     }
 }
 
@@ -242,10 +644,16 @@ impl RegistroDisponible {
 }
 
 impl Prestamo {
-    pub fn new(libro: &Libro, cliente: &Cliente, fecha_vencimiento: &Fecha) -> Self {
+    pub fn new(
+        libro: &Libro,
+        cliente: &Cliente,
+        fecha_prestamo: &FechaHora,
+        fecha_vencimiento: &Fecha,
+    ) -> Self {
         Prestamo {
             libro: libro.clone(),
             cliente: cliente.clone(),
+            fecha_prestamo: *fecha_prestamo,
             fecha_vencimiento: fecha_vencimiento.clone(),
             fecha_devolucion: None,
             estado: Estado::EnPrestamo,
@@ -266,9 +674,127 @@ impl Estado {
     }
 }
 
+impl RegistroCsv for Libro {
+    const COLUMNAS: &'static [&'static str] =
+        &["isbn", "titulo", "autor", "cant_paginas", "genero"];
+
+    fn a_fila(&self) -> Vec<String> {
+        vec![
+            self.isbn.clone(),
+            self.titulo.clone(),
+            self.autor.clone(),
+            self.cant_paginas.to_string(),
+            tabla::variante(&self.genero),
+        ]
+    }
+
+    fn de_fila(fila: &Fila) -> Result<Self, ErrorFila> {
+        libro_de_fila(fila, "")
+    }
+}
+
+impl RegistroCsv for RegistroDisponible {
+    const COLUMNAS: &'static [&'static str] = &[
+        "isbn",
+        "titulo",
+        "autor",
+        "cant_paginas",
+        "genero",
+        "cant_disponibles",
+    ];
+    const OPCIONALES: &'static [&'static str] = &["cant_disponibles"];
+
+    fn a_fila(&self) -> Vec<String> {
+        let mut fila = self.libro.a_fila();
+        fila.push(self.cant_disponibles.to_string());
+        fila
+    }
+
+    fn de_fila(fila: &Fila) -> Result<Self, ErrorFila> {
+        Ok(RegistroDisponible::new(
+            Libro::de_fila(fila)?,
+            fila.valor_opcional("cant_disponibles")?.unwrap_or(1),
+        ))
+    }
+}
+
+impl RegistroCsv for Prestamo {
+    const COLUMNAS: &'static [&'static str] = &[
+        "libro_isbn",
+        "libro_titulo",
+        "libro_autor",
+        "libro_cant_paginas",
+        "libro_genero",
+        "cliente_nombre",
+        "cliente_telefono",
+        "cliente_email",
+        "fecha_prestamo",
+        "fecha_vencimiento",
+        "fecha_devolucion",
+        "estado",
+    ];
+    const OPCIONALES: &'static [&'static str] = &["fecha_devolucion"];
+
+    fn a_fila(&self) -> Vec<String> {
+        let mut fila = self.libro.a_fila();
+        fila.extend([
+            self.cliente.nombre.clone(),
+            self.cliente.telefono.clone(),
+            self.cliente.email.clone(),
+            self.fecha_prestamo.a_rfc3339(),
+            tabla::fecha(&self.fecha_vencimiento),
+            tabla::opcional(&self.fecha_devolucion, tabla::fecha),
+            self.estado.a_str(),
+        ]);
+        fila
+    }
+
+    fn de_fila(fila: &Fila) -> Result<Self, ErrorFila> {
+        let cliente = Cliente::new(
+            fila.texto("cliente_nombre")?,
+            fila.texto("cliente_telefono")?,
+            fila.texto("cliente_email")?,
+        );
+        let mut prestamo = Prestamo::new(
+            &libro_de_fila(fila, "libro_")?,
+            &cliente,
+            &fila.valor("fecha_prestamo")?,
+            &fila.valor("fecha_vencimiento")?,
+        );
+        prestamo.estado = fila.variante("estado")?;
+        prestamo.fecha_devolucion = fila.valor_opcional("fecha_devolucion")?;
+        match (&prestamo.estado, &prestamo.fecha_devolucion) {
+            (Estado::Devuelto, None) => Err(fila.error_en(
+                "fecha_devolucion",
+                "un prestamo devuelto necesita la fecha de devolucion",
+            )),
+            (Estado::EnPrestamo, Some(_)) => Err(fila.error_en(
+                "fecha_devolucion",
+                "un prestamo en curso no puede tener fecha de devolucion",
+            )),
+            _ => Ok(prestamo),
+        }
+    }
+}
+
+// Las columnas del libro van con `prefijo` adelante cuando estan dentro de otro registro.
+fn libro_de_fila(fila: &Fila, prefijo: &str) -> Result<Libro, ErrorFila> {
+    let columna = |nombre: &str| format!("{}{}", prefijo, nombre);
+    Ok(Libro::new(
+        fila.texto(&columna("isbn"))?,
+        fila.texto(&columna("titulo"))?,
+        fila.texto(&columna("autor"))?,
+        fila.valor(&columna("cant_paginas"))?,
+        fila.variante(&columna("genero"))?,
+    ))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::tp03::reloj::RelojManual;
+    use crate::tp05::repositorio::{RepositorioCompartido, RepositorioLleno, RepositorioMemoria};
+    use std::rc::Rc;
 
     struct TestData {
         biblioteca: Biblioteca,
@@ -284,6 +810,10 @@ mod tests {
         let mut biblioteca = Biblioteca::new(
             "Biblioteca Informatica".to_string(),
             "Calle 123".to_string(),
+        )
+        .con_repositorios(
+            RepositorioCompartido::memoria(),
+            RepositorioCompartido::memoria(),
         );
         let libro1 = Libro::new(
             "12345".to_string(),
@@ -314,8 +844,8 @@ mod tests {
         let mut fecha_pasada = Fecha::fecha_actual();
         fecha_pasada.restar_dias(1);
 
-        biblioteca.agregar_libro(&libro1, 5);
-        biblioteca.agregar_libro(&libro2, 4);
+        biblioteca.agregar_libro(&libro1, 5).unwrap();
+        biblioteca.agregar_libro(&libro2, 4).unwrap();
 
         TestData {
             biblioteca,
@@ -330,11 +860,93 @@ mod tests {
 
     #[test]
     fn test_biblioteca_new() {
-        let biblioteca = Biblioteca::new("Test Biblioteca".to_string(), "Avenida 456".to_string());
+        let biblioteca = Biblioteca::new("Test Biblioteca".to_string(), "Avenida 456".to_string())
+            .con_repositorios(
+                RepositorioCompartido::memoria(),
+                RepositorioCompartido::memoria(),
+            );
         assert_eq!(biblioteca.nombre, "Test Biblioteca");
         assert_eq!(biblioteca.direccion, "Avenida 456");
-        assert!(biblioteca.prestamos.is_empty());
-        assert!(biblioteca.disponibles.is_empty());
+        assert!(biblioteca.almacen.prestamos().unwrap().is_empty());
+        assert!(biblioteca.almacen.libros().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_abrir_sin_datos_inicializa() {
+        let libros = Rc::new(RepositorioMemoria::new());
+        let prestamos = Rc::new(RepositorioMemoria::new());
+        let biblioteca = Biblioteca::abrir(
+            "Biblioteca".to_string(),
+            "Calle 1".to_string(),
+            RepositorioCompartido::from(libros.clone()),
+            RepositorioCompartido::from(prestamos.clone()),
+        )
+        .unwrap();
+        assert!(biblioteca.almacen.libros().unwrap().is_empty());
+        assert!(libros.contenido().unwrap().contains("\"datos\": []"));
+        assert!(prestamos.contenido().unwrap().contains("\"datos\": []"));
+    }
+
+    #[test]
+    fn test_cargar_prestamos_sin_fecha_de_prestamo() {
+        // asi quedaron guardados antes de agregar fecha_prestamo
+        let repo = RepositorioMemoria::new()
+            .con_esquema(esquema_prestamos())
+            .con_contenido(include_str!("registros/ej04/prestamos.json"));
+        let prestamos: Vec<Prestamo> = repo.cargar().unwrap().unwrap();
+        assert!(!prestamos.is_empty());
+
+        let v1 = RepositorioMemoria::new()
+            .con_esquema(esquema_prestamos())
+            .con_contenido(
+                r#"[{"libro": {"isbn": "1", "titulo": "t", "autor": "a", "cant_paginas": 1,
+                               "genero": "Novela"},
+                     "cliente": {"nombre": "n", "telefono": "t", "email": "e"},
+                     "fecha_vencimiento": {"año": 2025, "dia": 7, "mes": 7},
+                     "fecha_devolucion": null,
+                     "estado": "EnPrestamo"}]"#,
+            );
+        let prestamos: Vec<Prestamo> = v1.cargar().unwrap().unwrap();
+        assert_eq!(prestamos[0].fecha_prestamo.fecha(), Fecha::new(7, 7, 2025));
+    }
+
+    #[test]
+    fn test_guardar_cargar_guardar_identico() {
+        let libros = Rc::new(RepositorioMemoria::new());
+        let prestamos = Rc::new(RepositorioMemoria::new());
+        let data = setup();
+        let mut biblioteca = Biblioteca::new("Biblioteca".to_string(), "Calle 1".to_string())
+            .con_repositorios(
+                RepositorioCompartido::from(libros.clone()),
+                RepositorioCompartido::from(prestamos.clone()),
+            );
+        biblioteca.agregar_libro(&data.libro1, 5).unwrap();
+        biblioteca.agregar_libro(&data.libro2, 4).unwrap();
+        biblioteca
+            .prestar(&data.cliente1, &data.libro1, &data.fecha_vencimiento)
+            .unwrap();
+        biblioteca
+            .prestar(&data.cliente2, &data.libro2, &data.fecha_vencimiento)
+            .unwrap();
+        biblioteca
+            .devolver_libro(&data.libro2, &data.cliente2)
+            .unwrap();
+        let libros_guardados = libros.contenido().unwrap();
+        let prestamos_guardados = prestamos.contenido().unwrap();
+
+        let restaurado = AlmacenJson::abrir(
+            RepositorioCompartido::from(libros.clone()),
+            RepositorioCompartido::from(prestamos.clone()),
+        )
+        .unwrap();
+        assert_eq!(restaurado.cant_disponibles(&data.libro1).unwrap(), 4);
+        assert_eq!(restaurado.cant_prestamos_cli(&data.cliente1).unwrap(), 1);
+        assert_eq!(restaurado.prestamos().unwrap().len(), 2);
+
+        restaurado.persistir_libros().unwrap();
+        restaurado.persistir_prestamos().unwrap();
+        assert_eq!(libros.contenido().unwrap(), libros_guardados);
+        assert_eq!(prestamos.contenido().unwrap(), prestamos_guardados);
     }
 
     #[test]
@@ -402,8 +1014,15 @@ mod tests {
     #[test]
     fn test_prestamo_new() {
         let data = setup();
-        let prestamo = Prestamo::new(&data.libro1, &data.cliente1, &data.fecha_vencimiento);
+        let ahora = FechaHora::ahora();
+        let prestamo = Prestamo::new(
+            &data.libro1,
+            &data.cliente1,
+            &ahora,
+            &data.fecha_vencimiento,
+        );
         assert!(prestamo.libro.igual(&data.libro1));
+        assert_eq!(prestamo.fecha_prestamo, ahora);
         assert!(prestamo.cliente.igual(&data.cliente1));
         assert_eq!(prestamo.fecha_devolucion, None);
         assert!(prestamo.estado.igual(&Estado::EnPrestamo));
@@ -429,16 +1048,16 @@ mod tests {
             300,
             Genero::Otros,
         );
-        biblioteca.agregar_libro(&libro3, 2);
-        assert_eq!(biblioteca.cant_disponibles(&libro3), 2);
+        biblioteca.agregar_libro(&libro3, 2).unwrap();
+        assert_eq!(biblioteca.cant_disponibles(&libro3).unwrap(), 2);
     }
 
     #[test]
     fn test_cant_disponibles() {
         let data = setup();
         let biblioteca = data.biblioteca;
-        assert_eq!(biblioteca.cant_disponibles(&data.libro1), 5);
-        assert_eq!(biblioteca.cant_disponibles(&data.libro2), 4);
+        assert_eq!(biblioteca.cant_disponibles(&data.libro1).unwrap(), 5);
+        assert_eq!(biblioteca.cant_disponibles(&data.libro2).unwrap(), 4);
         let libro_no_existe = Libro::new(
             "00000".to_string(),
             "No existe".to_string(),
@@ -446,17 +1065,17 @@ mod tests {
             100,
             Genero::Infantil,
         );
-        assert_eq!(biblioteca.cant_disponibles(&libro_no_existe), 0);
+        assert_eq!(biblioteca.cant_disponibles(&libro_no_existe).unwrap(), 0);
     }
 
     #[test]
     fn test_incrementar_decrementar_disponibilidad() {
         let data = setup();
         let mut biblioteca = data.biblioteca;
-        biblioteca.decrementar_disponibilidad(&data.libro1);
-        assert_eq!(biblioteca.cant_disponibles(&data.libro1), 4);
-        biblioteca.incrementar_disponibilidad(&data.libro1);
-        assert_eq!(biblioteca.cant_disponibles(&data.libro1), 5);
+        biblioteca.decrementar_disponibilidad(&data.libro1).unwrap();
+        assert_eq!(biblioteca.cant_disponibles(&data.libro1).unwrap(), 4);
+        biblioteca.incrementar_disponibilidad(&data.libro1).unwrap();
+        assert_eq!(biblioteca.cant_disponibles(&data.libro1).unwrap(), 5);
         let libro_no_existe = Libro::new(
             "00000".to_string(),
             "No existe".to_string(),
@@ -464,17 +1083,24 @@ mod tests {
             100,
             Genero::Infantil,
         );
-        biblioteca.incrementar_disponibilidad(&libro_no_existe); // No deberia paniquear
-        biblioteca.decrementar_disponibilidad(&libro_no_existe); // No deberia paniquear
+        // No deberian paniquear
+        biblioteca
+            .incrementar_disponibilidad(&libro_no_existe)
+            .unwrap();
+        biblioteca
+            .decrementar_disponibilidad(&libro_no_existe)
+            .unwrap();
     }
 
     #[test]
     fn test_prestar_success() {
         let data = setup();
         let mut biblioteca = data.biblioteca;
-        assert!(biblioteca.prestar(&data.cliente1, &data.libro1, &data.fecha_vencimiento));
-        assert_eq!(biblioteca.cant_disponibles(&data.libro1), 4);
-        assert_eq!(biblioteca.cant_prestamos_cli(&data.cliente1), 1);
+        biblioteca
+            .prestar(&data.cliente1, &data.libro1, &data.fecha_vencimiento)
+            .unwrap();
+        assert_eq!(biblioteca.cant_disponibles(&data.libro1).unwrap(), 4);
+        assert_eq!(biblioteca.cant_prestamos_cli(&data.cliente1).unwrap(), 1);
     }
 
     #[test]
@@ -483,11 +1109,16 @@ mod tests {
         let mut biblioteca = data.biblioteca;
         // Alcanza el maximo de 5 prestamos
         for _ in 0..5 {
-            biblioteca.prestar(&data.cliente1, &data.libro1, &data.fecha_vencimiento);
+            biblioteca
+                .prestar(&data.cliente1, &data.libro1, &data.fecha_vencimiento)
+                .unwrap();
         }
-        assert_eq!(biblioteca.cant_prestamos_cli(&data.cliente1), 5);
-        assert!(!biblioteca.prestar(&data.cliente1, &data.libro1, &data.fecha_vencimiento));
-        assert_eq!(biblioteca.cant_prestamos_cli(&data.cliente1), 5); // No cambia
+        assert_eq!(biblioteca.cant_prestamos_cli(&data.cliente1).unwrap(), 5);
+        assert!(matches!(
+            biblioteca.prestar(&data.cliente1, &data.libro1, &data.fecha_vencimiento),
+            Err(BibliotecaError::LimitePrestamos(5))
+        ));
+        assert_eq!(biblioteca.cant_prestamos_cli(&data.cliente1).unwrap(), 5); // No cambia
     }
 
     #[test]
@@ -496,43 +1127,136 @@ mod tests {
         let mut biblioteca = data.biblioteca;
         // Deplete all copies of libro2
         for _ in 0..4 {
-            biblioteca.decrementar_disponibilidad(&data.libro2);
+            biblioteca.decrementar_disponibilidad(&data.libro2).unwrap();
         }
-        assert_eq!(biblioteca.cant_disponibles(&data.libro2), 0);
-        assert!(!biblioteca.prestar(&data.cliente1, &data.libro2, &data.fecha_vencimiento));
+        assert_eq!(biblioteca.cant_disponibles(&data.libro2).unwrap(), 0);
+        let error = biblioteca
+            .prestar(&data.cliente1, &data.libro2, &data.fecha_vencimiento)
+            .unwrap_err();
+        assert!(matches!(error, BibliotecaError::SinEjemplares(_)));
+    }
+
+    #[test]
+    fn test_sin_espacio_en_disco() {
+        let data = setup();
+        let mut biblioteca = Biblioteca::new("Biblioteca".to_string(), "Calle 1".to_string())
+            .con_repositorios(
+                RepositorioLleno::compartido(),
+                RepositorioLleno::compartido(),
+            );
+        let error = biblioteca.agregar_libro(&data.libro1, 5).unwrap_err();
+        assert!(matches!(
+            error,
+            BibliotecaError::Persistencia(PersistenciaError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn test_prestar_sin_espacio_para_el_stock() {
+        let data = setup();
+        let repo_prestamos = Rc::new(RepositorioMemoria::new());
+        let mut almacen = AlmacenJson::new(
+            RepositorioLleno::compartido(),
+            RepositorioCompartido::from(repo_prestamos.clone()),
+        );
+        almacen.disponibles = vec![RegistroDisponible::new(data.libro1.clone(), 2)];
+        let mut biblioteca =
+            Biblioteca::new("Biblioteca".to_string(), "Calle 1".to_string()).con_almacen(almacen);
+
+        // el prestamo se llega a guardar, pero no el descuento del ejemplar
+        assert!(
+            biblioteca
+                .prestar(&data.cliente1, &data.libro1, &data.fecha_vencimiento)
+                .is_err()
+        );
+        assert!(
+            biblioteca
+                .buscar_prestamo(&data.libro1, &data.cliente1)
+                .unwrap()
+                .is_none()
+        );
+        assert_eq!(biblioteca.cant_disponibles(&data.libro1).unwrap(), 2);
+        let guardados: Vec<Prestamo> = repo_prestamos.cargar().unwrap().unwrap();
+        assert!(guardados.is_empty());
+    }
+
+    #[test]
+    fn test_devolver_sin_espacio_para_el_stock() {
+        let data = setup();
+        let repo_prestamos = Rc::new(RepositorioMemoria::new());
+        let mut almacen = AlmacenJson::new(
+            RepositorioCompartido::memoria(),
+            RepositorioCompartido::from(repo_prestamos.clone()),
+        );
+        almacen
+            .agregar_libro(&RegistroDisponible::new(data.libro1.clone(), 2))
+            .unwrap();
+        let prestamo = Prestamo::new(
+            &data.libro1,
+            &data.cliente1,
+            &FechaHora::try_from(&data.fecha_vencimiento).unwrap(),
+            &data.fecha_vencimiento,
+        );
+        almacen.registrar_prestamo(&prestamo).unwrap();
+        almacen.repo_libros = RepositorioLleno::compartido();
+        let mut biblioteca =
+            Biblioteca::new("Biblioteca".to_string(), "Calle 1".to_string()).con_almacen(almacen);
+
+        // la devolucion se llega a guardar, pero no el ejemplar que vuelve
+        assert!(
+            biblioteca
+                .devolver_libro(&data.libro1, &data.cliente1)
+                .is_err()
+        );
+        assert_eq!(biblioteca.cant_prestamos_cli(&data.cliente1).unwrap(), 1);
+        assert_eq!(biblioteca.cant_disponibles(&data.libro1).unwrap(), 1);
+        let guardados: Vec<Prestamo> = repo_prestamos.cargar().unwrap().unwrap();
+        assert!(guardados[0].estado.igual(&Estado::EnPrestamo));
+        assert!(guardados[0].fecha_devolucion.is_none());
     }
 
     #[test]
     fn test_cant_prestamos_cli() {
         let data = setup();
         let mut biblioteca = data.biblioteca;
-        biblioteca.prestar(&data.cliente1, &data.libro1, &data.fecha_vencimiento);
-        biblioteca.prestar(&data.cliente1, &data.libro2, &data.fecha_vencimiento);
-        assert_eq!(biblioteca.cant_prestamos_cli(&data.cliente1), 2);
-        assert_eq!(biblioteca.cant_prestamos_cli(&data.cliente2), 0);
+        biblioteca
+            .prestar(&data.cliente1, &data.libro1, &data.fecha_vencimiento)
+            .unwrap();
+        biblioteca
+            .prestar(&data.cliente1, &data.libro2, &data.fecha_vencimiento)
+            .unwrap();
+        assert_eq!(biblioteca.cant_prestamos_cli(&data.cliente1).unwrap(), 2);
+        assert_eq!(biblioteca.cant_prestamos_cli(&data.cliente2).unwrap(), 0);
 
-        biblioteca.devolver_libro(&data.libro1, &data.cliente1);
-        assert_eq!(biblioteca.cant_prestamos_cli(&data.cliente1), 1);
+        biblioteca
+            .devolver_libro(&data.libro1, &data.cliente1)
+            .unwrap();
+        assert_eq!(biblioteca.cant_prestamos_cli(&data.cliente1).unwrap(), 1);
     }
 
     #[test]
     fn test_buscar_prestamo() {
         let data = setup();
         let mut biblioteca = data.biblioteca;
-        biblioteca.prestar(&data.cliente1, &data.libro1, &data.fecha_vencimiento);
+        biblioteca
+            .prestar(&data.cliente1, &data.libro1, &data.fecha_vencimiento)
+            .unwrap();
         assert!(
             biblioteca
                 .buscar_prestamo(&data.libro1, &data.cliente1)
+                .unwrap()
                 .is_some()
         );
         assert!(
             biblioteca
                 .buscar_prestamo(&data.libro1, &data.cliente2)
+                .unwrap()
                 .is_none()
         );
         assert!(
             biblioteca
                 .buscar_prestamo(&data.libro2, &data.cliente1)
+                .unwrap()
                 .is_none()
         );
     }
@@ -541,11 +1265,15 @@ mod tests {
     fn test_prestamos_a_vencer() {
         let data = setup();
         let mut biblioteca = data.biblioteca;
-        biblioteca.prestar(&data.cliente1, &data.libro1, &data.fecha_vencimiento);
-        biblioteca.prestar(&data.cliente2, &data.libro2, &data.fecha_pasada);
-        let prestamos = biblioteca.prestamos_a_vencer(10);
+        biblioteca
+            .prestar(&data.cliente1, &data.libro1, &data.fecha_vencimiento)
+            .unwrap();
+        biblioteca
+            .prestar(&data.cliente2, &data.libro2, &data.fecha_pasada)
+            .unwrap();
+        let prestamos = biblioteca.prestamos_a_vencer(10).unwrap();
         assert_eq!(prestamos.len(), 2);
-        let prestamos = biblioteca.prestamos_a_vencer(1);
+        let prestamos = biblioteca.prestamos_a_vencer(1).unwrap();
         assert_eq!(prestamos.len(), 1);
     }
 
@@ -553,9 +1281,13 @@ mod tests {
     fn test_prestamos_vencidos() {
         let data = setup();
         let mut biblioteca = data.biblioteca;
-        biblioteca.prestar(&data.cliente1, &data.libro1, &data.fecha_pasada);
-        biblioteca.prestar(&data.cliente2, &data.libro2, &data.fecha_vencimiento);
-        let prestamos = biblioteca.prestamos_vencidos();
+        biblioteca
+            .prestar(&data.cliente1, &data.libro1, &data.fecha_pasada)
+            .unwrap();
+        biblioteca
+            .prestar(&data.cliente2, &data.libro2, &data.fecha_vencimiento)
+            .unwrap();
+        let prestamos = biblioteca.prestamos_vencidos().unwrap();
         assert_eq!(prestamos.len(), 1);
     }
 
@@ -563,27 +1295,221 @@ mod tests {
     fn test_devolver_libro() {
         let data = setup();
         let mut biblioteca = data.biblioteca;
-        biblioteca.prestar(&data.cliente1, &data.libro1, &data.fecha_vencimiento);
-        assert_eq!(biblioteca.cant_disponibles(&data.libro1), 4);
-        assert_eq!(biblioteca.cant_prestamos_cli(&data.cliente1), 1);
-        assert!(biblioteca.devolver_libro(&data.libro1, &data.cliente1));
-        assert_eq!(biblioteca.cant_disponibles(&data.libro1), 5);
-        assert_eq!(biblioteca.cant_prestamos_cli(&data.cliente1), 0);
+        biblioteca
+            .prestar(&data.cliente1, &data.libro1, &data.fecha_vencimiento)
+            .unwrap();
+        assert_eq!(biblioteca.cant_disponibles(&data.libro1).unwrap(), 4);
+        assert_eq!(biblioteca.cant_prestamos_cli(&data.cliente1).unwrap(), 1);
+        assert!(
+            biblioteca
+                .devolver_libro(&data.libro1, &data.cliente1)
+                .unwrap()
+        );
+        assert_eq!(biblioteca.cant_disponibles(&data.libro1).unwrap(), 5);
+        assert_eq!(biblioteca.cant_prestamos_cli(&data.cliente1).unwrap(), 0);
+
+        assert!(
+            !biblioteca
+                .devolver_libro(&data.libro2, &data.cliente1)
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_prestar_con_calendario_vence_en_dia_habil() {
+        let data = setup();
+        let mut biblioteca = data
+            .biblioteca
+            .con_calendario(Calendario::cargar(crate::tp03::calendario::RUTA_FERIADOS).unwrap());
+        // sabado 5/7/2025 -> lunes 7/7/2025
+        biblioteca
+            .prestar(&data.cliente1, &data.libro1, &Fecha::new(5, 7, 2025))
+            .unwrap();
+        // miercoles 9/7/2025 es feriado -> jueves 10/7/2025
+        biblioteca
+            .prestar(&data.cliente2, &data.libro1, &Fecha::new(9, 7, 2025))
+            .unwrap();
+        assert_eq!(
+            biblioteca
+                .buscar_prestamo(&data.libro1, &data.cliente1)
+                .unwrap()
+                .unwrap()
+                .fecha_vencimiento,
+            Fecha::new(7, 7, 2025)
+        );
+        assert_eq!(
+            biblioteca
+                .buscar_prestamo(&data.libro1, &data.cliente2)
+                .unwrap()
+                .unwrap()
+                .fecha_vencimiento,
+            Fecha::new(10, 7, 2025)
+        );
+    }
+
+    #[test]
+    fn test_vencimientos_con_reloj_manual() {
+        let data = setup();
+        let reloj = Rc::new(RelojManual::new(Fecha::new(1, 7, 2025)).unwrap());
+        let mut biblioteca = data
+            .biblioteca
+            .con_reloj(RelojCompartido::from(reloj.clone()));
+        biblioteca
+            .prestar(&data.cliente1, &data.libro1, &Fecha::new(10, 7, 2025))
+            .unwrap();
+        reloj.avanzar_segundos(90);
+        biblioteca
+            .prestar(&data.cliente2, &data.libro2, &Fecha::new(20, 7, 2025))
+            .unwrap();
+        let p1 = biblioteca
+            .buscar_prestamo(&data.libro1, &data.cliente1)
+            .unwrap()
+            .unwrap();
+        let p2 = biblioteca
+            .buscar_prestamo(&data.libro2, &data.cliente2)
+            .unwrap()
+            .unwrap();
+        assert_eq!(p1.fecha_prestamo.fecha(), Fecha::new(1, 7, 2025));
+        assert!(p1.fecha_prestamo < p2.fecha_prestamo);
+        assert!(biblioteca.prestamos_vencidos().unwrap().is_empty());
+        assert_eq!(biblioteca.prestamos_a_vencer(10).unwrap().len(), 1);
+
+        reloj.avanzar_dias(15);
+        assert_eq!(biblioteca.prestamos_vencidos().unwrap().len(), 1);
+        assert!(
+            biblioteca
+                .devolver_libro(&data.libro1, &data.cliente1)
+                .unwrap()
+        );
+        assert_eq!(
+            biblioteca
+                .buscar_prestamo(&data.libro1, &data.cliente1)
+                .unwrap()
+                .unwrap()
+                .fecha_devolucion,
+            Some(Fecha::new(16, 7, 2025))
+        );
+
+        reloj.avanzar_meses(1);
+        assert_eq!(biblioteca.prestamos_vencidos().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_exportar_e_importar_csv() {
+        let data = setup();
+        let mut origen = data.biblioteca;
+        origen
+            .prestar(&data.cliente1, &data.libro1, &data.fecha_vencimiento)
+            .unwrap();
+        origen
+            .prestar(&data.cliente2, &data.libro2, &data.fecha_vencimiento)
+            .unwrap();
+        origen.devolver_libro(&data.libro2, &data.cliente2).unwrap();
+        let mut libros = vec![];
+        origen.exportar_libros_csv(&mut libros).unwrap();
+        let mut prestamos = vec![];
+        origen.exportar_prestamos_csv(&mut prestamos).unwrap();
+        let texto = String::from_utf8(libros.clone()).unwrap();
+        assert_eq!(
+            texto.lines().take(2).collect::<Vec<_>>(),
+            vec![
+                "isbn,titulo,autor,cant_paginas,genero,cant_disponibles",
+                "12345,Un libro,Un autor1,400,Novela,4"
+            ]
+        );
 
-        assert!(!biblioteca.devolver_libro(&data.libro2, &data.cliente1));
+        // sin lugar en el disco no queda ninguno
+        let mut lleno = Biblioteca::new("Otra".to_string(), "Calle 2".to_string())
+            .con_repositorios(
+                RepositorioLleno::compartido(),
+                RepositorioLleno::compartido(),
+            );
+        let mapeo = MapeoColumnas::new();
+        assert!(
+            lleno
+                .importar_libros_csv(libros.as_slice(), &mapeo)
+                .is_err()
+        );
+        assert!(lleno.almacen.libros().unwrap().is_empty());
+
+        let (repo_libros, repo_prestamos) = (
+            Rc::new(RepositorioMemoria::new()),
+            Rc::new(RepositorioMemoria::new()),
+        );
+        let mut destino = Biblioteca::new("Otra".to_string(), "Calle 2".to_string())
+            .con_repositorios(
+                RepositorioCompartido::from(repo_libros.clone()),
+                RepositorioCompartido::from(repo_prestamos.clone()),
+            );
+        assert_eq!(
+            destino
+                .importar_libros_csv(libros.as_slice(), &mapeo)
+                .unwrap(),
+            2
+        );
+        assert_eq!(
+            destino
+                .importar_prestamos_csv(prestamos.as_slice(), &mapeo)
+                .unwrap(),
+            2
+        );
+        // cada archivo se escribe una sola vez
+        assert_eq!(repo_libros.guardados(), 1);
+        assert_eq!(repo_prestamos.guardados(), 1);
+        assert_eq!(destino.cant_disponibles(&data.libro1).unwrap(), 4);
+        assert_eq!(destino.cant_disponibles(&data.libro2).unwrap(), 4);
+        assert_eq!(destino.cant_prestamos_cli(&data.cliente1).unwrap(), 1);
+        let (antes, despues) = (
+            origen.almacen.prestamos().unwrap(),
+            destino.almacen.prestamos().unwrap(),
+        );
+        assert_eq!(despues[0].fecha_prestamo, antes[0].fecha_prestamo);
+        assert!(despues[1].estado.igual(&Estado::Devuelto));
+        assert_eq!(despues[1].fecha_devolucion, antes[1].fecha_devolucion);
+    }
+
+    #[test]
+    fn test_importar_prestamos_csv_invalidos() {
+        let mut biblioteca = setup().biblioteca;
+        let texto = "libro_isbn,libro_titulo,libro_autor,libro_cant_paginas,libro_genero,\
+                     cliente_nombre,cliente_telefono,cliente_email,fecha_prestamo,\
+                     fecha_vencimiento,fecha_devolucion,estado\n\
+                     1,t,a,10,Novela,n,1,e,2025-07-01T10:00:00-03:00,2025-07-08,,Devuelto\n\
+                     1,t,a,10,Novela,n,1,e,01/07/2025,2025-07-08,,EnPrestamo\n\
+                     1,t,a,10,Novela,n,1,e,2025-07-01T10:00:00-03:00,2025-07-08,,EnPrestamo\n";
+        match biblioteca.importar_prestamos_csv(texto.as_bytes(), &MapeoColumnas::new()) {
+            Err(ErrorCsv::Filas(errores)) => {
+                let columnas: Vec<(u64, Option<&str>)> = errores
+                    .iter()
+                    .map(|e| (e.linea, e.columna.as_deref()))
+                    .collect();
+                assert_eq!(
+                    columnas,
+                    vec![(2, Some("fecha_devolucion")), (3, Some("fecha_prestamo"))]
+                );
+            }
+            otro => panic!("se esperaban errores por fila: {:?}", otro),
+        }
+        assert!(biblioteca.almacen.prestamos().unwrap().is_empty());
     }
 
     #[test]
     fn test_quitar_prestamo() {
         let data = setup();
         let mut biblioteca = data.biblioteca;
-        biblioteca.prestar(&data.cliente1, &data.libro1, &data.fecha_vencimiento);
-        assert_eq!(biblioteca.prestamos.len(), 1);
-        biblioteca.quitar_prestamo(&data.cliente1, &data.libro1);
-        assert_eq!(biblioteca.prestamos.len(), 0);
+        biblioteca
+            .prestar(&data.cliente1, &data.libro1, &data.fecha_vencimiento)
+            .unwrap();
+        assert_eq!(biblioteca.almacen.prestamos().unwrap().len(), 1);
+        biblioteca
+            .quitar_prestamo(&data.cliente1, &data.libro1)
+            .unwrap();
+        assert_eq!(biblioteca.almacen.prestamos().unwrap().len(), 0);
 
-        biblioteca.quitar_prestamo(&data.cliente1, &data.libro2);
-        assert_eq!(biblioteca.prestamos.len(), 0);
+        biblioteca
+            .quitar_prestamo(&data.cliente1, &data.libro2)
+            .unwrap();
+        assert_eq!(biblioteca.almacen.prestamos().unwrap().len(), 0);
     }
 }
 
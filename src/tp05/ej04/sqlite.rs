@@ -0,0 +1,681 @@
+#![allow(dead_code, unused_variables)]
+
+use super::{AlmacenBiblioteca, Cliente, Estado, Genero, Libro, Prestamo, RegistroDisponible};
+use crate::tp03::ej03::Fecha;
+use crate::tp03::fecha_hora::FechaHora;
+use crate::tp05::repositorio::{DIRECTORIO_REGISTROS, PersistenciaError};
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+use rusqlite::{Connection, OptionalExtension, Row, params};
+use std::fs;
+use std::path::Path;
+
+// Version de las tablas, guardada en PRAGMA user_version. 0 es una base recien creada.
+const VERSION_TABLAS: u32 = 1;
+
+const TABLAS: &str = "
+    CREATE TABLE libros (
+        isbn TEXT PRIMARY KEY,
+        titulo TEXT NOT NULL,
+        autor TEXT NOT NULL,
+        cant_paginas INTEGER NOT NULL,
+        genero TEXT NOT NULL
+    );
+    CREATE TABLE ejemplares_disponibles (
+        isbn TEXT PRIMARY KEY REFERENCES libros (isbn),
+        cantidad INTEGER NOT NULL
+    );
+    CREATE TABLE clientes (
+        email TEXT PRIMARY KEY,
+        nombre TEXT NOT NULL,
+        telefono TEXT NOT NULL
+    );
+    CREATE TABLE prestamos (
+        id INTEGER PRIMARY KEY,
+        isbn TEXT NOT NULL REFERENCES libros (isbn),
+        email TEXT NOT NULL REFERENCES clientes (email),
+        fecha_prestamo TEXT NOT NULL,
+        fecha_vencimiento TEXT NOT NULL,
+        fecha_devolucion TEXT,
+        estado TEXT NOT NULL
+    );
+    CREATE INDEX prestamos_por_cliente ON prestamos (email, estado);
+    CREATE INDEX prestamos_por_libro ON prestamos (isbn, email);
+    CREATE INDEX prestamos_por_vencimiento ON prestamos (fecha_vencimiento);
+";
+
+// Las columnas en el orden que espera `prestamo`.
+const SELECT_PRESTAMOS: &str = "
+    SELECT l.isbn, l.titulo, l.autor, l.cant_paginas, l.genero,
+           c.nombre, c.telefono, c.email,
+           p.fecha_prestamo, p.fecha_vencimiento, p.fecha_devolucion, p.estado
+    FROM prestamos p
+    JOIN libros l ON l.isbn = p.isbn
+    JOIN clientes c ON c.email = p.email";
+
+// Biblioteca en una base SQLite embebida. Cada cambio se escribe al momento y las consultas
+// se resuelven con SQL, sin cargar todos los prestamos en memoria.
+pub struct AlmacenSqlite {
+    conexion: Connection,
+}
+
+impl AlmacenSqlite {
+    // Abre la base, creandola con las tablas vacias si no existe.
+    pub fn abrir(ruta: impl AsRef<Path>) -> Result<Self, PersistenciaError> {
+        if let Some(directorio) = ruta.as_ref().parent() {
+            fs::create_dir_all(directorio)?;
+        }
+        AlmacenSqlite::preparar(Connection::open(ruta)?)
+    }
+
+    // Igual que abrir pero en DIRECTORIO_REGISTROS, junto a los archivos JSON.
+    pub fn cargar() -> Result<Self, PersistenciaError> {
+        AlmacenSqlite::abrir(Path::new(DIRECTORIO_REGISTROS).join("ej04/biblioteca.sqlite"))
+    }
+
+    pub fn en_memoria() -> Result<Self, PersistenciaError> {
+        AlmacenSqlite::preparar(Connection::open_in_memory()?)
+    }
+
+    fn preparar(conexion: Connection) -> Result<Self, PersistenciaError> {
+        conexion.pragma_update(None, "foreign_keys", true)?;
+        let version: u32 = conexion.pragma_query_value(None, "user_version", |fila| fila.get(0))?;
+        match version {
+            0 => {
+                let transaccion = conexion.unchecked_transaction()?;
+                transaccion.execute_batch(TABLAS)?;
+                transaccion.pragma_update(None, "user_version", VERSION_TABLAS)?;
+                transaccion.commit()?;
+            }
+            VERSION_TABLAS => {}
+            _ => {
+                return Err(PersistenciaError::VersionDesconocida {
+                    version,
+                    soportada: VERSION_TABLAS,
+                });
+            }
+        }
+        Ok(AlmacenSqlite { conexion })
+    }
+
+    // Copia los libros y prestamos de otro almacen, por ejemplo para pasar de los archivos
+    // JSON a la base. Todo o nada.
+    pub fn importar(&mut self, origen: &dyn AlmacenBiblioteca) -> Result<(), PersistenciaError> {
+        let transaccion = self.conexion.transaction()?;
+        for registro in origen.libros()? {
+            insertar_libro(&transaccion, &registro)?;
+        }
+        for prestamo in origen.prestamos()? {
+            insertar_prestamo(&transaccion, &prestamo)?;
+        }
+        transaccion.commit()?;
+        Ok(())
+    }
+
+    fn consultar_prestamos(
+        &self,
+        filtro: &str,
+        parametros: impl rusqlite::Params,
+    ) -> Result<Vec<Prestamo>, PersistenciaError> {
+        let sql = format!("{} {} ORDER BY p.id", SELECT_PRESTAMOS, filtro);
+        let mut consulta = self.conexion.prepare_cached(&sql)?;
+        let prestamos = consulta
+            .query_map(parametros, prestamo)?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(prestamos)
+    }
+}
+
+// Si el libro ya estaba se mantiene el primer registro, igual que en AlmacenJson.
+fn insertar_libro(conexion: &Connection, registro: &RegistroDisponible) -> rusqlite::Result<()> {
+    let libro = &registro.libro;
+    conexion.execute(
+        "INSERT OR IGNORE INTO libros (isbn, titulo, autor, cant_paginas, genero)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            libro.isbn,
+            libro.titulo,
+            libro.autor,
+            libro.cant_paginas,
+            libro.genero
+        ],
+    )?;
+    conexion.execute(
+        "INSERT OR IGNORE INTO ejemplares_disponibles (isbn, cantidad) VALUES (?1, ?2)",
+        params![libro.isbn, registro.cant_disponibles],
+    )?;
+    Ok(())
+}
+
+fn insertar_prestamo(conexion: &Connection, prestamo: &Prestamo) -> rusqlite::Result<()> {
+    let (libro, cliente) = (&prestamo.libro, &prestamo.cliente);
+    conexion.execute(
+        "INSERT OR IGNORE INTO libros (isbn, titulo, autor, cant_paginas, genero)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            libro.isbn,
+            libro.titulo,
+            libro.autor,
+            libro.cant_paginas,
+            libro.genero
+        ],
+    )?;
+    conexion.execute(
+        "INSERT INTO clientes (email, nombre, telefono) VALUES (?1, ?2, ?3)
+         ON CONFLICT (email) DO UPDATE SET nombre = excluded.nombre, telefono = excluded.telefono",
+        params![cliente.email, cliente.nombre, cliente.telefono],
+    )?;
+    conexion.execute(
+        "INSERT INTO prestamos
+         (isbn, email, fecha_prestamo, fecha_vencimiento, fecha_devolucion, estado)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            libro.isbn,
+            cliente.email,
+            prestamo.fecha_prestamo,
+            prestamo.fecha_vencimiento,
+            prestamo.fecha_devolucion,
+            prestamo.estado
+        ],
+    )?;
+    Ok(())
+}
+
+fn prestamo(fila: &Row) -> rusqlite::Result<Prestamo> {
+    Ok(Prestamo {
+        libro: Libro {
+            isbn: fila.get(0)?,
+            titulo: fila.get(1)?,
+            autor: fila.get(2)?,
+            cant_paginas: fila.get(3)?,
+            genero: fila.get(4)?,
+        },
+        cliente: Cliente {
+            nombre: fila.get(5)?,
+            telefono: fila.get(6)?,
+            email: fila.get(7)?,
+        },
+        fecha_prestamo: fila.get(8)?,
+        fecha_vencimiento: fila.get(9)?,
+        fecha_devolucion: fila.get(10)?,
+        estado: fila.get(11)?,
+    })
+}
+
+impl AlmacenBiblioteca for AlmacenSqlite {
+    fn libros(&self) -> Result<Vec<RegistroDisponible>, PersistenciaError> {
+        let mut consulta = self.conexion.prepare_cached(
+            "SELECT l.isbn, l.titulo, l.autor, l.cant_paginas, l.genero, d.cantidad
+             FROM ejemplares_disponibles d JOIN libros l ON l.isbn = d.isbn
+             ORDER BY d.rowid",
+        )?;
+        let libros = consulta
+            .query_map([], |fila| {
+                Ok(RegistroDisponible {
+                    libro: Libro {
+                        isbn: fila.get(0)?,
+                        titulo: fila.get(1)?,
+                        autor: fila.get(2)?,
+                        cant_paginas: fila.get(3)?,
+                        genero: fila.get(4)?,
+                    },
+                    cant_disponibles: fila.get(5)?,
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(libros)
+    }
+
+    fn prestamos(&self) -> Result<Vec<Prestamo>, PersistenciaError> {
+        self.consultar_prestamos("", [])
+    }
+
+    fn agregar_libro(&mut self, registro: &RegistroDisponible) -> Result<(), PersistenciaError> {
+        self.agregar_libros(std::slice::from_ref(registro))
+    }
+
+    fn agregar_libros(
+        &mut self,
+        registros: &[RegistroDisponible],
+    ) -> Result<(), PersistenciaError> {
+        let transaccion = self.conexion.transaction()?;
+        for registro in registros {
+            insertar_libro(&transaccion, registro)?;
+        }
+        transaccion.commit()?;
+        Ok(())
+    }
+
+    fn cant_disponibles(&self, lib: &Libro) -> Result<u8, PersistenciaError> {
+        let cantidad = self
+            .conexion
+            .prepare_cached("SELECT cantidad FROM ejemplares_disponibles WHERE isbn = ?1")?
+            .query_row([&lib.isbn], |fila| fila.get(0))
+            .optional()?;
+        Ok(cantidad.unwrap_or(0))
+    }
+
+    fn cambiar_disponibles(&mut self, lib: &Libro, cant: u8) -> Result<(), PersistenciaError> {
+        self.conexion.execute(
+            "UPDATE ejemplares_disponibles SET cantidad = ?2 WHERE isbn = ?1",
+            params![lib.isbn, cant],
+        )?;
+        Ok(())
+    }
+
+    fn agregar_prestamo(&mut self, prestamo: &Prestamo) -> Result<(), PersistenciaError> {
+        self.agregar_prestamos(std::slice::from_ref(prestamo))
+    }
+
+    fn agregar_prestamos(&mut self, prestamos: &[Prestamo]) -> Result<(), PersistenciaError> {
+        let transaccion = self.conexion.transaction()?;
+        for prestamo in prestamos {
+            insertar_prestamo(&transaccion, prestamo)?;
+        }
+        transaccion.commit()?;
+        Ok(())
+    }
+
+    // En una sola transaccion, asi no hace falta deshacer nada.
+    fn registrar_prestamo(&mut self, prestamo: &Prestamo) -> Result<(), PersistenciaError> {
+        let transaccion = self.conexion.transaction()?;
+        insertar_prestamo(&transaccion, prestamo)?;
+        transaccion.execute(
+            "UPDATE ejemplares_disponibles SET cantidad = MAX(cantidad - 1, 0) WHERE isbn = ?1",
+            [&prestamo.libro.isbn],
+        )?;
+        transaccion.commit()?;
+        Ok(())
+    }
+
+    fn deshacer_prestamo(&mut self, prestamo: &Prestamo) -> Result<(), PersistenciaError> {
+        self.conexion.execute(
+            "DELETE FROM prestamos
+             WHERE id = (SELECT MAX(id) FROM prestamos) AND isbn = ?1 AND email = ?2",
+            params![prestamo.libro.isbn, prestamo.cliente.email],
+        )?;
+        Ok(())
+    }
+
+    fn buscar_prestamo(
+        &self,
+        lib: &Libro,
+        cli: &Cliente,
+    ) -> Result<Option<Prestamo>, PersistenciaError> {
+        let prestamos = self.consultar_prestamos(
+            "WHERE p.isbn = ?1 AND p.email = ?2",
+            [&lib.isbn, &cli.email],
+        )?;
+        Ok(prestamos.into_iter().next())
+    }
+
+    fn cant_prestamos_cli(&self, cli: &Cliente) -> Result<u8, PersistenciaError> {
+        let cantidad: i64 = self
+            .conexion
+            .prepare_cached("SELECT COUNT(*) FROM prestamos WHERE email = ?1 AND estado = ?2")?
+            .query_row(params![cli.email, Estado::EnPrestamo], |fila| fila.get(0))?;
+        Ok(u8::try_from(cantidad).unwrap_or(u8::MAX))
+    }
+
+    fn prestamos_que_vencen_hasta(
+        &self,
+        fecha: &Fecha,
+    ) -> Result<Vec<Prestamo>, PersistenciaError> {
+        self.consultar_prestamos("WHERE p.fecha_vencimiento <= ?1", [fecha])
+    }
+
+    fn devolver(
+        &mut self,
+        lib: &Libro,
+        cli: &Cliente,
+        fecha: &Fecha,
+    ) -> Result<bool, PersistenciaError> {
+        let cambiados = self.conexion.execute(
+            "UPDATE prestamos SET estado = ?3, fecha_devolucion = ?4
+             WHERE isbn = ?1 AND email = ?2",
+            params![lib.isbn, cli.email, Estado::Devuelto, fecha],
+        )?;
+        Ok(cambiados > 0)
+    }
+
+    fn registrar_devolucion(
+        &mut self,
+        lib: &Libro,
+        cli: &Cliente,
+        fecha: &Fecha,
+    ) -> Result<bool, PersistenciaError> {
+        let transaccion = self.conexion.transaction()?;
+        let cambiados = transaccion.execute(
+            "UPDATE prestamos SET estado = ?3, fecha_devolucion = ?4
+             WHERE isbn = ?1 AND email = ?2",
+            params![lib.isbn, cli.email, Estado::Devuelto, fecha],
+        )?;
+        if cambiados == 0 {
+            return Ok(false);
+        }
+        transaccion.execute(
+            "UPDATE ejemplares_disponibles SET cantidad = MIN(cantidad + 1, 255) WHERE isbn = ?1",
+            [&lib.isbn],
+        )?;
+        transaccion.commit()?;
+        Ok(true)
+    }
+
+    fn deshacer_devolucion(
+        &mut self,
+        lib: &Libro,
+        cli: &Cliente,
+        anteriores: &[Prestamo],
+    ) -> Result<(), PersistenciaError> {
+        let transaccion = self.conexion.transaction()?;
+        let ids = transaccion
+            .prepare("SELECT id FROM prestamos WHERE isbn = ?1 AND email = ?2 ORDER BY id")?
+            .query_map([&lib.isbn, &cli.email], |fila| fila.get::<_, i64>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        for (id, anterior) in ids.into_iter().zip(anteriores) {
+            transaccion.execute(
+                "UPDATE prestamos SET estado = ?2, fecha_devolucion = ?3 WHERE id = ?1",
+                params![id, anterior.estado, anterior.fecha_devolucion],
+            )?;
+        }
+        transaccion.commit()?;
+        Ok(())
+    }
+
+    fn quitar_prestamos(&mut self, lib: &Libro, cli: &Cliente) -> Result<(), PersistenciaError> {
+        self.conexion.execute(
+            "DELETE FROM prestamos WHERE isbn = ?1 AND email = ?2",
+            params![lib.isbn, cli.email],
+        )?;
+        Ok(())
+    }
+}
+
+fn texto_invalido(texto: &str) -> FromSqlError {
+    FromSqlError::Other(format!("valor desconocido: {}", texto).into())
+}
+
+// Las fechas van en ISO (aaaa-mm-dd) para que comparar el texto sea comparar las fechas.
+impl ToSql for Fecha {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(self.formatear(Fecha::FORMATO_ISO).into())
+    }
+}
+
+impl FromSql for Fecha {
+    fn column_result(valor: ValueRef<'_>) -> FromSqlResult<Self> {
+        valor
+            .as_str()?
+            .parse()
+            .map_err(|e: crate::tp03::ej03::FechaError| FromSqlError::Other(Box::new(e)))
+    }
+}
+
+impl ToSql for FechaHora {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(self.a_rfc3339().into())
+    }
+}
+
+impl FromSql for FechaHora {
+    fn column_result(valor: ValueRef<'_>) -> FromSqlResult<Self> {
+        valor
+            .as_str()?
+            .parse()
+            .map_err(|e: crate::tp03::ej03::FechaError| FromSqlError::Other(Box::new(e)))
+    }
+}
+
+impl ToSql for Genero {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        let texto = match self {
+            Genero::Novela => "Novela",
+            Genero::Infantil => "Infantil",
+            Genero::Tecnico => "Tecnico",
+            Genero::Otros => "Otros",
+        };
+        Ok(texto.into())
+    }
+}
+
+impl FromSql for Genero {
+    fn column_result(valor: ValueRef<'_>) -> FromSqlResult<Self> {
+        match valor.as_str()? {
+            "Novela" => Ok(Genero::Novela),
+            "Infantil" => Ok(Genero::Infantil),
+            "Tecnico" => Ok(Genero::Tecnico),
+            "Otros" => Ok(Genero::Otros),
+            otro => Err(texto_invalido(otro)),
+        }
+    }
+}
+
+impl ToSql for Estado {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(self.a_str().into())
+    }
+}
+
+impl FromSql for Estado {
+    fn column_result(valor: ValueRef<'_>) -> FromSqlResult<Self> {
+        match valor.as_str()? {
+            "Devuelto" => Ok(Estado::Devuelto),
+            "EnPrestamo" => Ok(Estado::EnPrestamo),
+            otro => Err(texto_invalido(otro)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tp03::reloj::{RelojCompartido, RelojManual};
+    use crate::tp05::ej04::{AlmacenJson, Biblioteca, BibliotecaError};
+    use crate::tp05::pruebas::directorio_temporal;
+    use crate::tp05::repositorio::RepositorioCompartido;
+    use std::rc::Rc;
+
+    fn libro(isbn: &str) -> Libro {
+        Libro::new(
+            isbn.to_string(),
+            format!("Libro {}", isbn),
+            "Autor".to_string(),
+            100,
+            Genero::Tecnico,
+        )
+    }
+
+    fn cliente(email: &str) -> Cliente {
+        Cliente::new("Nombre".to_string(), "123".to_string(), email.to_string())
+    }
+
+    // Resumen comparable de todo lo que la biblioteca puede consultar.
+    fn estado(b: &Biblioteca) -> String {
+        serde_json::json!({
+            "libros": b.almacen.libros().unwrap(),
+            "prestamos": b.almacen.prestamos().unwrap(),
+            "vencidos": b.prestamos_vencidos().unwrap(),
+            "a_vencer": b.prestamos_a_vencer(5).unwrap(),
+            "cant_a": b.cant_prestamos_cli(&cliente("a@a.com")).unwrap(),
+            "disponibles_1": b.cant_disponibles(&libro("1")).unwrap(),
+            "buscar": b.buscar_prestamo(&libro("2"), &cliente("b@b.com")).unwrap(),
+        })
+        .to_string()
+    }
+
+    fn operar(almacen: impl AlmacenBiblioteca + 'static) -> Biblioteca {
+        let reloj = Rc::new(RelojManual::new(Fecha::new(1, 7, 2025)).unwrap());
+        let mut b = Biblioteca::new("B".to_string(), "C".to_string())
+            .con_almacen(almacen)
+            .con_reloj(RelojCompartido::from(reloj.clone()));
+        b.agregar_libro(&libro("1"), 2).unwrap();
+        b.agregar_libro(&libro("2"), 1).unwrap();
+        b.prestar(&cliente("a@a.com"), &libro("1"), &Fecha::new(3, 7, 2025))
+            .unwrap();
+        b.prestar(&cliente("b@b.com"), &libro("2"), &Fecha::new(20, 7, 2025))
+            .unwrap();
+        assert!(matches!(
+            b.prestar(&cliente("a@a.com"), &libro("2"), &Fecha::new(5, 7, 2025)),
+            Err(BibliotecaError::SinEjemplares(_))
+        ));
+        reloj.avanzar_dias(4);
+        b.prestar(&cliente("a@a.com"), &libro("1"), &Fecha::new(10, 7, 2025))
+            .unwrap();
+        assert!(b.devolver_libro(&libro("1"), &cliente("a@a.com")).unwrap());
+        assert!(!b.devolver_libro(&libro("9"), &cliente("a@a.com")).unwrap());
+        b.prestar(&cliente("a@a.com"), &libro("1"), &Fecha::new(8, 7, 2025))
+            .unwrap();
+        b.quitar_prestamo(&cliente("b@b.com"), &libro("9")).unwrap();
+        b
+    }
+
+    #[test]
+    fn test_mismos_resultados_que_json() {
+        let json = operar(AlmacenJson::new(
+            RepositorioCompartido::memoria(),
+            RepositorioCompartido::memoria(),
+        ));
+        let sqlite = operar(AlmacenSqlite::en_memoria().unwrap());
+        assert_eq!(estado(&sqlite), estado(&json));
+        assert_eq!(sqlite.prestamos_vencidos().unwrap().len(), 1);
+        assert_eq!(sqlite.cant_prestamos_cli(&cliente("a@a.com")).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_prestar_en_una_transaccion() {
+        let mut almacen = AlmacenSqlite::en_memoria().unwrap();
+        almacen
+            .agregar_libro(&RegistroDisponible::new(libro("1"), 2))
+            .unwrap();
+        // falla el descuento del ejemplar, despues de insertar el prestamo
+        almacen
+            .conexion
+            .execute_batch(
+                "CREATE TRIGGER sin_stock BEFORE UPDATE ON ejemplares_disponibles
+                 BEGIN SELECT RAISE(ABORT, 'sin stock'); END;",
+            )
+            .unwrap();
+        let mut b = Biblioteca::new("B".to_string(), "C".to_string()).con_almacen(almacen);
+
+        assert!(
+            b.prestar(&cliente("a@a.com"), &libro("1"), &Fecha::new(3, 7, 2025))
+                .is_err()
+        );
+        assert!(b.almacen.prestamos().unwrap().is_empty());
+        assert_eq!(b.cant_disponibles(&libro("1")).unwrap(), 2);
+    }
+
+    #[test]
+    fn test_devolver_en_una_transaccion() {
+        let mut almacen = AlmacenSqlite::en_memoria().unwrap();
+        almacen
+            .agregar_libro(&RegistroDisponible::new(libro("1"), 2))
+            .unwrap();
+        let prestamo = Prestamo::new(
+            &libro("1"),
+            &cliente("a@a.com"),
+            &FechaHora::try_from(&Fecha::new(1, 7, 2025)).unwrap(),
+            &Fecha::new(3, 7, 2025),
+        );
+        almacen.registrar_prestamo(&prestamo).unwrap();
+
+        // deshacer_devolucion deja los prestamos como estaban
+        let hoy = Fecha::new(2, 7, 2025);
+        assert!(
+            almacen
+                .devolver(&libro("1"), &cliente("a@a.com"), &hoy)
+                .unwrap()
+        );
+        almacen
+            .deshacer_devolucion(
+                &libro("1"),
+                &cliente("a@a.com"),
+                std::slice::from_ref(&prestamo),
+            )
+            .unwrap();
+        assert_eq!(almacen.cant_prestamos_cli(&cliente("a@a.com")).unwrap(), 1);
+
+        // falla sumar el ejemplar, despues de marcar la devolucion
+        almacen
+            .conexion
+            .execute_batch(
+                "CREATE TRIGGER sin_stock BEFORE UPDATE ON ejemplares_disponibles
+                 BEGIN SELECT RAISE(ABORT, 'sin stock'); END;",
+            )
+            .unwrap();
+        let mut b = Biblioteca::new("B".to_string(), "C".to_string()).con_almacen(almacen);
+        assert!(b.devolver_libro(&libro("1"), &cliente("a@a.com")).is_err());
+        assert_eq!(b.cant_prestamos_cli(&cliente("a@a.com")).unwrap(), 1);
+        assert_eq!(b.cant_disponibles(&libro("1")).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_reabrir_la_base() {
+        let dir = directorio_temporal("sqlite");
+        let ruta = dir.join("biblioteca.sqlite");
+
+        let guardado = estado(&operar(AlmacenSqlite::abrir(&ruta).unwrap()));
+        let reabierta = Biblioteca::new("B".to_string(), "C".to_string())
+            .con_almacen(AlmacenSqlite::abrir(&ruta).unwrap())
+            .con_reloj(RelojCompartido::from(Rc::new(
+                RelojManual::new(Fecha::new(5, 7, 2025)).unwrap(),
+            )));
+        assert_eq!(estado(&reabierta), guardado);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_importar_desde_json() {
+        let json = operar(AlmacenJson::new(
+            RepositorioCompartido::memoria(),
+            RepositorioCompartido::memoria(),
+        ));
+        let mut almacen = AlmacenSqlite::en_memoria().unwrap();
+        almacen.importar(&*json.almacen).unwrap();
+        let sqlite = Biblioteca::new("B".to_string(), "C".to_string())
+            .con_almacen(almacen)
+            .con_reloj(RelojCompartido::from(Rc::new(
+                RelojManual::new(Fecha::new(5, 7, 2025)).unwrap(),
+            )));
+        assert_eq!(estado(&sqlite), estado(&json));
+    }
+
+    #[test]
+    fn test_consultas_usan_indices() {
+        let almacen = AlmacenSqlite::en_memoria().unwrap();
+        let plan = |sql: &str| -> String {
+            let mut consulta = almacen
+                .conexion
+                .prepare(&format!("EXPLAIN QUERY PLAN {}", sql))
+                .unwrap();
+            consulta
+                .query_map([], |fila| fila.get::<_, String>(3))
+                .unwrap()
+                .map(Result::unwrap)
+                .collect()
+        };
+        assert!(
+            plan("SELECT COUNT(*) FROM prestamos WHERE email = 'a' AND estado = 'EnPrestamo'")
+                .contains("prestamos_por_cliente")
+        );
+        assert!(
+            plan(&format!(
+                "{} WHERE p.fecha_vencimiento <= '2025-07-01'",
+                SELECT_PRESTAMOS
+            ))
+            .contains("prestamos_por_vencimiento")
+        );
+    }
+
+    #[test]
+    fn test_version_desconocida() {
+        let conexion = Connection::open_in_memory().unwrap();
+        conexion.pragma_update(None, "user_version", 7).unwrap();
+        assert!(matches!(
+            AlmacenSqlite::preparar(conexion),
+            Err(PersistenciaError::VersionDesconocida {
+                version: 7,
+                soportada: 1
+            })
+        ));
+    }
+}
@@ -1,17 +1,35 @@
 #![allow(dead_code, unused_variables)]
-use std::collections::HashMap;
-use std::fs::File;
-use std::io::Write;
+use std::collections::{HashMap, HashSet};
+use std::io;
 
 use serde::{Deserialize, Serialize};
-use serde_json;
 
 use crate::tp03::ej03::Fecha;
+use crate::tp03::recurrencia::{FinRecurrencia, Recurrencia};
+use crate::tp03::reloj::{Reloj, RelojCompartido};
+
+use crate::tp05::esquema::Esquema;
+use crate::tp05::repositorio::{
+    DIRECTORIO_REGISTROS, PersistenciaError, Repositorio, RepositorioCompartido,
+};
+use crate::tp05::tabla::{self, ErrorCsv, ErrorFila, Fila, MapeoColumnas, RegistroCsv};
 
 #[derive(Serialize, Deserialize, PartialEq, Clone)]
 pub struct StreamingRust {
     usuarios_activos: Vec<Usuario>,
     usuarios_cancelados: Vec<Usuario>,
+    #[serde(skip)]
+    reloj: RelojCompartido,
+    #[serde(skip, default = "repositorio_por_defecto")]
+    repositorio: RepositorioCompartido<StreamingRust>,
+}
+
+fn repositorio_por_defecto() -> RepositorioCompartido<StreamingRust> {
+    RepositorioCompartido::json(
+        DIRECTORIO_REGISTROS,
+        "ej05/streaming_rust.json",
+        Esquema::new(),
+    )
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Clone)]
@@ -83,6 +101,7 @@ impl Subscripcion {
     pub fn new(
         costo_mensual: f32,
         duracion: u8,
+        fecha_inicio: Fecha,
         metodo_pago: MetodoPago,
         tipo_subscripcion: TipoSubscripcion,
         usr_email: String,
@@ -90,12 +109,27 @@ impl Subscripcion {
         Subscripcion {
             costo_mensual,
             duracion,
-            fecha_inicio: Fecha::fecha_actual(),
+            fecha_inicio,
             metodo_pago,
             tipo_subscripcion,
             usr_email,
         }
     }
+
+    // Una renovacion mensual por cada mes contratado, empezando en la fecha de inicio.
+    pub fn renovaciones(&self) -> Recurrencia {
+        Recurrencia::cada_n_meses(
+            self.fecha_inicio.clone(),
+            1,
+            FinRecurrencia::Cantidad(self.duracion as u32),
+        )
+    }
+
+    pub fn fecha_vencimiento(&self) -> Fecha {
+        let mut vencimiento = self.fecha_inicio.clone();
+        vencimiento.sumar_meses(self.duracion as i32);
+        vencimiento
+    }
 }
 
 impl StreamingRust {
@@ -103,18 +137,82 @@ impl StreamingRust {
         StreamingRust {
             usuarios_activos: vec![],
             usuarios_cancelados: vec![],
+            reloj: RelojCompartido::default(),
+            repositorio: repositorio_por_defecto(),
         }
     }
 
-    pub fn persistir(&self) {
-        let mut f = File::create("src/tp05/registros/ej05/streaming_rust.json").unwrap();
-        let registros_serializado = serde_json::to_string_pretty(&self).unwrap();
-        f.write_all(registros_serializado.as_bytes()).unwrap();
+    // Restaura el sistema guardado en el repositorio. Si todavia no hay nada guardado arranca
+    // vacio y lo guarda, para dejar el archivo inicializado.
+    pub fn abrir(
+        repositorio: RepositorioCompartido<StreamingRust>,
+    ) -> Result<Self, PersistenciaError> {
+        StreamingRust::new()
+            .con_repositorio(repositorio)
+            .restaurar()
     }
 
-    pub fn crear_usr(&mut self, usuario: &Usuario) {
+    // Igual que abrir pero con el archivo por defecto en DIRECTORIO_REGISTROS.
+    pub fn cargar() -> Result<Self, PersistenciaError> {
+        StreamingRust::new().restaurar()
+    }
+
+    fn restaurar(mut self) -> Result<Self, PersistenciaError> {
+        match self.repositorio.cargar()? {
+            Some(guardado) => {
+                self.usuarios_activos = guardado.usuarios_activos;
+                self.usuarios_cancelados = guardado.usuarios_cancelados;
+            }
+            None => self.repositorio.guardar(&self)?,
+        }
+        Ok(self)
+    }
+
+    pub fn con_repositorio(mut self, repositorio: RepositorioCompartido<StreamingRust>) -> Self {
+        self.repositorio = repositorio;
+        self
+    }
+
+    pub fn con_reloj(mut self, reloj: RelojCompartido) -> Self {
+        self.reloj = reloj;
+        self
+    }
+
+    // Igual que Subscripcion::new pero la fecha de inicio sale del reloj de la plataforma.
+    pub fn nueva_subscripcion(
+        &self,
+        costo_mensual: f32,
+        duracion: u8,
+        metodo_pago: MetodoPago,
+        tipo_subscripcion: TipoSubscripcion,
+        usr_email: String,
+    ) -> Subscripcion {
+        Subscripcion::new(
+            costo_mensual,
+            duracion,
+            self.reloj.hoy(),
+            metodo_pago,
+            tipo_subscripcion,
+            usr_email,
+        )
+    }
+
+    pub fn subscripciones_vencidas(&self) -> Vec<&Subscripcion> {
+        let hoy = self.reloj.hoy();
+        self.usuarios_activos
+            .iter()
+            .flat_map(|u| u.subscripciones.iter())
+            .filter(|s| s.fecha_vencimiento() <= hoy)
+            .collect()
+    }
+
+    pub fn persistir(&self) -> Result<(), PersistenciaError> {
+        self.repositorio.guardar(self)
+    }
+
+    pub fn crear_usr(&mut self, usuario: &Usuario) -> Result<(), PersistenciaError> {
         self.usuarios_activos.push(usuario.clone());
-        self.persistir();
+        self.persistir()
     }
 
     fn buscar_usuario(&mut self, email: &str) -> Option<&mut Usuario> {
@@ -125,52 +223,138 @@ impl StreamingRust {
         self.usuarios_activos.iter().position(|u| u.email == email)
     }
 
-    pub fn upgrade_subscripcion(&mut self, email: &str, sub_index: usize) -> bool {
+    pub fn upgrade_subscripcion(
+        &mut self,
+        email: &str,
+        sub_index: usize,
+    ) -> Result<bool, PersistenciaError> {
         if let Some(user) = self.buscar_usuario(email) {
             if sub_index < user.subscripciones.len() {
                 user.subscripciones[sub_index].tipo_subscripcion.upgrade();
-                self.persistir();
-                return true;
+                self.persistir()?;
+                return Ok(true);
             }
         }
-        false
+        Ok(false)
     }
 
-    pub fn cancelar_subscripcion(&mut self, email: &str, sub_index: usize) -> bool {
+    pub fn cancelar_subscripcion(
+        &mut self,
+        email: &str,
+        sub_index: usize,
+    ) -> Result<bool, PersistenciaError> {
         if let Some(user) = self.buscar_usuario(email) {
             if sub_index < user.subscripciones.len() {
                 if user.subscripciones.len() == 1 {
                     if let Some(i) = self.buscar_usuario_i(email) {
                         let usuario_cancelado = self.usuarios_activos.swap_remove(i);
                         self.usuarios_cancelados.push(usuario_cancelado);
-                        self.persistir();
-                        return true;
+                        self.persistir()?;
+                        return Ok(true);
                     }
                 } else {
                     user.subscripciones.remove(sub_index);
-                    self.persistir();
-                    return true;
+                    self.persistir()?;
+                    return Ok(true);
                 }
             }
         }
-        false
+        Ok(false)
     }
 
-    pub fn downgrade_subscripcion(&mut self, email: &str, sub_index: usize) -> bool {
+    pub fn downgrade_subscripcion(
+        &mut self,
+        email: &str,
+        sub_index: usize,
+    ) -> Result<bool, PersistenciaError> {
         if let Some(user) = self.buscar_usuario(email) {
             if sub_index < user.subscripciones.len() {
                 if user.subscripciones[sub_index].tipo_subscripcion == TipoSubscripcion::Basic {
-                    let result = self.cancelar_subscripcion(email, sub_index);
-                    self.persistir();
-                    return result;
+                    return self.cancelar_subscripcion(email, sub_index);
                 } else {
                     user.subscripciones[sub_index].tipo_subscripcion.downgrade();
-                    self.persistir();
-                    return true;
+                    self.persistir()?;
+                    return Ok(true);
                 }
             }
         }
-        false
+        Ok(false)
+    }
+
+    // Solo los usuarios activos; los cancelados quedan en el JSON como historial.
+    pub fn exportar_usuarios_csv(&self, escritor: impl io::Write) -> Result<(), ErrorCsv> {
+        tabla::exportar(&self.usuarios_activos, escritor)
+    }
+
+    pub fn exportar_subscripciones_csv(&self, escritor: impl io::Write) -> Result<(), ErrorCsv> {
+        let subscripciones = self
+            .usuarios_activos
+            .iter()
+            .flat_map(|u| u.subscripciones.iter());
+        tabla::exportar(subscripciones, escritor)
+    }
+
+    // Da de alta los usuarios sin subscripciones. Si algun email ya esta registrado, o se
+    // repite en el archivo, no agrega ninguno.
+    pub fn importar_usuarios_csv(
+        &mut self,
+        lector: impl io::Read,
+        mapeo: &MapeoColumnas,
+    ) -> Result<usize, ErrorCsv> {
+        let filas: Vec<(u64, Usuario)> = tabla::importar_con_lineas(lector, mapeo)?;
+        let mut emails: HashSet<String> = self
+            .usuarios_activos
+            .iter()
+            .chain(&self.usuarios_cancelados)
+            .map(|u| u.email.clone())
+            .collect();
+        let errores: Vec<ErrorFila> = filas
+            .iter()
+            .filter(|(_, usuario)| !emails.insert(usuario.email.clone()))
+            .map(|(linea, usuario)| ErrorFila {
+                linea: *linea,
+                columna: Some("email".to_string()),
+                detalle: format!("{} ya esta registrado", usuario.email),
+            })
+            .collect();
+        if !errores.is_empty() {
+            return Err(ErrorCsv::Filas(errores));
+        }
+        let cantidad = filas.len();
+        self.usuarios_activos
+            .extend(filas.into_iter().map(|(_, usuario)| usuario));
+        self.persistir()?;
+        Ok(cantidad)
+    }
+
+    // Cada subscripcion se agrega al usuario activo de su usr_email.
+    pub fn importar_subscripciones_csv(
+        &mut self,
+        lector: impl io::Read,
+        mapeo: &MapeoColumnas,
+    ) -> Result<usize, ErrorCsv> {
+        let filas: Vec<(u64, Subscripcion)> = tabla::importar_con_lineas(lector, mapeo)?;
+        let mut errores = vec![];
+        let mut destinos = vec![];
+        for (linea, subscripcion) in &filas {
+            match self.buscar_usuario_i(&subscripcion.usr_email) {
+                Some(i) => destinos.push(i),
+                None => errores.push(ErrorFila {
+                    linea: *linea,
+                    columna: Some("usr_email".to_string()),
+                    detalle: format!("no hay un usuario activo {}", subscripcion.usr_email),
+                }),
+            }
+        }
+        if !errores.is_empty() {
+            return Err(ErrorCsv::Filas(errores));
+        }
+        let cantidad = filas.len();
+        for (i, (_, subscripcion)) in destinos.into_iter().zip(filas) {
+            self.usuarios_activos[i].add_subscripcion(subscripcion);
+        }
+        self.persistir()?;
+        Ok(cantidad)
     }
 
     fn metodo_mas_utilizado_generico(coleccion: &[Usuario]) -> Option<MetodoPago> {
@@ -236,9 +420,123 @@ impl StreamingRust {
     }
 }
 
+impl RegistroCsv for Usuario {
+    const COLUMNAS: &'static [&'static str] = &["nombre", "email"];
+
+    fn a_fila(&self) -> Vec<String> {
+        vec![self.nombre.clone(), self.email.clone()]
+    }
+
+    fn de_fila(fila: &Fila) -> Result<Self, ErrorFila> {
+        let email = fila.texto("email")?;
+        if !email.contains('@') {
+            return Err(fila.error_en("email", format!("'{}' no es un email", email)));
+        }
+        Ok(Usuario::new(fila.texto("nombre")?, email))
+    }
+}
+
+const METODOS_PAGO: &[&str] = &[
+    "Efectivo",
+    "MercadoPago",
+    "TarjetaCredito",
+    "Transferencia",
+    "Cripto",
+];
+
+// El metodo de pago ocupa una columna con la variante y una por cada dato de las variantes;
+// solo se completan las de la variante elegida.
+impl RegistroCsv for Subscripcion {
+    const COLUMNAS: &'static [&'static str] = &[
+        "usr_email",
+        "costo_mensual",
+        "duracion",
+        "fecha_inicio",
+        "tipo_subscripcion",
+        "metodo_pago",
+        "alias_mp",
+        "num_tarjeta",
+        "cbu",
+        "direccion",
+        "moneda",
+    ];
+    const OPCIONALES: &'static [&'static str] =
+        &["alias_mp", "num_tarjeta", "cbu", "direccion", "moneda"];
+
+    fn a_fila(&self) -> Vec<String> {
+        let mut fila = vec![
+            self.usr_email.clone(),
+            self.costo_mensual.to_string(),
+            self.duracion.to_string(),
+            tabla::fecha(&self.fecha_inicio),
+            tabla::variante(&self.tipo_subscripcion),
+        ];
+        let mut datos = vec![String::new(); 5];
+        let nombre = match &self.metodo_pago {
+            MetodoPago::Efectivo => "Efectivo",
+            MetodoPago::MercadoPago { alias_mp } => {
+                datos[0] = alias_mp.clone();
+                "MercadoPago"
+            }
+            MetodoPago::TarjetaCredito { num_tarjeta } => {
+                datos[1] = num_tarjeta.clone();
+                "TarjetaCredito"
+            }
+            MetodoPago::Transferencia { cbu } => {
+                datos[2] = cbu.clone();
+                "Transferencia"
+            }
+            MetodoPago::Cripto { direccion, moneda } => {
+                datos[3] = direccion.clone();
+                datos[4] = moneda.clone();
+                "Cripto"
+            }
+        };
+        fila.push(nombre.to_string());
+        fila.extend(datos);
+        fila
+    }
+
+    fn de_fila(fila: &Fila) -> Result<Self, ErrorFila> {
+        let metodo_pago = match fila.nombre_variante("metodo_pago", METODOS_PAGO)? {
+            "Efectivo" => MetodoPago::Efectivo,
+            "MercadoPago" => MetodoPago::MercadoPago {
+                alias_mp: fila.texto("alias_mp")?,
+            },
+            "TarjetaCredito" => MetodoPago::TarjetaCredito {
+                num_tarjeta: fila.texto("num_tarjeta")?,
+            },
+            "Transferencia" => MetodoPago::Transferencia {
+                cbu: fila.texto("cbu")?,
+            },
+            _ => MetodoPago::Cripto {
+                direccion: fila.texto("direccion")?,
+                moneda: fila.texto("moneda")?,
+            },
+        };
+        let costo_mensual: f32 = fila.valor("costo_mensual")?;
+        if !costo_mensual.is_finite() || costo_mensual < 0.0 {
+            return Err(fila.error_en("costo_mensual", "no puede ser negativo"));
+        }
+        Ok(Subscripcion::new(
+            costo_mensual,
+            fila.valor("duracion")?,
+            fila.valor("fecha_inicio")?,
+            metodo_pago,
+            fila.variante("tipo_subscripcion")?,
+            fila.texto("usr_email")?,
+        ))
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::tp03::reloj::RelojManual;
+    use crate::tp05::pruebas::directorio_temporal;
+    use crate::tp05::repositorio::{RepositorioCompartido, RepositorioLleno, RepositorioMemoria};
+    use std::fs;
+    use std::rc::Rc;
 
     struct TestData {
         sistema: StreamingRust,
@@ -246,7 +544,7 @@ mod test {
     }
 
     fn setup() -> TestData {
-        let mut sr = StreamingRust::new();
+        let mut sr = StreamingRust::new().con_repositorio(RepositorioCompartido::memoria());
         let mut u1 = Usuario::new("tao".to_string(), "tao@example.com".to_string());
         let mut u2 = Usuario::new("clasico".to_string(), "clasico@example.com".to_string());
         let mut u3 = Usuario::new(
@@ -260,6 +558,7 @@ mod test {
         let sub_basic_efectivo = Subscripcion::new(
             5000.0,
             12,
+            Fecha::new(1, 3, 2025),
             MetodoPago::Efectivo,
             TipoSubscripcion::Basic,
             "tao@example.com".to_string(),
@@ -267,6 +566,7 @@ mod test {
         let sub_classic_efectivo = Subscripcion::new(
             5000.0,
             12,
+            Fecha::new(1, 3, 2025),
             MetodoPago::Efectivo,
             TipoSubscripcion::Classic,
             "clasico@example.com".to_string(),
@@ -274,6 +574,7 @@ mod test {
         let sub_super_credito = Subscripcion::new(
             5000.0,
             12,
+            Fecha::new(1, 3, 2025),
             MetodoPago::TarjetaCredito {
                 num_tarjeta: "123".to_string(),
             },
@@ -283,6 +584,7 @@ mod test {
         let sub_classic_cripto = Subscripcion::new(
             5000.0,
             12,
+            Fecha::new(1, 3, 2025),
             MetodoPago::Cripto {
                 direccion: "0x1829821".to_string(),
                 moneda: "ETH".to_string(),
@@ -294,21 +596,161 @@ mod test {
         u2.add_subscripcion(sub_classic_efectivo);
         u3.add_subscripcion(sub_super_credito);
         u4.add_subscripcion(sub_classic_cripto);
-        sr.crear_usr(&u1);
-        sr.crear_usr(&u2);
-        sr.crear_usr(&u3);
-        sr.crear_usr(&u4);
+        sr.crear_usr(&u1).unwrap();
+        sr.crear_usr(&u2).unwrap();
+        sr.crear_usr(&u3).unwrap();
+        sr.crear_usr(&u4).unwrap();
         TestData {
             sistema: sr,
             usuarios: vec![u1, u2, u3, u4],
         }
     }
 
+    #[test]
+    fn test_ej05_fecha_vencimiento_sub() {
+        let mut sub = Subscripcion::new(
+            5000.0,
+            1,
+            Fecha::new(31, 1, 2025),
+            MetodoPago::Efectivo,
+            TipoSubscripcion::Basic,
+            "tao@example.com".to_string(),
+        );
+        assert_eq!(sub.fecha_vencimiento(), Fecha::new(28, 2, 2025));
+        sub.duracion = 12;
+        assert_eq!(sub.fecha_vencimiento(), Fecha::new(31, 1, 2026));
+    }
+
+    #[test]
+    fn test_ej05_renovaciones_sub() {
+        let sub = Subscripcion::new(
+            5000.0,
+            3,
+            Fecha::new(31, 12, 2024),
+            MetodoPago::Efectivo,
+            TipoSubscripcion::Basic,
+            "tao@example.com".to_string(),
+        );
+        let renovaciones: Vec<Fecha> = sub.renovaciones().iter().collect();
+        assert_eq!(
+            renovaciones,
+            vec![
+                Fecha::new(31, 12, 2024),
+                Fecha::new(31, 1, 2025),
+                Fecha::new(28, 2, 2025),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ej05_subscripciones_con_reloj_manual() {
+        let reloj = Rc::new(RelojManual::new(Fecha::new(15, 1, 2025)).unwrap());
+        let mut sr = StreamingRust::new()
+            .con_repositorio(RepositorioCompartido::memoria())
+            .con_reloj(RelojCompartido::from(reloj.clone()));
+        let mut u = Usuario::new("tao".to_string(), "tao@example.com".to_string());
+        u.add_subscripcion(sr.nueva_subscripcion(
+            5000.0,
+            1,
+            MetodoPago::Efectivo,
+            TipoSubscripcion::Basic,
+            "tao@example.com".to_string(),
+        ));
+        u.add_subscripcion(sr.nueva_subscripcion(
+            5000.0,
+            6,
+            MetodoPago::Efectivo,
+            TipoSubscripcion::Super,
+            "tao@example.com".to_string(),
+        ));
+        assert_eq!(u.subscripciones[0].fecha_inicio, Fecha::new(15, 1, 2025));
+        sr.crear_usr(&u).unwrap();
+        assert!(sr.subscripciones_vencidas().is_empty());
+
+        reloj.avanzar_meses(1);
+        assert_eq!(sr.subscripciones_vencidas().len(), 1);
+        reloj.avanzar_meses(5);
+        assert_eq!(sr.subscripciones_vencidas().len(), 2);
+    }
+
+    #[test]
+    fn test_ej05_abrir_sin_datos_inicializa() {
+        let repo = Rc::new(RepositorioMemoria::new());
+        let sr = StreamingRust::abrir(RepositorioCompartido::from(repo.clone())).unwrap();
+        assert!(sr.usuarios_activos.is_empty());
+        assert!(repo.contenido().unwrap().contains("usuarios_cancelados"));
+    }
+
+    #[test]
+    fn test_ej05_guardar_cargar_guardar_identico() {
+        let repo = Rc::new(RepositorioMemoria::new());
+        let mut sr = setup()
+            .sistema
+            .con_repositorio(RepositorioCompartido::from(repo.clone()));
+        sr.cancelar_subscripcion("tao@example.com", 0).unwrap();
+        let guardado = repo.contenido().unwrap();
+
+        let restaurado = StreamingRust::abrir(RepositorioCompartido::from(repo.clone())).unwrap();
+        assert!(restaurado == sr);
+        assert_eq!(restaurado.usuarios_cancelados.len(), 1);
+        restaurado.persistir().unwrap();
+        assert_eq!(repo.contenido().unwrap(), guardado);
+    }
+
+    #[test]
+    fn test_ej05_dos_procesos_sobre_el_mismo_archivo() {
+        let dir = directorio_temporal("ej05-procesos");
+        let abrir = || {
+            StreamingRust::abrir(RepositorioCompartido::json(
+                &dir,
+                "streaming_rust.json",
+                Esquema::new(),
+            ))
+            .unwrap()
+        };
+        let mut interactivo = abrir();
+        let mut lote = abrir();
+
+        let tao = Usuario::new("tao".to_string(), "tao@example.com".to_string());
+        lote.crear_usr(&tao).unwrap();
+        // el interactivo no vio al usuario nuevo: guardar lo borraria
+        let otro = Usuario::new("otro".to_string(), "otro@example.com".to_string());
+        assert!(matches!(
+            interactivo.crear_usr(&otro),
+            Err(PersistenciaError::Conflicto(_))
+        ));
+
+        let mut interactivo = abrir();
+        interactivo.crear_usr(&otro).unwrap();
+        assert_eq!(abrir().usuarios_activos.len(), 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_ej05_upgrade_sub_sin_espacio_en_disco() {
+        let mut sr = setup()
+            .sistema
+            .con_repositorio(RepositorioLleno::compartido());
+        assert!(matches!(
+            sr.upgrade_subscripcion("tao@example.com", 0),
+            Err(PersistenciaError::Io(_))
+        ));
+        // sin usuario no hay nada que guardar, asi que no falla
+        assert_eq!(
+            sr.upgrade_subscripcion("nadie@example.com", 0).ok(),
+            Some(false)
+        );
+    }
+
     #[test]
     fn test_ej05_upgrade_sub() {
         let mut data = setup();
-        data.sistema.upgrade_subscripcion("tao@example.com", 0); // basic -> classic
-        data.sistema.upgrade_subscripcion("clasico@example.com", 0); // classic -> super
+        data.sistema
+            .upgrade_subscripcion("tao@example.com", 0)
+            .unwrap(); // basic -> classic
+        data.sistema
+            .upgrade_subscripcion("clasico@example.com", 0)
+            .unwrap(); // classic -> super
         let usuarios_post = data.sistema.usuarios_activos.clone();
         assert_eq!(
             usuarios_post[0].subscripciones[0].tipo_subscripcion,
@@ -324,7 +766,8 @@ mod test {
     fn test_ej05_upgrade_sub_super_stays_super() {
         let mut data = setup();
         data.sistema
-            .upgrade_subscripcion("supercred@example.com", 0); // super -> super
+            .upgrade_subscripcion("supercred@example.com", 0)
+            .unwrap(); // super -> super
         let usuarios_post = data.sistema.usuarios_activos.clone();
         let super_user = usuarios_post
             .iter()
@@ -339,7 +782,10 @@ mod test {
     #[test]
     fn test_ej05_upgrade_sub_invalid_index() {
         let mut data = setup();
-        let result = data.sistema.upgrade_subscripcion("tao@example.com", 99);
+        let result = data
+            .sistema
+            .upgrade_subscripcion("tao@example.com", 99)
+            .unwrap();
         assert!(!result);
     }
     #[test]
@@ -347,7 +793,8 @@ mod test {
         let mut data = setup();
         let result = data
             .sistema
-            .upgrade_subscripcion("nonexistent@example.com", 0);
+            .upgrade_subscripcion("nonexistent@example.com", 0)
+            .unwrap();
         assert!(!result);
     }
 
@@ -355,9 +802,12 @@ mod test {
     fn test_ej05_downgrade_sub() {
         let mut data = setup();
         let usuarios_cancelados_pre = data.sistema.usuarios_cancelados.clone();
-        data.sistema.downgrade_subscripcion("tao@example.com", 0); // basic -> cancelado
         data.sistema
-            .downgrade_subscripcion("clasico@example.com", 0); // classic -> basic
+            .downgrade_subscripcion("tao@example.com", 0)
+            .unwrap(); // basic -> cancelado
+        data.sistema
+            .downgrade_subscripcion("clasico@example.com", 0)
+            .unwrap(); // classic -> basic
         let usuarios_post = data.sistema.usuarios_activos.clone();
         let usuarios_cancelados_post = data.sistema.usuarios_cancelados.clone();
 
@@ -378,7 +828,10 @@ mod test {
     #[test]
     fn test_ej05_downgrade_sub_invalid_index() {
         let mut data = setup();
-        let result = data.sistema.downgrade_subscripcion("tao@example.com", 99);
+        let result = data
+            .sistema
+            .downgrade_subscripcion("tao@example.com", 99)
+            .unwrap();
         assert!(!result);
     }
     #[test]
@@ -386,7 +839,8 @@ mod test {
         let mut data = setup();
         let result = data
             .sistema
-            .downgrade_subscripcion("nonexistent@example.com", 0);
+            .downgrade_subscripcion("nonexistent@example.com", 0)
+            .unwrap();
         assert!(!result);
     }
 
@@ -394,7 +848,9 @@ mod test {
     fn test_ej05_cancelar_sub() {
         let mut data = setup();
         let usuarios_cancelados_pre = data.sistema.usuarios_cancelados.clone();
-        data.sistema.cancelar_subscripcion("tao@example.com", 0);
+        data.sistema
+            .cancelar_subscripcion("tao@example.com", 0)
+            .unwrap();
         let usuarios_post = data.sistema.usuarios_activos.clone();
         let usuarios_cancelados_post = data.sistema.usuarios_cancelados.clone();
 
@@ -410,6 +866,7 @@ mod test {
         u_multi.add_subscripcion(Subscripcion::new(
             100.0,
             1,
+            Fecha::new(1, 3, 2025),
             MetodoPago::Efectivo,
             TipoSubscripcion::Basic,
             "multi@example.com".to_string(),
@@ -417,13 +874,17 @@ mod test {
         u_multi.add_subscripcion(Subscripcion::new(
             200.0,
             1,
+            Fecha::new(1, 3, 2025),
             MetodoPago::Efectivo,
             TipoSubscripcion::Classic,
             "multi@example.com".to_string(),
         ));
-        data.sistema.crear_usr(&u_multi);
+        data.sistema.crear_usr(&u_multi).unwrap();
         let initial_active_users_count = data.sistema.usuarios_activos.len();
-        let result = data.sistema.cancelar_subscripcion("multi@example.com", 0);
+        let result = data
+            .sistema
+            .cancelar_subscripcion("multi@example.com", 0)
+            .unwrap();
         assert!(result);
         let multi_user = data
             .sistema
@@ -439,7 +900,10 @@ mod test {
     #[test]
     fn test_ej05_cancelar_sub_invalid_index() {
         let mut data = setup();
-        let result = data.sistema.cancelar_subscripcion("tao@example.com", 99);
+        let result = data
+            .sistema
+            .cancelar_subscripcion("tao@example.com", 99)
+            .unwrap();
         assert!(!result);
     }
 
@@ -448,7 +912,8 @@ mod test {
         let mut data = setup();
         let result = data
             .sistema
-            .cancelar_subscripcion("nonexistent@example.com", 0);
+            .cancelar_subscripcion("nonexistent@example.com", 0)
+            .unwrap();
         assert!(!result);
     }
 
@@ -462,7 +927,9 @@ mod test {
     #[test]
     fn test_ej05_medio_mas_utilizado() {
         let mut data = setup();
-        data.sistema.cancelar_subscripcion("tao@example.com", 0);
+        data.sistema
+            .cancelar_subscripcion("tao@example.com", 0)
+            .unwrap();
         let medio_max = data.sistema.metodo_mas_utilizado();
         assert_eq!(medio_max.unwrap(), MetodoPago::Efectivo);
     }
@@ -492,6 +959,7 @@ mod test {
         let sub_basic_efectivo = Subscripcion::new(
             5000.0,
             12,
+            Fecha::new(1, 3, 2025),
             MetodoPago::Efectivo,
             TipoSubscripcion::Basic,
             "basic1@example.com".to_string(),
@@ -499,18 +967,119 @@ mod test {
         let sub_basic_efectivo2 = Subscripcion::new(
             5000.0,
             12,
+            Fecha::new(1, 3, 2025),
             MetodoPago::Efectivo,
             TipoSubscripcion::Basic,
             "basic2@example.com".to_string(),
         );
         u5.add_subscripcion(sub_basic_efectivo);
         u6.add_subscripcion(sub_basic_efectivo2);
-        data.sistema.crear_usr(&u5);
-        data.sistema.crear_usr(&u6);
+        data.sistema.crear_usr(&u5).unwrap();
+        data.sistema.crear_usr(&u6).unwrap();
 
         let max_sub = data.sistema.subscripcion_mas_contratada();
         assert_eq!(max_sub.unwrap(), TipoSubscripcion::Basic);
     }
+
+    #[test]
+    fn test_ej05_exportar_e_importar_csv() {
+        let data = setup();
+        let mut usuarios = vec![];
+        data.sistema.exportar_usuarios_csv(&mut usuarios).unwrap();
+        let mut subscripciones = vec![];
+        data.sistema
+            .exportar_subscripciones_csv(&mut subscripciones)
+            .unwrap();
+        let texto = String::from_utf8(subscripciones.clone()).unwrap();
+        assert_eq!(
+            texto.lines().nth(4).unwrap(),
+            "classicripto@example.com,5000,12,2025-03-01,Classic,Cripto,,,,0x1829821,ETH"
+        );
+
+        let repo = Rc::new(RepositorioMemoria::new());
+        let mut copia =
+            StreamingRust::new().con_repositorio(RepositorioCompartido::from(repo.clone()));
+        let mapeo = MapeoColumnas::new();
+        assert_eq!(
+            copia
+                .importar_usuarios_csv(usuarios.as_slice(), &mapeo)
+                .unwrap(),
+            4
+        );
+        assert_eq!(
+            copia
+                .importar_subscripciones_csv(subscripciones.as_slice(), &mapeo)
+                .unwrap(),
+            4
+        );
+        assert!(copia.usuarios_activos == data.sistema.usuarios_activos);
+        assert_eq!(repo.guardados(), 2);
+    }
+
+    #[test]
+    fn test_ej05_importar_csv_con_errores() {
+        let mut data = setup();
+        let usuarios = "nombre,email\nnuevo,nuevo@example.com\nrepetido,tao@example.com\n\
+                        otro,sin-arroba\n";
+        match data
+            .sistema
+            .importar_usuarios_csv(usuarios.as_bytes(), &MapeoColumnas::new())
+        {
+            Err(ErrorCsv::Filas(errores)) => {
+                assert_eq!(errores.len(), 1);
+                assert_eq!(errores[0].linea, 4);
+            }
+            _ => panic!("se esperaba el email invalido"),
+        }
+        let usuarios = "nombre,email\nnuevo,nuevo@example.com\nrepetido,tao@example.com\n";
+        match data
+            .sistema
+            .importar_usuarios_csv(usuarios.as_bytes(), &MapeoColumnas::new())
+        {
+            Err(ErrorCsv::Filas(errores)) => {
+                assert_eq!(errores[0].linea, 3);
+                assert_eq!(errores[0].detalle, "tao@example.com ya esta registrado");
+            }
+            _ => panic!("se esperaba el email repetido"),
+        }
+        assert_eq!(data.sistema.usuarios_activos.len(), 4);
+
+        let subscripciones = "usr_email,costo_mensual,duracion,fecha_inicio,tipo_subscripcion,\
+                              metodo_pago,alias_mp,cbu\n\
+                              tao@example.com,100,3,2025-01-01,basic,mercado pago,tao.mp,\n\
+                              tao@example.com,100,3,2025-01-01,Basic,Transferencia,,\n\
+                              nadie@example.com,100,3,2025-01-01,Basic,Efectivo,,\n";
+        match data
+            .sistema
+            .importar_subscripciones_csv(subscripciones.as_bytes(), &MapeoColumnas::new())
+        {
+            Err(ErrorCsv::Filas(errores)) => {
+                assert_eq!(errores.len(), 1);
+                assert_eq!(
+                    (errores[0].linea, errores[0].columna.as_deref()),
+                    (3, Some("cbu"))
+                );
+            }
+            _ => panic!("se esperaba el cbu faltante"),
+        }
+        let subscripciones = "usr_email,costo_mensual,duracion,fecha_inicio,tipo_subscripcion,\
+                              metodo_pago,alias_mp\n\
+                              tao@example.com,100,3,2025-01-01,basic,mercado pago,tao.mp\n\
+                              nadie@example.com,100,3,2025-01-01,Basic,Efectivo,\n";
+        match data
+            .sistema
+            .importar_subscripciones_csv(subscripciones.as_bytes(), &MapeoColumnas::new())
+        {
+            Err(ErrorCsv::Filas(errores)) => {
+                assert_eq!(
+                    (errores[0].linea, errores[0].columna.as_deref()),
+                    (3, Some("usr_email"))
+                );
+            }
+            _ => panic!("se esperaba el usuario inexistente"),
+        }
+        assert_eq!(data.sistema.usuarios_activos[0].subscripciones.len(), 1);
+    }
 }
 
 /* ENUNCIADO TP4
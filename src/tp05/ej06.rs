@@ -1,17 +1,177 @@
 #![allow(dead_code, unused_variables)]
 use crate::tp03::ej03::Fecha;
+use crate::tp03::fecha_hora::FechaHora;
+use crate::tp03::rango_fechas::RangoFechas;
+use crate::tp03::reloj::{Reloj, RelojCompartido};
+use crate::tp05::cifrado::Cifrado;
+use crate::tp05::diario::{Diario, DiarioCompartido, DiarioJsonl};
+use crate::tp05::esquema::{Esquema, fecha_a_fecha_hora};
+use crate::tp05::repositorio::{
+    DIRECTORIO_REGISTROS, PersistenciaError, Repositorio, RepositorioCompartido, RepositorioJson,
+    serializar_ordenado,
+};
+use crate::tp05::tabla::{self, ErrorCsv, ErrorFila, Fila, MapeoColumnas, RegistroCsv};
 use serde::{Deserialize, Serialize};
-use serde_json;
-use std::collections::HashMap;
-use std::fs::File;
-use std::io::Write;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::io;
+use std::path::Path;
 
 #[derive(Serialize, Deserialize)]
 struct Sistema {
     usuarios: Vec<Usuario>,
+    #[serde(serialize_with = "serializar_ordenado")]
     cotizaciones: HashMap<String, f64>,
     criptomonedas: Vec<Criptomoneda>,
     transacciones: Vec<Transaccion>,
+    // Secuencia del ultimo evento del diario incluido en esta instantanea.
+    ultima_secuencia: u64,
+    #[serde(skip)]
+    reloj: RelojCompartido,
+    #[serde(skip, default = "repositorio_por_defecto")]
+    repositorio: RepositorioCompartido<Sistema>,
+    #[serde(skip, default = "diario_por_defecto")]
+    diario: DiarioCompartido<EventoSistema>,
+    #[serde(skip)]
+    eventos_sin_instantanea: usize,
+    #[serde(skip)]
+    instantanea_cada: usize,
+}
+
+#[derive(Debug)]
+enum SistemaError {
+    // La operacion no paso las validaciones; el mensaje explica por que.
+    Rechazada(String),
+    Persistencia(PersistenciaError),
+}
+
+impl fmt::Display for SistemaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SistemaError::Rechazada(motivo) => write!(f, "{}", motivo),
+            SistemaError::Persistencia(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for SistemaError {}
+
+impl From<String> for SistemaError {
+    fn from(motivo: String) -> Self {
+        SistemaError::Rechazada(motivo)
+    }
+}
+
+impl From<PersistenciaError> for SistemaError {
+    fn from(e: PersistenciaError) -> Self {
+        SistemaError::Persistencia(e)
+    }
+}
+
+// Cada cuantas operaciones se guarda el estado completo y se compacta el diario.
+const INSTANTANEA_CADA: usize = 100;
+
+// Version 2: las transacciones guardan fecha_hora en lugar de fecha, y aparece
+// ultima_secuencia para el diario.
+fn esquema() -> Esquema {
+    Esquema::new().migracion(agregar_hora_y_secuencia)
+}
+
+fn agregar_hora_y_secuencia(mut datos: Value) -> Result<Value, String> {
+    let sistema = datos.as_object_mut().ok_or("se esperaba un objeto")?;
+    if let Some(Value::Array(transacciones)) = sistema.get_mut("transacciones") {
+        for transaccion in transacciones {
+            let transaccion = transaccion
+                .as_object_mut()
+                .ok_or("se esperaba una transaccion")?;
+            if let Some(fecha) = transaccion.remove("fecha") {
+                transaccion.insert("fecha_hora".to_string(), fecha_a_fecha_hora(fecha)?);
+            }
+        }
+    }
+    sistema.insert("ultima_secuencia".to_string(), Value::from(0));
+    Ok(datos)
+}
+
+const ARCHIVO_INSTANTANEA: &str = "ej06/blockchain-rust.json";
+const ARCHIVO_DIARIO: &str = "ej06/blockchain-rust.jsonl";
+
+fn repositorio_por_defecto() -> RepositorioCompartido<Sistema> {
+    RepositorioCompartido::json(DIRECTORIO_REGISTROS, ARCHIVO_INSTANTANEA, esquema())
+}
+
+fn diario_por_defecto() -> DiarioCompartido<EventoSistema> {
+    DiarioCompartido::jsonl(DIRECTORIO_REGISTROS, ARCHIVO_DIARIO, Esquema::new())
+}
+
+// La instantanea y el diario de `directorio`, cifrados si hay contraseña.
+fn archivos(
+    directorio: impl AsRef<Path>,
+    cifrado: Option<Cifrado>,
+) -> (RepositorioJson, DiarioJsonl) {
+    let repositorio = RepositorioJson::new(&directorio, ARCHIVO_INSTANTANEA).con_esquema(esquema());
+    let diario = DiarioJsonl::new(&directorio, ARCHIVO_DIARIO);
+    match cifrado {
+        Some(cifrado) => (
+            repositorio.con_cifrado(cifrado.clone()),
+            diario.con_cifrado(cifrado),
+        ),
+        None => (repositorio, diario),
+    }
+}
+
+// Una operacion confirmada, tal como queda en el diario.
+#[derive(Debug, Serialize, Deserialize)]
+struct EventoSistema {
+    secuencia: u64,
+    transaccion: Transaccion,
+}
+
+fn mover_saldos(usr: &mut Usuario, transaccion: &Transaccion) {
+    match &transaccion.tipo {
+        TipoTransaccion::IngresoFiat => usr.monto_fiat += transaccion.monto_fiat,
+        TipoTransaccion::CompraCripto {
+            cripto,
+            monto_cripto,
+            cotizacion,
+        } => {
+            *usr.balance_criptos.entry(cripto.clone()).or_insert(0.0) += monto_cripto;
+            usr.monto_fiat -= cotizacion * monto_cripto;
+        }
+        TipoTransaccion::VentaCripto {
+            cripto,
+            monto_cripto,
+            ..
+        } => {
+            *usr.balance_criptos.entry(cripto.clone()).or_insert(0.0) -= monto_cripto;
+            usr.monto_fiat += transaccion.monto_fiat;
+        }
+        TipoTransaccion::RetiroCripto { cripto, monto, .. } => {
+            *usr.balance_criptos.entry(cripto.clone()).or_insert(0.0) -= monto;
+        }
+        TipoTransaccion::RecepcionCripto { cripto, monto, .. } => {
+            *usr.balance_criptos.entry(cripto.clone()).or_insert(0.0) += monto;
+        }
+        TipoTransaccion::RetiroFiat { .. } => usr.monto_fiat -= transaccion.monto_fiat,
+    }
+}
+
+// La columna del CSV con el monto que deja al usuario en negativo
+fn columna_del_monto(tipo: &TipoTransaccion) -> &'static str {
+    match tipo {
+        TipoTransaccion::CompraCripto { .. } | TipoTransaccion::VentaCripto { .. } => {
+            "monto_cripto"
+        }
+        TipoTransaccion::RetiroCripto { .. } | TipoTransaccion::RecepcionCripto { .. } => "monto",
+        TipoTransaccion::IngresoFiat | TipoTransaccion::RetiroFiat { .. } => "monto_fiat",
+    }
+}
+
+// Si el ultimo evento del diario es el ultimo que aplico quien tiene `ultima_secuencia`. Un
+// diario vacio no dice nada: la instantanea ya tiene todo lo que hubo antes.
+fn al_dia(ultimo: Option<&EventoSistema>, ultima_secuencia: u64) -> bool {
+    ultimo.is_none_or(|evento| evento.secuencia == ultima_secuencia)
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -22,6 +182,7 @@ struct Usuario {
     dni: u32,
     validado: bool,
     monto_fiat: f64,
+    #[serde(serialize_with = "serializar_ordenado")]
     balance_criptos: HashMap<String, f64>,
 }
 
@@ -40,7 +201,7 @@ struct Blockchain {
 
 #[derive(Debug, Serialize, Deserialize)]
 struct Transaccion {
-    fecha: Fecha,
+    fecha_hora: FechaHora,
     tipo: TipoTransaccion,
     monto_fiat: f64,
     usuario: Usuario,
@@ -90,14 +251,24 @@ impl Blockchain {
 }
 
 impl Transaccion {
-    fn new(fecha: Fecha, tipo: TipoTransaccion, monto_fiat: f64, usuario: Usuario) -> Self {
+    fn new(
+        fecha_hora: FechaHora,
+        tipo: TipoTransaccion,
+        monto_fiat: f64,
+        usuario: Usuario,
+    ) -> Self {
         Transaccion {
-            fecha,
+            fecha_hora,
             tipo,
             monto_fiat,
             usuario,
         }
     }
+
+    // Dia de la transaccion en la zona en la que se registro.
+    fn fecha(&self) -> Fecha {
+        self.fecha_hora.fecha()
+    }
 }
 
 impl Usuario {
@@ -132,38 +303,182 @@ impl Sistema {
             cotizaciones,
             criptomonedas,
             transacciones: vec![],
+            ultima_secuencia: 0,
+            reloj: RelojCompartido::default(),
+            repositorio: repositorio_por_defecto(),
+            diario: diario_por_defecto(),
+            eventos_sin_instantanea: 0,
+            instantanea_cada: INSTANTANEA_CADA,
+        }
+    }
+
+    // Restaura la ultima instantanea del repositorio y le aplica los eventos del diario que
+    // vinieron despues. Si todavia no hay nada guardado arranca vacio y lo guarda.
+    fn abrir(
+        repositorio: RepositorioCompartido<Sistema>,
+        diario: DiarioCompartido<EventoSistema>,
+    ) -> Result<Self, PersistenciaError> {
+        Sistema::new(vec![], HashMap::new(), vec![])
+            .con_repositorio(repositorio)
+            .con_diario(diario)
+            .restaurar()
+    }
+
+    // Igual que abrir pero con los archivos por defecto en DIRECTORIO_REGISTROS.
+    fn cargar() -> Result<Self, PersistenciaError> {
+        Sistema::new(vec![], HashMap::new(), vec![]).restaurar()
+    }
+
+    // Igual que cargar pero con los archivos cifrados con `contraseña`. Si todavia estan sin
+    // cifrar es un error: primero hay que cifrarlos con cambiar_contraseña(None, ..).
+    fn cargar_cifrado(contraseña: &str) -> Result<Self, PersistenciaError> {
+        let (repositorio, diario) = archivos(DIRECTORIO_REGISTROS, Some(Cifrado::new(contraseña)));
+        Sistema::abrir(
+            RepositorioCompartido::new(repositorio),
+            DiarioCompartido::new(diario),
+        )
+    }
+
+    // Cambia la contraseña de la instantanea, sus respaldos y el diario en DIRECTORIO_REGISTROS.
+    // Sin contraseña actual cifra archivos que estaban sin cifrar, y sin nueva los descifra.
+    fn cambiar_contraseña(
+        actual: Option<&str>,
+        nueva: Option<&str>,
+    ) -> Result<(), PersistenciaError> {
+        Sistema::re_cifrar(
+            DIRECTORIO_REGISTROS,
+            actual.map(Cifrado::new),
+            nueva.map(Cifrado::new),
+        )
+    }
+
+    fn re_cifrar(
+        directorio: impl AsRef<Path>,
+        actual: Option<Cifrado>,
+        nueva: Option<Cifrado>,
+    ) -> Result<(), PersistenciaError> {
+        let (mut repositorio, mut diario) = archivos(directorio, actual);
+        // Si la contraseña actual no sirve para alguno de los archivos, respaldos incluidos, no
+        // se toca ninguno.
+        Repositorio::<Sistema>::cargar(&repositorio)?;
+        repositorio.validar_cifrado()?;
+        Diario::<EventoSistema>::leer(&diario)?;
+        diario.re_cifrar(nueva.clone())?;
+        repositorio.re_cifrar(nueva)
+    }
+
+    fn restaurar(mut self) -> Result<Self, PersistenciaError> {
+        match self.repositorio.cargar()? {
+            Some(guardado) => {
+                self.usuarios = guardado.usuarios;
+                self.cotizaciones = guardado.cotizaciones;
+                self.criptomonedas = guardado.criptomonedas;
+                self.transacciones = guardado.transacciones;
+                self.ultima_secuencia = guardado.ultima_secuencia;
+            }
+            None => self.repositorio.guardar(&self)?,
         }
+
+        for evento in self.diario.leer()? {
+            // ya incluido en la instantanea, quedo de una compactacion interrumpida
+            if evento.secuencia <= self.ultima_secuencia {
+                continue;
+            }
+            if !self.aplicar(&evento.transaccion) {
+                return Err(PersistenciaError::Corrupto(format!(
+                    "el evento {} es de un usuario inexistente",
+                    evento.secuencia
+                )));
+            }
+            self.ultima_secuencia = evento.secuencia;
+            self.transacciones.push(evento.transaccion);
+            self.eventos_sin_instantanea += 1;
+        }
+        Ok(self)
     }
 
-    fn agregar_criptomoneda(&mut self, criptomoneda: &Criptomoneda) {
+    fn con_repositorio(mut self, repositorio: RepositorioCompartido<Sistema>) -> Self {
+        self.repositorio = repositorio;
+        self
+    }
+
+    fn con_reloj(mut self, reloj: RelojCompartido) -> Self {
+        self.reloj = reloj;
+        self
+    }
+
+    fn con_diario(mut self, diario: DiarioCompartido<EventoSistema>) -> Self {
+        self.diario = diario;
+        self
+    }
+
+    fn con_instantanea_cada(mut self, eventos: usize) -> Self {
+        self.instantanea_cada = eventos;
+        self
+    }
+
+    // Agrega la operacion al diario y recien despues la aplica, asi lo que esta en memoria
+    // nunca se adelanta a lo guardado. Si otro proceso agrego operaciones que este no tiene,
+    // es Conflicto: habria dos eventos con la misma secuencia y al restaurar se saltearia uno.
+    fn registrar(&mut self, transaccion: Transaccion) -> Result<(), PersistenciaError> {
+        let anterior = self.ultima_secuencia;
+        let evento = EventoSistema {
+            secuencia: anterior + 1,
+            transaccion,
+        };
+        self.diario
+            .agregar_despues_de(&evento, &|ultimo| al_dia(ultimo, anterior))?;
+        self.aplicar(&evento.transaccion);
+        self.ultima_secuencia = evento.secuencia;
+        self.transacciones.push(evento.transaccion);
+
+        self.eventos_sin_instantanea += 1;
+        if self.eventos_sin_instantanea >= self.instantanea_cada {
+            // La operacion ya esta a salvo en el diario. Si la instantanea falla el contador
+            // no se reinicia y se vuelve a intentar en la operacion siguiente.
+            let _ = self.persistir();
+        }
+        Ok(())
+    }
+
+    // Efecto de una operacion ya validada sobre los saldos del usuario. Es lo mismo que se
+    // repite al reconstruir el estado desde el diario. Devuelve false si el usuario no existe.
+    fn aplicar(&mut self, transaccion: &Transaccion) -> bool {
+        let Some(usr) = self.buscar_usuario(transaccion.usuario.email.clone()) else {
+            return false;
+        };
+        mover_saldos(usr, transaccion);
+        true
+    }
+
+    fn agregar_criptomoneda(
+        &mut self,
+        criptomoneda: &Criptomoneda,
+    ) -> Result<(), PersistenciaError> {
         self.criptomonedas.push(criptomoneda.clone());
-        self.persistir();
+        self.persistir()
     }
 
-    fn agregar_usuario(&mut self, usuario: &Usuario) {
+    fn agregar_usuario(&mut self, usuario: &Usuario) -> Result<(), PersistenciaError> {
         self.usuarios.push(usuario.clone());
-        self.persistir();
+        self.persistir()
     }
 
     fn buscar_usuario(&mut self, email: String) -> Option<&mut Usuario> {
         self.usuarios.iter_mut().find(|u| u.email == email)
     }
 
-    fn ingresar_dinero(&mut self, monto_fiat: f64, usuario: &Usuario) -> Result<(), String> {
-        let u = self
-            .buscar_usuario(usuario.email.clone())
+    fn ingresar_dinero(&mut self, monto_fiat: f64, usuario: &Usuario) -> Result<(), SistemaError> {
+        self.buscar_usuario(usuario.email.clone())
             .ok_or_else(|| format!("El usuario {} no fue encontrado", usuario.email))?;
-        u.monto_fiat += monto_fiat;
 
         let transaccion = Transaccion::new(
-            Fecha::fecha_actual(),
+            self.reloj.ahora(),
             TipoTransaccion::IngresoFiat,
             monto_fiat,
             usuario.clone(),
         );
-
-        self.transacciones.push(transaccion);
-        self.persistir();
+        self.registrar(transaccion)?;
 
         Ok(())
     }
@@ -174,7 +489,7 @@ impl Sistema {
         cantidad: f64,
         criptomoneda: &Criptomoneda,
         usuario: &Usuario,
-    ) -> Result<(), String> {
+    ) -> Result<(), SistemaError> {
         let cotizacion = self
             .cotizaciones
             .get(&criptomoneda.prefijo)
@@ -185,11 +500,12 @@ impl Sistema {
             return Err(format!(
                 "El costo de la operación supera el monto de fiat {} < {}",
                 monto_fiat, costo,
-            ));
+            )
+            .into());
         }
 
         let transaccion = Transaccion::new(
-            Fecha::fecha_actual(),
+            self.reloj.ahora(),
             TipoTransaccion::CompraCripto {
                 cripto: criptomoneda.prefijo.clone(),
                 monto_cripto: cantidad,
@@ -204,27 +520,18 @@ impl Sistema {
             .ok_or_else(|| format!("El usuario {} no fue encontrado", usuario.email))?;
 
         if !usr.validado {
-            return Err("El usuario no está validado".to_string());
+            return Err("El usuario no está validado".to_string().into());
         }
 
         if usr.monto_fiat < costo {
             return Err(format!(
                 "El costo de la operación supera el monto de fiat del usuario {} < {}",
                 usr.monto_fiat, costo,
-            ));
+            )
+            .into());
         }
 
-        let cant_act = usr
-            .balance_criptos
-            .get(&criptomoneda.prefijo)
-            .unwrap_or(&0.0);
-        let cant_final: f64 = cant_act + cantidad;
-        usr.balance_criptos
-            .insert(criptomoneda.prefijo.clone(), cant_final);
-        usr.monto_fiat -= costo;
-
-        self.transacciones.push(transaccion);
-        self.persistir();
+        self.registrar(transaccion)?;
         Ok(())
     }
 
@@ -233,7 +540,7 @@ impl Sistema {
         criptomoneda: &Criptomoneda,
         monto_cripto: f64,
         usuario: &Usuario,
-    ) -> Result<(), String> {
+    ) -> Result<(), SistemaError> {
         let cotizacion = self
             .cotizaciones
             .get(&criptomoneda.prefijo)
@@ -242,7 +549,7 @@ impl Sistema {
         let costo = monto_cripto * cotizacion;
 
         let transaccion = Transaccion::new(
-            Fecha::fecha_actual(),
+            self.reloj.ahora(),
             TipoTransaccion::VentaCripto {
                 cripto: criptomoneda.prefijo.clone(),
                 monto_cripto,
@@ -256,7 +563,7 @@ impl Sistema {
             .buscar_usuario(usuario.email.clone())
             .ok_or_else(|| format!("El usuario {} no fue encontrado", usuario.email))?;
         if !usr.validado {
-            return Err("El usuario no está validado".to_string());
+            return Err("El usuario no está validado".to_string().into());
         }
 
         let cant_act = usr
@@ -267,16 +574,11 @@ impl Sistema {
             return Err(format!(
                 "La cantidad de cripto no puede superar a la del usuario: {} < {}",
                 cant_act, monto_cripto
-            ));
+            )
+            .into());
         }
 
-        let cant_final: f64 = cant_act - monto_cripto;
-        usr.balance_criptos
-            .insert(criptomoneda.prefijo.clone(), cant_final);
-        usr.monto_fiat += costo;
-
-        self.transacciones.push(transaccion);
-        self.persistir();
+        self.registrar(transaccion)?;
         Ok(())
     }
 
@@ -286,7 +588,7 @@ impl Sistema {
         criptomoneda: &Criptomoneda,
         cantidad: f64,
         blockchain: &Blockchain,
-    ) -> Result<(), String> {
+    ) -> Result<(), SistemaError> {
         let cotizacion = self
             .cotizaciones
             .get(&criptomoneda.prefijo)
@@ -295,7 +597,7 @@ impl Sistema {
         let hash = blockchain.generar_hash();
 
         let transaccion = Transaccion::new(
-            Fecha::fecha_actual(),
+            self.reloj.ahora(),
             TipoTransaccion::RetiroCripto {
                 cripto: criptomoneda.prefijo.clone(),
                 blockchain: blockchain.prefijo.clone(),
@@ -312,7 +614,7 @@ impl Sistema {
             .ok_or_else(|| format!("El usuario {} no fue encontrado", usuario.email))?;
 
         if !usr.validado {
-            return Err("El usuario no está validado".to_string());
+            return Err("El usuario no está validado".to_string().into());
         }
 
         let cant_act = usr
@@ -320,15 +622,10 @@ impl Sistema {
             .get(&criptomoneda.prefijo)
             .unwrap_or(&0.0);
         if cantidad > *cant_act {
-            return Err(format!("Balance insuficiente: {} < {}", cant_act, cantidad));
+            return Err(format!("Balance insuficiente: {} < {}", cant_act, cantidad).into());
         }
 
-        let cant_final = cant_act - cantidad;
-        usr.balance_criptos
-            .insert(criptomoneda.prefijo.clone(), cant_final);
-
-        self.transacciones.push(transaccion);
-        self.persistir();
+        self.registrar(transaccion)?;
         Ok(())
     }
 
@@ -338,7 +635,7 @@ impl Sistema {
         criptomoneda: &Criptomoneda,
         monto: f64,
         blockchain: &Blockchain,
-    ) -> Result<(), String> {
+    ) -> Result<(), SistemaError> {
         let cotizacion = *self
             .cotizaciones
             .get(&criptomoneda.prefijo)
@@ -346,12 +643,11 @@ impl Sistema {
 
         let fiat_equivalente = monto * cotizacion;
 
-        let usr = self
-            .buscar_usuario(usuario.email.clone())
+        self.buscar_usuario(usuario.email.clone())
             .ok_or_else(|| format!("El usuario {} no fue encontrado", usuario.email))?;
 
         let transaccion = Transaccion::new(
-            Fecha::fecha_actual(),
+            self.reloj.ahora(),
             TipoTransaccion::RecepcionCripto {
                 cripto: criptomoneda.prefijo.clone(),
                 blockchain: blockchain.prefijo.clone(),
@@ -361,16 +657,7 @@ impl Sistema {
             fiat_equivalente,
             usuario.clone(),
         );
-
-        let current_balance = *usr
-            .balance_criptos
-            .get(&criptomoneda.prefijo)
-            .unwrap_or(&0.0);
-        usr.balance_criptos
-            .insert(criptomoneda.prefijo.clone(), current_balance + monto);
-
-        self.transacciones.push(transaccion);
-        self.persistir();
+        self.registrar(transaccion)?;
         Ok(())
     }
 
@@ -379,7 +666,7 @@ impl Sistema {
         usuario: &Usuario,
         monto_fiat: f64,
         medio: MedioRetiroFiat,
-    ) -> Result<(), String> {
+    ) -> Result<(), SistemaError> {
         let usr = self
             .buscar_usuario(usuario.email.clone())
             .ok_or_else(|| format!("El usuario {} no fue encontrado", usuario.email))?;
@@ -388,27 +675,91 @@ impl Sistema {
             return Err(format!(
                 "El usuario no tiene el monto requerido: {} < {}",
                 usr.monto_fiat, monto_fiat
-            ));
+            )
+            .into());
         }
 
-        usr.monto_fiat -= monto_fiat;
-
         let transaccion = Transaccion::new(
-            Fecha::fecha_actual(),
+            self.reloj.ahora(),
             TipoTransaccion::RetiroFiat { medio },
             monto_fiat,
             usuario.clone(),
         );
-        self.transacciones.push(transaccion);
-        self.persistir();
+        self.registrar(transaccion)?;
 
         Ok(())
     }
 
     fn cripto_mas_cantidad_ventas(&self) -> Option<String> {
-        let mut cantidades: HashMap<String, u64> = HashMap::new();
+        self.cripto_mas_cantidad_ventas_filtrado(None)
+    }
+
+    fn cripto_mas_cantidad_compras(&self) -> Option<String> {
+        self.cripto_mas_cantidad_compras_filtrado(None)
+    }
+
+    fn cripto_mas_volumen_ventas(&self) -> Option<String> {
+        self.cripto_mas_volumen_ventas_filtrado(None)
+    }
+
+    fn cripto_mas_volumen_compras(&self) -> Option<String> {
+        self.cripto_mas_volumen_compras_filtrado(None)
+    }
+
+    // Las mismas estadisticas contando solo las transacciones del periodo. El dia de cada
+    // transaccion se toma en la zona en la que se registro.
+    fn cripto_mas_cantidad_ventas_en(&self, periodo: &RangoFechas) -> Option<String> {
+        self.cripto_mas_cantidad_ventas_filtrado(Some(periodo))
+    }
+
+    fn cripto_mas_cantidad_compras_en(&self, periodo: &RangoFechas) -> Option<String> {
+        self.cripto_mas_cantidad_compras_filtrado(Some(periodo))
+    }
+
+    fn cripto_mas_volumen_ventas_en(&self, periodo: &RangoFechas) -> Option<String> {
+        self.cripto_mas_volumen_ventas_filtrado(Some(periodo))
+    }
+
+    fn cripto_mas_volumen_compras_en(&self, periodo: &RangoFechas) -> Option<String> {
+        self.cripto_mas_volumen_compras_filtrado(Some(periodo))
+    }
+
+    // Monto total en fiat operado por semana ISO, indexado por (año ISO, semana).
+    fn volumen_fiat_por_semana(&self) -> BTreeMap<(i32, u32), f64> {
+        let mut volumenes: BTreeMap<(i32, u32), f64> = BTreeMap::new();
+        for transaccion in &self.transacciones {
+            if let Ok(semana) = transaccion.fecha().semana_iso() {
+                *volumenes.entry(semana).or_insert(0.0) += transaccion.monto_fiat;
+            }
+        }
+        volumenes
+    }
 
+    // Monto total en fiat operado por trimestre, indexado por (año, trimestre).
+    fn volumen_fiat_por_trimestre(&self) -> BTreeMap<(i32, u32), f64> {
+        let mut volumenes: BTreeMap<(i32, u32), f64> = BTreeMap::new();
         for transaccion in &self.transacciones {
+            let fecha = transaccion.fecha();
+            *volumenes
+                .entry((fecha.año, fecha.trimestre()))
+                .or_insert(0.0) += transaccion.monto_fiat;
+        }
+        volumenes
+    }
+
+    fn transacciones_en<'a>(
+        &'a self,
+        periodo: Option<&'a RangoFechas>,
+    ) -> impl Iterator<Item = &'a Transaccion> + 'a {
+        self.transacciones
+            .iter()
+            .filter(move |t| periodo.is_none_or(|p| p.contiene(&t.fecha())))
+    }
+
+    fn cripto_mas_cantidad_ventas_filtrado(&self, periodo: Option<&RangoFechas>) -> Option<String> {
+        let mut cantidades: HashMap<String, u64> = HashMap::new();
+
+        for transaccion in self.transacciones_en(periodo) {
             if let TipoTransaccion::VentaCripto { cripto, .. } = &transaccion.tipo {
                 *cantidades.entry(cripto.clone()).or_insert(0) += 1;
             }
@@ -420,10 +771,13 @@ impl Sistema {
             .map(|(cripto, _)| cripto)
     }
 
-    fn cripto_mas_cantidad_compras(&self) -> Option<String> {
+    fn cripto_mas_cantidad_compras_filtrado(
+        &self,
+        periodo: Option<&RangoFechas>,
+    ) -> Option<String> {
         let mut cantidades: HashMap<String, u64> = HashMap::new();
 
-        for transaccion in &self.transacciones {
+        for transaccion in self.transacciones_en(periodo) {
             if let TipoTransaccion::CompraCripto { cripto, .. } = &transaccion.tipo {
                 *cantidades.entry(cripto.clone()).or_insert(0) += 1;
             }
@@ -435,10 +789,10 @@ impl Sistema {
             .map(|(cripto, _)| cripto)
     }
 
-    fn cripto_mas_volumen_ventas(&self) -> Option<String> {
+    fn cripto_mas_volumen_ventas_filtrado(&self, periodo: Option<&RangoFechas>) -> Option<String> {
         let mut volumenes: HashMap<String, f64> = HashMap::new();
 
-        for transaccion in &self.transacciones {
+        for transaccion in self.transacciones_en(periodo) {
             if let TipoTransaccion::VentaCripto {
                 cripto,
                 monto_cripto,
@@ -455,10 +809,10 @@ impl Sistema {
             .map(|(cripto, _)| cripto)
     }
 
-    fn cripto_mas_volumen_compras(&self) -> Option<String> {
+    fn cripto_mas_volumen_compras_filtrado(&self, periodo: Option<&RangoFechas>) -> Option<String> {
         let mut volumenes: HashMap<String, f64> = HashMap::new();
 
-        for transaccion in &self.transacciones {
+        for transaccion in self.transacciones_en(periodo) {
             if let TipoTransaccion::CompraCripto {
                 cripto,
                 monto_cripto,
@@ -475,16 +829,273 @@ impl Sistema {
             .map(|(cripto, _)| cripto)
     }
 
-    pub fn persistir(&self) {
-        let mut f = File::create("src/tp05/registros/ej06/blockchain-rust.json").unwrap();
-        let registros_serializado = serde_json::to_string_pretty(&self).unwrap();
-        f.write_all(registros_serializado.as_bytes()).unwrap();
+    fn exportar_transacciones_csv(&self, escritor: impl io::Write) -> Result<(), ErrorCsv> {
+        tabla::exportar(&self.transacciones, escritor)
+    }
+
+    // Registra las transacciones de otro sistema como si se hubieran hecho aca, con su efecto
+    // sobre los saldos. Cada fila se revisa contra los saldos que dejan las anteriores: el
+    // usuario tiene que existir y no puede quedar con fiat o criptos en negativo. Si alguna
+    // falla no se importa ninguna, y las demas se guardan juntas en una instantanea.
+    fn importar_transacciones_csv(
+        &mut self,
+        lector: impl io::Read,
+        mapeo: &MapeoColumnas,
+    ) -> Result<usize, ErrorCsv> {
+        let filas: Vec<(u64, Transaccion)> = tabla::importar_con_lineas(lector, mapeo)?;
+        let mut usuarios = self.usuarios.clone();
+        let mut errores = vec![];
+        for (linea, t) in &filas {
+            let Some(usr) = usuarios.iter_mut().find(|u| u.email == t.usuario.email) else {
+                errores.push(ErrorFila {
+                    linea: *linea,
+                    columna: Some("usuario_email".to_string()),
+                    detalle: format!("El usuario {} no fue encontrado", t.usuario.email),
+                });
+                continue;
+            };
+            let antes = usr.clone();
+            mover_saldos(usr, t);
+            if usr.monto_fiat < 0.0 || usr.balance_criptos.values().any(|b| *b < 0.0) {
+                *usr = antes;
+                errores.push(ErrorFila {
+                    linea: *linea,
+                    columna: Some(columna_del_monto(&t.tipo).to_string()),
+                    detalle: format!("Saldo insuficiente del usuario {}", t.usuario.email),
+                });
+            }
+        }
+        if !errores.is_empty() {
+            return Err(ErrorCsv::Filas(errores));
+        }
+
+        let cantidad = filas.len();
+        let usuarios_antes = std::mem::replace(&mut self.usuarios, usuarios);
+        let transacciones_antes = self.transacciones.len();
+        self.transacciones.extend(filas.into_iter().map(|(_, t)| t));
+        if let Err(e) = self.persistir() {
+            self.usuarios = usuarios_antes;
+            self.transacciones.truncate(transacciones_antes);
+            return Err(e.into());
+        }
+        Ok(cantidad)
+    }
+
+    // Guarda una instantanea del estado completo y vacia el diario, cuyos eventos ya quedaron
+    // incluidos en ella. Si el diario tiene eventos de otro proceso que este no aplico, no se
+    // guarda nada: vaciarlo los perderia.
+    pub fn persistir(&mut self) -> Result<(), PersistenciaError> {
+        let diario = self.diario.clone();
+        let ultima = self.ultima_secuencia;
+        diario.vaciar_con_instantanea(&|ultimo| al_dia(ultimo, ultima), &mut || {
+            self.repositorio.guardar(self)
+        })?;
+        self.eventos_sin_instantanea = 0;
+        Ok(())
     }
 }
 
+const TIPOS_TRANSACCION: &[&str] = &[
+    "IngresoFiat",
+    "CompraCripto",
+    "VentaCripto",
+    "RetiroCripto",
+    "RecepcionCripto",
+    "RetiroFiat",
+];
+
+// El usuario va como estaba al momento de la transaccion, con sus criptos en una sola
+// columna ("BTC=0.5;XMR=2"). Los datos de cada tipo de transaccion tienen su columna y
+// quedan vacios en los tipos que no los usan.
+impl RegistroCsv for Transaccion {
+    const COLUMNAS: &'static [&'static str] = &[
+        "fecha_hora",
+        "tipo",
+        "monto_fiat",
+        "usuario_nombre",
+        "usuario_apellido",
+        "usuario_email",
+        "usuario_dni",
+        "usuario_validado",
+        "usuario_monto_fiat",
+        "usuario_balance_criptos",
+        "cripto",
+        "monto_cripto",
+        "cotizacion",
+        "blockchain",
+        "hash",
+        "monto",
+        "medio",
+    ];
+    const OPCIONALES: &'static [&'static str] = &[
+        "usuario_balance_criptos",
+        "cripto",
+        "monto_cripto",
+        "cotizacion",
+        "blockchain",
+        "hash",
+        "monto",
+        "medio",
+    ];
+
+    fn a_fila(&self) -> Vec<String> {
+        let usuario = &self.usuario;
+        let balance: BTreeMap<_, _> = usuario.balance_criptos.iter().collect();
+        let balance: Vec<String> = balance
+            .into_iter()
+            .map(|(cripto, monto)| format!("{}={}", cripto, monto))
+            .collect();
+        let mut fila = vec![
+            self.fecha_hora.a_rfc3339(),
+            String::new(),
+            self.monto_fiat.to_string(),
+            usuario.nombre.clone(),
+            usuario.apellido.clone(),
+            usuario.email.clone(),
+            usuario.dni.to_string(),
+            usuario.validado.to_string(),
+            usuario.monto_fiat.to_string(),
+            balance.join(";"),
+        ];
+        // cripto, monto_cripto, cotizacion, blockchain, hash, monto, medio
+        let mut datos = vec![String::new(); 7];
+        fila[1] = match &self.tipo {
+            TipoTransaccion::IngresoFiat => "IngresoFiat",
+            TipoTransaccion::CompraCripto {
+                cripto,
+                monto_cripto,
+                cotizacion,
+            }
+            | TipoTransaccion::VentaCripto {
+                cripto,
+                monto_cripto,
+                cotizacion,
+            } => {
+                datos[0] = cripto.clone();
+                datos[1] = monto_cripto.to_string();
+                datos[2] = cotizacion.to_string();
+                if matches!(self.tipo, TipoTransaccion::CompraCripto { .. }) {
+                    "CompraCripto"
+                } else {
+                    "VentaCripto"
+                }
+            }
+            TipoTransaccion::RetiroCripto {
+                cripto,
+                blockchain,
+                hash,
+                monto,
+                cotizacion,
+            } => {
+                datos[0] = cripto.clone();
+                datos[2] = cotizacion.to_string();
+                datos[3] = blockchain.clone();
+                datos[4] = hash.clone();
+                datos[5] = monto.to_string();
+                "RetiroCripto"
+            }
+            TipoTransaccion::RecepcionCripto {
+                cripto,
+                blockchain,
+                monto,
+                cotizacion,
+            } => {
+                datos[0] = cripto.clone();
+                datos[2] = cotizacion.to_string();
+                datos[3] = blockchain.clone();
+                datos[5] = monto.to_string();
+                "RecepcionCripto"
+            }
+            TipoTransaccion::RetiroFiat { medio } => {
+                datos[6] = tabla::variante(medio);
+                "RetiroFiat"
+            }
+        }
+        .to_string();
+        fila.extend(datos);
+        fila
+    }
+
+    fn de_fila(fila: &Fila) -> Result<Self, ErrorFila> {
+        let tipo = match fila.nombre_variante("tipo", TIPOS_TRANSACCION)? {
+            "IngresoFiat" => TipoTransaccion::IngresoFiat,
+            "CompraCripto" => TipoTransaccion::CompraCripto {
+                cripto: fila.texto("cripto")?,
+                monto_cripto: fila.valor("monto_cripto")?,
+                cotizacion: fila.valor("cotizacion")?,
+            },
+            "VentaCripto" => TipoTransaccion::VentaCripto {
+                cripto: fila.texto("cripto")?,
+                monto_cripto: fila.valor("monto_cripto")?,
+                cotizacion: fila.valor("cotizacion")?,
+            },
+            "RetiroCripto" => TipoTransaccion::RetiroCripto {
+                cripto: fila.texto("cripto")?,
+                blockchain: fila.texto("blockchain")?,
+                hash: fila.texto("hash")?,
+                monto: fila.valor("monto")?,
+                cotizacion: fila.valor("cotizacion")?,
+            },
+            "RecepcionCripto" => TipoTransaccion::RecepcionCripto {
+                cripto: fila.texto("cripto")?,
+                blockchain: fila.texto("blockchain")?,
+                monto: fila.valor("monto")?,
+                cotizacion: fila.valor("cotizacion")?,
+            },
+            _ => TipoTransaccion::RetiroFiat {
+                medio: fila.variante("medio")?,
+            },
+        };
+        let mut usuario = Usuario::new(
+            fila.texto("usuario_nombre")?,
+            fila.texto("usuario_apellido")?,
+            fila.texto("usuario_email")?,
+            fila.valor("usuario_dni")?,
+            fila.valor("usuario_monto_fiat")?,
+            fila.valor("usuario_validado")?,
+        );
+        usuario.balance_criptos = balance_de_fila(fila)?;
+        Ok(Transaccion::new(
+            fila.valor("fecha_hora")?,
+            tipo,
+            fila.valor("monto_fiat")?,
+            usuario,
+        ))
+    }
+}
+
+fn balance_de_fila(fila: &Fila) -> Result<HashMap<String, f64>, ErrorFila> {
+    let Some(texto) = fila.opcional("usuario_balance_criptos") else {
+        return Ok(HashMap::new());
+    };
+    texto
+        .split(';')
+        .map(|par| {
+            par.split_once('=')
+                .and_then(|(cripto, monto)| {
+                    Some((cripto.trim().to_string(), monto.trim().parse().ok()?))
+                })
+                .ok_or_else(|| {
+                    fila.error_en(
+                        "usuario_balance_criptos",
+                        format!("'{}' no es de la forma CRIPTO=monto", par),
+                    )
+                })
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::tp03::reloj::{RelojFijo, RelojManual};
+    use crate::tp05::cifrado::de_prueba;
+    use crate::tp05::diario::{DiarioLleno, DiarioMemoria};
+    use crate::tp05::pruebas::directorio_temporal;
+    use crate::tp05::repositorio::{
+        RepositorioCompartido, RepositorioJson, RepositorioLleno, RepositorioMemoria,
+    };
+    use std::rc::Rc;
 
     struct TestData {
         sistema: Sistema,
@@ -522,7 +1133,9 @@ mod test {
         let mut hm: HashMap<String, f64> = HashMap::new();
         hm.insert(xmr.prefijo.clone(), 300.0);
 
-        let mut s = Sistema::new(vec![u1.clone(), u2.clone()], hm, vec![xmr.clone()]);
+        let mut s = Sistema::new(vec![u1.clone(), u2.clone()], hm, vec![xmr.clone()])
+            .con_repositorio(RepositorioCompartido::memoria())
+            .con_diario(DiarioCompartido::memoria());
         s.buscar_usuario("a@a.com".to_string())
             .unwrap()
             .balance_criptos
@@ -536,6 +1149,409 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_abrir_sin_archivo_lo_inicializa() {
+        let dir = directorio_temporal("ej06");
+        let repo = RepositorioJson::new(&dir, "sistema.json");
+        assert!(!repo.ruta().exists());
+
+        let s = Sistema::abrir(
+            RepositorioCompartido::new(repo),
+            DiarioCompartido::memoria(),
+        )
+        .unwrap();
+        assert!(s.usuarios.is_empty());
+        let texto = std::fs::read_to_string(dir.join("sistema.json")).unwrap();
+        assert!(texto.contains("\"transacciones\": []"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_cargar_archivo_version_1() {
+        // el archivo guardado cuando las transacciones tenian solo fecha
+        let repo = RepositorioMemoria::new()
+            .con_esquema(esquema())
+            .con_contenido(include_str!("registros/ej06/blockchain-rust.json"));
+        let s: Sistema = repo.cargar().unwrap().unwrap();
+        assert!(!s.transacciones.is_empty());
+
+        let v1 = RepositorioMemoria::new()
+            .con_esquema(esquema())
+            .con_contenido(
+                r#"{"usuarios": [], "cotizaciones": {}, "criptomonedas": [],
+                "transacciones": [{"fecha": {"año": 2025, "dia": 30, "mes": 6},
+                                   "tipo": "IngresoFiat", "monto_fiat": 10.0,
+                                   "usuario": {"nombre": "a", "apellido": "b", "email": "c",
+                                               "dni": 1, "validado": true, "monto_fiat": 0.0,
+                                               "balance_criptos": {}}}]}"#,
+            );
+        let s: Sistema = v1.cargar().unwrap().unwrap();
+        assert_eq!(s.transacciones[0].fecha(), Fecha::new(30, 6, 2025));
+        assert_eq!(s.ultima_secuencia, 0);
+    }
+
+    #[test]
+    fn test_guardar_cargar_guardar_identico() {
+        let td = setup();
+        let repo = Rc::new(RepositorioMemoria::new());
+        let mut s = td
+            .sistema
+            .con_repositorio(RepositorioCompartido::from(repo.clone()));
+        let u1 = td.usuarios[0].clone();
+        let xmr = td.criptomonedas[0].clone();
+        for (prefijo, cotizacion) in [("BTC", 60000.0), ("ETH", 3000.5), ("ADA", 0.1)] {
+            s.cotizaciones.insert(prefijo.to_string(), cotizacion);
+            s.buscar_usuario(u1.email.clone())
+                .unwrap()
+                .balance_criptos
+                .insert(prefijo.to_string(), 0.3);
+        }
+        s.ingresar_dinero(1000.0, &u1).unwrap();
+        s.comprar_criptomoneda(400.0, 1.0, &xmr, &u1).unwrap();
+        s.persistir().unwrap();
+        let guardado = repo.contenido().unwrap();
+
+        let mut restaurado = Sistema::abrir(
+            RepositorioCompartido::from(repo.clone()),
+            DiarioCompartido::memoria(),
+        )
+        .unwrap();
+        assert_eq!(restaurado.transacciones.len(), 2);
+        assert_eq!(restaurado.cotizaciones.len(), 4);
+        assert_eq!(
+            restaurado.usuarios[0].balance_criptos.get("XMR"),
+            Some(&3.0)
+        );
+        restaurado.persistir().unwrap();
+        assert_eq!(repo.contenido().unwrap(), guardado);
+    }
+
+    fn abrir_en(dir: &Path, cifrado: Option<Cifrado>) -> Result<Sistema, PersistenciaError> {
+        let (repositorio, diario) = archivos(dir, cifrado);
+        Sistema::abrir(
+            RepositorioCompartido::new(repositorio),
+            DiarioCompartido::new(diario),
+        )
+    }
+
+    // Ningun archivo del directorio tiene datos personales en claro.
+    fn sin_datos_en_claro(dir: &Path) -> bool {
+        std::fs::read_dir(dir.join("ej06"))
+            .unwrap()
+            .map(|entrada| std::fs::read_to_string(entrada.unwrap().path()).unwrap())
+            .all(|texto| !texto.contains("a@a.com") && !texto.contains("Tao"))
+    }
+
+    #[test]
+    fn test_instantanea_y_diario_cifrados() {
+        let dir = directorio_temporal("ej06-cifrado");
+        let u1 = setup().usuarios[0].clone();
+        let mut s = abrir_en(&dir, Some(de_prueba("secreto"))).unwrap();
+        s.agregar_usuario(&u1).unwrap();
+        s.ingresar_dinero(100.0, &u1).unwrap();
+        s.persistir().unwrap();
+        // este queda solo en el diario
+        s.ingresar_dinero(50.0, &u1).unwrap();
+        assert!(sin_datos_en_claro(&dir));
+
+        let restaurado = abrir_en(&dir, Some(de_prueba("secreto"))).unwrap();
+        assert_eq!(restaurado.transacciones.len(), 2);
+        assert_eq!(restaurado.usuarios[0].monto_fiat, u1.monto_fiat + 150.0);
+        assert!(matches!(
+            abrir_en(&dir, Some(de_prueba("otra"))),
+            Err(PersistenciaError::Cifrado(_))
+        ));
+        assert!(matches!(
+            abrir_en(&dir, None),
+            Err(PersistenciaError::Cifrado(_))
+        ));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_con_contraseña_no_lee_archivos_sin_cifrar() {
+        let dir = directorio_temporal("ej06-en-claro");
+        let u1 = setup().usuarios[0].clone();
+        let mut s = abrir_en(&dir, None).unwrap();
+        s.agregar_usuario(&u1).unwrap();
+        assert!(matches!(
+            abrir_en(&dir, Some(de_prueba("secreto"))),
+            Err(PersistenciaError::Cifrado(_))
+        ));
+
+        // una linea en claro agregada al diario de un sistema cifrado
+        Sistema::re_cifrar(&dir, None, Some(de_prueba("secreto"))).unwrap();
+        abrir_en(&dir, Some(de_prueba("secreto"))).unwrap();
+        s.ingresar_dinero(100.0, &u1).unwrap();
+        assert!(matches!(
+            abrir_en(&dir, Some(de_prueba("secreto"))),
+            Err(PersistenciaError::Cifrado(_))
+        ));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_cambiar_contraseña() {
+        let dir = directorio_temporal("ej06-rotacion");
+        let u1 = setup().usuarios[0].clone();
+        // datos que se guardaron antes de empezar a cifrar, con respaldos y diario
+        let mut s = abrir_en(&dir, None).unwrap();
+        s.agregar_usuario(&u1).unwrap();
+        s.ingresar_dinero(100.0, &u1).unwrap();
+        assert!(!sin_datos_en_claro(&dir));
+
+        Sistema::re_cifrar(&dir, None, Some(de_prueba("vieja"))).unwrap();
+        assert!(sin_datos_en_claro(&dir));
+        assert_eq!(
+            abrir_en(&dir, Some(de_prueba("vieja")))
+                .unwrap()
+                .transacciones
+                .len(),
+            1
+        );
+
+        // con la contraseña equivocada no cambia nada
+        assert!(matches!(
+            Sistema::re_cifrar(&dir, Some(de_prueba("otra")), Some(de_prueba("nueva"))),
+            Err(PersistenciaError::Cifrado(_))
+        ));
+        Sistema::re_cifrar(&dir, Some(de_prueba("vieja")), Some(de_prueba("nueva"))).unwrap();
+        assert!(abrir_en(&dir, Some(de_prueba("vieja"))).is_err());
+        assert_eq!(
+            abrir_en(&dir, Some(de_prueba("nueva")))
+                .unwrap()
+                .transacciones
+                .len(),
+            1
+        );
+        // los respaldos tambien quedaron con la contraseña nueva
+        let (repositorio, _) = archivos(&dir, Some(de_prueba("nueva")));
+        assert!(!repositorio.respaldos().is_empty());
+        for n in 1..=repositorio.respaldos().len() {
+            repositorio.restaurar_respaldo(n).unwrap();
+            assert!(Repositorio::<Sistema>::cargar(&repositorio).is_ok());
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_cambiar_contraseña_con_un_respaldo_ilegible() {
+        let dir = directorio_temporal("ej06-respaldo-ilegible");
+        let u1 = setup().usuarios[0].clone();
+        let mut s = abrir_en(&dir, Some(de_prueba("vieja"))).unwrap();
+        s.agregar_usuario(&u1).unwrap();
+        s.persistir().unwrap();
+        s.ingresar_dinero(100.0, &u1).unwrap();
+
+        let (repositorio, diario) = archivos(&dir, None);
+        std::fs::write(repositorio.ruta_respaldo(1), "{").unwrap();
+        let diario_antes = std::fs::read_to_string(diario.ruta()).unwrap();
+        assert!(matches!(
+            Sistema::re_cifrar(&dir, Some(de_prueba("vieja")), Some(de_prueba("nueva"))),
+            Err(PersistenciaError::Cifrado(_) | PersistenciaError::Corrupto(_))
+        ));
+        // ni el diario ni la instantanea cambiaron
+        assert_eq!(
+            std::fs::read_to_string(diario.ruta()).unwrap(),
+            diario_antes
+        );
+        assert_eq!(
+            abrir_en(&dir, Some(de_prueba("vieja")))
+                .unwrap()
+                .transacciones
+                .len(),
+            1
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    // Sistema de setup con repositorio y diario que el test puede inspeccionar, y con los
+    // usuarios ya guardados en la instantanea.
+    fn setup_con_diario() -> (TestData, Rc<RepositorioMemoria>, Rc<DiarioMemoria>) {
+        let mut td = setup();
+        let repo = Rc::new(RepositorioMemoria::new());
+        let diario = Rc::new(DiarioMemoria::new());
+        td.sistema = td
+            .sistema
+            .con_repositorio(RepositorioCompartido::from(repo.clone()))
+            .con_diario(DiarioCompartido::from(diario.clone()));
+        td.sistema.persistir().unwrap();
+        (td, repo, diario)
+    }
+
+    fn saldos(s: &Sistema) -> String {
+        serde_json::to_string(&s.usuarios).unwrap()
+    }
+
+    #[test]
+    fn test_operaciones_se_agregan_al_diario() {
+        let (td, repo, diario) = setup_con_diario();
+        let mut s = td.sistema;
+        let u1 = td.usuarios[0].clone();
+        let instantanea = repo.contenido();
+
+        s.ingresar_dinero(100.0, &u1).unwrap();
+        s.vender_criptomoneda(&td.criptomonedas[0], 1.0, &u1)
+            .unwrap();
+        // una operacion rechazada no deja rastro
+        assert!(
+            s.retirar_fiat(&u1, 1_000_000.0, MedioRetiroFiat::MercadoPago)
+                .is_err()
+        );
+
+        let eventos: Vec<EventoSistema> = diario.leer().unwrap();
+        assert_eq!(eventos.len(), 2);
+        assert_eq!(eventos[0].secuencia, 1);
+        assert!(matches!(
+            eventos[1].transaccion.tipo,
+            TipoTransaccion::VentaCripto { .. }
+        ));
+        // la instantanea no se reescribio
+        assert_eq!(repo.contenido(), instantanea);
+    }
+
+    #[test]
+    fn test_reconstruir_saldos_desde_el_diario() {
+        let (td, repo, diario) = setup_con_diario();
+        let mut s = td.sistema;
+        let u1 = td.usuarios[0].clone();
+        let xmr = td.criptomonedas[0].clone();
+        let blockchain = td.blockchains[0].clone();
+
+        s.ingresar_dinero(1000.0, &u1).unwrap();
+        s.comprar_criptomoneda(700.0, 2.0, &xmr, &u1).unwrap();
+        s.vender_criptomoneda(&xmr, 0.5, &u1).unwrap();
+        s.retirar_criptomoneda(&u1, &xmr, 1.0, &blockchain).unwrap();
+        s.recibir_criptomoneda(&u1, &xmr, 0.25, &blockchain)
+            .unwrap();
+        s.retirar_fiat(&u1, 200.0, MedioRetiroFiat::TransferenciaBancaria)
+            .unwrap();
+
+        let restaurado = Sistema::abrir(
+            RepositorioCompartido::from(repo.clone()),
+            DiarioCompartido::from(diario.clone()),
+        )
+        .unwrap();
+        assert_eq!(saldos(&restaurado), saldos(&s));
+        assert_eq!(restaurado.transacciones.len(), 6);
+        assert_eq!(restaurado.ultima_secuencia, 6);
+    }
+
+    #[test]
+    fn test_instantanea_periodica_compacta_el_diario() {
+        let (td, repo, diario) = setup_con_diario();
+        let mut s = td.sistema.con_instantanea_cada(2);
+        let u1 = td.usuarios[0].clone();
+
+        s.ingresar_dinero(10.0, &u1).unwrap();
+        assert_eq!(Diario::<EventoSistema>::leer(&*diario).unwrap().len(), 1);
+        s.ingresar_dinero(20.0, &u1).unwrap();
+        // queda solo el ultimo evento, que ya esta en la instantanea
+        assert_eq!(Diario::<EventoSistema>::leer(&*diario).unwrap().len(), 1);
+        s.ingresar_dinero(30.0, &u1).unwrap();
+        assert_eq!(Diario::<EventoSistema>::leer(&*diario).unwrap().len(), 2);
+
+        let instantanea: Sistema = repo.cargar().unwrap().unwrap();
+        assert_eq!(instantanea.ultima_secuencia, 2);
+        assert_eq!(instantanea.transacciones.len(), 2);
+
+        let restaurado = Sistema::abrir(
+            RepositorioCompartido::from(repo.clone()),
+            DiarioCompartido::from(diario.clone()),
+        )
+        .unwrap();
+        assert_eq!(restaurado.transacciones.len(), 3);
+        assert_eq!(saldos(&restaurado), saldos(&s));
+    }
+
+    #[test]
+    fn test_compactacion_interrumpida_no_duplica_eventos() {
+        let (td, repo, diario) = setup_con_diario();
+        let mut s = td.sistema;
+        let u1 = td.usuarios[0].clone();
+        s.ingresar_dinero(10.0, &u1).unwrap();
+        s.ingresar_dinero(20.0, &u1).unwrap();
+        // se guardo la instantanea pero no llego a vaciarse el diario
+        repo.guardar(&s).unwrap();
+        s.ingresar_dinero(30.0, &u1).unwrap();
+
+        let restaurado = Sistema::abrir(
+            RepositorioCompartido::from(repo.clone()),
+            DiarioCompartido::from(diario.clone()),
+        )
+        .unwrap();
+        assert_eq!(restaurado.transacciones.len(), 3);
+        assert_eq!(restaurado.usuarios[0].monto_fiat, 560.0);
+    }
+
+    #[test]
+    fn test_dos_procesos_sobre_el_mismo_diario() {
+        let dir = directorio_temporal("ej06-procesos");
+        let u1 = setup().usuarios[0].clone();
+        let mut a = abrir_en(&dir, None).unwrap();
+        a.agregar_usuario(&u1).unwrap();
+        let mut b = abrir_en(&dir, None).unwrap();
+
+        a.ingresar_dinero(10.0, &u1).unwrap();
+        // b no vio el evento de a: no puede repetir su secuencia ni compactar sin el
+        assert!(matches!(
+            b.ingresar_dinero(20.0, &u1),
+            Err(SistemaError::Persistencia(PersistenciaError::Conflicto(_)))
+        ));
+        assert!(b.transacciones.is_empty());
+        assert!(matches!(
+            b.persistir(),
+            Err(PersistenciaError::Conflicto(_))
+        ));
+
+        // reabierto si puede, y despues de su compactacion el que quedo atras es a
+        let mut b = abrir_en(&dir, None).unwrap();
+        b.ingresar_dinero(20.0, &u1).unwrap();
+        b.persistir().unwrap();
+        assert!(matches!(
+            a.ingresar_dinero(5.0, &u1),
+            Err(SistemaError::Persistencia(PersistenciaError::Conflicto(_)))
+        ));
+
+        let restaurado = abrir_en(&dir, None).unwrap();
+        assert_eq!(restaurado.transacciones.len(), 2);
+        assert_eq!(restaurado.usuarios[0].monto_fiat, u1.monto_fiat + 30.0);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_operacion_sin_espacio_para_el_diario() {
+        let td = setup();
+        let mut s = td.sistema.con_diario(DiarioLleno::compartido());
+        let u1 = td.usuarios[0].clone();
+        let antes = saldos(&s);
+
+        let error = s.ingresar_dinero(100.0, &u1).unwrap_err();
+        assert!(matches!(
+            error,
+            SistemaError::Persistencia(PersistenciaError::Io(_))
+        ));
+        // no se registro, asi que tampoco se aplico
+        assert_eq!(saldos(&s), antes);
+        assert!(s.transacciones.is_empty());
+    }
+
+    #[test]
+    fn test_instantanea_fallida_no_rechaza_la_operacion() {
+        let (td, _, diario) = setup_con_diario();
+        let mut s = td
+            .sistema
+            .con_repositorio(RepositorioLleno::compartido())
+            .con_instantanea_cada(1);
+        let u1 = td.usuarios[0].clone();
+
+        s.ingresar_dinero(10.0, &u1).unwrap();
+        s.ingresar_dinero(20.0, &u1).unwrap();
+        // los eventos quedan en el diario hasta que se pueda guardar la instantanea
+        assert_eq!(Diario::<EventoSistema>::leer(&*diario).unwrap().len(), 2);
+        assert!(s.persistir().is_err());
+    }
+
     #[test]
     fn test_ingresar_dinero_exitoso() {
         let td = setup();
@@ -567,11 +1583,32 @@ mod test {
             true,
         );
         assert_eq!(
-            s.ingresar_dinero(100.0, &user).unwrap_err(),
+            s.ingresar_dinero(100.0, &user).unwrap_err().to_string(),
             "El usuario c@c.com no fue encontrado"
         );
     }
 
+    #[test]
+    fn test_transacciones_fechadas_con_reloj_manual() {
+        let td = setup();
+        let reloj = Rc::new(RelojManual::new(Fecha::new(1, 3, 2025)).unwrap());
+        let mut s = td.sistema.con_reloj(RelojCompartido::from(reloj.clone()));
+        let u1 = td.usuarios[0].clone();
+        let xmr = td.criptomonedas[0].clone();
+
+        s.ingresar_dinero(1000.0, &u1).unwrap();
+        reloj.avanzar_meses(2);
+        s.comprar_criptomoneda(400.0, 1.0, &xmr, &u1).unwrap();
+        reloj.avanzar_segundos(1);
+        s.vender_criptomoneda(&xmr, 1.0, &u1).unwrap();
+
+        assert_eq!(s.transacciones[0].fecha(), Fecha::new(1, 3, 2025));
+        assert_eq!(s.transacciones[1].fecha(), Fecha::new(1, 5, 2025));
+        // mismo dia, pero se pueden ordenar
+        assert_eq!(s.transacciones[2].fecha(), Fecha::new(1, 5, 2025));
+        assert!(s.transacciones[1].fecha_hora < s.transacciones[2].fecha_hora);
+    }
+
     #[test]
     fn test_comprar_criptomoneda_exitoso() {
         let td = setup();
@@ -599,7 +1636,9 @@ mod test {
         let xmr = td.criptomonedas[0].clone();
 
         assert_eq!(
-            s.comprar_criptomoneda(600.0, 2.0, &xmr, &u1).unwrap_err(),
+            s.comprar_criptomoneda(600.0, 2.0, &xmr, &u1)
+                .unwrap_err()
+                .to_string(),
             "El costo de la operación supera el monto de fiat del usuario 500 < 600"
         );
     }
@@ -612,7 +1651,9 @@ mod test {
         let xmr = td.criptomonedas[0].clone();
 
         assert_eq!(
-            s.comprar_criptomoneda(400.0, 1.0, &xmr, &u2).unwrap_err(),
+            s.comprar_criptomoneda(400.0, 1.0, &xmr, &u2)
+                .unwrap_err()
+                .to_string(),
             "El usuario no está validado"
         );
     }
@@ -629,7 +1670,9 @@ mod test {
         };
 
         assert_eq!(
-            s.comprar_criptomoneda(400.0, 1.0, &xmr, &u1).unwrap_err(),
+            s.comprar_criptomoneda(400.0, 1.0, &xmr, &u1)
+                .unwrap_err()
+                .to_string(),
             "No se econtró una cotizacion para BTC"
         );
     }
@@ -660,7 +1703,9 @@ mod test {
         let xmr = td.criptomonedas[0].clone();
 
         assert_eq!(
-            s.vender_criptomoneda(&xmr, 3.0, &u1).unwrap_err(),
+            s.vender_criptomoneda(&xmr, 3.0, &u1)
+                .unwrap_err()
+                .to_string(),
             "La cantidad de cripto no puede superar a la del usuario: 2 < 3"
         );
     }
@@ -673,7 +1718,9 @@ mod test {
         let xmr = td.criptomonedas[0].clone();
 
         assert_eq!(
-            s.vender_criptomoneda(&xmr, 1.0, &u2).unwrap_err(),
+            s.vender_criptomoneda(&xmr, 1.0, &u2)
+                .unwrap_err()
+                .to_string(),
             "El usuario no está validado"
         );
     }
@@ -690,7 +1737,9 @@ mod test {
         };
 
         assert_eq!(
-            s.vender_criptomoneda(&xmr, 1.0, &u1).unwrap_err(),
+            s.vender_criptomoneda(&xmr, 1.0, &u1)
+                .unwrap_err()
+                .to_string(),
             "No se econtró una cotizacion para BTC"
         );
     }
@@ -724,7 +1773,8 @@ mod test {
 
         assert_eq!(
             s.retirar_criptomoneda(&u1, &xmr, 3.0, &blockchain)
-                .unwrap_err(),
+                .unwrap_err()
+                .to_string(),
             "Balance insuficiente: 2 < 3"
         );
     }
@@ -739,7 +1789,8 @@ mod test {
 
         assert_eq!(
             s.retirar_criptomoneda(&u2, &xmr, 1.0, &blockchain)
-                .unwrap_err(),
+                .unwrap_err()
+                .to_string(),
             "El usuario no está validado"
         );
     }
@@ -758,7 +1809,8 @@ mod test {
 
         assert_eq!(
             s.retirar_criptomoneda(&u1, &xmr, 1.0, &blockchain)
-                .unwrap_err(),
+                .unwrap_err()
+                .to_string(),
             "No se econtró una cotizacion para BTC"
         );
     }
@@ -791,7 +1843,8 @@ mod test {
 
         assert_eq!(
             s.retirar_fiat(&u1, 600.0, MedioRetiroFiat::MercadoPago)
-                .unwrap_err(),
+                .unwrap_err()
+                .to_string(),
             "El usuario no tiene el monto requerido: 500 < 600"
         );
     }
@@ -810,7 +1863,8 @@ mod test {
 
         assert_eq!(
             s.retirar_fiat(&user, 100.0, MedioRetiroFiat::MercadoPago)
-                .unwrap_err(),
+                .unwrap_err()
+                .to_string(),
             "El usuario c@c.com no fue encontrado"
         );
     }
@@ -834,7 +1888,7 @@ mod test {
             }],
         };
         s.cotizaciones.insert("BTC".to_string(), 50000.0);
-        s.agregar_criptomoneda(&btc);
+        s.agregar_criptomoneda(&btc).unwrap();
         s.buscar_usuario("a@a.com".to_string())
             .unwrap()
             .balance_criptos
@@ -869,7 +1923,7 @@ mod test {
             }],
         };
         s.cotizaciones.insert("BTC".to_string(), 50000.0);
-        s.agregar_criptomoneda(&btc);
+        s.agregar_criptomoneda(&btc).unwrap();
         s.buscar_usuario("a@a.com".to_string())
             .unwrap()
             .balance_criptos
@@ -897,7 +1951,7 @@ mod test {
         );
         let mut s = setup().sistema;
         let initial_user_count = s.usuarios.len();
-        s.agregar_usuario(&u);
+        s.agregar_usuario(&u).unwrap();
         assert_eq!(s.usuarios.len(), initial_user_count + 1);
         assert!(s.buscar_usuario("c@c.com".to_string()).is_some());
     }
@@ -954,7 +2008,8 @@ mod test {
 
         assert_eq!(
             s.recibir_criptomoneda(&non_existent_user, &xmr, 1.0, &td.blockchains[0])
-                .unwrap_err(),
+                .unwrap_err()
+                .to_string(),
             "El usuario nonexistent@example.com no fue encontrado"
         );
     }
@@ -972,7 +2027,8 @@ mod test {
 
         assert_eq!(
             s.recibir_criptomoneda(&u1, &unknown_crypto, 1.0, &td.blockchains[0])
-                .unwrap_err(),
+                .unwrap_err()
+                .to_string(),
             "No se econtró una cotizacion para UNK"
         );
     }
@@ -997,7 +2053,7 @@ mod test {
             }],
         };
         s.cotizaciones.insert("BTC".to_string(), 50000.0);
-        s.agregar_criptomoneda(&btc);
+        s.agregar_criptomoneda(&btc).unwrap();
         s.ingresar_dinero(60000.0, &u1).unwrap();
         s.comprar_criptomoneda(60000.0, 1.2, &btc, &u1).unwrap();
 
@@ -1030,13 +2086,80 @@ mod test {
             }],
         };
         s.cotizaciones.insert("BTC".to_string(), 50000.0);
-        s.agregar_criptomoneda(&btc);
+        s.agregar_criptomoneda(&btc).unwrap();
         s.ingresar_dinero(30000.0, &u1).unwrap();
         s.comprar_criptomoneda(30000.0, 0.5, &btc, &u1).unwrap();
 
         assert_eq!(s.cripto_mas_volumen_compras(), Some("XMR".to_string()));
     }
 
+    #[test]
+    fn test_estadisticas_por_periodo() {
+        let td = setup();
+        let reloj = Rc::new(RelojManual::new(Fecha::new(10, 7, 2025)).unwrap());
+        let mut s = td.sistema.con_reloj(RelojCompartido::from(reloj.clone()));
+        let u1 = td.usuarios[0].clone();
+        let xmr = td.criptomonedas[0].clone();
+        let btc = Criptomoneda {
+            prefijo: "BTC".to_string(),
+            nombre: "Bitcoin".to_string(),
+            blockchains_soportadas: vec![],
+        };
+        s.cotizaciones.insert("BTC".to_string(), 100.0);
+        s.agregar_criptomoneda(&btc).unwrap();
+        s.ingresar_dinero(2000.0, &u1).unwrap();
+
+        // julio: dos compras chicas de XMR
+        s.comprar_criptomoneda(400.0, 1.0, &xmr, &u1).unwrap();
+        s.comprar_criptomoneda(400.0, 1.0, &xmr, &u1).unwrap();
+        // agosto: una compra grande de BTC y una venta de XMR
+        reloj.avanzar_meses(1);
+        s.comprar_criptomoneda(500.0, 3.0, &btc, &u1).unwrap();
+        s.vender_criptomoneda(&xmr, 1.0, &u1).unwrap();
+
+        let julio = RangoFechas::mes(7, 2025).unwrap();
+        let agosto = RangoFechas::mes(8, 2025).unwrap();
+
+        assert_eq!(s.cripto_mas_cantidad_compras(), Some("XMR".to_string()));
+        assert_eq!(s.cripto_mas_volumen_compras(), Some("BTC".to_string()));
+        assert_eq!(
+            s.cripto_mas_volumen_compras_en(&julio),
+            Some("XMR".to_string())
+        );
+        assert_eq!(
+            s.cripto_mas_cantidad_compras_en(&agosto),
+            Some("BTC".to_string())
+        );
+        assert_eq!(s.cripto_mas_cantidad_ventas_en(&julio), None);
+        assert_eq!(
+            s.cripto_mas_volumen_ventas_en(&agosto),
+            Some("XMR".to_string())
+        );
+    }
+
+    #[test]
+    fn test_volumen_por_semana_y_trimestre() {
+        let td = setup();
+        // lunes 30/6/2025, semana 27 y fin del segundo trimestre
+        let reloj = Rc::new(RelojManual::new(Fecha::new(30, 6, 2025)).unwrap());
+        let mut s = td.sistema.con_reloj(RelojCompartido::from(reloj.clone()));
+        let u1 = td.usuarios[0].clone();
+        let xmr = td.criptomonedas[0].clone();
+
+        s.ingresar_dinero(1000.0, &u1).unwrap();
+        reloj.avanzar_dias(1);
+        s.comprar_criptomoneda(400.0, 1.0, &xmr, &u1).unwrap();
+        reloj.avanzar_dias(7);
+        s.ingresar_dinero(50.0, &u1).unwrap();
+
+        let semanas: Vec<((i32, u32), f64)> = s.volumen_fiat_por_semana().into_iter().collect();
+        assert_eq!(semanas, vec![((2025, 27), 1400.0), ((2025, 28), 50.0)]);
+
+        let trimestres: Vec<((i32, u32), f64)> =
+            s.volumen_fiat_por_trimestre().into_iter().collect();
+        assert_eq!(trimestres, vec![((2025, 2), 1000.0), ((2025, 3), 450.0)]);
+    }
+
     #[test]
     fn test_criptomoneda_mayor_volumen_compras_no_compras() {
         let s = setup().sistema;
@@ -1111,6 +2234,148 @@ mod test {
             }
         ));
     }
+
+    #[test]
+    fn test_exportar_e_importar_transacciones_csv() {
+        let momento = FechaHora::new(&Fecha::new(1, 7, 2025), 10, 30, 0, -3 * 3600).unwrap();
+        let mut td = setup();
+        let mut s = td
+            .sistema
+            .con_reloj(RelojCompartido::new(RelojFijo::en(momento)));
+        let u1 = td.usuarios[0].clone();
+        let xmr = td.criptomonedas[0].clone();
+        let blockchain = td.blockchains[0].clone();
+        s.comprar_criptomoneda(300.0, 1.0, &xmr, &u1).unwrap();
+        s.recibir_criptomoneda(&u1, &xmr, 0.5, &blockchain).unwrap();
+        s.retirar_fiat(&u1, 100.0, MedioRetiroFiat::MercadoPago)
+            .unwrap();
+
+        let mut csv = vec![];
+        s.exportar_transacciones_csv(&mut csv).unwrap();
+        let texto = String::from_utf8(csv).unwrap();
+        let lineas: Vec<&str> = texto.lines().collect();
+        assert_eq!(
+            lineas[1],
+            "2025-07-01T10:30:00-03:00,CompraCripto,300,Tao,Lop,a@a.com,12345678,true,500,,\
+             XMR,1,300,,,,"
+        );
+        assert_eq!(
+            lineas[3],
+            "2025-07-01T10:30:00-03:00,RetiroFiat,100,Tao,Lop,a@a.com,12345678,true,500,,\
+             ,,,,,,MercadoPago"
+        );
+
+        // en otro sistema con el mismo usuario, los saldos quedan iguales
+        td = setup();
+        let mut otro = td.sistema;
+        assert_eq!(
+            otro.importar_transacciones_csv(texto.as_bytes(), &MapeoColumnas::new())
+                .unwrap(),
+            3
+        );
+        assert_eq!(saldos(&otro), saldos(&s));
+        // entran por la instantanea, no por el diario
+        assert_eq!(otro.transacciones.len(), 3);
+        assert_eq!(otro.ultima_secuencia, 0);
+        assert_eq!(
+            otro.transacciones[0].fecha_hora,
+            s.transacciones[0].fecha_hora
+        );
+    }
+
+    #[test]
+    fn test_importar_transacciones_csv_con_errores() {
+        let mut s = setup().sistema;
+        let texto = "fecha_hora,tipo,monto_fiat,usuario_nombre,usuario_apellido,usuario_email,\
+                     usuario_dni,usuario_validado,usuario_monto_fiat,usuario_balance_criptos,\
+                     cripto,monto_cripto,cotizacion\n\
+                     2025-07-01T10:30:00-03:00,compra cripto,300,T,L,a@a.com,1,true,0,XMR=1;BTC,\
+                     XMR,1,300\n\
+                     2025-07-01T10:30:00-03:00,CompraCripto,300,T,L,a@a.com,1,si,0,,XMR,1,300\n\
+                     2025-07-01T10:30:00-03:00,Regalo,300,T,L,a@a.com,1,true,0,,,,\n\
+                     2025-07-01T10:30:00-03:00,VentaCripto,300,T,L,a@a.com,1,true,0,,XMR,,300\n";
+        let Err(ErrorCsv::Filas(errores)) =
+            s.importar_transacciones_csv(texto.as_bytes(), &MapeoColumnas::new())
+        else {
+            panic!("se esperaban errores por fila");
+        };
+        let resumen: Vec<(u64, Option<&str>)> = errores
+            .iter()
+            .map(|e| (e.linea, e.columna.as_deref()))
+            .collect();
+        assert_eq!(
+            resumen,
+            vec![
+                (2, Some("usuario_balance_criptos")),
+                (3, Some("usuario_validado")),
+                (4, Some("tipo")),
+                (5, Some("monto_cripto"))
+            ]
+        );
+
+        let texto = "fecha_hora,tipo,monto_fiat,usuario_nombre,usuario_apellido,usuario_email,\
+                     usuario_dni,usuario_validado,usuario_monto_fiat\n\
+                     2025-07-01T10:30:00-03:00,IngresoFiat,300,T,L,a@a.com,1,true,0\n\
+                     2025-07-01T10:30:00-03:00,IngresoFiat,300,X,Y,x@x.com,1,true,0\n";
+        let Err(ErrorCsv::Filas(errores)) =
+            s.importar_transacciones_csv(texto.as_bytes(), &MapeoColumnas::new())
+        else {
+            panic!("se esperaba el usuario inexistente");
+        };
+        assert_eq!(errores[0].linea, 3);
+        assert!(s.transacciones.is_empty());
+    }
+
+    #[test]
+    fn test_importar_transacciones_csv_sin_saldo() {
+        let mut s = setup().sistema;
+        let antes = saldos(&s);
+        // a@a.com arranca con 500 de fiat y 2 XMR
+        let texto = "fecha_hora,tipo,monto_fiat,usuario_nombre,usuario_apellido,usuario_email,\
+                     usuario_dni,usuario_validado,usuario_monto_fiat,cripto,monto_cripto,\
+                     cotizacion,monto,blockchain,hash,medio\n\
+                     2025-07-01T10:30:00-03:00,CompraCripto,300,T,L,a@a.com,1,true,0,XMR,1,300,,,,\n\
+                     2025-07-01T10:30:00-03:00,VentaCripto,1200,T,L,a@a.com,1,true,0,XMR,4,300,,,,\n\
+                     2025-07-01T10:30:00-03:00,RetiroFiat,300,T,L,a@a.com,1,true,0,,,,,,,\
+                     MercadoPago\n\
+                     2025-07-01T10:30:00-03:00,RetiroFiat,200,T,L,a@a.com,1,true,0,,,,,,,\
+                     MercadoPago\n";
+        let Err(ErrorCsv::Filas(errores)) =
+            s.importar_transacciones_csv(texto.as_bytes(), &MapeoColumnas::new())
+        else {
+            panic!("se esperaban filas sin saldo");
+        };
+        let resumen: Vec<(u64, Option<&str>)> = errores
+            .iter()
+            .map(|e| (e.linea, e.columna.as_deref()))
+            .collect();
+        // la venta supera el XMR que hay sumando el de la compra anterior, y el primer retiro
+        // el fiat que quedo; el ultimo entra porque el primero no se aplico
+        assert_eq!(
+            resumen,
+            vec![(3, Some("monto_cripto")), (4, Some("monto_fiat"))]
+        );
+        assert_eq!(saldos(&s), antes);
+        assert!(s.transacciones.is_empty());
+    }
+
+    #[test]
+    fn test_importar_transacciones_csv_sin_poder_guardar() {
+        let mut s = setup()
+            .sistema
+            .con_repositorio(RepositorioLleno::compartido());
+        let antes = saldos(&s);
+        let texto = "fecha_hora,tipo,monto_fiat,usuario_nombre,usuario_apellido,usuario_email,\
+                     usuario_dni,usuario_validado,usuario_monto_fiat\n\
+                     2025-07-01T10:30:00-03:00,IngresoFiat,300,T,L,a@a.com,1,true,0\n\
+                     2025-07-01T10:30:00-03:00,IngresoFiat,300,T,L,a@a.com,1,true,0\n";
+        assert!(
+            s.importar_transacciones_csv(texto.as_bytes(), &MapeoColumnas::new())
+                .is_err()
+        );
+        assert_eq!(saldos(&s), antes);
+        assert!(s.transacciones.is_empty());
+    }
 }
 
 /*
@@ -0,0 +1,245 @@
+#![allow(dead_code, unused_variables)]
+
+use crate::tp03::ej03::Fecha;
+use crate::tp03::fecha_hora::{DESPLAZAMIENTO_ARGENTINA, FechaHora};
+use crate::tp05::repositorio::PersistenciaError;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+// Pasa los datos de una version a la siguiente, trabajando sobre el JSON sin tipar.
+pub type Migracion = fn(Value) -> Result<Value, String>;
+
+// Version del formato de un archivo y como llevar los archivos viejos a la actual. Los
+// archivos sin sobre son de la version 1, el formato original; cada migracion registrada
+// suma una version.
+#[derive(Clone, Default)]
+pub struct Esquema {
+    migraciones: Vec<Migracion>,
+}
+
+// Lo que se escribe realmente en el archivo.
+#[derive(Serialize, Deserialize)]
+struct Sobre<T> {
+    version: u32,
+    datos: T,
+}
+
+impl Esquema {
+    pub fn new() -> Self {
+        Esquema::default()
+    }
+
+    // Agrega la migracion de la version actual a la siguiente.
+    pub fn migracion(mut self, migracion: Migracion) -> Self {
+        self.migraciones.push(migracion);
+        self
+    }
+
+    pub fn version(&self) -> u32 {
+        self.migraciones.len() as u32 + 1
+    }
+
+    pub fn a_texto<T: Serialize>(&self, datos: &T) -> Result<String, PersistenciaError> {
+        let sobre = Sobre {
+            version: self.version(),
+            datos,
+        };
+        Ok(serde_json::to_string_pretty(&sobre)?)
+    }
+
+    pub fn a_linea<T: Serialize>(&self, datos: &T) -> Result<String, PersistenciaError> {
+        let sobre = Sobre {
+            version: self.version(),
+            datos,
+        };
+        Ok(serde_json::to_string(&sobre)?)
+    }
+
+    // Saca los datos del sobre, aplica las migraciones que falten y los convierte a T.
+    pub fn desenvolver<T: DeserializeOwned>(&self, valor: Value) -> Result<T, PersistenciaError> {
+        let (version, mut datos) = match valor {
+            Value::Object(mut sobre) if sobre.len() == 2 && sobre.contains_key("datos") => {
+                let version = sobre.get("version").and_then(Value::as_u64);
+                match (version, sobre.remove("datos")) {
+                    (Some(version), Some(datos)) => {
+                        // una version que no entra en u32 se informa como la maxima
+                        let version = u32::try_from(version).map_err(|_| {
+                            PersistenciaError::VersionDesconocida {
+                                version: u32::MAX,
+                                soportada: self.version(),
+                            }
+                        })?;
+                        (version, datos)
+                    }
+                    (_, datos) => {
+                        sobre.insert("datos".to_string(), datos.unwrap_or(Value::Null));
+                        (1, Value::Object(sobre))
+                    }
+                }
+            }
+            otro => (1, otro),
+        };
+
+        if version == 0 || version > self.version() {
+            return Err(PersistenciaError::VersionDesconocida {
+                version,
+                soportada: self.version(),
+            });
+        }
+        for (i, migracion) in self
+            .migraciones
+            .iter()
+            .enumerate()
+            .skip(version as usize - 1)
+        {
+            datos = migracion(datos).map_err(|detalle| PersistenciaError::Migracion {
+                version: i as u32 + 1,
+                detalle,
+            })?;
+        }
+        Ok(serde_json::from_value(datos)?)
+    }
+}
+
+// "ROJO" -> "Rojo": los enums de tp03 se serializaban en mayusculas y los de tp05 no.
+pub fn normalizar_variante(valor: &mut Value) {
+    if let Value::String(texto) = valor {
+        let mut letras = texto.chars();
+        if let Some(primera) = letras.next() {
+            *texto = primera
+                .to_uppercase()
+                .chain(letras.flat_map(char::to_lowercase))
+                .collect();
+        }
+    }
+}
+
+// Una Fecha serializada pasa a la FechaHora del inicio de ese dia en Argentina, para los
+// campos que antes guardaban solo el dia.
+pub fn fecha_a_fecha_hora(fecha: Value) -> Result<Value, String> {
+    let fecha: Fecha = serde_json::from_value(fecha).map_err(|e| e.to_string())?;
+    let inicio =
+        FechaHora::inicio_del_dia(&fecha, DESPLAZAMIENTO_ARGENTINA).map_err(|e| e.to_string())?;
+    serde_json::to_value(inicio).map_err(|e| e.to_string())
+}
+
+// Aplica `f` a cada elemento de una lista; falla si los datos no son una lista.
+pub fn por_cada_elemento(
+    datos: Value,
+    f: impl Fn(&mut serde_json::Map<String, Value>) -> Result<(), String>,
+) -> Result<Value, String> {
+    let Value::Array(mut elementos) = datos else {
+        return Err("se esperaba una lista".to_string());
+    };
+    for elemento in &mut elementos {
+        match elemento {
+            Value::Object(campos) => f(campos)?,
+            _ => return Err("se esperaba una lista de objetos".to_string()),
+        }
+    }
+    Ok(Value::Array(elementos))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn duplicar(mut datos: Value) -> Result<Value, String> {
+        datos["n"] = json!(datos["n"].as_u64().ok_or("falta n")? * 2);
+        Ok(datos)
+    }
+
+    fn renombrar(mut datos: Value) -> Result<Value, String> {
+        let n = datos["n"].take();
+        Ok(json!({ "numero": n }))
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct V3 {
+        numero: u64,
+    }
+
+    fn esquema() -> Esquema {
+        Esquema::new().migracion(duplicar).migracion(renombrar)
+    }
+
+    #[test]
+    fn test_version_y_sobre() {
+        assert_eq!(Esquema::new().version(), 1);
+        assert_eq!(esquema().version(), 3);
+        let texto = esquema().a_linea(&json!({"numero": 4})).unwrap();
+        assert_eq!(texto, r#"{"version":3,"datos":{"numero":4}}"#);
+    }
+
+    #[test]
+    fn test_migra_desde_cada_version() {
+        // sin sobre: version 1
+        let v: V3 = esquema().desenvolver(json!({"n": 2})).unwrap();
+        assert_eq!(v, V3 { numero: 4 });
+        let v: V3 = esquema()
+            .desenvolver(json!({"version": 2, "datos": {"n": 2}}))
+            .unwrap();
+        assert_eq!(v, V3 { numero: 2 });
+        let v: V3 = esquema()
+            .desenvolver(json!({"version": 3, "datos": {"numero": 2}}))
+            .unwrap();
+        assert_eq!(v, V3 { numero: 2 });
+    }
+
+    #[test]
+    fn test_version_desconocida_y_migracion_fallida() {
+        assert!(matches!(
+            esquema().desenvolver::<V3>(json!({"version": 4, "datos": {}})),
+            Err(PersistenciaError::VersionDesconocida {
+                version: 4,
+                soportada: 3
+            })
+        ));
+        assert!(matches!(
+            esquema().desenvolver::<V3>(json!({"version": 4_294_967_297u64, "datos": {}})),
+            Err(PersistenciaError::VersionDesconocida {
+                version: u32::MAX,
+                soportada: 3
+            })
+        ));
+        assert!(matches!(
+            esquema().desenvolver::<V3>(json!({"x": 1})),
+            Err(PersistenciaError::Migracion { version: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn test_datos_que_parecen_un_sobre() {
+        // un objeto con campos "datos" y otro que no es una version valida no es un sobre
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Raro {
+            datos: u32,
+            version: String,
+        }
+        let v: Raro = Esquema::new()
+            .desenvolver(json!({"datos": 1, "version": "x"}))
+            .unwrap();
+        assert_eq!(v.datos, 1);
+    }
+
+    #[test]
+    fn test_fecha_a_fecha_hora() {
+        assert_eq!(
+            fecha_a_fecha_hora(json!({"año": 2025, "dia": 30, "mes": 6})),
+            Ok(json!("2025-06-30T00:00:00-03:00"))
+        );
+        assert!(fecha_a_fecha_hora(json!({"año": 2025, "dia": 31, "mes": 6})).is_err());
+    }
+
+    #[test]
+    fn test_normalizar_variante() {
+        let mut v = json!("AMARILLO");
+        normalizar_variante(&mut v);
+        assert_eq!(v, json!("Amarillo"));
+        let mut v = json!("Rojo");
+        normalizar_variante(&mut v);
+        assert_eq!(v, json!("Rojo"));
+    }
+}
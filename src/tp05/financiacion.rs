@@ -0,0 +1,464 @@
+#![allow(dead_code, unused_variables)]
+
+use crate::tp05::ej01::Auto;
+use crate::tp05::precios::ReglasPrecio;
+use crate::tp05::tabla::{self, ErrorCsv, ErrorFila, Fila, RegistroCsv};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io;
+
+// Como se devuelve el capital financiado.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum Sistema {
+    // Cuota fija: al principio se paga mas interes y al final mas capital.
+    Frances,
+    // Capital fijo: la cuota baja a medida que baja el saldo.
+    Aleman,
+    // Cuotas iguales sin interes; puede tener gastos, que son los que dan el CFT.
+    TasaCero,
+}
+
+// Un pedido de financiacion: con plan() se arma la tabla de cuotas. La tasa es nominal
+// anual (TNA) en porcentaje y se cobra por mes, TNA / 12, como en los creditos prendarios.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Financiacion {
+    precio: f64,
+    anticipo: f64,
+    tasa_anual: f64,
+    meses: u32,
+    sistema: Sistema,
+    // Se pagan al otorgar el credito, junto con el anticipo.
+    gastos_otorgamiento: f64,
+    // Seguro y otros cargos que se suman a cada cuota.
+    cargo_mensual: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Cuota {
+    pub numero: u32,
+    pub amortizacion: f64,
+    pub interes: f64,
+    pub cargos: f64,
+    // Lo que se paga ese mes.
+    pub total: f64,
+    // Lo que queda por devolver despues de pagarla.
+    pub saldo: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlanDePagos {
+    pub sistema: Sistema,
+    pub precio: f64,
+    pub anticipo: f64,
+    pub tasa_anual: f64,
+    pub gastos_otorgamiento: f64,
+    pub cuotas: Vec<Cuota>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum FinanciacionError {
+    PrecioInvalido(f64),
+    // Negativo, o tan grande que no queda nada por financiar.
+    AnticipoInvalido(f64),
+    TasaInvalida(f64),
+    CargoInvalido(f64),
+    // Los gastos de otorgamiento igualan o superan lo que se financia.
+    GastosExcesivos(f64),
+    SinCuotas,
+    DemasiadasCuotas(u32),
+}
+
+impl fmt::Display for FinanciacionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FinanciacionError::PrecioInvalido(precio) => {
+                write!(f, "El precio a financiar no es valido: {}", precio)
+            }
+            FinanciacionError::AnticipoInvalido(anticipo) => write!(
+                f,
+                "El anticipo no puede ser negativo ni cubrir todo el precio: {}",
+                anticipo
+            ),
+            FinanciacionError::TasaInvalida(tasa) => {
+                write!(f, "La tasa anual no puede ser negativa: {}", tasa)
+            }
+            FinanciacionError::CargoInvalido(cargo) => {
+                write!(f, "Los gastos y cargos no pueden ser negativos: {}", cargo)
+            }
+            FinanciacionError::GastosExcesivos(gastos) => write!(
+                f,
+                "Los gastos de otorgamiento no pueden cubrir todo el monto financiado: {}",
+                gastos
+            ),
+            FinanciacionError::SinCuotas => write!(f, "El plan tiene que tener al menos una cuota"),
+            FinanciacionError::DemasiadasCuotas(meses) => write!(
+                f,
+                "El plan no puede tener mas de {} cuotas: {}",
+                MESES_MAXIMOS, meses
+            ),
+        }
+    }
+}
+
+impl std::error::Error for FinanciacionError {}
+
+// Diez años, mas de lo que dura cualquier prendario.
+pub const MESES_MAXIMOS: u32 = 120;
+
+// Los importes de la tabla se redondean a centavos; la ultima cuota absorbe la diferencia
+// para que el capital devuelto sea exactamente el financiado.
+fn centavos(monto: f64) -> f64 {
+    (monto * 100.0).round() / 100.0
+}
+
+fn no_negativo(monto: f64) -> bool {
+    monto.is_finite() && monto >= 0.0
+}
+
+impl Financiacion {
+    // Sin anticipo, al 0% y en sistema frances hasta que se indique otra cosa.
+    pub fn new(precio: f64, meses: u32) -> Self {
+        Financiacion {
+            precio,
+            anticipo: 0.0,
+            tasa_anual: 0.0,
+            meses,
+            sistema: Sistema::Frances,
+            gastos_otorgamiento: 0.0,
+            cargo_mensual: 0.0,
+        }
+    }
+
+    // Con el precio de venta del auto segun `reglas`.
+    pub fn para_auto(auto: &Auto, reglas: &ReglasPrecio, meses: u32) -> Self {
+        Financiacion::new(auto.calcular_precio_con(reglas), meses)
+    }
+
+    pub fn con_anticipo(mut self, anticipo: f64) -> Self {
+        self.anticipo = anticipo;
+        self
+    }
+
+    // 60.0 es una TNA del 60%.
+    pub fn con_tasa_anual(mut self, tasa_anual: f64) -> Self {
+        self.tasa_anual = tasa_anual;
+        self
+    }
+
+    pub fn con_sistema(mut self, sistema: Sistema) -> Self {
+        self.sistema = sistema;
+        self
+    }
+
+    pub fn con_gastos_otorgamiento(mut self, gastos: f64) -> Self {
+        self.gastos_otorgamiento = gastos;
+        self
+    }
+
+    pub fn con_cargo_mensual(mut self, cargo: f64) -> Self {
+        self.cargo_mensual = cargo;
+        self
+    }
+
+    fn validar(&self) -> Result<(), FinanciacionError> {
+        if !self.precio.is_finite() || self.precio <= 0.0 {
+            return Err(FinanciacionError::PrecioInvalido(self.precio));
+        }
+        if !no_negativo(self.anticipo) || self.anticipo >= self.precio {
+            return Err(FinanciacionError::AnticipoInvalido(self.anticipo));
+        }
+        if !no_negativo(self.tasa_anual) {
+            return Err(FinanciacionError::TasaInvalida(self.tasa_anual));
+        }
+        for cargo in [self.gastos_otorgamiento, self.cargo_mensual] {
+            if !no_negativo(cargo) {
+                return Err(FinanciacionError::CargoInvalido(cargo));
+            }
+        }
+        // sin esto el CFT no tiene solucion y la biseccion termina en el tope
+        if self.gastos_otorgamiento >= centavos(self.precio - self.anticipo) {
+            return Err(FinanciacionError::GastosExcesivos(self.gastos_otorgamiento));
+        }
+        if self.meses == 0 {
+            return Err(FinanciacionError::SinCuotas);
+        }
+        if self.meses > MESES_MAXIMOS {
+            return Err(FinanciacionError::DemasiadasCuotas(self.meses));
+        }
+        Ok(())
+    }
+
+    fn tasa_mensual(&self) -> f64 {
+        match self.sistema {
+            Sistema::TasaCero => 0.0,
+            Sistema::Frances | Sistema::Aleman => self.tasa_anual / 100.0 / 12.0,
+        }
+    }
+
+    pub fn plan(&self) -> Result<PlanDePagos, FinanciacionError> {
+        self.validar()?;
+        let financiado = centavos(self.precio - self.anticipo);
+        let tasa = self.tasa_mensual();
+        let n = self.meses;
+        // La cuota del frances o la amortizacion del aleman, que se repiten todos los meses.
+        let fija = match self.sistema {
+            Sistema::Frances if tasa > 0.0 => {
+                centavos(financiado * tasa / (1.0 - (1.0 + tasa).powi(-(n as i32))))
+            }
+            _ => centavos(financiado / n as f64),
+        };
+        let cargos = centavos(self.cargo_mensual);
+
+        let mut saldo = financiado;
+        let mut cuotas = Vec::with_capacity(n as usize);
+        for numero in 1..=n {
+            let interes = centavos(saldo * tasa);
+            let amortizacion = if numero == n {
+                saldo
+            } else {
+                match self.sistema {
+                    Sistema::Aleman => fija,
+                    Sistema::Frances | Sistema::TasaCero => fija - interes,
+                }
+            };
+            saldo = centavos(saldo - amortizacion);
+            cuotas.push(Cuota {
+                numero,
+                amortizacion: centavos(amortizacion),
+                interes,
+                cargos,
+                total: centavos(amortizacion + interes + cargos),
+                saldo,
+            });
+        }
+
+        Ok(PlanDePagos {
+            sistema: self.sistema,
+            precio: self.precio,
+            anticipo: self.anticipo,
+            tasa_anual: match self.sistema {
+                Sistema::TasaCero => 0.0,
+                _ => self.tasa_anual,
+            },
+            gastos_otorgamiento: centavos(self.gastos_otorgamiento),
+            cuotas,
+        })
+    }
+}
+
+impl PlanDePagos {
+    pub fn monto_financiado(&self) -> f64 {
+        centavos(self.cuotas.iter().map(|c| c.amortizacion).sum())
+    }
+
+    pub fn total_intereses(&self) -> f64 {
+        centavos(self.cuotas.iter().map(|c| c.interes).sum())
+    }
+
+    // Cuotas mas gastos de otorgamiento, sin el anticipo.
+    pub fn total_a_pagar(&self) -> f64 {
+        centavos(self.cuotas.iter().map(|c| c.total).sum::<f64>() + self.gastos_otorgamiento)
+    }
+
+    // Costo financiero total como tasa efectiva anual en porcentaje: la tasa que iguala lo
+    // que se recibe (lo financiado menos los gastos) con lo que se paga en cada cuota,
+    // contando intereses y cargos.
+    pub fn cft(&self) -> f64 {
+        let recibido = self.monto_financiado() - self.gastos_otorgamiento;
+        let valor_actual = |tasa: f64| -> f64 {
+            self.cuotas
+                .iter()
+                .map(|c| c.total / (1.0 + tasa).powi(c.numero as i32))
+                .sum::<f64>()
+                - recibido
+        };
+        if valor_actual(0.0) <= 0.0 {
+            return 0.0;
+        }
+        // El valor actual baja cuando sube la tasa: se busca por biseccion donde se anula.
+        let mut alta = 1.0;
+        while valor_actual(alta) > 0.0 && alta < 1e6 {
+            alta *= 2.0;
+        }
+        let mut baja = 0.0;
+        for _ in 0..200 {
+            let medio = (baja + alta) / 2.0;
+            if valor_actual(medio) > 0.0 {
+                baja = medio;
+            } else {
+                alta = medio;
+            }
+        }
+        ((1.0 + baja).powi(12) - 1.0) * 100.0
+    }
+
+    pub fn exportar_csv(&self, escritor: impl io::Write) -> Result<(), ErrorCsv> {
+        tabla::exportar(&self.cuotas, escritor)
+    }
+}
+
+impl RegistroCsv for Cuota {
+    const COLUMNAS: &'static [&'static str] = &[
+        "numero",
+        "amortizacion",
+        "interes",
+        "cargos",
+        "total",
+        "saldo",
+    ];
+
+    fn a_fila(&self) -> Vec<String> {
+        vec![
+            self.numero.to_string(),
+            format!("{:.2}", self.amortizacion),
+            format!("{:.2}", self.interes),
+            format!("{:.2}", self.cargos),
+            format!("{:.2}", self.total),
+            format!("{:.2}", self.saldo),
+        ]
+    }
+
+    fn de_fila(fila: &Fila) -> Result<Self, ErrorFila> {
+        Ok(Cuota {
+            numero: fila.valor("numero")?,
+            amortizacion: fila.valor("amortizacion")?,
+            interes: fila.valor("interes")?,
+            cargos: fila.valor("cargos")?,
+            total: fila.valor("total")?,
+            saldo: fila.valor("saldo")?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tp05::ej01::Color;
+    use crate::tp05::tabla::MapeoColumnas;
+    use crate::tp05::vin::Vin;
+
+    #[test]
+    fn test_sistema_frances() {
+        let plan = Financiacion::new(15000.0, 12)
+            .con_anticipo(3000.0)
+            .con_tasa_anual(12.0)
+            .plan()
+            .unwrap();
+        assert_eq!(plan.monto_financiado(), 12000.0);
+        assert_eq!(plan.cuotas.len(), 12);
+        assert!(plan.cuotas[..11].iter().all(|c| c.total == 1066.19));
+        assert_eq!(plan.cuotas[0].interes, 120.0);
+        assert_eq!(plan.cuotas[0].amortizacion, 946.19);
+        assert_eq!(plan.cuotas[11].saldo, 0.0);
+        assert_eq!(plan.total_intereses(), 794.23);
+        // sin cargos el CFT es la tasa efectiva: 1.01^12 - 1
+        assert!((plan.cft() - 12.6825).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_sistema_aleman() {
+        let plan = Financiacion::new(12000.0, 12)
+            .con_tasa_anual(12.0)
+            .con_sistema(Sistema::Aleman)
+            .plan()
+            .unwrap();
+        assert!(plan.cuotas.iter().all(|c| c.amortizacion == 1000.0));
+        assert_eq!(plan.cuotas[0].total, 1120.0);
+        assert_eq!(plan.cuotas[11].total, 1010.0);
+        assert_eq!(plan.total_intereses(), 780.0);
+        assert_eq!(plan.total_a_pagar(), 12780.0);
+        assert!((plan.cft() - 12.6825).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_tasa_cero_con_gastos() {
+        let plan = Financiacion::new(10000.0, 3)
+            .con_tasa_anual(50.0)
+            .con_sistema(Sistema::TasaCero)
+            .plan()
+            .unwrap();
+        let totales: Vec<f64> = plan.cuotas.iter().map(|c| c.total).collect();
+        assert_eq!(totales, vec![3333.33, 3333.33, 3333.34]);
+        assert_eq!(plan.total_intereses(), 0.0);
+        assert_eq!(plan.tasa_anual, 0.0);
+        assert_eq!(plan.cft(), 0.0);
+
+        // sin interes pero con gastos el credito igual tiene costo
+        let plan = Financiacion::new(12000.0, 12)
+            .con_sistema(Sistema::TasaCero)
+            .con_gastos_otorgamiento(300.0)
+            .con_cargo_mensual(20.0)
+            .plan()
+            .unwrap();
+        assert_eq!(plan.cuotas[0].total, 1020.0);
+        assert_eq!(plan.total_a_pagar(), 12540.0);
+        assert!(plan.cft() > 8.0 && plan.cft() < 9.0);
+    }
+
+    #[test]
+    fn test_financiar_un_auto() {
+        let auto = Auto::new(
+            Vin::new("1HGCM82633A004352").unwrap(),
+            "Fiat".to_string(),
+            "Uno".to_string(),
+            2010,
+            10000.0,
+            Color::Negro,
+        );
+        let reglas = ReglasPrecio::por_defecto();
+        let financiacion = Financiacion::para_auto(&auto, reglas, 6).con_anticipo(3000.0);
+        let plan = financiacion.plan().unwrap();
+        assert_eq!(plan.precio, 9000.0);
+        assert_eq!(plan.monto_financiado(), 6000.0);
+
+        assert_eq!(
+            financiacion.clone().con_anticipo(9000.0).plan(),
+            Err(FinanciacionError::AnticipoInvalido(9000.0))
+        );
+        assert_eq!(
+            Financiacion::para_auto(&auto, reglas, 0).plan(),
+            Err(FinanciacionError::SinCuotas)
+        );
+        assert_eq!(
+            Financiacion::para_auto(&auto, reglas, u32::MAX).plan(),
+            Err(FinanciacionError::DemasiadasCuotas(u32::MAX))
+        );
+        assert!(
+            Financiacion::para_auto(&auto, reglas, MESES_MAXIMOS)
+                .plan()
+                .is_ok()
+        );
+        assert_eq!(
+            financiacion.clone().con_gastos_otorgamiento(6000.0).plan(),
+            Err(FinanciacionError::GastosExcesivos(6000.0))
+        );
+        assert_eq!(
+            financiacion
+                .con_tasa_anual(-1.0)
+                .plan()
+                .unwrap_err()
+                .to_string(),
+            "La tasa anual no puede ser negativa: -1"
+        );
+    }
+
+    #[test]
+    fn test_exportar_tabla_csv() {
+        let plan = Financiacion::new(3000.0, 3)
+            .con_tasa_anual(12.0)
+            .con_sistema(Sistema::Aleman)
+            .plan()
+            .unwrap();
+        let mut csv = vec![];
+        plan.exportar_csv(&mut csv).unwrap();
+        assert_eq!(
+            String::from_utf8(csv.clone()).unwrap(),
+            "numero,amortizacion,interes,cargos,total,saldo\n\
+             1,1000.00,30.00,0.00,1030.00,2000.00\n\
+             2,1000.00,20.00,0.00,1020.00,1000.00\n\
+             3,1000.00,10.00,0.00,1010.00,0.00\n"
+        );
+        let cuotas: Vec<Cuota> = tabla::importar(csv.as_slice(), &MapeoColumnas::new()).unwrap();
+        assert_eq!(cuotas, plan.cuotas);
+    }
+}
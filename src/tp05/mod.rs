@@ -1,6 +1,16 @@
+pub mod cifrado;
+pub mod diario;
 pub mod ej01;
 pub mod ej02;
 pub mod ej03;
 pub mod ej04;
 pub mod ej05;
 pub mod ej06;
+pub mod esquema;
+pub mod financiacion;
+pub mod precios;
+#[cfg(test)]
+pub mod pruebas;
+pub mod repositorio;
+pub mod tabla;
+pub mod vin;
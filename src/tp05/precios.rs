@@ -0,0 +1,456 @@
+#![allow(dead_code, unused_variables)]
+
+use crate::tp03::consulta_autos::DatosAuto;
+use crate::tp05::ej01::{Auto, Color};
+use crate::tp05::repositorio::{
+    DIRECTORIO_REGISTROS, PersistenciaError, Repositorio, RepositorioJson,
+};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::LazyLock;
+
+// Las reglas con las que se pasa del precio bruto al de venta. Marketing las edita en un
+// JSON como este, sin sobre de version:
+//
+// {"reglas": [
+//   {"nombre": "BMW", "si": {"marcas": ["BMW"]}, "efecto": {"Porcentaje": 15.0}},
+//   {"nombre": "Patentamiento", "prioridad": 1, "efecto": {"Fijo": 500.0}}
+// ]}
+//
+// Se aplican por prioridad, de menor a mayor (0 si no se indica). Los porcentajes de una
+// misma prioridad se suman y se calculan sobre el precio que dejo la prioridad anterior;
+// despues se suman los montos fijos.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReglasPrecio {
+    reglas: Vec<Regla>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Regla {
+    nombre: String,
+    #[serde(default)]
+    prioridad: u32,
+    #[serde(default)]
+    si: Condicion,
+    efecto: Efecto,
+}
+
+// Todas las condiciones indicadas tienen que cumplirse; sin ninguna, la regla aplica siempre.
+// Las listas aceptan cualquiera de sus valores, los rangos incluyen los extremos y el precio
+// es el bruto.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Condicion {
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    marcas: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    colores: Vec<Color>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    año_desde: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    año_hasta: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    precio_desde: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    precio_hasta: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Efecto {
+    // 25.0 es un recargo del 25%, -10.0 un descuento del 10%.
+    Porcentaje(f64),
+    Fijo(f64),
+}
+
+// El precio de un auto y las reglas que lo formaron, en el orden en que se aplicaron.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cotizacion {
+    pub precio_bruto: f64,
+    pub precio_final: f64,
+    pub aplicadas: Vec<ReglaAplicada>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReglaAplicada {
+    pub nombre: String,
+    pub efecto: Efecto,
+    // Cuanto sumo (o resto, si es negativo) al precio.
+    pub importe: f64,
+}
+
+#[derive(Debug)]
+pub enum ReglasError {
+    Persistencia(PersistenciaError),
+    Invalida { regla: String, detalle: String },
+}
+
+impl fmt::Display for ReglasError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReglasError::Persistencia(e) => write!(f, "{}", e),
+            ReglasError::Invalida { regla, detalle } => {
+                write!(
+                    f,
+                    "La regla de precio '{}' no es valida: {}",
+                    regla, detalle
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReglasError {}
+
+impl From<PersistenciaError> for ReglasError {
+    fn from(e: PersistenciaError) -> Self {
+        ReglasError::Persistencia(e)
+    }
+}
+
+const ARCHIVO_REGLAS: &str = "ej01/reglas_precio.json";
+
+// Las que estaban fijas en Auto::calcular_precio. El archivo de ejemplo arranca con estas
+// mismas, pero lo que edite marketing se lee en cada corrida con cargar().
+static POR_DEFECTO: LazyLock<ReglasPrecio> = LazyLock::new(|| {
+    ReglasPrecio::new()
+        .con_regla(
+            Regla::new("Colores vivos", Efecto::Porcentaje(25.0)).con_colores(vec![
+                Color::Rojo,
+                Color::Amarillo,
+                Color::Azul,
+            ]),
+        )
+        .con_regla(
+            Regla::new("Colores neutros", Efecto::Porcentaje(-10.0)).con_colores(vec![
+                Color::Verde,
+                Color::Blanco,
+                Color::Negro,
+            ]),
+        )
+        .con_regla(Regla::new("BMW", Efecto::Porcentaje(15.0)).con_marcas(vec!["BMW"]))
+        .con_regla(
+            Regla::new("Anterior al 2000", Efecto::Porcentaje(-5.0)).con_años(None, Some(1999)),
+        )
+});
+
+impl ReglasPrecio {
+    pub fn new() -> Self {
+        ReglasPrecio { reglas: vec![] }
+    }
+
+    pub fn por_defecto() -> &'static ReglasPrecio {
+        &POR_DEFECTO
+    }
+
+    // Si el repositorio todavia no tiene reglas se usan las por defecto.
+    pub fn abrir(repositorio: &impl Repositorio<ReglasPrecio>) -> Result<Self, ReglasError> {
+        let reglas = repositorio
+            .cargar()?
+            .unwrap_or_else(|| ReglasPrecio::por_defecto().clone());
+        reglas.validar()?;
+        Ok(reglas)
+    }
+
+    // Igual que abrir pero con el archivo que edita marketing en DIRECTORIO_REGISTROS.
+    pub fn cargar() -> Result<Self, ReglasError> {
+        ReglasPrecio::abrir(&RepositorioJson::new(DIRECTORIO_REGISTROS, ARCHIVO_REGLAS))
+    }
+
+    pub fn con_regla(mut self, regla: Regla) -> Self {
+        self.reglas.push(regla);
+        self
+    }
+
+    pub fn validar(&self) -> Result<(), ReglasError> {
+        for regla in &self.reglas {
+            regla.validar().map_err(|detalle| ReglasError::Invalida {
+                regla: regla.nombre.clone(),
+                detalle: detalle.to_string(),
+            })?;
+        }
+        Ok(())
+    }
+
+    pub fn precio(&self, auto: &Auto) -> f64 {
+        self.cotizar(auto).precio_final
+    }
+
+    pub fn cotizar(&self, auto: &Auto) -> Cotizacion {
+        let precio_bruto = auto.precio_bruto();
+        let mut reglas: Vec<&Regla> = self.reglas.iter().filter(|r| r.si.cumple(auto)).collect();
+        reglas.sort_by_key(|r| r.prioridad);
+
+        let mut precio = precio_bruto;
+        let mut aplicadas = vec![];
+        for grupo in reglas.chunk_by(|a, b| a.prioridad == b.prioridad) {
+            let base = precio;
+            let mut tasa = 0.0;
+            let mut fijo = 0.0;
+            for regla in grupo {
+                let importe = match regla.efecto {
+                    Efecto::Porcentaje(p) => {
+                        tasa += p / 100.0;
+                        base * p / 100.0
+                    }
+                    Efecto::Fijo(monto) => {
+                        fijo += monto;
+                        monto
+                    }
+                };
+                aplicadas.push(ReglaAplicada {
+                    nombre: regla.nombre.clone(),
+                    efecto: regla.efecto.clone(),
+                    importe,
+                });
+            }
+            precio = base * (1.0 + tasa) + fijo;
+        }
+        Cotizacion {
+            precio_bruto,
+            precio_final: precio,
+            aplicadas,
+        }
+    }
+}
+
+impl Default for ReglasPrecio {
+    fn default() -> Self {
+        ReglasPrecio::por_defecto().clone()
+    }
+}
+
+impl Regla {
+    pub fn new(nombre: &str, efecto: Efecto) -> Self {
+        Regla {
+            nombre: nombre.to_string(),
+            prioridad: 0,
+            si: Condicion::default(),
+            efecto,
+        }
+    }
+
+    pub fn con_prioridad(mut self, prioridad: u32) -> Self {
+        self.prioridad = prioridad;
+        self
+    }
+
+    pub fn con_marcas(mut self, marcas: Vec<&str>) -> Self {
+        self.si.marcas = marcas.into_iter().map(str::to_string).collect();
+        self
+    }
+
+    pub fn con_colores(mut self, colores: Vec<Color>) -> Self {
+        self.si.colores = colores;
+        self
+    }
+
+    pub fn con_años(mut self, desde: Option<u32>, hasta: Option<u32>) -> Self {
+        self.si.año_desde = desde;
+        self.si.año_hasta = hasta;
+        self
+    }
+
+    pub fn con_precios(mut self, desde: Option<f64>, hasta: Option<f64>) -> Self {
+        self.si.precio_desde = desde;
+        self.si.precio_hasta = hasta;
+        self
+    }
+
+    fn validar(&self) -> Result<(), &'static str> {
+        if self.nombre.trim().is_empty() {
+            return Err("no tiene nombre");
+        }
+        match self.efecto {
+            Efecto::Porcentaje(p) if !p.is_finite() || p < -100.0 => {
+                return Err("el porcentaje tiene que ser un numero mayor o igual a -100");
+            }
+            Efecto::Fijo(monto) if !monto.is_finite() => {
+                return Err("el monto fijo no es un numero");
+            }
+            _ => {}
+        }
+        let si = &self.si;
+        if let (Some(desde), Some(hasta)) = (si.año_desde, si.año_hasta)
+            && desde > hasta
+        {
+            return Err("año_desde es posterior a año_hasta");
+        }
+        let precios = [si.precio_desde, si.precio_hasta];
+        if precios.iter().flatten().any(|p| !p.is_finite()) {
+            return Err("precio_desde y precio_hasta tienen que ser numeros");
+        }
+        if let [Some(desde), Some(hasta)] = precios
+            && desde > hasta
+        {
+            return Err("precio_desde es mayor que precio_hasta");
+        }
+        Ok(())
+    }
+}
+
+impl Condicion {
+    fn cumple(&self, auto: &Auto) -> bool {
+        let precio = auto.precio_bruto();
+        (self.marcas.is_empty() || self.marcas.iter().any(|m| m == auto.marca()))
+            && (self.colores.is_empty() || self.colores.contains(auto.color()))
+            && self.año_desde.is_none_or(|desde| auto.año() >= desde)
+            && self.año_hasta.is_none_or(|hasta| auto.año() <= hasta)
+            && self.precio_desde.is_none_or(|desde| precio >= desde)
+            && self.precio_hasta.is_none_or(|hasta| precio <= hasta)
+    }
+}
+
+impl fmt::Display for Efecto {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Efecto::Porcentaje(p) => write!(f, "{:+}%", p),
+            Efecto::Fijo(monto) => write!(f, "{:+.2} fijo", monto),
+        }
+    }
+}
+
+impl Cotizacion {
+    // Una linea por regla, para mostrarle al cliente de donde sale el precio.
+    pub fn explicacion(&self) -> String {
+        let mut lineas = vec![format!("Precio bruto: {:.2}", self.precio_bruto)];
+        for regla in &self.aplicadas {
+            lineas.push(format!(
+                "{} ({}): {:+.2}",
+                regla.nombre, regla.efecto, regla.importe
+            ));
+        }
+        lineas.push(format!("Precio final: {:.2}", self.precio_final));
+        lineas.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tp05::repositorio::RepositorioMemoria;
+    use crate::tp05::vin::Vin;
+
+    fn auto(marca: &str, año: u32, precio_bruto: f64, color: Color) -> Auto {
+        Auto::new(
+            Vin::new("1HGCM82633A004352").unwrap(),
+            marca.to_string(),
+            "Modelo".to_string(),
+            año,
+            precio_bruto,
+            color,
+        )
+    }
+
+    #[test]
+    fn test_reglas_por_defecto_como_antes() {
+        let reglas = ReglasPrecio::por_defecto();
+        assert_eq!(
+            reglas.precio(&auto("Ford", 1990, 100.0, Color::Verde)),
+            85.0
+        );
+        assert_eq!(reglas.precio(&auto("BMW", 2005, 100.0, Color::Rojo)), 140.0);
+        assert_eq!(reglas.precio(&auto("BMW", 1999, 100.0, Color::Azul)), 135.0);
+
+        let cotizacion = reglas.cotizar(&auto("BMW", 1990, 1000.0, Color::Negro));
+        assert_eq!(
+            cotizacion.explicacion(),
+            "Precio bruto: 1000.00\n\
+             Colores neutros (-10%): -100.00\n\
+             BMW (+15%): +150.00\n\
+             Anterior al 2000 (-5%): -50.00\n\
+             Precio final: 1000.00"
+        );
+    }
+
+    #[test]
+    fn test_prioridades_y_montos_fijos() {
+        let reglas = ReglasPrecio::new()
+            .con_regla(Regla::new("Patentamiento", Efecto::Fijo(500.0)).con_prioridad(2))
+            .con_regla(Regla::new("IVA", Efecto::Porcentaje(21.0)).con_prioridad(1))
+            .con_regla(
+                Regla::new("Liquidacion", Efecto::Porcentaje(-20.0))
+                    .con_precios(Some(5000.0), None),
+            )
+            .con_regla(
+                Regla::new("Gama baja", Efecto::Fijo(-100.0)).con_precios(None, Some(4999.0)),
+            );
+
+        // 10000 -20% = 8000, +21% = 9680, +500 = 10180
+        let cotizacion = reglas.cotizar(&auto("Fiat", 2020, 10000.0, Color::Rojo));
+        assert_eq!(cotizacion.precio_final, 10180.0);
+        let nombres: Vec<&str> = cotizacion
+            .aplicadas
+            .iter()
+            .map(|r| r.nombre.as_str())
+            .collect();
+        assert_eq!(nombres, vec!["Liquidacion", "IVA", "Patentamiento"]);
+        assert_eq!(cotizacion.aplicadas[1].importe, 1680.0);
+
+        // 1000 -100 = 900, +21% = 1089, +500 = 1589
+        assert_eq!(
+            reglas.precio(&auto("Fiat", 2020, 1000.0, Color::Rojo)),
+            1589.0
+        );
+    }
+
+    #[test]
+    fn test_cargar_de_un_json() {
+        let repo = RepositorioMemoria::new().con_contenido(
+            r#"{"reglas": [
+                {"nombre": "Toyota usados", "si": {"marcas": ["Toyota"], "año_hasta": 2015},
+                 "efecto": {"Porcentaje": -30.0}},
+                {"nombre": "Flete", "prioridad": 5, "efecto": {"Fijo": 200.0}}
+            ]}"#,
+        );
+        let reglas = ReglasPrecio::abrir(&repo).unwrap();
+        assert_eq!(
+            reglas.precio(&auto("Toyota", 2010, 1000.0, Color::Rojo)),
+            900.0
+        );
+        assert_eq!(
+            reglas.precio(&auto("Toyota", 2016, 1000.0, Color::Rojo)),
+            1200.0
+        );
+
+        // sin nada guardado, las de siempre
+        let vacio = RepositorioMemoria::new();
+        assert_eq!(
+            &ReglasPrecio::abrir(&vacio).unwrap(),
+            ReglasPrecio::por_defecto()
+        );
+        ReglasPrecio::por_defecto().validar().unwrap();
+        assert_eq!(ReglasPrecio::por_defecto().reglas.len(), 4);
+
+        // el archivo de ejemplo se lee al correr, no al compilar
+        ReglasPrecio::cargar().unwrap();
+    }
+
+    #[test]
+    fn test_reglas_invalidas() {
+        let repo = RepositorioMemoria::new().con_contenido(
+            r#"{"reglas": [{"nombre": "Rango al reves", "si": {"año_desde": 2020, "año_hasta": 2010},
+                            "efecto": {"Fijo": 1.0}}]}"#,
+        );
+        match ReglasPrecio::abrir(&repo) {
+            Err(ReglasError::Invalida { regla, detalle }) => {
+                assert_eq!(regla, "Rango al reves");
+                assert_eq!(detalle, "año_desde es posterior a año_hasta");
+            }
+            otro => panic!("se esperaba una regla invalida: {:?}", otro),
+        }
+
+        let reglas =
+            ReglasPrecio::new().con_regla(Regla::new("Regalo", Efecto::Porcentaje(-150.0)));
+        assert!(matches!(
+            reglas.validar(),
+            Err(ReglasError::Invalida { .. })
+        ));
+
+        let repo = RepositorioMemoria::new()
+            .con_contenido(r#"{"reglas": [{"nombre": "x", "efecto": {"Descuento": 1.0}}]}"#);
+        assert!(matches!(
+            ReglasPrecio::abrir(&repo),
+            Err(ReglasError::Persistencia(_))
+        ));
+    }
+}
@@ -0,0 +1,14 @@
+#![allow(dead_code, unused_variables)]
+
+use std::fs;
+use std::path::PathBuf;
+
+// Ayudas compartidas por los tests de tp05.
+
+// Un directorio vacio para los tests que escriben archivos. `nombre` tiene que ser distinto
+// en cada test porque corren en paralelo.
+pub fn directorio_temporal(nombre: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("tp05-{}-{}", nombre, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
@@ -0,0 +1,1006 @@
+#![allow(dead_code, unused_variables)]
+
+use crate::tp03::compartido::Compartido;
+use crate::tp05::cifrado::{self, Cifrado};
+use crate::tp05::esquema::Esquema;
+use serde::de::DeserializeOwned;
+use serde::{Serialize, Serializer};
+use serde_json::error::Category;
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::OsString;
+use std::fmt;
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Directorio donde los sistemas guardan sus datos si no se les configura otro repositorio.
+pub const DIRECTORIO_REGISTROS: &str = "src/tp05/registros";
+
+// Cantidad de versiones anteriores que guarda RepositorioJson junto al archivo.
+pub const RESPALDOS_POR_DEFECTO: usize = 3;
+
+// Cuanto se espera a que otro proceso suelte un archivo antes de devolver Bloqueado.
+pub const ESPERA_BLOQUEO_POR_DEFECTO: Duration = Duration::from_secs(2);
+
+#[derive(Debug)]
+pub enum PersistenciaError {
+    Io(io::Error),
+    Serializacion(serde_json::Error),
+    // El contenido no es JSON valido o esta cortado, por ejemplo por una escritura a medias.
+    Corrupto(String),
+    SinRespaldo(usize),
+    // El archivo es de una version mas nueva que la que entiende este programa.
+    VersionDesconocida {
+        version: u32,
+        soportada: u32,
+    },
+    Migracion {
+        version: u32,
+        detalle: String,
+    },
+    // Otro proceso tiene tomado el archivo.
+    Bloqueado(PathBuf),
+    // Otro proceso modifico el archivo despues de que lo leimos; guardar pisaria sus cambios.
+    Conflicto(PathBuf),
+    // Contraseña incorrecta, datos cifrados manipulados o un formato de cifrado desconocido.
+    Cifrado(String),
+    #[cfg(feature = "sqlite")]
+    BaseDeDatos(rusqlite::Error),
+}
+
+impl fmt::Display for PersistenciaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PersistenciaError::Io(e) => write!(f, "Error de entrada/salida al persistir: {}", e),
+            PersistenciaError::Serializacion(e) => {
+                write!(f, "No se pudieron (de)serializar los datos: {}", e)
+            }
+            PersistenciaError::Corrupto(detalle) => write!(f, "Datos corruptos: {}", detalle),
+            PersistenciaError::SinRespaldo(n) => write!(f, "No existe el respaldo {}", n),
+            PersistenciaError::VersionDesconocida { version, soportada } => write!(
+                f,
+                "Los datos son de la version {} y solo se soporta hasta la {}",
+                version, soportada
+            ),
+            PersistenciaError::Migracion { version, detalle } => write!(
+                f,
+                "No se pudieron migrar los datos de la version {}: {}",
+                version, detalle
+            ),
+            PersistenciaError::Bloqueado(ruta) => write!(
+                f,
+                "El archivo {} esta siendo usado por otro proceso",
+                ruta.display()
+            ),
+            PersistenciaError::Conflicto(ruta) => write!(
+                f,
+                "El archivo {} cambio desde que se cargo; hay que volver a cargarlo antes de guardar",
+                ruta.display()
+            ),
+            PersistenciaError::Cifrado(detalle) => {
+                write!(f, "No se pudieron descifrar los datos: {}", detalle)
+            }
+            #[cfg(feature = "sqlite")]
+            PersistenciaError::BaseDeDatos(e) => write!(f, "Error de la base de datos: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for PersistenciaError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PersistenciaError::Io(e) => Some(e),
+            PersistenciaError::Serializacion(e) => Some(e),
+            #[cfg(feature = "sqlite")]
+            PersistenciaError::BaseDeDatos(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for PersistenciaError {
+    fn from(e: io::Error) -> Self {
+        PersistenciaError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for PersistenciaError {
+    fn from(e: serde_json::Error) -> Self {
+        PersistenciaError::Serializacion(e)
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl From<rusqlite::Error> for PersistenciaError {
+    fn from(e: rusqlite::Error) -> Self {
+        PersistenciaError::BaseDeDatos(e)
+    }
+}
+
+// Para `#[serde(serialize_with)]` en campos HashMap: escribe las claves ordenadas, asi guardar
+// dos veces el mismo estado produce exactamente el mismo archivo.
+pub fn serializar_ordenado<S, K, V>(mapa: &HashMap<K, V>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+    K: Ord + Serialize,
+    V: Serialize,
+{
+    mapa.iter()
+        .collect::<BTreeMap<_, _>>()
+        .serialize(serializer)
+}
+
+// JSON mal formado o incompleto es corrupcion; JSON valido con otra forma es un error de
+// serializacion.
+pub(crate) fn deserializar<T: DeserializeOwned>(
+    texto: &str,
+    origen: &str,
+) -> Result<T, PersistenciaError> {
+    serde_json::from_str(texto).map_err(|e| match e.classify() {
+        Category::Syntax | Category::Eof => {
+            PersistenciaError::Corrupto(format!("{} ({})", origen, e))
+        }
+        _ => PersistenciaError::Serializacion(e),
+    })
+}
+
+// `ruta.sufijo`, al lado del archivo.
+pub(crate) fn con_sufijo(ruta: &Path, sufijo: &str) -> PathBuf {
+    let mut nombre = OsString::from(ruta.as_os_str());
+    nombre.push(".");
+    nombre.push(sufijo);
+    PathBuf::from(nombre)
+}
+
+// Bloqueo consultivo sobre `ruta.lock`, que dura hasta que se descarta. Se bloquea un archivo
+// aparte porque el de datos se reemplaza con un rename en cada guardado. Solo protege contra
+// procesos que tambien lo piden, como los repositorios y diarios de este modulo.
+pub(crate) struct Bloqueo(File);
+
+impl Bloqueo {
+    // Para escribir: nadie mas puede leer ni escribir mientras tanto.
+    pub(crate) fn exclusivo(ruta: &Path, espera: Duration) -> Result<Self, PersistenciaError> {
+        Bloqueo::tomar(ruta, true, espera)
+    }
+
+    // Para leer: varios pueden leer a la vez, pero no mientras alguien escribe.
+    pub(crate) fn compartido(ruta: &Path, espera: Duration) -> Result<Self, PersistenciaError> {
+        Bloqueo::tomar(ruta, false, espera)
+    }
+
+    fn tomar(ruta: &Path, exclusivo: bool, espera: Duration) -> Result<Self, PersistenciaError> {
+        let archivo = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(con_sufijo(ruta, "lock"))?;
+        let limite = Instant::now() + espera;
+        loop {
+            let intento = if exclusivo {
+                archivo.try_lock()
+            } else {
+                archivo.try_lock_shared()
+            };
+            match intento {
+                Ok(()) => return Ok(Bloqueo(archivo)),
+                Err(TryLockError::WouldBlock) if Instant::now() < limite => {
+                    thread::sleep(Duration::from_millis(10))
+                }
+                Err(TryLockError::WouldBlock) => {
+                    return Err(PersistenciaError::Bloqueado(ruta.to_path_buf()));
+                }
+                Err(TryLockError::Error(e)) => return Err(e.into()),
+            }
+        }
+    }
+}
+
+// Lo que tenia el archivo la ultima vez que un repositorio lo leyo o lo escribio. Antes de
+// leerlo se espera que no exista: guardar sin haber cargado no pisa un archivo que ya estaba.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Huella {
+    Ausente,
+    Contenido(u64),
+}
+
+impl Huella {
+    fn de(texto: &str) -> Huella {
+        let mut hasher = DefaultHasher::new();
+        texto.hash(&mut hasher);
+        Huella::Contenido(hasher.finish())
+    }
+
+    fn leer(ruta: &Path) -> io::Result<Huella> {
+        match fs::read_to_string(ruta) {
+            Ok(texto) => Ok(Huella::de(&texto)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Huella::Ausente),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+pub trait Repositorio<T> {
+    fn guardar(&self, datos: &T) -> Result<(), PersistenciaError>;
+
+    // Ok(None) si todavia no se guardo nada.
+    fn cargar(&self) -> Result<Option<T>, PersistenciaError>;
+}
+
+// Aplica `f` sobre una copia de `datos`, la guarda y solo entonces la deja en `datos`: si no
+// se puede guardar, lo que queda en memoria sigue siendo lo que hay en el repositorio.
+pub fn cambiar_y_guardar<T: Clone, R>(
+    repositorio: &(impl Repositorio<T> + ?Sized),
+    datos: &mut T,
+    f: impl FnOnce(&mut T) -> R,
+) -> Result<R, PersistenciaError> {
+    let mut copia = datos.clone();
+    let resultado = f(&mut copia);
+    repositorio.guardar(&copia)?;
+    *datos = copia;
+    Ok(resultado)
+}
+
+// Guarda los datos como JSON en `directorio/archivo`, creando los directorios que falten.
+// Escribe primero un archivo temporal y lo renombra, asi un corte a mitad de escritura nunca
+// deja el archivo a medias. Antes de reemplazarlo copia la version anterior a `archivo.1`,
+// corriendo las mas viejas hasta `archivo.N`. Los datos van dentro de un sobre con la
+// version del esquema, y al cargar se migran los de versiones anteriores.
+//
+// Para compartir el archivo entre procesos toma un Bloqueo mientras lee o escribe, y antes de
+// guardar verifica que nadie lo haya cambiado desde que este repositorio lo cargo; si cambio
+// devuelve Conflicto en lugar de pisarlo.
+//
+// Con una contraseña el archivo y sus respaldos se guardan cifrados.
+pub struct RepositorioJson {
+    ruta: PathBuf,
+    respaldos: usize,
+    esquema: Esquema,
+    espera_bloqueo: Duration,
+    huella: Cell<Huella>,
+    cifrado: Option<Cifrado>,
+}
+
+// Guarda el JSON en memoria, para tests. Al pasar por serde se comporta igual que el archivo.
+#[derive(Default)]
+pub struct RepositorioMemoria {
+    contenido: RefCell<Option<String>>,
+    esquema: Esquema,
+    guardados: Cell<usize>,
+}
+
+pub type RepositorioCompartido<T> = Compartido<dyn Repositorio<T>>;
+
+impl RepositorioJson {
+    pub fn new(directorio: impl AsRef<Path>, archivo: &str) -> Self {
+        RepositorioJson {
+            ruta: directorio.as_ref().join(archivo),
+            respaldos: RESPALDOS_POR_DEFECTO,
+            esquema: Esquema::new(),
+            espera_bloqueo: ESPERA_BLOQUEO_POR_DEFECTO,
+            huella: Cell::new(Huella::Ausente),
+            cifrado: None,
+        }
+    }
+
+    pub fn con_esquema(mut self, esquema: Esquema) -> Self {
+        self.esquema = esquema;
+        self
+    }
+
+    // Con 0 no se guardan respaldos.
+    pub fn con_respaldos(mut self, respaldos: usize) -> Self {
+        self.respaldos = respaldos;
+        self
+    }
+
+    pub fn con_espera_bloqueo(mut self, espera: Duration) -> Self {
+        self.espera_bloqueo = espera;
+        self
+    }
+
+    // Lo que ya estaba guardado sin cifrar, incluidos los respaldos, se cifra con re_cifrar.
+    pub fn con_cifrado(mut self, cifrado: Cifrado) -> Self {
+        self.cifrado = Some(cifrado);
+        self
+    }
+
+    pub fn ruta(&self) -> &Path {
+        &self.ruta
+    }
+
+    // `archivo.n`; el 1 es el mas reciente.
+    pub fn ruta_respaldo(&self, n: usize) -> PathBuf {
+        self.con_sufijo(&n.to_string())
+    }
+
+    // Los respaldos que existen, del mas reciente al mas viejo.
+    pub fn respaldos(&self) -> Vec<PathBuf> {
+        (1..=self.respaldos)
+            .map(|n| self.ruta_respaldo(n))
+            .filter(|r| r.exists())
+            .collect()
+    }
+
+    // Vuelve a poner el respaldo `n` como archivo principal. Falla sin tocar nada si el
+    // respaldo no existe o tambien esta corrupto. Quien tenga cargados los datos anteriores
+    // va a recibir Conflicto al guardar, porque el archivo ya no es el que leyo.
+    pub fn restaurar_respaldo(&self, n: usize) -> Result<(), PersistenciaError> {
+        let _bloqueo = Bloqueo::exclusivo(&self.ruta, self.espera_bloqueo)?;
+        let respaldo = self.ruta_respaldo(n);
+        let texto = match fs::read_to_string(&respaldo) {
+            Ok(texto) => texto,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                return Err(PersistenciaError::SinRespaldo(n));
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let origen = respaldo.display().to_string();
+        let plano = cifrado::abrir(&texto, self.cifrado.as_ref(), &origen)?;
+        deserializar::<serde_json::Value>(&plano, &origen)?;
+        self.escribir_atomico(&self.ruta, &texto)
+    }
+
+    // Vuelve a escribir el archivo y sus respaldos con otra contraseña, o sin cifrar si es
+    // None. Primero descifra todo con la contraseña actual, asi si algo falla no se cambia
+    // ningun archivo. Los demas procesos que lo tengan abierto van a recibir Conflicto.
+    pub fn re_cifrar(&mut self, nuevo: Option<Cifrado>) -> Result<(), PersistenciaError> {
+        if self.sin_directorio() {
+            self.cifrado = nuevo;
+            return Ok(());
+        }
+        let _bloqueo = Bloqueo::exclusivo(&self.ruta, self.espera_bloqueo)?;
+        self.verificar_sin_cambios()?;
+
+        let mut archivos = vec![];
+        for (ruta, plano) in self.descifrar_todo()? {
+            archivos.push((ruta, cifrado::sellar(plano, nuevo.as_ref())?));
+        }
+        for (ruta, texto) in &archivos {
+            self.escribir_atomico(ruta, texto)?;
+            if *ruta == self.ruta {
+                self.huella.set(Huella::de(texto));
+            }
+        }
+        self.cifrado = nuevo;
+        Ok(())
+    }
+
+    // Verifica que el archivo y sus respaldos se puedan descifrar con la contraseña actual,
+    // sin cambiar nada. Sirve para re-cifrar este archivo junto con otros: si alguno no se
+    // puede leer no se toca ninguno.
+    pub fn validar_cifrado(&self) -> Result<(), PersistenciaError> {
+        if self.sin_directorio() {
+            return Ok(());
+        }
+        let _bloqueo = Bloqueo::compartido(&self.ruta, self.espera_bloqueo)?;
+        self.descifrar_todo()?;
+        Ok(())
+    }
+
+    // El texto en claro del archivo, si existe, y de cada respaldo.
+    fn descifrar_todo(&self) -> Result<Vec<(PathBuf, String)>, PersistenciaError> {
+        let principal = Some(self.ruta.clone()).filter(|r| r.exists());
+        principal
+            .into_iter()
+            .chain(self.respaldos())
+            .map(|ruta| {
+                let origen = ruta.display().to_string();
+                let plano =
+                    cifrado::abrir(&fs::read_to_string(&ruta)?, self.cifrado.as_ref(), &origen)?;
+                deserializar::<serde_json::Value>(&plano, &origen)?;
+                Ok((ruta, plano))
+            })
+            .collect()
+    }
+
+    fn con_sufijo(&self, sufijo: &str) -> PathBuf {
+        con_sufijo(&self.ruta, sufijo)
+    }
+
+    // Sin directorio no hay archivo, y no hace falta crearlo solo para bloquear.
+    fn sin_directorio(&self) -> bool {
+        self.ruta
+            .parent()
+            .is_some_and(|d| !d.as_os_str().is_empty() && !d.exists())
+    }
+
+    fn verificar_sin_cambios(&self) -> Result<(), PersistenciaError> {
+        if Huella::leer(&self.ruta)? != self.huella.get() {
+            return Err(PersistenciaError::Conflicto(self.ruta.clone()));
+        }
+        Ok(())
+    }
+
+    fn rotar_respaldos(&self) -> io::Result<()> {
+        if self.respaldos == 0 || !self.ruta.exists() {
+            return Ok(());
+        }
+        for n in (1..self.respaldos).rev() {
+            let respaldo = self.ruta_respaldo(n);
+            if respaldo.exists() {
+                fs::rename(&respaldo, self.ruta_respaldo(n + 1))?;
+            }
+        }
+        fs::copy(&self.ruta, self.ruta_respaldo(1))?;
+        Ok(())
+    }
+
+    fn escribir_atomico(&self, destino: &Path, contenido: &str) -> Result<(), PersistenciaError> {
+        let temporal = con_sufijo(destino, "tmp");
+        let mut archivo = File::create(&temporal)?;
+        archivo.write_all(contenido.as_bytes())?;
+        archivo.sync_all()?;
+        fs::rename(&temporal, destino)?;
+        self.sincronizar_directorio()?;
+        Ok(())
+    }
+
+    // Para que el rename sobreviva a un corte de luz hay que sincronizar tambien el directorio.
+    #[cfg(unix)]
+    fn sincronizar_directorio(&self) -> io::Result<()> {
+        match self.ruta.parent() {
+            Some(directorio) if !directorio.as_os_str().is_empty() => {
+                File::open(directorio)?.sync_all()
+            }
+            _ => Ok(()),
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn sincronizar_directorio(&self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<T: Serialize + DeserializeOwned> Repositorio<T> for RepositorioJson {
+    fn guardar(&self, datos: &T) -> Result<(), PersistenciaError> {
+        let serializado = cifrado::sellar(self.esquema.a_texto(datos)?, self.cifrado.as_ref())?;
+        if let Some(directorio) = self.ruta.parent() {
+            fs::create_dir_all(directorio)?;
+        }
+        let _bloqueo = Bloqueo::exclusivo(&self.ruta, self.espera_bloqueo)?;
+        self.verificar_sin_cambios()?;
+        self.rotar_respaldos()?;
+        self.escribir_atomico(&self.ruta, &serializado)?;
+        self.huella.set(Huella::de(&serializado));
+        Ok(())
+    }
+
+    fn cargar(&self) -> Result<Option<T>, PersistenciaError> {
+        if self.sin_directorio() {
+            self.huella.set(Huella::Ausente);
+            return Ok(None);
+        }
+        let _bloqueo = Bloqueo::compartido(&self.ruta, self.espera_bloqueo)?;
+        match fs::read_to_string(&self.ruta) {
+            Ok(texto) => {
+                let origen = self.ruta.display().to_string();
+                let plano = cifrado::abrir(&texto, self.cifrado.as_ref(), &origen)?;
+                let valor = deserializar(&plano, &origen)?;
+                let datos = self.esquema.desenvolver(valor)?;
+                self.huella.set(Huella::de(&texto));
+                Ok(Some(datos))
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                self.huella.set(Huella::Ausente);
+                Ok(None)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+impl RepositorioMemoria {
+    pub fn new() -> Self {
+        RepositorioMemoria::default()
+    }
+
+    pub fn con_esquema(mut self, esquema: Esquema) -> Self {
+        self.esquema = esquema;
+        self
+    }
+
+    // Arranca con datos ya guardados, por ejemplo en un formato viejo.
+    pub fn con_contenido(self, texto: &str) -> Self {
+        *self.contenido.borrow_mut() = Some(texto.to_string());
+        self
+    }
+
+    pub fn contenido(&self) -> Option<String> {
+        self.contenido.borrow().clone()
+    }
+
+    // Cuantas veces se guardo, para ver cuantas escrituras haria el archivo.
+    pub fn guardados(&self) -> usize {
+        self.guardados.get()
+    }
+}
+
+impl<T: Serialize + DeserializeOwned> Repositorio<T> for RepositorioMemoria {
+    fn guardar(&self, datos: &T) -> Result<(), PersistenciaError> {
+        *self.contenido.borrow_mut() = Some(self.esquema.a_texto(datos)?);
+        self.guardados.set(self.guardados.get() + 1);
+        Ok(())
+    }
+
+    fn cargar(&self) -> Result<Option<T>, PersistenciaError> {
+        match &*self.contenido.borrow() {
+            Some(texto) => {
+                let valor = deserializar(texto, "memoria")?;
+                Ok(Some(self.esquema.desenvolver(valor)?))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+impl<T> RepositorioCompartido<T> {
+    pub fn new<R: Repositorio<T> + 'static>(repositorio: R) -> Self {
+        Compartido::de_rc(Rc::new(repositorio))
+    }
+}
+
+impl<T: Serialize + DeserializeOwned> RepositorioCompartido<T> {
+    pub fn json(directorio: impl AsRef<Path>, archivo: &str, esquema: Esquema) -> Self {
+        RepositorioCompartido::new(RepositorioJson::new(directorio, archivo).con_esquema(esquema))
+    }
+
+    pub fn memoria() -> Self {
+        RepositorioCompartido::new(RepositorioMemoria::new())
+    }
+}
+
+impl<T, R: Repositorio<T> + 'static> From<Rc<R>> for RepositorioCompartido<T> {
+    fn from(repositorio: Rc<R>) -> Self {
+        Compartido::de_rc(repositorio)
+    }
+}
+
+impl<T> Repositorio<T> for RepositorioCompartido<T> {
+    fn guardar(&self, datos: &T) -> Result<(), PersistenciaError> {
+        (**self).guardar(datos)
+    }
+
+    fn cargar(&self) -> Result<Option<T>, PersistenciaError> {
+        (**self).cargar()
+    }
+}
+
+// Lleva la cuenta de los cambios sin guardar de un sistema que persiste toda su coleccion.
+// Fuera de una unidad de trabajo cada cambio se guarda enseguida; dentro, se guarda una sola
+// vez al cerrarla.
+#[derive(Debug, Default)]
+pub struct UnidadDeTrabajo {
+    abierta: bool,
+    pendiente: bool,
+}
+
+impl UnidadDeTrabajo {
+    // Anota un cambio y devuelve si hay que guardarlo ya.
+    pub fn cambio(&mut self) -> bool {
+        self.pendiente = true;
+        !self.abierta
+    }
+
+    pub fn pendiente(&self) -> bool {
+        self.pendiente
+    }
+
+    pub fn guardado(&mut self) {
+        self.pendiente = false;
+    }
+
+    // Devuelve false si ya habia una abierta: la de adentro pasa a ser parte de esa.
+    pub fn abrir(&mut self) -> bool {
+        !std::mem::replace(&mut self.abierta, true)
+    }
+
+    pub fn cerrar(&mut self) {
+        self.abierta = false;
+    }
+
+    // Al descartar los cambios de la unidad vuelve a lo pendiente de antes de abrirla.
+    pub fn revertir(&mut self, pendiente: bool) {
+        self.pendiente = pendiente;
+    }
+}
+
+// Un sistema que guarda sus cambios con una UnidadDeTrabajo. Alcanza con decir donde esta la
+// unidad, como copiar y restaurar lo que se guarda y como guardarlo.
+pub trait ConUnidadDeTrabajo: Sized {
+    type Copia;
+
+    fn unidad(&self) -> &UnidadDeTrabajo;
+    fn unidad_mut(&mut self) -> &mut UnidadDeTrabajo;
+    fn copia(&self) -> Self::Copia;
+    fn restaurar(&mut self, copia: Self::Copia);
+    fn persistir(&mut self) -> Result<(), PersistenciaError>;
+
+    // Aplica todos los cambios de `f` y guarda una sola vez al final. Si `f` o el guardado
+    // fallan el sistema vuelve a como estaba antes. Dentro de otra unidad de trabajo no
+    // guarda nada: lo hace la de afuera.
+    fn unidad_de_trabajo<R, E: From<PersistenciaError>>(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> Result<R, E>,
+    ) -> Result<R, E> {
+        if !self.unidad_mut().abrir() {
+            return f(self);
+        }
+        let copia = self.copia();
+        let pendiente = self.unidad().pendiente();
+
+        let resultado = {
+            // si `f` entra en panico la unidad igual queda cerrada
+            let abierta = UnidadAbierta(self);
+            f(abierta.0)
+        };
+        let resultado = resultado.and_then(|r| {
+            self.guardar_pendiente()?;
+            Ok(r)
+        });
+        if resultado.is_err() {
+            self.restaurar(copia);
+            self.unidad_mut().revertir(pendiente);
+        }
+        resultado
+    }
+
+    // Aplica un solo cambio y lo guarda. Si no se puede guardar el cambio se deshace, para
+    // que lo que queda en memoria sea lo mismo que hay en el archivo.
+    fn cambiar<R>(&mut self, f: impl FnOnce(&mut Self) -> R) -> Result<R, PersistenciaError> {
+        self.unidad_de_trabajo(|s| {
+            let r = f(s);
+            s.registrar_cambio()?;
+            Ok(r)
+        })
+    }
+
+    // Si hay cambios que todavia no se pudieron guardar.
+    fn cambios_sin_guardar(&self) -> bool {
+        self.unidad().pendiente()
+    }
+
+    // Anota un cambio; fuera de una unidad de trabajo lo guarda enseguida.
+    fn registrar_cambio(&mut self) -> Result<(), PersistenciaError> {
+        if self.unidad_mut().cambio() {
+            self.guardar_pendiente()
+        } else {
+            Ok(())
+        }
+    }
+
+    fn guardar_pendiente(&mut self) -> Result<(), PersistenciaError> {
+        if self.unidad().pendiente() {
+            self.persistir()?;
+            self.unidad_mut().guardado();
+        }
+        Ok(())
+    }
+}
+
+// Cierra la unidad al salir de `f`, tambien si sale por un panico.
+struct UnidadAbierta<'a, S: ConUnidadDeTrabajo>(&'a mut S);
+
+impl<S: ConUnidadDeTrabajo> Drop for UnidadAbierta<'_, S> {
+    fn drop(&mut self) {
+        self.0.unidad_mut().cerrar();
+    }
+}
+
+// Repositorio que siempre falla al guardar, como un disco lleno. Para los tests de los
+// sistemas.
+#[cfg(test)]
+pub struct RepositorioLleno;
+
+#[cfg(test)]
+impl RepositorioLleno {
+    pub fn compartido<T>() -> RepositorioCompartido<T> {
+        RepositorioCompartido::new(RepositorioLleno)
+    }
+}
+
+#[cfg(test)]
+impl<T> Repositorio<T> for RepositorioLleno {
+    fn guardar(&self, datos: &T) -> Result<(), PersistenciaError> {
+        Err(io::Error::new(io::ErrorKind::StorageFull, "no queda espacio en el disco").into())
+    }
+
+    fn cargar(&self) -> Result<Option<T>, PersistenciaError> {
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tp05::pruebas::directorio_temporal;
+
+    // Un directorio por test, porque corren en paralelo.
+
+    #[test]
+    fn test_memoria_guardar_y_cargar() {
+        let repo = RepositorioMemoria::new();
+        assert!(Repositorio::<Vec<u32>>::cargar(&repo).unwrap().is_none());
+        repo.guardar(&vec![1u32, 2, 3]).unwrap();
+        assert_eq!(repo.cargar().unwrap(), Some(vec![1u32, 2, 3]));
+        assert!(repo.contenido().unwrap().contains('2'));
+    }
+
+    #[test]
+    fn test_memoria_tipo_incorrecto() {
+        let repo = RepositorioMemoria::new();
+        repo.guardar(&vec!["a".to_string()]).unwrap();
+        assert!(matches!(
+            Repositorio::<Vec<u32>>::cargar(&repo),
+            Err(PersistenciaError::Serializacion(_))
+        ));
+    }
+
+    #[test]
+    fn test_json_en_directorio_configurable() {
+        let dir = directorio_temporal("repo");
+        let repo = RepositorioJson::new(&dir, "sub/datos.json");
+        assert!(Repositorio::<Vec<u32>>::cargar(&repo).unwrap().is_none());
+
+        repo.guardar(&vec![7u32]).unwrap();
+        assert!(repo.ruta().starts_with(&dir));
+        assert_eq!(repo.cargar().unwrap(), Some(vec![7u32]));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_json_rota_respaldos() {
+        let dir = directorio_temporal("respaldos");
+        let repo = RepositorioJson::new(&dir, "datos.json").con_respaldos(2);
+        for i in 1..=5u32 {
+            repo.guardar(&vec![i]).unwrap();
+        }
+        assert_eq!(repo.cargar().unwrap(), Some(vec![5u32]));
+        assert_eq!(
+            repo.respaldos(),
+            vec![repo.ruta_respaldo(1), repo.ruta_respaldo(2)]
+        );
+        let leer = |n| fs::read_to_string(repo.ruta_respaldo(n)).unwrap();
+        let texto = |v: u32| Esquema::new().a_texto(&vec![v]).unwrap();
+        assert_eq!(leer(1), texto(4));
+        assert_eq!(leer(2), texto(3));
+        assert!(!repo.ruta_respaldo(3).exists());
+        assert!(!dir.join("datos.json.tmp").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_json_corrupto_y_restaurar_respaldo() {
+        let dir = directorio_temporal("corrupto");
+        let repo = RepositorioJson::new(&dir, "datos.json");
+        repo.guardar(&vec![1u32, 2]).unwrap();
+        repo.guardar(&vec![1u32, 2, 3]).unwrap();
+
+        // escritura cortada a la mitad
+        fs::write(repo.ruta(), "[1, 2,").unwrap();
+        assert!(matches!(
+            Repositorio::<Vec<u32>>::cargar(&repo),
+            Err(PersistenciaError::Corrupto(_))
+        ));
+
+        repo.restaurar_respaldo(1).unwrap();
+        assert_eq!(repo.cargar().unwrap(), Some(vec![1u32, 2]));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_restaurar_respaldo_invalido() {
+        let dir = directorio_temporal("sin-respaldo");
+        let repo = RepositorioJson::new(&dir, "datos.json");
+        repo.guardar(&vec![1u32]).unwrap();
+        assert!(matches!(
+            repo.restaurar_respaldo(1),
+            Err(PersistenciaError::SinRespaldo(1))
+        ));
+
+        repo.guardar(&vec![2u32]).unwrap();
+        fs::write(repo.ruta_respaldo(1), "").unwrap();
+        assert!(matches!(
+            repo.restaurar_respaldo(1),
+            Err(PersistenciaError::Corrupto(_))
+        ));
+        // el archivo principal no se toco
+        assert_eq!(repo.cargar().unwrap(), Some(vec![2u32]));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_json_guarda_version_y_migra() {
+        fn sumar_uno(datos: serde_json::Value) -> Result<serde_json::Value, String> {
+            let n = datos.as_u64().ok_or("se esperaba un numero")?;
+            Ok(serde_json::Value::from(n + 1))
+        }
+
+        let dir = directorio_temporal("esquema");
+        // archivo viejo, sin sobre
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("datos.json"), "41").unwrap();
+
+        let repo = RepositorioJson::new(&dir, "datos.json")
+            .con_esquema(Esquema::new().migracion(sumar_uno));
+        assert_eq!(repo.cargar().unwrap(), Some(42u64));
+        repo.guardar(&42u64).unwrap();
+        assert_eq!(
+            fs::read_to_string(repo.ruta()).unwrap(),
+            "{\n  \"version\": 2,\n  \"datos\": 42\n}"
+        );
+        // la version 2 ya no se migra
+        assert_eq!(repo.cargar().unwrap(), Some(42u64));
+
+        // un programa que solo conoce la version 1 no la entiende
+        let viejo = RepositorioJson::new(&dir, "datos.json");
+        assert!(matches!(
+            Repositorio::<u64>::cargar(&viejo),
+            Err(PersistenciaError::VersionDesconocida { version: 2, .. })
+        ));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_json_bloqueado_por_otro_proceso() {
+        let dir = directorio_temporal("bloqueado");
+        let repo = RepositorioJson::new(&dir, "datos.json").con_espera_bloqueo(Duration::ZERO);
+        repo.guardar(&vec![1u32]).unwrap();
+        let otro = Bloqueo::exclusivo(repo.ruta(), Duration::ZERO).unwrap();
+
+        assert!(matches!(
+            repo.guardar(&vec![2u32]),
+            Err(PersistenciaError::Bloqueado(_))
+        ));
+        assert!(matches!(
+            Repositorio::<Vec<u32>>::cargar(&repo),
+            Err(PersistenciaError::Bloqueado(_))
+        ));
+        // varios pueden leer a la vez
+        drop(otro);
+        let lector = Bloqueo::compartido(repo.ruta(), Duration::ZERO).unwrap();
+        assert_eq!(repo.cargar().unwrap(), Some(vec![1u32]));
+        drop(lector);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_json_espera_a_que_se_libere() {
+        let dir = directorio_temporal("espera");
+        let repo = RepositorioJson::new(&dir, "datos.json");
+        repo.guardar(&vec![1u32]).unwrap();
+        let otro = Bloqueo::exclusivo(repo.ruta(), Duration::ZERO).unwrap();
+        let soltar = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            drop(otro);
+        });
+
+        repo.guardar(&vec![2u32]).unwrap();
+        soltar.join().unwrap();
+        assert_eq!(repo.cargar().unwrap(), Some(vec![2u32]));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_json_conflicto_si_otro_lo_modifico() {
+        let dir = directorio_temporal("conflicto");
+        let primero = RepositorioJson::new(&dir, "datos.json");
+        let segundo = RepositorioJson::new(&dir, "datos.json");
+        assert!(Repositorio::<Vec<u32>>::cargar(&primero).unwrap().is_none());
+        assert!(Repositorio::<Vec<u32>>::cargar(&segundo).unwrap().is_none());
+
+        primero.guardar(&vec![1u32]).unwrap();
+        assert!(matches!(
+            segundo.guardar(&vec![2u32]),
+            Err(PersistenciaError::Conflicto(_))
+        ));
+        // lo que guardo el primero sigue ahi
+        assert_eq!(segundo.cargar().unwrap(), Some(vec![1u32]));
+        segundo.guardar(&vec![1u32, 2]).unwrap();
+
+        assert!(matches!(
+            primero.guardar(&vec![3u32]),
+            Err(PersistenciaError::Conflicto(_))
+        ));
+        // guardar varias veces seguidas sin que nadie mas escriba no es un conflicto
+        assert_eq!(primero.cargar().unwrap(), Some(vec![1u32, 2]));
+        primero.guardar(&vec![1u32, 2, 3]).unwrap();
+        primero.guardar(&vec![1u32, 2, 3, 4]).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_json_conflicto_si_no_lo_leyo() {
+        let dir = directorio_temporal("sin-leer");
+        RepositorioJson::new(&dir, "datos.json")
+            .guardar(&vec![1u32])
+            .unwrap();
+
+        // el archivo ya existia y este repositorio nunca lo cargo
+        let repo = RepositorioJson::new(&dir, "datos.json");
+        assert!(matches!(
+            repo.guardar(&vec![2u32]),
+            Err(PersistenciaError::Conflicto(_))
+        ));
+        assert_eq!(repo.cargar().unwrap(), Some(vec![1u32]));
+        repo.guardar(&vec![1u32, 2]).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_json_cifrado_rechaza_archivos_sin_cifrar() {
+        let dir = directorio_temporal("cifrado");
+        RepositorioJson::new(&dir, "datos.json")
+            .guardar(&vec![1u32])
+            .unwrap();
+
+        let repo =
+            RepositorioJson::new(&dir, "datos.json").con_cifrado(cifrado::de_prueba("clave"));
+        assert!(matches!(
+            Repositorio::<Vec<u32>>::cargar(&repo),
+            Err(PersistenciaError::Cifrado(_))
+        ));
+        assert!(repo.guardar(&vec![2u32]).is_err());
+
+        // para cifrarlo se lee sin contraseña
+        let mut sin_cifrar = RepositorioJson::new(&dir, "datos.json");
+        Repositorio::<Vec<u32>>::cargar(&sin_cifrar).unwrap();
+        sin_cifrar.guardar(&vec![1u32, 2]).unwrap();
+        sin_cifrar
+            .re_cifrar(Some(cifrado::de_prueba("clave")))
+            .unwrap();
+        for ruta in [repo.ruta().to_path_buf(), repo.ruta_respaldo(1)] {
+            assert!(
+                fs::read_to_string(ruta)
+                    .unwrap()
+                    .starts_with("{\"cifrado\":1")
+            );
+        }
+        assert_eq!(repo.cargar().unwrap(), Some(vec![1u32, 2]));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_json_error_io() {
+        // un directorio en lugar de archivo
+        let dir = directorio_temporal("error-io");
+        fs::create_dir_all(dir.join("datos.json")).unwrap();
+        let repo = RepositorioJson::new(&dir, "datos.json");
+        assert!(matches!(
+            repo.guardar(&vec![1u32]),
+            Err(PersistenciaError::Io(_))
+        ));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[derive(Serialize)]
+    struct ConMapa {
+        #[serde(serialize_with = "serializar_ordenado")]
+        mapa: HashMap<String, u32>,
+    }
+
+    #[test]
+    fn test_serializar_ordenado() {
+        let mapa: HashMap<String, u32> = ["c", "a", "d", "b"]
+            .iter()
+            .enumerate()
+            .map(|(i, k)| (k.to_string(), i as u32))
+            .collect();
+        let json = serde_json::to_string(&ConMapa { mapa }).unwrap();
+        assert_eq!(json, r#"{"mapa":{"a":1,"b":3,"c":0,"d":2}}"#);
+    }
+
+    #[test]
+    fn test_compartido_ve_lo_mismo() {
+        let memoria = Rc::new(RepositorioMemoria::new());
+        let compartido: RepositorioCompartido<Vec<u32>> =
+            RepositorioCompartido::from(memoria.clone());
+        compartido.clone().guardar(&vec![5]).unwrap();
+        assert_eq!(memoria.cargar().unwrap(), Some(vec![5u32]));
+    }
+}
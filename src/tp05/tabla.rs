@@ -0,0 +1,461 @@
+#![allow(dead_code, unused_variables)]
+
+use crate::tp03::ej03::Fecha;
+use crate::tp05::esquema::normalizar_variante;
+use crate::tp05::repositorio::PersistenciaError;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+
+// Un tipo que se puede exportar a una planilla e importar de ella, una fila por registro.
+// Los campos anidados y los datos de cada variante de un enum van en columnas propias, que
+// quedan vacias cuando no corresponden.
+pub trait RegistroCsv: Sized {
+    // Los encabezados, en el orden en que se exportan.
+    const COLUMNAS: &'static [&'static str];
+
+    // Las que pueden faltar en el archivo; las demas son obligatorias.
+    const OPCIONALES: &'static [&'static str] = &[];
+
+    // Un valor por columna, en el mismo orden que COLUMNAS.
+    fn a_fila(&self) -> Vec<String>;
+
+    fn de_fila(fila: &Fila) -> Result<Self, ErrorFila>;
+}
+
+// Una fila del archivo, con los valores accesibles por el nombre de la columna.
+pub struct Fila<'a> {
+    linea: u64,
+    valores: &'a csv::StringRecord,
+    indices: &'a HashMap<&'static str, usize>,
+}
+
+// Por que no se pudo leer una fila.
+#[derive(Debug, PartialEq)]
+pub struct ErrorFila {
+    pub linea: u64,
+    pub columna: Option<String>,
+    pub detalle: String,
+}
+
+#[derive(Debug)]
+pub enum ErrorCsv {
+    Io(io::Error),
+    // El archivo no se pudo leer como CSV, por ejemplo porque no esta en UTF-8.
+    Formato(csv::Error),
+    FaltanColumnas(Vec<String>),
+    // Todas las filas invalidas, no solo la primera, para poder corregirlas de una vez.
+    Filas(Vec<ErrorFila>),
+    Persistencia(PersistenciaError),
+}
+
+// Como leer una planilla: el separador y los encabezados que no coinciden con el nombre de
+// la columna. Ademas de estos, los encabezados se comparan sin importar mayusculas ni
+// espacios: "Precio Bruto" es la columna precio_bruto.
+#[derive(Clone, Debug, Default)]
+pub struct MapeoColumnas {
+    alias: HashMap<String, String>,
+    delimitador: Option<u8>,
+}
+
+impl fmt::Display for ErrorFila {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.columna {
+            Some(columna) => write!(
+                f,
+                "linea {}, columna {}: {}",
+                self.linea, columna, self.detalle
+            ),
+            None => write!(f, "linea {}: {}", self.linea, self.detalle),
+        }
+    }
+}
+
+impl fmt::Display for ErrorCsv {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorCsv::Io(e) => write!(f, "Error de entrada/salida con el CSV: {}", e),
+            ErrorCsv::Formato(e) => write!(f, "El archivo no es un CSV valido: {}", e),
+            ErrorCsv::FaltanColumnas(columnas) => {
+                write!(f, "Faltan las columnas {}", columnas.join(", "))
+            }
+            ErrorCsv::Filas(errores) => {
+                write!(f, "Hay {} filas con errores", errores.len())?;
+                for error in errores {
+                    write!(f, "\n{}", error)?;
+                }
+                Ok(())
+            }
+            ErrorCsv::Persistencia(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ErrorCsv {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ErrorCsv::Io(e) => Some(e),
+            ErrorCsv::Formato(e) => Some(e),
+            ErrorCsv::Persistencia(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ErrorCsv {
+    fn from(e: io::Error) -> Self {
+        ErrorCsv::Io(e)
+    }
+}
+
+impl From<csv::Error> for ErrorCsv {
+    fn from(e: csv::Error) -> Self {
+        ErrorCsv::Formato(e)
+    }
+}
+
+impl From<PersistenciaError> for ErrorCsv {
+    fn from(e: PersistenciaError) -> Self {
+        ErrorCsv::Persistencia(e)
+    }
+}
+
+fn normalizar(encabezado: &str) -> String {
+    encabezado.trim().to_lowercase().replace([' ', '-'], "_")
+}
+
+impl MapeoColumnas {
+    pub fn new() -> Self {
+        MapeoColumnas::default()
+    }
+
+    pub fn con_alias(mut self, encabezado: &str, columna: &str) -> Self {
+        self.alias
+            .insert(normalizar(encabezado), columna.to_string());
+        self
+    }
+
+    // Las planillas en castellano suelen exportarse separadas por ';'.
+    pub fn con_delimitador(mut self, delimitador: u8) -> Self {
+        self.delimitador = Some(delimitador);
+        self
+    }
+
+    // La columna de T a la que corresponde un encabezado del archivo, si hay alguna.
+    fn columna<T: RegistroCsv>(&self, encabezado: &str) -> Option<&'static str> {
+        let normalizado = normalizar(encabezado);
+        let nombre = self.alias.get(&normalizado).unwrap_or(&normalizado);
+        T::COLUMNAS.iter().copied().find(|c| c == nombre)
+    }
+}
+
+impl<'a> Fila<'a> {
+    pub fn linea(&self) -> u64 {
+        self.linea
+    }
+
+    pub fn error(&self, detalle: impl Into<String>) -> ErrorFila {
+        ErrorFila {
+            linea: self.linea,
+            columna: None,
+            detalle: detalle.into(),
+        }
+    }
+
+    pub fn error_en(&self, columna: &str, detalle: impl Into<String>) -> ErrorFila {
+        ErrorFila {
+            linea: self.linea,
+            columna: Some(columna.to_string()),
+            detalle: detalle.into(),
+        }
+    }
+
+    // Vacio o ausente es None.
+    pub fn opcional(&self, columna: &str) -> Option<&'a str> {
+        self.indices
+            .get(columna)
+            .and_then(|&i| self.valores.get(i))
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+    }
+
+    pub fn texto(&self, columna: &str) -> Result<String, ErrorFila> {
+        self.opcional(columna)
+            .map(str::to_string)
+            .ok_or_else(|| self.error_en(columna, "no puede estar vacia"))
+    }
+
+    pub fn valor<T: FromStr>(&self, columna: &str) -> Result<T, ErrorFila>
+    where
+        T::Err: fmt::Display,
+    {
+        self.valor_opcional(columna)?
+            .ok_or_else(|| self.error_en(columna, "no puede estar vacia"))
+    }
+
+    pub fn valor_opcional<T: FromStr>(&self, columna: &str) -> Result<Option<T>, ErrorFila>
+    where
+        T::Err: fmt::Display,
+    {
+        self.opcional(columna)
+            .map(|texto| {
+                texto
+                    .parse()
+                    .map_err(|e| self.error_en(columna, format!("'{}': {}", texto, e)))
+            })
+            .transpose()
+    }
+
+    // El nombre de la variante de un enum con datos, que van en otras columnas. Se compara
+    // con `nombres` como los encabezados, sin importar mayusculas, espacios ni guiones.
+    pub fn nombre_variante(
+        &self,
+        columna: &str,
+        nombres: &[&'static str],
+    ) -> Result<&'static str, ErrorFila> {
+        let texto = self.texto(columna)?;
+        let buscado = normalizar(&texto).replace('_', "");
+        nombres
+            .iter()
+            .copied()
+            .find(|nombre| nombre.to_lowercase() == buscado)
+            .ok_or_else(|| self.error_en(columna, format!("valor desconocido '{}'", texto)))
+    }
+
+    // Una variante sin datos de un enum, por su nombre. Tambien acepta el nombre en
+    // mayusculas o minusculas ("rojo", "ROJO").
+    pub fn variante<T: DeserializeOwned>(&self, columna: &str) -> Result<T, ErrorFila> {
+        let texto = self.texto(columna)?;
+        let mut valor = Value::String(texto.clone());
+        if let Ok(variante) = serde_json::from_value(valor.clone()) {
+            return Ok(variante);
+        }
+        normalizar_variante(&mut valor);
+        serde_json::from_value(valor)
+            .map_err(|_| self.error_en(columna, format!("valor desconocido '{}'", texto)))
+    }
+}
+
+// Para exportar: el nombre de una variante sin datos, tal como la lee Fila::variante.
+pub fn variante<T: Serialize>(valor: &T) -> String {
+    match serde_json::to_value(valor) {
+        Ok(Value::String(nombre)) => nombre,
+        _ => String::new(),
+    }
+}
+
+pub fn fecha(fecha: &Fecha) -> String {
+    fecha.formatear(Fecha::FORMATO_ISO)
+}
+
+pub fn opcional<T>(valor: &Option<T>, f: impl Fn(&T) -> String) -> String {
+    valor.as_ref().map_or(String::new(), f)
+}
+
+pub fn exportar<'a, T: RegistroCsv + 'a>(
+    registros: impl IntoIterator<Item = &'a T>,
+    escritor: impl io::Write,
+) -> Result<(), ErrorCsv> {
+    let mut csv = csv::Writer::from_writer(escritor);
+    csv.write_record(T::COLUMNAS)?;
+    for registro in registros {
+        csv.write_record(registro.a_fila())?;
+    }
+    csv.flush()?;
+    Ok(())
+}
+
+// Lee todas las filas y devuelve los registros solo si todas son validas. Las columnas del
+// archivo pueden estar en cualquier orden y las que no son de T se ignoran.
+pub fn importar<T: RegistroCsv>(
+    lector: impl io::Read,
+    mapeo: &MapeoColumnas,
+) -> Result<Vec<T>, ErrorCsv> {
+    let filas = importar_con_lineas(lector, mapeo)?;
+    Ok(filas.into_iter().map(|(_, registro)| registro).collect())
+}
+
+// Igual que importar, con la linea de cada registro para poder reportar errores que
+// aparecen despues, al agregarlos al sistema.
+pub fn importar_con_lineas<T: RegistroCsv>(
+    lector: impl io::Read,
+    mapeo: &MapeoColumnas,
+) -> Result<Vec<(u64, T)>, ErrorCsv> {
+    let mut csv = csv::ReaderBuilder::new()
+        .flexible(true)
+        .delimiter(mapeo.delimitador.unwrap_or(b','))
+        .from_reader(lector);
+    let mut indices = HashMap::new();
+    for (i, encabezado) in csv.headers()?.iter().enumerate() {
+        if let Some(columna) = mapeo.columna::<T>(encabezado) {
+            indices.entry(columna).or_insert(i);
+        }
+    }
+    let faltan: Vec<String> = T::COLUMNAS
+        .iter()
+        .filter(|c| !indices.contains_key(*c) && !T::OPCIONALES.contains(c))
+        .map(|c| c.to_string())
+        .collect();
+    if !faltan.is_empty() {
+        return Err(ErrorCsv::FaltanColumnas(faltan));
+    }
+
+    let mut registros = vec![];
+    let mut errores = vec![];
+    for valores in csv.records() {
+        let valores = valores?;
+        let fila = Fila {
+            linea: valores.position().map_or(0, |p| p.line()),
+            valores: &valores,
+            indices: &indices,
+        };
+        if valores.iter().all(|v| v.trim().is_empty()) {
+            continue;
+        }
+        match T::de_fila(&fila) {
+            Ok(registro) => registros.push((fila.linea, registro)),
+            Err(error) => errores.push(error),
+        }
+    }
+    if errores.is_empty() {
+        Ok(registros)
+    } else {
+        Err(ErrorCsv::Filas(errores))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Talle {
+        Chico,
+        Grande,
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Remera {
+        color: String,
+        talle: Talle,
+        precio: f64,
+        fecha_alta: Option<Fecha>,
+    }
+
+    impl RegistroCsv for Remera {
+        const COLUMNAS: &'static [&'static str] = &["color", "talle", "precio", "fecha_alta"];
+        const OPCIONALES: &'static [&'static str] = &["fecha_alta"];
+
+        fn a_fila(&self) -> Vec<String> {
+            vec![
+                self.color.clone(),
+                variante(&self.talle),
+                self.precio.to_string(),
+                opcional(&self.fecha_alta, fecha),
+            ]
+        }
+
+        fn de_fila(fila: &Fila) -> Result<Self, ErrorFila> {
+            Ok(Remera {
+                color: fila.texto("color")?,
+                talle: fila.variante("talle")?,
+                precio: fila.valor("precio")?,
+                fecha_alta: fila.valor_opcional("fecha_alta")?,
+            })
+        }
+    }
+
+    fn remeras() -> Vec<Remera> {
+        vec![
+            Remera {
+                color: "Rojo, oscuro".to_string(),
+                talle: Talle::Chico,
+                precio: 10.5,
+                fecha_alta: Some(Fecha::new(1, 7, 2025)),
+            },
+            Remera {
+                color: "Dice \"hola\"".to_string(),
+                talle: Talle::Grande,
+                precio: 20.0,
+                fecha_alta: None,
+            },
+        ]
+    }
+
+    #[test]
+    fn test_exportar_e_importar() {
+        let mut salida = vec![];
+        exportar(&remeras(), &mut salida).unwrap();
+        let texto = String::from_utf8(salida).unwrap();
+        assert_eq!(
+            texto,
+            "color,talle,precio,fecha_alta\n\
+             \"Rojo, oscuro\",Chico,10.5,2025-07-01\n\
+             \"Dice \"\"hola\"\"\",Grande,20,\n"
+        );
+        let leidas: Vec<Remera> = importar(texto.as_bytes(), &MapeoColumnas::new()).unwrap();
+        assert_eq!(leidas, remeras());
+    }
+
+    #[test]
+    fn test_encabezados_de_una_planilla() {
+        // otro orden, mayusculas, columnas de mas, un alias y sin la columna opcional
+        let texto = "Precio,Notas,Size,COLOR\n10,x,chico,Azul\n\n30,y,GRANDE,Verde\n";
+        let mapeo = MapeoColumnas::new().con_alias("size", "talle");
+        let leidas: Vec<Remera> = importar(texto.as_bytes(), &mapeo).unwrap();
+        assert_eq!(leidas.len(), 2);
+        assert_eq!(leidas[0].talle, Talle::Chico);
+        assert_eq!(leidas[1].color, "Verde");
+        assert_eq!(leidas[1].fecha_alta, None);
+
+        match importar::<Remera>("color,precio\n".as_bytes(), &MapeoColumnas::new()) {
+            Err(ErrorCsv::FaltanColumnas(faltan)) => assert_eq!(faltan, vec!["talle"]),
+            _ => panic!("se esperaba FaltanColumnas"),
+        }
+    }
+
+    #[test]
+    fn test_errores_por_fila() {
+        let texto = "color,talle,precio,fecha_alta\n\
+                     Rojo,Chico,10,\n\
+                     ,Chico,10,\n\
+                     Azul,Mediano,diez,\n\
+                     Verde,Grande,5,2025-02-30\n";
+        let Err(ErrorCsv::Filas(errores)) =
+            importar::<Remera>(texto.as_bytes(), &MapeoColumnas::new())
+        else {
+            panic!("se esperaban errores de filas");
+        };
+        let resumen: Vec<(u64, Option<&str>)> = errores
+            .iter()
+            .map(|e| (e.linea, e.columna.as_deref()))
+            .collect();
+        // la fila 4 tiene dos errores pero se reporta el primero
+        assert_eq!(
+            resumen,
+            vec![
+                (3, Some("color")),
+                (4, Some("talle")),
+                (5, Some("fecha_alta"))
+            ]
+        );
+        let mensaje = ErrorCsv::Filas(errores).to_string();
+        assert!(mensaje.starts_with("Hay 3 filas con errores"));
+        assert!(mensaje.contains("linea 4, columna talle: valor desconocido 'Mediano'"));
+    }
+
+    #[test]
+    fn test_csv_invalido() {
+        // exportado de una planilla en latin-1
+        let texto: &[u8] = b"color,talle,precio\nCami\xf3n,Chico,10\n";
+        assert!(matches!(
+            importar::<Remera>(texto, &MapeoColumnas::new()),
+            Err(ErrorCsv::Formato(_))
+        ));
+    }
+}
@@ -0,0 +1,296 @@
+#![allow(dead_code, unused_variables)]
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+// Numero de identificacion del vehiculo (ISO 3779): 17 caracteres, sin I, O ni Q para que no
+// se confundan con 1 y 0. Las posiciones 1-3 identifican al fabricante (WMI), la 9 es un
+// digito verificador y la 10 el año del modelo.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(try_from = "String", into = "String")]
+pub struct Vin(String);
+
+pub const LARGO: usize = 17;
+
+const PESOS: [u32; LARGO] = [8, 7, 6, 5, 4, 3, 2, 10, 0, 9, 8, 7, 6, 5, 4, 3, 2];
+
+// Posicion (desde 0) del digito verificador.
+const VERIFICADOR: usize = 8;
+
+// Los codigos de año se repiten cada 30 años: A es 1980 y 2010, 9 es 2009 y 2039.
+const CODIGOS_AÑO: &str = "ABCDEFGHJKLMNPRSTVWXY123456789";
+
+const FABRICANTES: &[(&str, &str)] = &[
+    ("1FA", "Ford"),
+    ("1FT", "Ford"),
+    ("1G1", "Chevrolet"),
+    ("1HG", "Honda"),
+    ("2HG", "Honda"),
+    ("3VW", "Volkswagen"),
+    ("4T1", "Toyota"),
+    ("8A1", "Renault"),
+    ("8AD", "Peugeot"),
+    ("8AF", "Ford"),
+    ("8AG", "Chevrolet"),
+    ("8AJ", "Toyota"),
+    ("8AP", "Fiat"),
+    ("8AW", "Volkswagen"),
+    ("9BD", "Fiat"),
+    ("9BG", "Chevrolet"),
+    ("9BW", "Volkswagen"),
+    ("JHM", "Honda"),
+    ("JN1", "Nissan"),
+    ("JT2", "Toyota"),
+    ("JTD", "Toyota"),
+    ("KMH", "Hyundai"),
+    ("KNA", "Kia"),
+    ("SAJ", "Jaguar"),
+    ("VF1", "Renault"),
+    ("VF3", "Peugeot"),
+    ("VF7", "Citroën"),
+    ("WAU", "Audi"),
+    ("WBA", "BMW"),
+    ("WBS", "BMW"),
+    ("WDB", "Mercedes-Benz"),
+    ("WF0", "Ford"),
+    ("WVW", "Volkswagen"),
+    ("YV1", "Volvo"),
+    ("ZFA", "Fiat"),
+];
+
+#[derive(Debug, PartialEq)]
+pub enum VinError {
+    Largo(usize),
+    // posicion desde 1, como se cuentan en el VIN
+    Caracter { posicion: usize, caracter: char },
+    DigitoVerificador { esperado: char, encontrado: char },
+}
+
+impl fmt::Display for VinError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VinError::Largo(largo) => {
+                write!(f, "El VIN debe tener {} caracteres, tiene {}", LARGO, largo)
+            }
+            VinError::Caracter { posicion, caracter } => write!(
+                f,
+                "El VIN tiene un caracter invalido '{}' en la posicion {}",
+                caracter, posicion
+            ),
+            VinError::DigitoVerificador {
+                esperado,
+                encontrado,
+            } => write!(
+                f,
+                "El digito verificador del VIN deberia ser '{}' y es '{}'",
+                esperado, encontrado
+            ),
+        }
+    }
+}
+
+impl std::error::Error for VinError {}
+
+// El valor de cada caracter para el digito verificador; None si no puede estar en un VIN.
+fn valor(c: char) -> Option<u32> {
+    match c {
+        '0'..='9' => c.to_digit(10),
+        'A' | 'J' => Some(1),
+        'B' | 'K' | 'S' => Some(2),
+        'C' | 'L' | 'T' => Some(3),
+        'D' | 'M' | 'U' => Some(4),
+        'E' | 'N' | 'V' => Some(5),
+        'F' | 'W' => Some(6),
+        'G' | 'P' | 'X' => Some(7),
+        'H' | 'Y' => Some(8),
+        'R' | 'Z' => Some(9),
+        _ => None,
+    }
+}
+
+// Revisa largo y caracteres y devuelve el digito verificador que corresponde.
+fn calcular_digito(caracteres: &[char]) -> Result<char, VinError> {
+    if caracteres.len() != LARGO {
+        return Err(VinError::Largo(caracteres.len()));
+    }
+    let mut suma = 0;
+    for (i, &c) in caracteres.iter().enumerate() {
+        let valor = valor(c).ok_or(VinError::Caracter {
+            posicion: i + 1,
+            caracter: c,
+        })?;
+        suma += valor * PESOS[i];
+    }
+    Ok(match suma % 11 {
+        10 => 'X',
+        resto => char::from_digit(resto, 10).unwrap(),
+    })
+}
+
+fn normalizar(vin: &str) -> Vec<char> {
+    vin.trim().chars().flat_map(char::to_uppercase).collect()
+}
+
+impl Vin {
+    // Acepta minusculas y espacios alrededor.
+    pub fn new(vin: &str) -> Result<Vin, VinError> {
+        let caracteres = normalizar(vin);
+        let esperado = calcular_digito(&caracteres)?;
+        let encontrado = caracteres[VERIFICADOR];
+        if esperado != encontrado {
+            return Err(VinError::DigitoVerificador {
+                esperado,
+                encontrado,
+            });
+        }
+        Ok(Vin(caracteres.into_iter().collect()))
+    }
+
+    // Reemplaza la posicion 9 por el digito verificador que corresponde, para armar VINs
+    // validos a partir de los otros 16 caracteres.
+    pub fn completar(vin: &str) -> Result<Vin, VinError> {
+        let mut caracteres = normalizar(vin);
+        if caracteres.len() != LARGO {
+            return Err(VinError::Largo(caracteres.len()));
+        }
+        // el peso de la posicion 9 es 0, pero lo que haya tiene que ser un caracter valido
+        caracteres[VERIFICADOR] = '0';
+        caracteres[VERIFICADOR] = calcular_digito(&caracteres)?;
+        Ok(Vin(caracteres.into_iter().collect()))
+    }
+
+    pub fn como_texto(&self) -> &str {
+        &self.0
+    }
+
+    // World Manufacturer Identifier: las primeras tres posiciones.
+    pub fn wmi(&self) -> &str {
+        &self.0[..3]
+    }
+
+    pub fn fabricante(&self) -> Option<&'static str> {
+        FABRICANTES
+            .iter()
+            .find(|(wmi, _)| *wmi == self.wmi())
+            .map(|(_, fabricante)| *fabricante)
+    }
+
+    // Donde se fabrico, segun el primer caracter.
+    pub fn region(&self) -> &'static str {
+        match self.0.as_bytes()[0] {
+            b'1'..=b'5' => "Norteamérica",
+            b'6' | b'7' => "Oceanía",
+            b'8' | b'9' => "Sudamérica",
+            b'A'..=b'H' => "África",
+            b'J'..=b'R' => "Asia",
+            _ => "Europa",
+        }
+    }
+
+    // Para los autos de America del Norte (y los que siguen su convencion) la posicion 7 dice
+    // en cual de los dos ciclos cae el codigo: un numero es 1980-2009, una letra 2010-2039.
+    // None si la posicion 10 no es un codigo de año.
+    pub fn año_modelo(&self) -> Option<u32> {
+        let indice = CODIGOS_AÑO.find(self.0.as_bytes()[9] as char)? as u32;
+        let ciclo = if self.0.as_bytes()[6].is_ascii_digit() {
+            1980
+        } else {
+            2010
+        };
+        Some(ciclo + indice)
+    }
+}
+
+impl FromStr for Vin {
+    type Err = VinError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Vin::new(s)
+    }
+}
+
+impl TryFrom<String> for Vin {
+    type Error = VinError;
+
+    fn try_from(vin: String) -> Result<Self, Self::Error> {
+        Vin::new(&vin)
+    }
+}
+
+impl From<Vin> for String {
+    fn from(vin: Vin) -> Self {
+        vin.0
+    }
+}
+
+impl fmt::Display for Vin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vin_valido_y_decodificado() {
+        let vin = Vin::new(" 1hgcm82633a004352 ").unwrap();
+        assert_eq!(vin.to_string(), "1HGCM82633A004352");
+        assert_eq!(vin.wmi(), "1HG");
+        assert_eq!(vin.fabricante(), Some("Honda"));
+        assert_eq!(vin.region(), "Norteamérica");
+        assert_eq!(vin.año_modelo(), Some(2003));
+
+        // digito verificador X
+        let vin: Vin = "1M8GDM9AXKP042788".parse().unwrap();
+        assert_eq!(vin.fabricante(), None);
+        assert_eq!(vin.año_modelo(), Some(1989));
+
+        // letra en la posicion 7: segundo ciclo
+        let vin = Vin::new("2HGFE2F54MH000001").unwrap();
+        assert_eq!(vin.fabricante(), Some("Honda"));
+        assert_eq!(vin.año_modelo(), Some(2021));
+
+        let vin = Vin::completar("8AP17817?LP000001").unwrap();
+        assert_eq!(vin.como_texto(), "8AP178178LP000001");
+        assert_eq!(vin.fabricante(), Some("Fiat"));
+        assert_eq!(vin.region(), "Sudamérica");
+        assert_eq!(vin.año_modelo(), Some(1990));
+        assert_eq!(Vin::completar("8AP17817"), Err(VinError::Largo(8)));
+    }
+
+    #[test]
+    fn test_vin_invalido() {
+        assert_eq!(Vin::new("1HGCM82633A00435"), Err(VinError::Largo(16)));
+        assert_eq!(
+            Vin::new("1HGCM82633A0O4352"),
+            Err(VinError::Caracter {
+                posicion: 13,
+                caracter: 'O'
+            })
+        );
+        let error = Vin::new("1HGCM82643A004352").unwrap_err();
+        assert_eq!(
+            error,
+            VinError::DigitoVerificador {
+                esperado: '3',
+                encontrado: '4'
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "El digito verificador del VIN deberia ser '3' y es '4'"
+        );
+    }
+
+    #[test]
+    fn test_vin_en_json() {
+        let vin = Vin::new("1HGCM82633A004352").unwrap();
+        let json = serde_json::to_string(&vin).unwrap();
+        assert_eq!(json, "\"1HGCM82633A004352\"");
+        assert_eq!(serde_json::from_str::<Vin>(&json).unwrap(), vin);
+        assert!(serde_json::from_str::<Vin>("\"1HGCM82643A004352\"").is_err());
+    }
+}